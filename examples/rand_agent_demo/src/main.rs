@@ -1,7 +1,9 @@
 use config::Config;
 use rig_extra::agent::stream_to_stdout;
+use rig_extra::circuit_breaker::CircuitBreakerConfig;
 use rig_extra::completion::{Prompt, PromptError};
 use rig_extra::rand_agent::RandAgentBuilder;
+use rig_extra::retry::RetryConfig;
 use rig_extra::simple_rand_builder::AgentConfig;
 use rig_extra::streaming::StreamingPrompt;
 use std::sync::Arc;
@@ -31,12 +33,20 @@ async fn main() -> anyhow::Result<()> {
         })
         .unwrap_or_default();
 
+    // 解析重试配置
+    let retry_config: RetryConfig = settings.get("retry").unwrap_or_default();
+
     // 创建线程安全的 RandAgent
-    let rand_agent_builder = RandAgentBuilder::new()
+    let mut rand_agent_builder = RandAgentBuilder::new()
         .max_failures(5)
+        .retry_config(retry_config)
         .on_agent_invalid(|id| {
             println!("Invalid agent id: {id}");
         });
+    // 配置了 [circuit_breaker] 时启用提供方熔断
+    if let Ok(config) = settings.get::<CircuitBreakerConfig>("circuit_breaker") {
+        rand_agent_builder = rand_agent_builder.circuit_breaker(config);
+    }
     let rand_agent_builder =
         rand_agent_builder.simple_builder(agent_configs, "You are a helpful assistant".to_string());
     let thread_safe_agent = rand_agent_builder.build();
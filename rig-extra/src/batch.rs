@@ -0,0 +1,242 @@
+//! 批量提问的部分结果
+//!
+//! [`RandAgent::prompt_batch`] 不会因为某一项失败而让整批失败，而是返回 [`BatchOutcome`]，
+//! 其中记录每一项的状态和汇总统计。失败或被跳过的项可以通过
+//! [`RandAgent::retry_failed`] 单独重试，已成功的结果保持不变。
+//!
+//! ```rust,ignore
+//! use rig_extra::batch::BatchOptions;
+//!
+//! let outcome = rand_agent
+//!     .prompt_batch(documents.iter().map(|doc| format!("总结: {doc}")), BatchOptions::default())
+//!     .await;
+//! println!("{:?}", outcome.stats());
+//!
+//! let outcome = rand_agent.retry_failed(outcome, BatchOptions::default()).await;
+//! for output in outcome.outputs() {
+//!     println!("{output:?}");
+//! }
+//! ```
+
+use crate::rand_agent::{PROMPT_ALL_CONCURRENCY, RandAgent};
+use futures::StreamExt;
+use rig::completion::{Message, Prompt, PromptError};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// 批量提问的设置
+#[derive(Debug, Clone, Copy)]
+pub struct BatchOptions {
+    /// 同时进行的请求数，同时受代理池并发上限约束
+    pub concurrency: usize,
+    /// 失败项达到该数量后不再发起新的请求，其余项标记为跳过，为空时不限制
+    pub max_failures: Option<usize>,
+    /// 内容相同的 prompt 只请求一次，其余项标记为缓存
+    pub dedupe: bool,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: PROMPT_ALL_CONCURRENCY,
+            max_failures: None,
+            dedupe: true,
+        }
+    }
+}
+
+/// 单项的状态
+#[derive(Debug)]
+pub enum BatchItemStatus {
+    Succeeded(String),
+    Failed(PromptError),
+    /// 失败项过多，未发起请求
+    Skipped,
+    /// 复用了同批次中相同 prompt 的结果
+    Cached(String),
+}
+
+impl BatchItemStatus {
+    /// 成功或缓存的输出
+    pub fn output(&self) -> Option<&str> {
+        match self {
+            BatchItemStatus::Succeeded(content) | BatchItemStatus::Cached(content) => Some(content),
+            BatchItemStatus::Failed(_) | BatchItemStatus::Skipped => None,
+        }
+    }
+
+    /// 是否需要重试
+    pub fn is_pending(&self) -> bool {
+        matches!(self, BatchItemStatus::Failed(_) | BatchItemStatus::Skipped)
+    }
+}
+
+/// 批量提问中的一项
+#[derive(Debug)]
+pub struct BatchItem {
+    pub prompt: Message,
+    pub status: BatchItemStatus,
+}
+
+/// 汇总统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BatchStats {
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub cached: usize,
+    /// 最近一次执行的耗时
+    pub elapsed: Duration,
+}
+
+/// 批量提问的结果，顺序与输入一致
+#[derive(Debug)]
+pub struct BatchOutcome {
+    pub items: Vec<BatchItem>,
+    elapsed: Duration,
+}
+
+impl BatchOutcome {
+    pub fn stats(&self) -> BatchStats {
+        let mut stats = BatchStats {
+            total: self.items.len(),
+            elapsed: self.elapsed,
+            ..Default::default()
+        };
+        for item in &self.items {
+            match item.status {
+                BatchItemStatus::Succeeded(_) => stats.succeeded += 1,
+                BatchItemStatus::Failed(_) => stats.failed += 1,
+                BatchItemStatus::Skipped => stats.skipped += 1,
+                BatchItemStatus::Cached(_) => stats.cached += 1,
+            }
+        }
+        stats
+    }
+
+    /// 所有项都已成功或命中缓存
+    pub fn is_complete(&self) -> bool {
+        self.items.iter().all(|item| !item.status.is_pending())
+    }
+
+    /// 每一项的输出，失败或跳过的项为 None
+    pub fn outputs(&self) -> Vec<Option<&str>> {
+        self.items.iter().map(|item| item.status.output()).collect()
+    }
+
+    /// 失败或被跳过的项的下标
+    pub fn pending_indices(&self) -> Vec<usize> {
+        self.items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.status.is_pending())
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
+
+/// 执行 `items` 中失败或被跳过的项，其余项保持不变
+pub(crate) async fn run_batch(
+    agent: &RandAgent,
+    mut items: Vec<BatchItem>,
+    options: BatchOptions,
+) -> BatchOutcome {
+    let start = Instant::now();
+    let pending = items
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| item.status.is_pending())
+        .map(|(index, _)| index);
+
+    // 相同 prompt 的项只请求第一个，其余项记录为它的副本
+    let mut requests: Vec<usize> = Vec::new();
+    let mut duplicates: Vec<(usize, usize)> = Vec::new();
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    for index in pending {
+        let key = serde_json::to_string(&items[index].prompt).unwrap_or_default();
+        match seen.get(&key) {
+            Some(&first) if options.dedupe => duplicates.push((index, first)),
+            _ => {
+                seen.insert(key, index);
+                requests.push(index);
+            }
+        }
+    }
+
+    let failures = AtomicUsize::new(0);
+    let results: Vec<(usize, BatchItemStatus)> = futures::stream::iter(requests)
+        .map(|index| {
+            let prompt = items[index].prompt.clone();
+            let failures = &failures;
+            async move {
+                if options
+                    .max_failures
+                    .is_some_and(|max| failures.load(Ordering::SeqCst) >= max)
+                {
+                    return (index, BatchItemStatus::Skipped);
+                }
+                match agent.prompt(prompt).await {
+                    Ok(content) => (index, BatchItemStatus::Succeeded(content)),
+                    Err(err) => {
+                        failures.fetch_add(1, Ordering::SeqCst);
+                        (index, BatchItemStatus::Failed(err))
+                    }
+                }
+            }
+        })
+        .buffered(options.concurrency.max(1))
+        .collect()
+        .await;
+    for (index, status) in results {
+        items[index].status = status;
+    }
+
+    for (index, first) in duplicates {
+        items[index].status = match items[first].status.output() {
+            Some(content) => BatchItemStatus::Cached(content.to_string()),
+            None => BatchItemStatus::Skipped,
+        };
+    }
+
+    BatchOutcome {
+        items,
+        elapsed: start.elapsed(),
+    }
+}
+
+/// 创建待执行的项
+pub(crate) fn pending_items(prompts: impl IntoIterator<Item = Message>) -> Vec<BatchItem> {
+    prompts
+        .into_iter()
+        .map(|prompt| BatchItem {
+            prompt,
+            status: BatchItemStatus::Skipped,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rand_agent::RandAgentBuilder;
+
+    #[tokio::test]
+    async fn test_batch_without_agents() {
+        let agent = RandAgentBuilder::new().build();
+        let items = pending_items(["你好", "再见", "你好"].map(Message::user));
+        let options = BatchOptions {
+            max_failures: Some(1),
+            concurrency: 1,
+            ..Default::default()
+        };
+        let outcome = run_batch(&agent, items, options).await;
+
+        let stats = outcome.stats();
+        assert_eq!((stats.total, stats.failed, stats.skipped), (3, 1, 2));
+        assert!(!outcome.is_complete());
+        assert_eq!(outcome.pending_indices(), vec![0, 1, 2]);
+        assert_eq!(outcome.outputs(), vec![None, None, None]);
+    }
+}
@@ -0,0 +1,161 @@
+//! 节假日与农历工具
+//!
+//! 基于 tyme4rs 的日历计算，供应用直接调用(如排班、提醒等)，不必经过 LLM 工具接口。
+//! 法定假日数据自 2001-12-29 起。
+//!
+//! ```rust,ignore
+//! use chrono::NaiveDate;
+//! use rig_extra::calendar;
+//!
+//! let date = NaiveDate::from_ymd_opt(2025, 10, 1).unwrap();
+//! assert!(!calendar::is_workday(date));
+//! let holiday = calendar::next_legal_holiday(date).unwrap();
+//! let days = calendar::days_until(date, "春节");
+//! let spring_festival = calendar::lunar_to_solar(2026, 1, 1);
+//! ```
+
+use chrono::{Datelike, Local, NaiveDate, Weekday};
+use tyme4rs::tyme::Culture;
+use tyme4rs::tyme::lunar::LunarDay;
+use tyme4rs::tyme::solar::SolarDay;
+
+/// 向后查找的最大天数
+const MAX_SEARCH_DAYS: i64 = 400;
+
+/// 法定假日
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Holiday {
+    /// 日期
+    pub date: NaiveDate,
+    /// 假日名称，如 国庆节
+    pub name: String,
+}
+
+/// 农历日期
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LunarDate {
+    /// 农历年
+    pub year: isize,
+    /// 农历月，闰月为负数
+    pub month: isize,
+    /// 农历日
+    pub day: usize,
+    /// 中文表示，如 农历乙巳年八月初十
+    pub text: String,
+}
+
+/// 今天(服务器本地时区)
+pub fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+fn solar_day(date: NaiveDate) -> SolarDay {
+    SolarDay::from_ymd(
+        date.year() as isize,
+        date.month() as usize,
+        date.day() as usize,
+    )
+}
+
+/// 是否为工作日
+///
+/// 法定假日休息，调休上班的周末算工作日，其余按周一至周五计算
+pub fn is_workday(date: NaiveDate) -> bool {
+    match solar_day(date).get_legal_holiday() {
+        Some(holiday) => holiday.is_work(),
+        None => !matches!(date.weekday(), Weekday::Sat | Weekday::Sun),
+    }
+}
+
+/// 是否为法定假日(不含调休上班日)
+pub fn is_legal_holiday(date: NaiveDate) -> bool {
+    solar_day(date)
+        .get_legal_holiday()
+        .is_some_and(|holiday| !holiday.is_work())
+}
+
+/// 从 `from`(含)开始的下一个法定假日
+pub fn next_legal_holiday(from: NaiveDate) -> Option<Holiday> {
+    (0..MAX_SEARCH_DAYS)
+        .filter_map(|offset| from.checked_add_signed(chrono::Duration::days(offset)))
+        .find_map(|date| {
+            solar_day(date)
+                .get_legal_holiday()
+                .filter(|holiday| !holiday.is_work())
+                .map(|holiday| Holiday {
+                    date,
+                    name: holiday.get_name(),
+                })
+        })
+}
+
+/// 从 `from` 到下一个指定节日的天数，当天即为 0
+///
+/// 同时匹配公历节日(如 国庆节)和农历节日(如 春节、中秋节)
+pub fn days_until(from: NaiveDate, festival: &str) -> Option<i64> {
+    (0..MAX_SEARCH_DAYS).find(|&offset| {
+        let Some(date) = from.checked_add_signed(chrono::Duration::days(offset)) else {
+            return false;
+        };
+        let solar = solar_day(date);
+        solar
+            .get_festival()
+            .is_some_and(|f| f.get_name() == festival)
+            || solar
+                .get_lunar_day()
+                .get_festival()
+                .is_some_and(|f| f.get_name() == festival)
+    })
+}
+
+/// 公历转农历
+pub fn solar_to_lunar(date: NaiveDate) -> LunarDate {
+    let lunar = solar_day(date).get_lunar_day();
+    LunarDate {
+        year: lunar.get_year(),
+        month: lunar.get_month(),
+        day: lunar.get_day(),
+        text: lunar.to_string(),
+    }
+}
+
+/// 农历转公历，闰月使用负数月份，日期不存在时返回 None
+pub fn lunar_to_solar(year: isize, month: isize, day: usize) -> Option<NaiveDate> {
+    let solar = LunarDay::from_ymd(year, month, day).get_solar_day();
+    NaiveDate::from_ymd_opt(
+        solar.get_year() as i32,
+        solar.get_month() as u32,
+        solar.get_day() as u32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ymd(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_workday_and_holiday() {
+        // 国庆节
+        assert!(!is_workday(ymd(2025, 10, 1)));
+        assert!(is_legal_holiday(ymd(2025, 10, 1)));
+        // 国庆调休上班的周日
+        assert!(is_workday(ymd(2025, 9, 28)));
+        // 普通周六
+        assert!(!is_workday(ymd(2025, 11, 15)));
+
+        let holiday = next_legal_holiday(ymd(2025, 9, 28)).unwrap();
+        assert_eq!(holiday.date, ymd(2025, 10, 1));
+    }
+
+    #[test]
+    fn test_lunar_conversion() {
+        assert_eq!(lunar_to_solar(2025, 1, 1), Some(ymd(2025, 1, 29)));
+        let lunar = solar_to_lunar(ymd(2025, 1, 29));
+        assert_eq!((lunar.year, lunar.month, lunar.day), (2025, 1, 1));
+        assert_eq!(days_until(ymd(2025, 1, 1), "春节"), Some(28));
+    }
+}
@@ -0,0 +1,140 @@
+//! 故障注入
+//!
+//! 按配置的概率在 agent 调用中注入延迟、错误和异常响应，用于在真实故障发生前
+//! 验证故障转移、重试、熔断和响应校验等配置是否按预期工作。仅用于测试环境。
+//!
+//! ```rust,ignore
+//! let rand_agent = RandAgentBuilder::new()
+//!     .chaos(ChaosConfig {
+//!         latency_probability: 0.2,
+//!         latency_ms: 3000,
+//!         error_probability: 0.1,
+//!         malformed_probability: 0.1,
+//!     })
+//!     .build();
+//! ```
+//!
+//! 注入的错误为 `CompletionError::ProviderError`，与真实的提供方错误一样计入失败次数。
+
+use rand::Rng;
+use rig::completion::{CompletionError, PromptError, Usage};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::time::Duration;
+
+/// 注入错误的错误信息
+const CHAOS_ERROR_MESSAGE: &str = "chaos: injected provider error";
+
+/// 故障注入配置，概率取值 0.0 ~ 1.0
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ChaosConfig {
+    /// 注入延迟的概率
+    pub latency_probability: f64,
+    /// 注入的最大延迟(毫秒)，实际延迟在 0 到该值之间随机
+    pub latency_ms: u64,
+    /// 不调用提供方、直接返回错误的概率
+    pub error_probability: f64,
+    /// 将响应替换为空内容或截断内容的概率
+    pub malformed_probability: f64,
+}
+
+/// 一次调用中注入的故障
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ChaosFault {
+    Error,
+    Malformed,
+}
+
+impl ChaosConfig {
+    /// 按概率抽取延迟和故障
+    pub(crate) fn roll(&self, rng: &mut impl Rng) -> (Option<Duration>, Option<ChaosFault>) {
+        let latency =
+            if self.latency_ms > 0 && rng.random_bool(probability(self.latency_probability)) {
+                Some(Duration::from_millis(rng.random_range(0..=self.latency_ms)))
+            } else {
+                None
+            };
+        let fault = if rng.random_bool(probability(self.error_probability)) {
+            Some(ChaosFault::Error)
+        } else if rng.random_bool(probability(self.malformed_probability)) {
+            Some(ChaosFault::Malformed)
+        } else {
+            None
+        };
+        (latency, fault)
+    }
+
+    /// 包装一次 agent 调用，按配置注入故障
+    pub(crate) async fn inject<F>(&self, call: F) -> Result<(String, Usage), PromptError>
+    where
+        F: Future<Output = Result<(String, Usage), PromptError>>,
+    {
+        let (latency, fault) = self.roll(&mut rand::rng());
+        if let Some(latency) = latency {
+            tracing::warn!("chaos: 注入延迟 {latency:?}");
+            tokio::time::sleep(latency).await;
+        }
+
+        match fault {
+            Some(ChaosFault::Error) => {
+                tracing::warn!("chaos: 注入错误");
+                Err(PromptError::CompletionError(
+                    CompletionError::ProviderError(CHAOS_ERROR_MESSAGE.to_string()),
+                ))
+            }
+            Some(ChaosFault::Malformed) => {
+                tracing::warn!("chaos: 注入异常响应");
+                call.await
+                    .map(|(content, usage)| (malform(&content, &mut rand::rng()), usage))
+            }
+            None => call.await,
+        }
+    }
+}
+
+/// 概率限制在 0.0 ~ 1.0，避免 `random_bool` panic
+fn probability(p: f64) -> f64 {
+    if p.is_nan() { 0.0 } else { p.clamp(0.0, 1.0) }
+}
+
+/// 随机返回空内容或截断一半的内容
+fn malform(content: &str, rng: &mut impl Rng) -> String {
+    if rng.random_bool(0.5) {
+        String::new()
+    } else {
+        content.chars().take(content.chars().count() / 2).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chaos_roll() {
+        let mut rng = rand::rng();
+        let (latency, fault) = ChaosConfig::default().roll(&mut rng);
+        assert!(latency.is_none());
+        assert!(fault.is_none());
+
+        let config = ChaosConfig {
+            latency_probability: 1.0,
+            latency_ms: 10,
+            error_probability: 1.0,
+            malformed_probability: 1.0,
+        };
+        let (latency, fault) = config.roll(&mut rng);
+        assert!(latency.unwrap() <= Duration::from_millis(10));
+        assert_eq!(fault, Some(ChaosFault::Error));
+
+        let config = ChaosConfig {
+            malformed_probability: 2.0,
+            ..Default::default()
+        };
+        assert_eq!(config.roll(&mut rng).1, Some(ChaosFault::Malformed));
+
+        let malformed = malform("你好世界", &mut rng);
+        assert!(malformed.is_empty() || malformed == "你好");
+    }
+}
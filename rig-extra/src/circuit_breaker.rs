@@ -0,0 +1,124 @@
+//! 按提供方熔断
+//!
+//! 同一提供方(如多个 OpenRouter key)的 agent 在时间窗口内累计失败达到阈值时，
+//! 整个提供方在熔断期间内不再被选中，避免逐个 agent 试错浪费请求。
+//!
+//! ```rust,ignore
+//! let rand_agent = RandAgentBuilder::new()
+//!     .circuit_breaker(CircuitBreakerConfig {
+//!         failure_threshold: 5,
+//!         window_secs: 60,
+//!         open_secs: 120,
+//!     })
+//!     .build();
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// 提供方熔断配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CircuitBreakerConfig {
+    /// 窗口内触发熔断的失败次数
+    pub failure_threshold: u32,
+    /// 失败统计时间窗口(秒)
+    pub window_secs: u64,
+    /// 熔断持续时间(秒)
+    pub open_secs: u64,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            window_secs: 60,
+            open_secs: 60,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ProviderCircuit {
+    failures: VecDeque<Instant>,
+    open_until: Option<Instant>,
+}
+
+/// 提供方熔断器
+#[derive(Debug)]
+pub(crate) struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    providers: HashMap<String, ProviderCircuit>,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config,
+            providers: HashMap::new(),
+        }
+    }
+
+    /// 提供方是否处于熔断状态
+    pub(crate) fn is_open(&self, provider: &str) -> bool {
+        self.providers
+            .get(provider)
+            .and_then(|circuit| circuit.open_until)
+            .is_some_and(|until| Instant::now() < until)
+    }
+
+    /// 记录一次失败，窗口内失败次数达到阈值时熔断
+    pub(crate) fn record_failure(&mut self, provider: &str) {
+        let window = Duration::from_secs(self.config.window_secs);
+        let now = Instant::now();
+        let circuit = self.providers.entry(provider.to_string()).or_default();
+        circuit.failures.push_back(now);
+        while circuit
+            .failures
+            .front()
+            .is_some_and(|time| now.duration_since(*time) >= window)
+        {
+            circuit.failures.pop_front();
+        }
+        if circuit.failures.len() as u32 >= self.config.failure_threshold {
+            tracing::warn!(
+                "提供方 {provider} 在 {window:?} 内失败 {} 次，熔断 {} 秒",
+                circuit.failures.len(),
+                self.config.open_secs
+            );
+            circuit.open_until = Some(now + Duration::from_secs(self.config.open_secs));
+            circuit.failures.clear();
+        }
+    }
+
+    /// 记录一次成功，清空该提供方的失败统计
+    pub(crate) fn record_success(&mut self, provider: &str) {
+        if let Some(circuit) = self.providers.get_mut(provider) {
+            circuit.failures.clear();
+            circuit.open_until = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_circuit_opens_per_provider() {
+        let mut breaker = CircuitBreaker::new(CircuitBreakerConfig {
+            failure_threshold: 2,
+            window_secs: 60,
+            open_secs: 60,
+        });
+        breaker.record_failure("openrouter");
+        assert!(!breaker.is_open("openrouter"));
+        breaker.record_failure("openrouter");
+        assert!(breaker.is_open("openrouter"));
+        assert!(!breaker.is_open("bigmodel"));
+
+        breaker.record_success("openrouter");
+        assert!(!breaker.is_open("openrouter"));
+    }
+}
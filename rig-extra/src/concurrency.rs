@@ -0,0 +1,202 @@
+//! 代理池并发限制
+//!
+//! 大量任务同时通过 `Arc<RandAgent>` 发起请求时，超过上限的请求排队等待，而不是一起
+//! 涌向提供方触发限流。支持两级限制:
+//!
+//! - 全局上限: 代理池同时进行的请求总数
+//! - 单个 agent 上限: 已满的 agent 暂不被选中；所有可用 agent 都已满时在选中的 agent 上排队
+//!
+//! ```rust,ignore
+//! let rand_agent = Arc::new(
+//!     RandAgentBuilder::new()
+//!         .max_concurrent_requests(8)
+//!         .max_concurrent_per_agent(2)
+//!         .build(),
+//! );
+//! ```
+//!
+//! 流式请求在流结束或被丢弃时才释放名额。大量并发的流式请求会长时间占用连接，
+//! 可以用 `max_concurrent_streams_per_agent` 单独限制每个 agent 同时打开的流，
+//! 超出的流式请求优先选择其它 agent，都已满时排队等待，避免耗尽本地 Ollama 等服务的连接。
+
+use crate::lifecycle::InFlightGuard;
+use crate::tenant::TenantPermit;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// 并发限制器
+#[derive(Debug, Default)]
+pub(crate) struct ConcurrencyLimiter {
+    global: Option<Arc<Semaphore>>,
+    global_limit: Option<usize>,
+    per_agent: Option<usize>,
+    /// 按 agent id 延迟创建的信号量，运行期间新增的 agent 同样受限
+    agents: Mutex<HashMap<i32, Arc<Semaphore>>>,
+    streams_per_agent: Option<usize>,
+    /// 按 agent id 延迟创建的流式请求信号量
+    streams: Mutex<HashMap<i32, Arc<Semaphore>>>,
+}
+
+/// 请求占用的并发名额，丢弃时释放
+#[derive(Debug, Default)]
+pub(crate) struct RequestPermit {
+    _global: Option<OwnedSemaphorePermit>,
+    _agent: Option<OwnedSemaphorePermit>,
+    _stream: Option<OwnedSemaphorePermit>,
+    /// 计入进行中的请求，用于关闭时排空
+    _in_flight: Option<InFlightGuard>,
+    /// 租户的并发名额
+    _tenant: Option<TenantPermit>,
+}
+
+impl RequestPermit {
+    pub(crate) fn with_in_flight(self, guard: InFlightGuard) -> Self {
+        Self {
+            _in_flight: Some(guard),
+            ..self
+        }
+    }
+
+    pub(crate) fn with_tenant(self, tenant: Option<TenantPermit>) -> Self {
+        Self {
+            _tenant: tenant,
+            ..self
+        }
+    }
+}
+
+impl ConcurrencyLimiter {
+    pub(crate) fn new(global: Option<usize>, per_agent: Option<usize>) -> Self {
+        Self {
+            global: global.map(|limit| Arc::new(Semaphore::new(limit.max(1)))),
+            global_limit: global.map(|limit| limit.max(1)),
+            per_agent: per_agent.map(|limit| limit.max(1)),
+            agents: Mutex::new(HashMap::new()),
+            streams_per_agent: None,
+            streams: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 设置单个 agent 同时打开的流式请求数上限
+    pub(crate) fn with_streams_per_agent(mut self, limit: Option<usize>) -> Self {
+        self.streams_per_agent = limit.map(|limit| limit.max(1));
+        self
+    }
+
+    /// 全局并发上限
+    pub(crate) fn global_limit(&self) -> Option<usize> {
+        self.global_limit
+    }
+
+    /// 等待全局名额
+    pub(crate) async fn acquire_global(&self) -> Option<OwnedSemaphorePermit> {
+        let semaphore = self.global.clone()?;
+        // 信号量不会被关闭，acquire 不会失败
+        semaphore.acquire_owned().await.ok()
+    }
+
+    /// agent 是否还有空闲名额，未设置上限时总是返回 true
+    pub(crate) fn has_capacity(&self, id: i32) -> bool {
+        self.agent_semaphore(id)
+            .is_none_or(|semaphore| semaphore.available_permits() > 0)
+    }
+
+    /// agent 是否还能打开新的流，未设置上限时总是返回 true
+    pub(crate) fn has_stream_capacity(&self, id: i32) -> bool {
+        self.stream_semaphore(id)
+            .is_none_or(|semaphore| semaphore.available_permits() > 0)
+    }
+
+    /// 等待 agent 的名额，并与全局名额合并为一个请求名额
+    pub(crate) async fn acquire_agent(
+        &self,
+        id: i32,
+        global: Option<OwnedSemaphorePermit>,
+    ) -> RequestPermit {
+        let agent = match self.agent_semaphore(id) {
+            Some(semaphore) => semaphore.acquire_owned().await.ok(),
+            None => None,
+        };
+        RequestPermit {
+            _global: global,
+            _agent: agent,
+            _stream: None,
+            _in_flight: None,
+            _tenant: None,
+        }
+    }
+
+    /// 等待 agent 的流式请求名额，并入已有的请求名额
+    pub(crate) async fn acquire_stream(&self, id: i32, permit: RequestPermit) -> RequestPermit {
+        let stream = match self.stream_semaphore(id) {
+            Some(semaphore) => semaphore.acquire_owned().await.ok(),
+            None => None,
+        };
+        RequestPermit {
+            _stream: stream,
+            ..permit
+        }
+    }
+
+    fn agent_semaphore(&self, id: i32) -> Option<Arc<Semaphore>> {
+        self.per_agent
+            .map(|limit| semaphore_for(&self.agents, limit, id))
+    }
+
+    fn stream_semaphore(&self, id: i32) -> Option<Arc<Semaphore>> {
+        self.streams_per_agent
+            .map(|limit| semaphore_for(&self.streams, limit, id))
+    }
+}
+
+fn semaphore_for(
+    semaphores: &Mutex<HashMap<i32, Arc<Semaphore>>>,
+    limit: usize,
+    id: i32,
+) -> Arc<Semaphore> {
+    let mut semaphores = semaphores.lock().unwrap_or_else(|e| e.into_inner());
+    semaphores
+        .entry(id)
+        .or_insert_with(|| Arc::new(Semaphore::new(limit)))
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_concurrency_limits() {
+        let limiter = ConcurrencyLimiter::new(Some(2), Some(1));
+        assert!(limiter.has_capacity(1));
+
+        let global = limiter.acquire_global().await;
+        let first = limiter.acquire_agent(1, global).await;
+        assert!(!limiter.has_capacity(1));
+        assert!(limiter.has_capacity(2));
+
+        let global = limiter.acquire_global().await;
+        let _second = limiter.acquire_agent(2, global).await;
+        // 全局名额已用完
+        assert_eq!(limiter.global.as_ref().unwrap().available_permits(), 0);
+
+        drop(first);
+        assert!(limiter.has_capacity(1));
+        assert_eq!(limiter.global.as_ref().unwrap().available_permits(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_stream_limits() {
+        let limiter = ConcurrencyLimiter::new(None, None).with_streams_per_agent(Some(1));
+        let permit = limiter.acquire_agent(1, None).await;
+        let stream = limiter.acquire_stream(1, permit).await;
+        assert!(!limiter.has_stream_capacity(1));
+        // 普通请求不受流式请求上限影响
+        assert!(limiter.has_capacity(1));
+        assert!(limiter.has_stream_capacity(2));
+
+        drop(stream);
+        assert!(limiter.has_stream_capacity(1));
+    }
+}
@@ -0,0 +1,244 @@
+//! 从 JSON/YAML/TOML 配置加载代理
+//!
+//! 省去每个示例中重复的 `config` crate 样板代码。配置可以是 agent 配置的列表，
+//! 也可以是包含 `agents` 列表的对象(与 Settings.toml 中的 `[[agents]]` 相同)，其余配置项忽略。
+//!
+//! ```rust,ignore
+//! use rig_extra::config_file::{ConfigFormat, load_agent_configs};
+//!
+//! // 按扩展名判断格式
+//! let rand_agent = RandAgentBuilder::from_config_file("Settings.toml", "你是一个助手")?.build();
+//!
+//! let rand_agent = RandAgentBuilder::from_config_str(
+//!     r#"[{"id": 1, "provider": "bigmodel", "model_name": "glm-4-flash", "api_key": "xxx"}]"#,
+//!     ConfigFormat::Json,
+//!     "你是一个助手",
+//! )?
+//! .build();
+//!
+//! // 只解析配置
+//! let configs = load_agent_configs("agents.yaml")?;
+//! ```
+//!
+//! 某个 agent 配置无效时，错误信息中包含它在列表中的位置和 id。
+
+use crate::rand_agent::RandAgentBuilder;
+use crate::simple_rand_builder::AgentConfig;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use yaml_rust2::{Yaml, YamlLoader};
+
+/// 配置格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl ConfigFormat {
+    /// 按扩展名判断格式
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "json" => Some(ConfigFormat::Json),
+            "yaml" | "yml" => Some(ConfigFormat::Yaml),
+            "toml" => Some(ConfigFormat::Toml),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigFileError {
+    #[error("读取配置文件 {path} 失败: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("无法从扩展名判断配置文件 {0} 的格式，支持 json/yaml/yml/toml")]
+    UnknownFormat(PathBuf),
+    #[error("解析 {format:?} 配置失败: {message}")]
+    Parse {
+        format: ConfigFormat,
+        message: String,
+    },
+    #[error("配置中没有 agent 列表，应为列表或包含 agents 列表的对象")]
+    MissingAgents,
+    #[error("第 {index} 个 agent 配置{}无效: {message}", id.map(|id| format!("(id = {id})")).unwrap_or_default())]
+    InvalidEntry {
+        /// 从 1 开始
+        index: usize,
+        id: Option<i64>,
+        message: String,
+    },
+}
+
+/// 读取配置文件中的 agent 配置，按扩展名判断格式
+pub fn load_agent_configs(path: impl AsRef<Path>) -> Result<Vec<AgentConfig>, ConfigFileError> {
+    let path = path.as_ref();
+    let format =
+        ConfigFormat::from_path(path).ok_or_else(|| ConfigFileError::UnknownFormat(path.into()))?;
+    let content = std::fs::read_to_string(path).map_err(|source| ConfigFileError::Io {
+        path: path.into(),
+        source,
+    })?;
+    parse_agent_configs(&content, format)
+}
+
+/// 解析配置字符串中的 agent 配置
+pub fn parse_agent_configs(
+    content: &str,
+    format: ConfigFormat,
+) -> Result<Vec<AgentConfig>, ConfigFileError> {
+    let parse_error = |message: String| ConfigFileError::Parse { format, message };
+    let document = match format {
+        ConfigFormat::Json => {
+            serde_json::from_str(content).map_err(|e| parse_error(e.to_string()))?
+        }
+        ConfigFormat::Toml => toml::from_str(content).map_err(|e| parse_error(e.to_string()))?,
+        ConfigFormat::Yaml => {
+            let documents =
+                YamlLoader::load_from_str(content).map_err(|e| parse_error(e.to_string()))?;
+            match documents.into_iter().next() {
+                Some(document) => yaml_to_json(document).map_err(parse_error)?,
+                None => Value::Null,
+            }
+        }
+    };
+
+    let entries = match document {
+        Value::Array(entries) => entries,
+        Value::Object(mut document) => match document.remove("agents") {
+            Some(Value::Array(entries)) => entries,
+            _ => return Err(ConfigFileError::MissingAgents),
+        },
+        _ => return Err(ConfigFileError::MissingAgents),
+    };
+
+    entries
+        .into_iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            let id = entry.get("id").and_then(Value::as_i64);
+            serde_json::from_value(entry).map_err(|e| ConfigFileError::InvalidEntry {
+                index: index + 1,
+                id,
+                message: e.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn yaml_to_json(yaml: Yaml) -> Result<Value, String> {
+    Ok(match yaml {
+        Yaml::Null => Value::Null,
+        Yaml::Boolean(value) => Value::Bool(value),
+        Yaml::Integer(value) => Value::from(value),
+        Yaml::Real(ref text) => yaml
+            .as_f64()
+            .map(Value::from)
+            .ok_or_else(|| format!("无效的数字 {text}"))?,
+        Yaml::String(value) => Value::String(value),
+        Yaml::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(yaml_to_json)
+                .collect::<Result<_, _>>()?,
+        ),
+        Yaml::Hash(hash) => Value::Object(
+            hash.into_iter()
+                .map(|(key, value)| {
+                    let key = match key {
+                        Yaml::String(key) => key,
+                        Yaml::Integer(key) => key.to_string(),
+                        Yaml::Boolean(key) => key.to_string(),
+                        key => return Err(format!("不支持的键 {key:?}")),
+                    };
+                    Ok((key, yaml_to_json(value)?))
+                })
+                .collect::<Result<_, _>>()?,
+        ),
+        Yaml::Alias(_) | Yaml::BadValue => return Err("不支持 YAML 别名".to_string()),
+    })
+}
+
+impl RandAgentBuilder {
+    /// 从配置文件构建，按扩展名判断格式，见 [`crate::config_file`]
+    pub fn from_config_file(
+        path: impl AsRef<Path>,
+        global_system_prompt: impl Into<String>,
+    ) -> Result<Self, ConfigFileError> {
+        let configs = load_agent_configs(path)?;
+        Ok(Self::new().simple_builder(configs, global_system_prompt.into()))
+    }
+
+    /// 从配置字符串构建，见 [`crate::config_file`]
+    pub fn from_config_str(
+        content: &str,
+        format: ConfigFormat,
+        global_system_prompt: impl Into<String>,
+    ) -> Result<Self, ConfigFileError> {
+        let configs = parse_agent_configs(content, format)?;
+        Ok(Self::new().simple_builder(configs, global_system_prompt.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_agent_configs() {
+        let toml = r#"
+            bigmodel_api_key = "ignored"
+
+            [[agents]]
+            id = 1
+            provider = "bigmodel"
+            model_name = "glm-4-flash"
+            api_key = "xxx"
+            temperature = 0.5
+        "#;
+        let yaml = "
+agents:
+  - id: 1
+    provider: bigmodel
+    model_name: glm-4-flash
+    api_key: xxx
+    temperature: 0.5
+";
+        let json = r#"[{"id": 1, "provider": "bigmodel", "model_name": "glm-4-flash",
+                        "api_key": "xxx", "temperature": 0.5}]"#;
+        for (content, format) in [
+            (toml, ConfigFormat::Toml),
+            (yaml, ConfigFormat::Yaml),
+            (json, ConfigFormat::Json),
+        ] {
+            let configs = parse_agent_configs(content, format).unwrap();
+            assert_eq!(configs.len(), 1, "{format:?}");
+            assert_eq!(configs[0].model_name, "glm-4-flash");
+            assert_eq!(configs[0].temperature, Some(0.5));
+        }
+
+        let err = parse_agent_configs(
+            r#"[{"id": 1, "provider": "bigmodel", "model_name": "a", "api_key": "x"},
+                {"id": 2, "provider": "unknown", "model_name": "b"}]"#,
+            ConfigFormat::Json,
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("第 2 个 agent 配置(id = 2)无效"),
+            "{err}"
+        );
+        assert!(matches!(
+            parse_agent_configs("{}", ConfigFormat::Json),
+            Err(ConfigFileError::MissingAgents)
+        ));
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("Settings.YML")),
+            Some(ConfigFormat::Yaml)
+        );
+    }
+}
@@ -0,0 +1,226 @@
+//! 配置文件热加载
+//!
+//! 监视 Settings.toml 中的 `[[agents]]` 配置，文件修改后重新构建代理并原子替换
+//! [`RandAgent`] 的代理池，无需重启服务即可增删 agent 或轮换 api key。
+//! 配置解析失败时保留当前代理池。
+//!
+//! ```rust,ignore
+//! let handle = rand_agent
+//!     .watch_config("Settings.toml")
+//!     .system_prompt("You are a helpful assistant")
+//!     .on_added(|info| println!("新增 agent: {}", info.id))
+//!     .on_removed(|info| println!("移除 agent: {}", info.id))
+//!     .spawn();
+//!
+//! // 不再需要时停止监视
+//! handle.stop();
+//! ```
+
+use crate::AgentInfo;
+use crate::rand_agent::{RandAgent, RandAgentBuilder};
+use crate::simple_rand_builder::AgentConfig;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::task::JoinHandle;
+
+/// agent 变更回调
+pub type OnPoolChangeCallback = Option<Arc<dyn Fn(&AgentInfo) + Send + Sync + 'static>>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigWatchError {
+    #[error("读取配置文件失败: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("解析配置文件失败: {0}")]
+    Toml(#[from] toml::de::Error),
+}
+
+/// 一次重新加载引起的代理池变化，按 id 比较
+#[derive(Debug, Clone, Default)]
+pub struct PoolChange {
+    pub added: Vec<AgentInfo>,
+    pub removed: Vec<AgentInfo>,
+}
+
+/// 配置文件中与代理池相关的部分，其余配置项忽略
+#[derive(Deserialize)]
+struct AgentsFile {
+    #[serde(default)]
+    agents: Vec<AgentConfig>,
+}
+
+/// 配置文件监视器
+pub struct ConfigWatcher {
+    rand_agent: RandAgent,
+    path: PathBuf,
+    interval: Duration,
+    system_prompt: String,
+    max_failures: Option<u32>,
+    on_added: OnPoolChangeCallback,
+    on_removed: OnPoolChangeCallback,
+}
+
+impl ConfigWatcher {
+    pub(crate) fn new(rand_agent: RandAgent, path: PathBuf) -> Self {
+        Self {
+            rand_agent,
+            path,
+            interval: Duration::from_secs(5),
+            system_prompt: String::new(),
+            max_failures: None,
+            on_added: None,
+            on_removed: None,
+        }
+    }
+
+    /// 检查文件修改的间隔，默认 5 秒
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// 未单独配置 system_prompt 的 agent 使用的系统提示词
+    pub fn system_prompt(mut self, system_prompt: impl Into<String>) -> Self {
+        self.system_prompt = system_prompt.into();
+        self
+    }
+
+    /// 未单独配置 max_failures 的 agent 使用的最大失败次数
+    pub fn max_failures(mut self, max_failures: u32) -> Self {
+        self.max_failures = Some(max_failures);
+        self
+    }
+
+    /// 新增 agent 时调用
+    pub fn on_added<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&AgentInfo) + Send + Sync + 'static,
+    {
+        self.on_added = Some(Arc::new(callback));
+        self
+    }
+
+    /// 移除 agent 时调用
+    pub fn on_removed<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&AgentInfo) + Send + Sync + 'static,
+    {
+        self.on_removed = Some(Arc::new(callback));
+        self
+    }
+
+    /// 立即读取配置文件并替换代理池
+    pub async fn reload(&self) -> Result<PoolChange, ConfigWatchError> {
+        let configs = load_agent_configs(&self.path).await?;
+        let mut builder = RandAgentBuilder::new();
+        if let Some(max_failures) = self.max_failures {
+            builder = builder.max_failures(max_failures);
+        }
+        let states = builder
+            .simple_builder(configs, self.system_prompt.clone())
+            .take_agent_states();
+        let change = self.rand_agent.swap_agents(states).await;
+
+        for info in &change.added {
+            if let Some(callback) = &self.on_added {
+                callback(info);
+            }
+        }
+        for info in &change.removed {
+            if let Some(callback) = &self.on_removed {
+                callback(info);
+            }
+        }
+        Ok(change)
+    }
+
+    /// 在后台任务中监视配置文件，文件修改时间变化后重新加载
+    pub fn spawn(self) -> ConfigWatchHandle {
+        let rand_agent = self.rand_agent.clone();
+        let task = tokio::spawn(async move {
+            let mut last_modified = modified_time(&self.path).await;
+            let mut interval = tokio::time::interval(self.interval);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let modified = modified_time(&self.path).await;
+                if modified.is_none() || modified == last_modified {
+                    continue;
+                }
+                last_modified = modified;
+                match self.reload().await {
+                    Ok(change) => tracing::info!(
+                        "配置文件 {} 已重新加载，新增 {} 个 agent，移除 {} 个 agent",
+                        self.path.display(),
+                        change.added.len(),
+                        change.removed.len()
+                    ),
+                    Err(err) => {
+                        tracing::error!("{}，保留当前代理池", crate::redact::redacted(&err))
+                    }
+                }
+            }
+        });
+        rand_agent.register_background_task(task.abort_handle());
+        ConfigWatchHandle { task }
+    }
+}
+
+/// 后台监视任务的句柄，drop 时停止监视
+pub struct ConfigWatchHandle {
+    task: JoinHandle<()>,
+}
+
+impl ConfigWatchHandle {
+    /// 停止监视，等同于 drop 句柄
+    pub fn stop(self) {}
+}
+
+impl Drop for ConfigWatchHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn modified_time(path: &Path) -> Option<SystemTime> {
+    tokio::fs::metadata(path).await.ok()?.modified().ok()
+}
+
+async fn load_agent_configs(path: &Path) -> Result<Vec<AgentConfig>, ConfigWatchError> {
+    let content = tokio::fs::read_to_string(path).await?;
+    Ok(parse_agent_configs(&content)?)
+}
+
+fn parse_agent_configs(content: &str) -> Result<Vec<AgentConfig>, toml::de::Error> {
+    Ok(toml::from_str::<AgentsFile>(content)?.agents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_agent_configs() {
+        let content = r#"
+            bigmodel_api_key = ""
+
+            [retry]
+            max_times = 3
+
+            [[agents]]
+            id = 1
+            provider = "bigmodel"
+            model_name = "glm-4-flash"
+            api_key = "xxx"
+            reserved_for = "interactive"
+        "#;
+        let configs = parse_agent_configs(content).unwrap();
+        assert_eq!(configs.len(), 1);
+        assert_eq!(configs[0].id, 1);
+        assert_eq!(configs[0].reserved_for.as_deref(), Some("interactive"));
+
+        assert!(parse_agent_configs("[[agents]]\nid = 1").is_err());
+        assert!(parse_agent_configs("").unwrap().is_empty());
+    }
+}
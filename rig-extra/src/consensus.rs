@@ -0,0 +1,234 @@
+//! 多模型投票
+//!
+//! [`ConsensusAgent`] 同时向代理池中 K 个不同的 agent 发送相同的 prompt，再由聚合器从各自的
+//! 回答中选出最终结果。内置两种聚合方式:
+//!
+//! - 多数投票 [`MajorityVote`]，完全相同(忽略首尾空白)的回答中出现次数最多的胜出，适合分类、判断等短答案
+//! - 评审选择 [`JudgeSelection`]，由一个评审模型从候选回答中挑选最好的一个
+//!
+//! ```rust,ignore
+//! use rig_extra::consensus::{ConsensusAgent, JudgeSelection};
+//!
+//! let consensus = ConsensusAgent::new(rand_agent, 3);
+//! let outcome = consensus.vote("这条评论是正面还是负面? 只回答 正面 或 负面").await?;
+//! println!("{} ({} 票)", outcome.answer, outcome.votes);
+//! for (info, result) in &outcome.responses {
+//!     println!("{}: {:?}", info.model, result);
+//! }
+//!
+//! // 使用评审模型挑选
+//! let consensus = ConsensusAgent::new(rand_agent, 3).aggregator(JudgeSelection::new(judge));
+//! ```
+//!
+//! 也可以实现 [`Aggregator`] 自定义聚合方式。
+
+use crate::AgentInfo;
+use crate::dyn_agent::DynPromptAgent;
+use crate::rand_agent::{RandAgent, no_valid_agent_error};
+use futures::future::BoxFuture;
+use rig::completion::{CompletionError, Message, Prompt, PromptError};
+use std::sync::Arc;
+
+/// 从候选回答中选出最终结果
+pub trait Aggregator: Send + Sync {
+    /// 返回选中回答在 `answers` 中的下标，`answers` 至少有一个元素
+    fn select<'a>(
+        &'a self,
+        prompt: &'a Message,
+        answers: &'a [String],
+    ) -> BoxFuture<'a, Result<usize, PromptError>>;
+}
+
+/// 多数投票，票数相同时选择先出现的回答
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MajorityVote;
+
+impl Aggregator for MajorityVote {
+    fn select<'a>(
+        &'a self,
+        _prompt: &'a Message,
+        answers: &'a [String],
+    ) -> BoxFuture<'a, Result<usize, PromptError>> {
+        Box::pin(async move {
+            let mut best = (0, 0);
+            for (index, answer) in answers.iter().enumerate() {
+                let votes = count_votes(answers, answer);
+                if votes > best.1 {
+                    best = (index, votes);
+                }
+            }
+            Ok(best.0)
+        })
+    }
+}
+
+/// 由评审模型挑选最好的回答
+#[derive(Clone)]
+pub struct JudgeSelection {
+    judge: Arc<dyn DynPromptAgent>,
+}
+
+impl JudgeSelection {
+    pub fn new(judge: impl DynPromptAgent + 'static) -> Self {
+        Self {
+            judge: Arc::new(judge),
+        }
+    }
+
+    fn judge_prompt(prompt: &Message, answers: &[String]) -> String {
+        let question = match prompt {
+            Message::User { content } => content
+                .iter()
+                .filter_map(|content| match content {
+                    rig::message::UserContent::Text(text) => Some(text.text.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Message::Assistant { .. } => String::new(),
+        };
+        let mut judge_prompt = format!(
+            "以下是同一个问题的 {} 个候选回答，请选出最准确、最完整的一个，只输出它的编号。\n\n问题:\n{question}\n",
+            answers.len()
+        );
+        for (index, answer) in answers.iter().enumerate() {
+            judge_prompt.push_str(&format!("\n候选回答 {}:\n{answer}\n", index + 1));
+        }
+        judge_prompt
+    }
+}
+
+impl Aggregator for JudgeSelection {
+    fn select<'a>(
+        &'a self,
+        prompt: &'a Message,
+        answers: &'a [String],
+    ) -> BoxFuture<'a, Result<usize, PromptError>> {
+        Box::pin(async move {
+            let reply = self
+                .judge
+                .prompt_dyn(Message::user(Self::judge_prompt(prompt, answers)))
+                .await?;
+            parse_choice(&reply, answers.len()).ok_or_else(|| {
+                PromptError::CompletionError(CompletionError::ResponseError(format!(
+                    "评审模型的回复中没有有效的编号: {reply}"
+                )))
+            })
+        })
+    }
+}
+
+/// 投票结果
+#[derive(Debug)]
+pub struct ConsensusOutcome {
+    /// 选中的回答
+    pub answer: String,
+    /// 选中回答的 agent
+    pub agent: AgentInfo,
+    /// 与选中回答完全相同的回答数
+    pub votes: usize,
+    /// 各 agent 的原始结果，用于审计
+    pub responses: Vec<(AgentInfo, Result<String, PromptError>)>,
+}
+
+/// 多模型投票代理
+#[derive(Clone)]
+pub struct ConsensusAgent {
+    agent: RandAgent,
+    k: usize,
+    aggregator: Arc<dyn Aggregator>,
+}
+
+impl ConsensusAgent {
+    /// 每次向 `k` 个不同的 agent 提问，默认使用 [`MajorityVote`]
+    pub fn new(agent: RandAgent, k: usize) -> Self {
+        Self {
+            agent,
+            k: k.max(1),
+            aggregator: Arc::new(MajorityVote),
+        }
+    }
+
+    /// 设置聚合方式
+    pub fn aggregator(mut self, aggregator: impl Aggregator + 'static) -> Self {
+        self.aggregator = Arc::new(aggregator);
+        self
+    }
+
+    /// 提问并返回投票结果和所有中间回答，全部 agent 失败时返回最后一个错误
+    pub async fn vote(
+        &self,
+        prompt: impl Into<Message> + Send,
+    ) -> Result<ConsensusOutcome, PromptError> {
+        let prompt = prompt.into();
+        let mut responses = self.agent.prompt_sample(prompt.clone(), self.k).await;
+
+        let (succeeded, answers): (Vec<usize>, Vec<String>) = responses
+            .iter()
+            .enumerate()
+            .filter_map(|(index, (_, result))| Some((index, result.as_ref().ok()?.clone())))
+            .unzip();
+        if answers.is_empty() {
+            return Err(match responses.pop() {
+                Some((_, Err(err))) => err,
+                _ => no_valid_agent_error(),
+            });
+        }
+
+        // 自定义聚合器返回越界下标时退回第一个回答
+        let chosen = self.aggregator.select(&prompt, &answers).await?;
+        let chosen = if chosen < answers.len() { chosen } else { 0 };
+        Ok(ConsensusOutcome {
+            votes: count_votes(&answers, &answers[chosen]),
+            agent: responses[succeeded[chosen]].0.clone(),
+            answer: answers[chosen].clone(),
+            responses,
+        })
+    }
+}
+
+impl Prompt for ConsensusAgent {
+    #[allow(refining_impl_trait)]
+    async fn prompt(&self, prompt: impl Into<Message> + Send) -> Result<String, PromptError> {
+        self.vote(prompt).await.map(|outcome| outcome.answer)
+    }
+}
+
+fn count_votes(answers: &[String], answer: &str) -> usize {
+    answers
+        .iter()
+        .filter(|other| other.trim() == answer.trim())
+        .count()
+}
+
+/// 解析评审模型回复中的第一个编号(从 1 开始)，返回下标
+fn parse_choice(reply: &str, count: usize) -> Option<usize> {
+    reply
+        .split(|c: char| !c.is_ascii_digit())
+        .filter_map(|number| number.parse::<usize>().ok())
+        .find(|number| (1..=count).contains(number))
+        .map(|number| number - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_majority_vote() {
+        let answers = vec![
+            "负面".to_string(),
+            "正面".to_string(),
+            " 正面\n".to_string(),
+        ];
+        let chosen = MajorityVote
+            .select(&Message::user("?"), &answers)
+            .await
+            .unwrap();
+        assert_eq!(chosen, 1);
+        assert_eq!(count_votes(&answers, &answers[chosen]), 2);
+
+        assert_eq!(parse_choice("最好的是候选回答 2。", 3), Some(1));
+        assert_eq!(parse_choice("5", 3), None);
+    }
+}
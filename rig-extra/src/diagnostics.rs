@@ -0,0 +1,359 @@
+//! 启动自检
+//!
+//! 在应用的就绪检查中运行，逐项检查配置、密钥、各提供方的连通性和工具密钥，
+//! 返回可序列化的报告，便于输出为 JSON 或在 `/readyz` 之类的接口中使用。
+//!
+//! ```rust,ignore
+//! use rig_extra::diagnostics::{self, Diagnostics};
+//!
+//! // 只检查代理配置
+//! let report = diagnostics::run_all(&configs).await;
+//! println!("{}", serde_json::to_string_pretty(&report)?);
+//!
+//! // 附加工具密钥和自定义检查(如 MCP 服务)
+//! let report = Diagnostics::new(configs)
+//!     .probe_timeout(Duration::from_secs(10))
+//!     .serpapi_key(serpapi_key)
+//!     .check("mcp:filesystem", async { mcp_client.list_tools().await.map(|_| ()).map_err(|e| e.to_string()) })
+//!     .run()
+//!     .await;
+//! if !report.healthy {
+//!     std::process::exit(1);
+//! }
+//! ```
+
+use crate::rand_agent::RandAgentBuilder;
+use crate::redact::redact;
+use crate::simple_rand_builder::{AgentConfig, ProviderEnum};
+use futures::future::BoxFuture;
+use rig::completion::Prompt;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+/// 默认的连通性检查超时
+pub const DEFAULT_PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 检查类别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckKind {
+    /// 配置项
+    Config,
+    /// api key 等密钥
+    Secret,
+    /// 向提供方发送最小请求
+    Provider,
+    /// 工具密钥
+    Tool,
+    /// 自定义检查
+    Custom,
+}
+
+/// 检查结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    /// 不影响使用，但需要关注
+    Warn,
+    Fail,
+}
+
+/// 单项检查
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub name: String,
+    pub kind: CheckKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<i32>,
+    pub status: CheckStatus,
+    /// 说明或错误信息(已脱敏)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+}
+
+impl CheckResult {
+    fn new(name: impl Into<String>, kind: CheckKind, agent_id: Option<i32>) -> Self {
+        Self {
+            name: name.into(),
+            kind,
+            agent_id,
+            status: CheckStatus::Pass,
+            message: None,
+            latency_ms: None,
+        }
+    }
+
+    fn with_status(mut self, status: CheckStatus, message: impl Into<String>) -> Self {
+        self.status = status;
+        self.message = Some(redact(&message.into()));
+        self
+    }
+}
+
+/// 自检报告
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticsReport {
+    /// 没有失败项时为 true，警告不影响结果
+    pub healthy: bool,
+    pub checks: Vec<CheckResult>,
+}
+
+impl DiagnosticsReport {
+    fn new(checks: Vec<CheckResult>) -> Self {
+        Self {
+            healthy: checks.iter().all(|check| check.status != CheckStatus::Fail),
+            checks,
+        }
+    }
+
+    /// 失败的检查项
+    pub fn failures(&self) -> impl Iterator<Item = &CheckResult> {
+        self.checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Fail)
+    }
+}
+
+type CustomCheck = BoxFuture<'static, Result<(), String>>;
+
+/// 自检构建器
+pub struct Diagnostics {
+    configs: Vec<AgentConfig>,
+    probe: bool,
+    probe_timeout: Duration,
+    #[cfg(feature = "rig-extra-tools")]
+    serpapi_key: Option<String>,
+    custom: Vec<(String, CustomCheck)>,
+}
+
+impl Diagnostics {
+    pub fn new(configs: Vec<AgentConfig>) -> Self {
+        Self {
+            configs,
+            probe: true,
+            probe_timeout: DEFAULT_PROBE_TIMEOUT,
+            #[cfg(feature = "rig-extra-tools")]
+            serpapi_key: None,
+            custom: Vec::new(),
+        }
+    }
+
+    /// 是否向提供方发送请求，关闭后只做本地检查
+    pub fn probe(mut self, probe: bool) -> Self {
+        self.probe = probe;
+        self
+    }
+
+    /// 单个提供方连通性检查的超时
+    pub fn probe_timeout(mut self, timeout: Duration) -> Self {
+        self.probe_timeout = timeout;
+        self
+    }
+
+    /// 检查 SerpAPI 密钥是否有效及剩余额度
+    #[cfg(feature = "rig-extra-tools")]
+    pub fn serpapi_key(mut self, api_key: impl Into<String>) -> Self {
+        self.serpapi_key = Some(api_key.into());
+        self
+    }
+
+    /// 添加自定义检查，如 MCP 服务是否可用，返回错误信息表示失败
+    pub fn check<F>(mut self, name: impl Into<String>, check: F) -> Self
+    where
+        F: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        self.custom.push((name.into(), Box::pin(check)));
+        self
+    }
+
+    /// 执行所有检查，提供方和自定义检查并发进行
+    pub async fn run(self) -> DiagnosticsReport {
+        let mut checks = validate_configs(&self.configs);
+        let invalid: HashSet<i32> = checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Fail)
+            .filter_map(|check| check.agent_id)
+            .collect();
+
+        let probe_timeout = self.probe_timeout;
+        let probes = self
+            .configs
+            .into_iter()
+            .filter(|config| self.probe && !invalid.contains(&config.id))
+            .map(|config| probe_agent(config, probe_timeout));
+        checks.extend(futures::future::join_all(probes).await);
+
+        #[cfg(feature = "rig-extra-tools")]
+        if let Some(api_key) = self.serpapi_key {
+            checks.push(check_serpapi(&api_key).await);
+        }
+
+        let custom = self.custom.into_iter().map(|(name, check)| async move {
+            let start = Instant::now();
+            let result = check.await;
+            let mut check = CheckResult::new(name, CheckKind::Custom, None);
+            check.latency_ms = Some(start.elapsed().as_millis() as u64);
+            match result {
+                Ok(()) => check,
+                Err(err) => check.with_status(CheckStatus::Fail, err),
+            }
+        });
+        checks.extend(futures::future::join_all(custom).await);
+
+        DiagnosticsReport::new(checks)
+    }
+}
+
+/// 使用默认设置检查代理配置并探测每个提供方
+pub async fn run_all(configs: &[AgentConfig]) -> DiagnosticsReport {
+    Diagnostics::new(configs.to_vec()).run().await
+}
+
+/// 本地检查配置项和密钥
+fn validate_configs(configs: &[AgentConfig]) -> Vec<CheckResult> {
+    let mut checks = Vec::new();
+    let mut seen = HashSet::new();
+    for config in configs {
+        let name = format!("{}:{}", config.provider, config.model_name);
+        let mut check = CheckResult::new(&name, CheckKind::Config, Some(config.id));
+        let mut problems = Vec::new();
+        if !seen.insert(config.id) {
+            problems.push(format!("id {} 重复", config.id));
+        }
+        if config.model_name.trim().is_empty() {
+            problems.push("model_name 为空".to_string());
+        }
+        if let Some(url) = &config.api_base_url
+            && reqwest::Url::parse(url).is_err()
+        {
+            problems.push(format!("api_base_url 无效: {url}"));
+        }
+        if config.header_map().len() < config.headers.len() + config.user_agent.iter().count() {
+            problems.push("存在无效的请求头".to_string());
+        }
+        if !problems.is_empty() {
+            check = check.with_status(CheckStatus::Fail, problems.join("; "));
+        }
+        checks.push(check);
+
+        let mut secret = CheckResult::new(&name, CheckKind::Secret, Some(config.id));
+        let keys = config.keys();
+        if keys.iter().all(|key| key.trim().is_empty())
+            && !matches!(config.provider, ProviderEnum::Ollama)
+        {
+            secret = secret.with_status(CheckStatus::Fail, "api_key 为空");
+        } else if keys.iter().any(|key| key.trim() != *key) {
+            secret = secret.with_status(CheckStatus::Warn, "api_key 首尾包含空白字符");
+        }
+        checks.push(secret);
+    }
+    checks
+}
+
+/// 向提供方发送最小请求
+async fn probe_agent(config: AgentConfig, timeout: Duration) -> CheckResult {
+    let mut check = CheckResult::new(
+        format!("{}:{}", config.provider, config.model_name),
+        CheckKind::Provider,
+        Some(config.id),
+    );
+    let rand_agent = RandAgentBuilder::new()
+        .prompt_timeout(timeout)
+        .simple_builder(vec![config], "只回复 ok".to_string())
+        .build();
+    if rand_agent.is_empty().await {
+        return check.with_status(CheckStatus::Fail, "客户端构建失败");
+    }
+
+    let start = Instant::now();
+    let result = rand_agent.prompt("ping").await;
+    check.latency_ms = Some(start.elapsed().as_millis() as u64);
+    match result {
+        Ok(_) => check,
+        Err(err) => check.with_status(CheckStatus::Fail, err.to_string()),
+    }
+}
+
+/// 通过账户接口检查 SerpAPI 密钥和剩余搜索次数，不消耗额度
+#[cfg(feature = "rig-extra-tools")]
+async fn check_serpapi(api_key: &str) -> CheckResult {
+    let mut check = CheckResult::new("serpapi", CheckKind::Tool, None);
+    let start = Instant::now();
+    let response = reqwest::Client::new()
+        .get("https://serpapi.com/account")
+        .query(&[("api_key", api_key)])
+        .send()
+        .await;
+    check.latency_ms = Some(start.elapsed().as_millis() as u64);
+
+    let account: serde_json::Value = match response {
+        Ok(response) if response.status().is_success() => match response.json().await {
+            Ok(account) => account,
+            Err(err) => return check.with_status(CheckStatus::Fail, err.to_string()),
+        },
+        Ok(response) => {
+            return check.with_status(CheckStatus::Fail, format!("HTTP {}", response.status()));
+        }
+        Err(err) => return check.with_status(CheckStatus::Fail, err.to_string()),
+    };
+    match account["total_searches_left"].as_i64() {
+        Some(0) => check.with_status(CheckStatus::Fail, "搜索额度已用完"),
+        Some(left) => check.with_status(CheckStatus::Pass, format!("剩余 {left} 次搜索")),
+        None => check.with_status(CheckStatus::Warn, "无法读取剩余搜索次数"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(id: i32, provider: &str, model_name: &str, api_key: &str) -> AgentConfig {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "provider": provider,
+            "model_name": model_name,
+            "api_key": api_key,
+            "api_base_url": null,
+            "system_prompt": null,
+            "agent_name": null,
+            "max_failures": null,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_local_checks() {
+        let report = Diagnostics::new(vec![
+            config(1, "ollama", "qwen3:8b", ""),
+            config(1, "openai", "gpt-4o-mini", ""),
+        ])
+        .probe(false)
+        .check("mcp:filesystem", async {
+            Err("连接被拒绝".to_string())
+        })
+        .run()
+        .await;
+
+        assert!(!report.healthy);
+        let failures: Vec<_> = report
+            .failures()
+            .map(|check| (check.kind, check.agent_id))
+            .collect();
+        assert_eq!(
+            failures,
+            vec![
+                (CheckKind::Config, Some(1)),
+                (CheckKind::Secret, Some(1)),
+                (CheckKind::Custom, None)
+            ]
+        );
+        // 第一个配置合法，ollama 不需要 api key
+        assert_eq!(report.checks[0].status, CheckStatus::Pass);
+        assert_eq!(report.checks[1].status, CheckStatus::Pass);
+    }
+}
@@ -0,0 +1,134 @@
+//! 统一的动态 agent 接口
+//!
+//! [`DynPromptAgent`] 同时由单个 agent([`BoxAgent`])和代理池([`RandAgent`])实现，
+//! 应用代码可以直接接收 `Arc<dyn DynPromptAgent>`，例如测试中使用单个 agent，生产环境使用代理池，
+//! 而不需要在各处引入泛型参数。
+//!
+//! ```rust,ignore
+//! use rig_extra::dyn_agent::DynPromptAgent;
+//! use std::sync::Arc;
+//!
+//! async fn answer(agent: Arc<dyn DynPromptAgent>) -> Result<String, PromptError> {
+//!     agent.prompt_dyn("你好".into()).await
+//! }
+//! ```
+
+use crate::error::RandAgentError;
+use crate::rand_agent::RandAgent;
+use futures::StreamExt;
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use rig::agent::MultiTurnStreamItem;
+use rig::client::builder::{BoxAgent, FinalCompletionResponse};
+use rig::completion::{Chat, Message, Prompt, PromptError};
+use rig::streaming::StreamingPrompt;
+
+/// 动态流式响应的数据项
+pub type DynStreamItem = Result<MultiTurnStreamItem<FinalCompletionResponse>, RandAgentError>;
+
+/// 动态流式响应
+pub type DynStream = BoxStream<'static, DynStreamItem>;
+
+/// 支持单轮提问、多轮对话和流式输出的 agent trait object
+pub trait DynPromptAgent: Send + Sync {
+    /// 单轮提问
+    fn prompt_dyn(&self, prompt: Message) -> BoxFuture<'_, Result<String, PromptError>>;
+
+    /// 携带历史记录的多轮对话
+    fn chat_dyn(
+        &self,
+        prompt: Message,
+        chat_history: Vec<Message>,
+    ) -> BoxFuture<'_, Result<String, PromptError>>;
+
+    /// 流式提问
+    fn stream_dyn(&self, prompt: Message) -> BoxFuture<'_, Result<DynStream, RandAgentError>>;
+}
+
+impl DynPromptAgent for BoxAgent<'static> {
+    fn prompt_dyn(&self, prompt: Message) -> BoxFuture<'_, Result<String, PromptError>> {
+        Box::pin(async move { self.prompt(prompt).await })
+    }
+
+    fn chat_dyn(
+        &self,
+        prompt: Message,
+        chat_history: Vec<Message>,
+    ) -> BoxFuture<'_, Result<String, PromptError>> {
+        Box::pin(async move { self.chat(prompt, chat_history).await })
+    }
+
+    fn stream_dyn(&self, prompt: Message) -> BoxFuture<'_, Result<DynStream, RandAgentError>> {
+        Box::pin(async move {
+            let stream = self.stream_prompt(prompt).await;
+            Ok(stream
+                .map(|item| item.map_err(|err| RandAgentError::AgentError(Box::new(err))))
+                .boxed())
+        })
+    }
+}
+
+impl DynPromptAgent for RandAgent {
+    fn prompt_dyn(&self, prompt: Message) -> BoxFuture<'_, Result<String, PromptError>> {
+        Box::pin(async move { Prompt::prompt(self, prompt).await })
+    }
+
+    fn chat_dyn(
+        &self,
+        prompt: Message,
+        chat_history: Vec<Message>,
+    ) -> BoxFuture<'_, Result<String, PromptError>> {
+        Box::pin(async move { Chat::chat(self, prompt, chat_history).await })
+    }
+
+    fn stream_dyn(&self, prompt: Message) -> BoxFuture<'_, Result<DynStream, RandAgentError>> {
+        Box::pin(async move { self.stream_prompt(prompt).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rand_agent::tests::{FakeModel, fake_pool};
+
+    #[tokio::test]
+    async fn test_box_agent_as_dyn() {
+        let model = FakeModel::ok();
+        let agent: Box<dyn DynPromptAgent> = Box::new(model.agent());
+
+        assert_eq!(agent.prompt_dyn("你好".into()).await.unwrap(), "reply 1");
+        let history = vec![Message::user("你好"), Message::assistant("reply 1")];
+        assert_eq!(
+            agent.chat_dyn("再见".into(), history).await.unwrap(),
+            "reply 2"
+        );
+        assert_eq!(model.calls(), 2);
+
+        // 假模型不支持流式，错误通过流返回
+        let mut stream = agent.stream_dyn("你好".into()).await.unwrap();
+        assert!(stream.next().await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rand_agent_as_dyn() {
+        let models = [FakeModel::failing(), FakeModel::ok()];
+        let pool = fake_pool(&models, |builder| builder.max_failures(1));
+        let agent: Box<dyn DynPromptAgent> = Box::new(pool.clone());
+
+        // 失败的 agent 最多被选中一次，标记为无效后请求都由另一个 agent 处理
+        let mut succeeded = 0;
+        for _ in 0..4 {
+            if agent.prompt_dyn("你好".into()).await.is_ok() {
+                succeeded += 1;
+            }
+        }
+        assert!(succeeded >= 3);
+        assert!(models[0].calls() <= 1);
+        let history = vec![Message::user("你好"), Message::assistant("reply 1")];
+        assert!(agent.chat_dyn("再见".into(), history).await.is_ok());
+        assert_eq!(models[1].calls(), succeeded + 1);
+
+        let mut stream = agent.stream_dyn("你好".into()).await.unwrap();
+        assert!(stream.next().await.unwrap().is_err());
+    }
+}
@@ -1,12 +1,56 @@
+use crate::i18n::MessageKey;
+use crate::redact::redact;
 use rig::completion::PromptError;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum RandAgentError {
-    #[error("No valid agents available")]
+    #[error("{}", MessageKey::NoValidAgents.text())]
     NoValidAgents,
-    #[error("Agent error: {0}")]
+    #[error("{}: {}", MessageKey::AgentError.text(), redact(&_0.to_string()))]
     AgentError(#[from] Box<dyn std::error::Error + Send + Sync>),
-    #[error("PromptError error: {0}")]
-    PromptError(#[from] PromptError),
+    /// rig 的 PromptError 较大，装箱后 `Result<_, RandAgentError>` 不会因此变大
+    ///
+    /// 注意: 变体内容为 `Box<PromptError>`，由 `PromptError` 构造时使用 `RandAgentError::from` 或 `?`
+    #[error("{}: {}", MessageKey::PromptError.text(), redact(&_0.to_string()))]
+    PromptError(#[source] Box<PromptError>),
+    /// 所有可用 agent 都达到了速率限制
+    #[error("{}: {retry_after:?}", MessageKey::RateLimited.text())]
+    RateLimited { retry_after: std::time::Duration },
+    /// 租户超出额度，见 [`crate::tenant`]
+    #[error(transparent)]
+    TenantLimitExceeded(#[from] crate::tenant::TenantLimitExceeded),
+    /// 代理池已调用 `shutdown`，不再接受新请求
+    #[error("{}", MessageKey::ShuttingDown.text())]
+    ShuttingDown,
+}
+
+impl From<PromptError> for RandAgentError {
+    fn from(err: PromptError) -> Self {
+        RandAgentError::PromptError(Box::new(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::redact::register_secret;
+    use rig::completion::CompletionError;
+
+    #[test]
+    fn test_display_redacts_registered_secret() {
+        register_secret("error-test-secret-9f3a2c");
+
+        let err = RandAgentError::from(PromptError::CompletionError(
+            CompletionError::ProviderError("401 invalid key error-test-secret-9f3a2c".into()),
+        ));
+        let message = err.to_string();
+        assert!(!message.contains("error-test-secret-9f3a2c"), "{message}");
+        assert!(message.contains("401 invalid key ***"), "{message}");
+
+        let err = RandAgentError::AgentError("key error-test-secret-9f3a2c rejected".into());
+        let message = err.to_string();
+        assert!(!message.contains("error-test-secret-9f3a2c"), "{message}");
+        assert!(message.ends_with("key *** rejected"), "{message}");
+    }
 }
@@ -1,68 +1,218 @@
 use http::header;
 use reqwest::Method;
 use reqwest::header::HeaderValue;
-use rig::client::{AsEmbeddings, AsTranscription, CompletionClient, ProviderClient, ProviderValue};
+use rig::client::{
+    AsTranscription, CompletionClient, EmbeddingsClient, ProviderClient, ProviderValue,
+};
 use rig::completion::{CompletionError, CompletionRequest};
-use rig::message::{MessageError, Text};
+use rig::message::{DocumentSourceKind, MessageError, Text};
 use rig::providers::openai;
-use rig::{OneOrMany, client, completion, http_client, message};
+use rig::{OneOrMany, client, completion, embeddings, http_client, message};
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
 
 use crate::json_utils;
 use crate::json_utils::merge;
+use crate::redact::redact;
 use rig::providers::openai::send_compatible_streaming_request;
 use rig::streaming::StreamingCompletionResponse;
+use std::sync::Arc;
 use tracing::{Instrument, info_span};
 
 // ================================================================
 // BIGMODEL 客户端
 // ================================================================
 const BIGMODEL_API_BASE_URL: &str = "https://open.bigmodel.cn/api/paas/v4/";
+const BIGMODEL_COMPLETIONS_PATH: &str = "chat/completions";
+
+/// 请求发送前调用，可直接修改 JSON 请求体，如添加文档中未列出的参数
+pub type RequestHook = Arc<dyn Fn(&mut Value) + Send + Sync + 'static>;
+
+/// 收到非流式响应后、解析前调用，可用于记录原始响应
+pub type ResponseHook = Arc<dyn Fn(&Value) + Send + Sync + 'static>;
+
+/// 请求/响应钩子
+#[derive(Clone, Default)]
+struct Hooks {
+    request: Option<RequestHook>,
+    response: Option<ResponseHook>,
+}
+
+impl std::fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Hooks")
+            .field("request", &self.request.is_some())
+            .field("response", &self.response.is_some())
+            .finish()
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct Client {
     api_key: String,
     base_url: String,
+    completions_path: String,
     default_headers: http_client::HeaderMap,
     http_client: reqwest::Client,
+    hooks: Hooks,
 }
 
-impl Client {
+/// 客户端构建器，用于设置超时、代理或使用自定义的 reqwest 客户端
+///
+/// ```rust,ignore
+/// let client = Client::builder(&api_key)
+///     .timeout(Duration::from_secs(60))
+///     .proxy(reqwest::Proxy::all("http://proxy.example.com:8080")?)
+///     .build()?;
+/// ```
+#[derive(Debug)]
+pub struct ClientBuilder {
+    api_key: String,
+    base_url: String,
+    completions_path: String,
+    default_headers: http_client::HeaderMap,
+    timeout: Option<std::time::Duration>,
+    proxy: Option<reqwest::Proxy>,
+    http_client: Option<reqwest::Client>,
+}
+
+impl ClientBuilder {
     pub fn new(api_key: &str) -> Self {
-        Self::from_url(api_key, BIGMODEL_API_BASE_URL)
+        Self {
+            api_key: api_key.to_string(),
+            base_url: BIGMODEL_API_BASE_URL.to_string(),
+            completions_path: BIGMODEL_COMPLETIONS_PATH.to_string(),
+            default_headers: http_client::HeaderMap::new(),
+            timeout: None,
+            proxy: None,
+            http_client: None,
+        }
     }
 
-    pub fn from_url(api_key: &str, base_url: &str) -> Self {
-        let mut default_headers = reqwest::header::HeaderMap::new();
+    /// 接口地址，结尾有无 `/` 均可
+    pub fn base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.to_string();
+        self
+    }
+
+    /// 对话补全接口相对于 `base_url` 的路径，默认 `chat/completions`，
+    /// 用于路径不同的 OpenAI 兼容网关
+    pub fn completions_path(mut self, path: &str) -> Self {
+        self.completions_path = path.to_string();
+        self
+    }
+
+    /// 每个请求附带的请求头，同名请求头会被覆盖
+    pub fn default_headers(mut self, headers: http_client::HeaderMap) -> Self {
+        self.default_headers.extend(headers);
+        self
+    }
+
+    /// 整个请求(含读取响应)的超时时间，使用自定义客户端时无效
+    pub fn timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// 使用代理，使用自定义客户端时无效
+    pub fn proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// 使用自定义的 reqwest 客户端，认证请求头由本客户端添加
+    pub fn custom_client(mut self, client: reqwest::Client) -> Self {
+        self.http_client = Some(client);
+        self
+    }
+
+    pub fn build(self) -> Result<Client, reqwest::Error> {
+        let http_client = match self.http_client {
+            Some(client) => client,
+            None => {
+                let mut builder = reqwest::Client::builder();
+                if let Some(timeout) = self.timeout {
+                    builder = builder.timeout(timeout);
+                }
+                if let Some(proxy) = self.proxy {
+                    builder = builder.proxy(proxy);
+                }
+                builder.build()?
+            }
+        };
+
+        let mut default_headers = http_client::HeaderMap::new();
         default_headers.insert(
             reqwest::header::CONTENT_TYPE,
-            "application/json".parse().unwrap(),
+            HeaderValue::from_static("application/json"),
         );
+        default_headers.extend(self.default_headers);
 
-        Self {
-            api_key: api_key.to_string(),
-            base_url: base_url.to_string(),
+        Ok(Client {
+            api_key: self.api_key,
+            base_url: self.base_url,
+            completions_path: self.completions_path,
             default_headers,
-            http_client: reqwest::Client::builder()
-                .default_headers({
-                    let mut headers = reqwest::header::HeaderMap::new();
-                    headers.insert(
-                        "Authorization",
-                        format!("Bearer {api_key}")
-                            .parse()
-                            .expect("Bearer token should parse"),
-                    );
-                    headers
-                })
-                .build()
-                .expect("bigmodel reqwest client should build"),
-        }
+            http_client,
+            hooks: Hooks::default(),
+        })
+    }
+}
+
+impl Client {
+    pub fn new(api_key: &str) -> Self {
+        Self::from_url(api_key, BIGMODEL_API_BASE_URL)
+    }
+
+    pub fn from_url(api_key: &str, base_url: &str) -> Self {
+        Self::builder(api_key)
+            .base_url(base_url)
+            .build()
+            .expect("bigmodel reqwest client should build")
+    }
+
+    pub fn builder(api_key: &str) -> ClientBuilder {
+        ClientBuilder::new(api_key)
+    }
+
+    /// 添加自定义请求头，如 User-Agent 或署名用的 `X-Title`，同名请求头会被覆盖
+    pub fn with_headers(mut self, headers: http_client::HeaderMap) -> Self {
+        self.default_headers.extend(headers);
+        self
+    }
+
+    /// 设置请求钩子，该客户端创建的所有模型在发送请求前都会调用
+    ///
+    /// ```rust,ignore
+    /// let client = Client::new(&api_key).with_request_hook(|request| {
+    ///     request["do_sample"] = json!(false);
+    /// });
+    /// ```
+    pub fn with_request_hook(mut self, hook: impl Fn(&mut Value) + Send + Sync + 'static) -> Self {
+        self.hooks.request = Some(Arc::new(hook));
+        self
+    }
+
+    /// 设置响应钩子，该客户端创建的所有模型收到非流式响应后都会调用
+    pub fn with_response_hook(mut self, hook: impl Fn(&Value) + Send + Sync + 'static) -> Self {
+        self.hooks.response = Some(Arc::new(hook));
+        self
+    }
+
+    /// 拼接 `base_url` 和相对路径，两者之间恰好保留一个 `/`
+    fn url(&self, path: &str) -> String {
+        format!(
+            "{}/{}",
+            self.base_url.trim_end_matches('/'),
+            path.trim_start_matches('/')
+        )
     }
 
     fn post(&self, path: &str) -> reqwest::RequestBuilder {
-        let url = format!("{}/{}", self.base_url, path).replace("//", "/");
-        self.http_client.post(url)
+        self.http_client
+            .post(self.url(path))
+            .headers(self.default_headers.clone())
+            .bearer_auth(&self.api_key)
     }
 
     pub fn completion_model(&self, model: &str) -> CompletionModel {
@@ -100,7 +250,30 @@ impl ProviderClient for Client {
 
 impl AsTranscription for Client {}
 
-impl AsEmbeddings for Client {}
+// 暂不支持图片和音频生成，开启对应特性时提供空实现
+#[cfg(feature = "rig-image")]
+impl rig::client::AsImageGeneration for Client {}
+
+#[cfg(feature = "rig-audio")]
+impl rig::client::AsAudioGeneration for Client {}
+
+impl EmbeddingsClient for Client {
+    type EmbeddingModel = EmbeddingModel;
+
+    /// 未知模型的维度为 0，此时不发送 `dimensions` 参数
+    fn embedding_model(&self, model: &str) -> Self::EmbeddingModel {
+        let ndims = match model {
+            BIGMODEL_EMBEDDING_2 => 1024,
+            BIGMODEL_EMBEDDING_3 => 2048,
+            _ => 0,
+        };
+        EmbeddingModel::new(self.clone(), model, ndims)
+    }
+
+    fn embedding_model_with_ndims(&self, model: &str, ndims: usize) -> Self::EmbeddingModel {
+        EmbeddingModel::new(self.clone(), model, ndims)
+    }
+}
 
 impl CompletionClient for Client {
     type CompletionModel = CompletionModel;
@@ -126,7 +299,101 @@ enum ApiResponse<T> {
 // Bigmodel Completion API
 // ================================================================
 pub const BIGMODEL_GLM_4_FLASH: &str = "glm-4-flash";
+pub const BIGMODEL_GLM_4_FLASHX: &str = "glm-4-flashx";
+pub const BIGMODEL_GLM_4_PLUS: &str = "glm-4-plus";
+pub const BIGMODEL_GLM_4_AIR: &str = "glm-4-air";
+pub const BIGMODEL_GLM_4_AIRX: &str = "glm-4-airx";
+pub const BIGMODEL_GLM_4_LONG: &str = "glm-4-long";
+pub const BIGMODEL_GLM_4V: &str = "glm-4v";
+pub const BIGMODEL_GLM_4V_PLUS: &str = "glm-4v-plus";
+pub const BIGMODEL_GLM_4V_FLASH: &str = "glm-4v-flash";
+pub const BIGMODEL_GLM_4_5: &str = "glm-4.5";
+pub const BIGMODEL_GLM_4_5_AIR: &str = "glm-4.5-air";
+pub const BIGMODEL_GLM_4_5_AIRX: &str = "glm-4.5-airx";
+pub const BIGMODEL_GLM_4_5_X: &str = "glm-4.5-x";
 pub const BIGMODEL_GLM_4_5_FLASH: &str = "glm-4.5-flash";
+pub const BIGMODEL_GLM_4_5V: &str = "glm-4.5v";
+pub const BIGMODEL_GLM_Z1_AIR: &str = "glm-z1-air";
+pub const BIGMODEL_GLM_Z1_AIRX: &str = "glm-z1-airx";
+pub const BIGMODEL_GLM_Z1_FLASH: &str = "glm-z1-flash";
+
+/// 模型输入模态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Modality {
+    /// 纯文本
+    Text,
+    /// 文本 + 图片
+    Vision,
+}
+
+/// 模型元数据
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ModelInfo {
+    pub name: &'static str,
+    /// 上下文长度(tokens)
+    pub context_length: u32,
+    pub modality: Modality,
+    /// 输入价格(元/百万tokens)
+    pub input_price: f64,
+    /// 输出价格(元/百万tokens)
+    pub output_price: f64,
+}
+
+impl ModelInfo {
+    const fn new(
+        name: &'static str,
+        context_length: u32,
+        modality: Modality,
+        input_price: f64,
+        output_price: f64,
+    ) -> Self {
+        Self {
+            name,
+            context_length,
+            modality,
+            input_price,
+            output_price,
+        }
+    }
+
+    /// 是否免费
+    pub fn is_free(&self) -> bool {
+        self.input_price == 0.0 && self.output_price == 0.0
+    }
+}
+
+/// 价格参考智谱开放平台价格页，按输入长度分档计费的模型取最低档，仅供参考
+const MODELS: &[ModelInfo] = &[
+    ModelInfo::new(BIGMODEL_GLM_4_FLASH, 128_000, Modality::Text, 0.0, 0.0),
+    ModelInfo::new(BIGMODEL_GLM_4_FLASHX, 128_000, Modality::Text, 0.1, 0.1),
+    ModelInfo::new(BIGMODEL_GLM_4_PLUS, 128_000, Modality::Text, 5.0, 5.0),
+    ModelInfo::new(BIGMODEL_GLM_4_AIR, 128_000, Modality::Text, 0.5, 0.5),
+    ModelInfo::new(BIGMODEL_GLM_4_AIRX, 8_000, Modality::Text, 10.0, 10.0),
+    ModelInfo::new(BIGMODEL_GLM_4_LONG, 1_000_000, Modality::Text, 1.0, 1.0),
+    ModelInfo::new(BIGMODEL_GLM_4V, 8_000, Modality::Vision, 50.0, 50.0),
+    ModelInfo::new(BIGMODEL_GLM_4V_PLUS, 8_000, Modality::Vision, 4.0, 4.0),
+    ModelInfo::new(BIGMODEL_GLM_4V_FLASH, 8_000, Modality::Vision, 0.0, 0.0),
+    ModelInfo::new(BIGMODEL_GLM_4_5, 128_000, Modality::Text, 0.8, 2.0),
+    ModelInfo::new(BIGMODEL_GLM_4_5_AIR, 128_000, Modality::Text, 0.8, 2.0),
+    ModelInfo::new(BIGMODEL_GLM_4_5_AIRX, 128_000, Modality::Text, 4.0, 12.0),
+    ModelInfo::new(BIGMODEL_GLM_4_5_X, 128_000, Modality::Text, 8.0, 16.0),
+    ModelInfo::new(BIGMODEL_GLM_4_5_FLASH, 128_000, Modality::Text, 0.0, 0.0),
+    ModelInfo::new(BIGMODEL_GLM_4_5V, 64_000, Modality::Vision, 2.0, 6.0),
+    ModelInfo::new(BIGMODEL_GLM_Z1_AIR, 128_000, Modality::Text, 0.5, 0.5),
+    ModelInfo::new(BIGMODEL_GLM_Z1_AIRX, 32_000, Modality::Text, 5.0, 5.0),
+    ModelInfo::new(BIGMODEL_GLM_Z1_FLASH, 128_000, Modality::Text, 0.0, 0.0),
+];
+
+/// 获取所有已知模型的元数据
+pub fn models() -> &'static [ModelInfo] {
+    MODELS
+}
+
+/// 根据模型名称获取元数据
+pub fn model_info(name: &str) -> Option<&'static ModelInfo> {
+    MODELS.iter().find(|model| model.name == name)
+}
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -138,18 +405,99 @@ pub struct CompletionResponse {
     #[serde(rename = "request_id")]
     pub request_id: String,
     pub usage: Usage,
+    /// 开启联网搜索时返回的搜索结果，可用于引用来源
+    #[serde(default, rename = "web_search", skip_serializing_if = "Vec::is_empty")]
+    pub web_search: Vec<WebSearchResult>,
+    /// 推理模型的思考过程，从 `reasoning_content` 或回复开头的 `<think>` 标签中取出
+    #[serde(skip)]
+    pub reasoning: Option<String>,
+}
+
+impl CompletionResponse {
+    /// 把第一个回复中的思考过程移到 [`Self::reasoning`]，回复只保留最终答案
+    fn extract_reasoning(&mut self) {
+        let Some(Choice {
+            message:
+                Message::Assistant {
+                    content,
+                    reasoning_content,
+                    ..
+                },
+            ..
+        }) = self.choices.first_mut()
+        else {
+            return;
+        };
+        if let Some(reasoning) = reasoning_content.take() {
+            self.reasoning = Some(reasoning);
+            return;
+        }
+        let Some(text) = content.as_deref() else {
+            return;
+        };
+        let trimmed = text.trim_start();
+        if let Some(rest) = trimmed.strip_prefix("<think>")
+            && let Some((reasoning, answer)) = rest.split_once("</think>")
+        {
+            self.reasoning = Some(reasoning.trim().to_string());
+            *content = Some(answer.trim_start().to_string());
+        }
+    }
+
+    /// 把 [`Self::reasoning`] 以 `<think>` 标签放回第一个回复的开头
+    fn inline_reasoning(&mut self) {
+        let Some(reasoning) = &self.reasoning else {
+            return;
+        };
+        if let Some(Choice {
+            message: Message::Assistant { content, .. },
+            ..
+        }) = self.choices.first_mut()
+        {
+            let answer = content.as_deref().unwrap_or_default();
+            *content = Some(format!("<think>\n{reasoning}\n</think>\n{answer}"));
+        }
+    }
+}
+
+/// 推理模型的思考过程如何出现在最终回复中
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ThinkingOutput {
+    /// 从回复中去掉，只保存在 [`CompletionResponse::reasoning`]
+    #[default]
+    Strip,
+    /// 以 `<think>...</think>` 的形式放在回复开头
+    Inline,
+}
+
+/// 联网搜索结果
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct WebSearchResult {
+    pub title: String,
+    pub content: String,
+    pub link: String,
+    /// 网站名称
+    pub media: String,
+    pub icon: String,
+    /// 角标序号，如 `[ref_1]`
+    pub refer: String,
+    pub publish_date: String,
 }
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
 #[serde(tag = "role", rename_all = "lowercase")]
 pub enum Message {
     User {
-        content: String,
+        content: UserContent,
     },
     Assistant {
         content: Option<String>,
         #[serde(default, deserialize_with = "json_utils::null_or_vec")]
         tool_calls: Vec<ToolCall>,
+        /// 推理模型的思考过程
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reasoning_content: Option<String>,
     },
     System {
         content: String,
@@ -169,6 +517,41 @@ impl Message {
     }
 }
 
+/// 用户消息内容，纯文本时序列化为字符串，包含图片时序列化为内容数组(GLM-4V 等视觉模型)
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+#[serde(untagged)]
+pub enum UserContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text { text: String },
+    ImageUrl { image_url: ImageUrl },
+}
+
+/// 图片的 URL 或 base64 编码(不带 `data:` 前缀)
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+pub struct ImageUrl {
+    pub url: String,
+}
+
+impl TryFrom<message::Image> for ImageUrl {
+    type Error = MessageError;
+
+    fn try_from(image: message::Image) -> Result<Self, Self::Error> {
+        match image.data {
+            DocumentSourceKind::Url(url) | DocumentSourceKind::String(url) => Ok(Self { url }),
+            DocumentSourceKind::Base64(data) => Ok(Self { url: data }),
+            _ => Err(MessageError::ConversionError(
+                "Images must be a URL or base64 encoded".into(),
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct ToolResultContent {
     text: String,
@@ -192,13 +575,16 @@ impl TryFrom<message::Message> for Message {
     fn try_from(message: message::Message) -> Result<Self, Self::Error> {
         Ok(match message {
             message::Message::User { content } => {
-                let mut texts = Vec::new();
-                let mut images = Vec::new();
+                let mut parts = Vec::new();
 
                 for uc in content.into_iter() {
                     match uc {
-                        message::UserContent::Text(message::Text { text }) => texts.push(text),
-                        message::UserContent::Image(img) => images.push(img.data),
+                        message::UserContent::Text(message::Text { text }) => {
+                            parts.push(ContentPart::Text { text })
+                        }
+                        message::UserContent::Image(img) => parts.push(ContentPart::ImageUrl {
+                            image_url: img.try_into()?,
+                        }),
                         message::UserContent::ToolResult(result) => {
                             let content = result
                                 .content
@@ -221,11 +607,23 @@ impl TryFrom<message::Message> for Message {
                     }
                 }
 
-                let collapsed_content = texts.join(" ");
+                let has_image = parts
+                    .iter()
+                    .any(|part| matches!(part, ContentPart::ImageUrl { .. }));
+                let content = if has_image {
+                    UserContent::Parts(parts)
+                } else {
+                    let texts: Vec<String> = parts
+                        .into_iter()
+                        .filter_map(|part| match part {
+                            ContentPart::Text { text } => Some(text),
+                            ContentPart::ImageUrl { .. } => None,
+                        })
+                        .collect();
+                    UserContent::Text(texts.join(" "))
+                };
 
-                Message::User {
-                    content: collapsed_content,
-                }
+                Message::User { content }
             }
             message::Message::Assistant { content, .. } => {
                 let mut texts = Vec::new();
@@ -244,6 +642,7 @@ impl TryFrom<message::Message> for Message {
                 Message::Assistant {
                     content: Some(collapsed_content),
                     tool_calls,
+                    reasoning_content: None,
                 }
             }
         })
@@ -342,6 +741,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
             Message::Assistant {
                 tool_calls,
                 content,
+                ..
             } => {
                 if !tool_calls.is_empty() {
                     let tool_result = tool_calls
@@ -401,6 +801,60 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 pub struct CompletionModel {
     client: Client,
     pub model: String,
+    hooks: Hooks,
+    web_search: bool,
+    thinking: Option<bool>,
+    thinking_output: ThinkingOutput,
+    tool_choice: ToolChoice,
+    parallel_tool_calls: Option<bool>,
+}
+
+/// 工具调用方式
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ToolChoice {
+    /// 由模型决定是否调用工具
+    #[default]
+    Auto,
+    /// 不调用工具
+    None,
+    /// 必须调用工具
+    Required,
+    /// 必须调用指定的函数
+    Function(String),
+}
+
+impl ToolChoice {
+    fn to_json(&self) -> Value {
+        match self {
+            ToolChoice::Auto => json!("auto"),
+            ToolChoice::None => json!("none"),
+            ToolChoice::Required => json!("required"),
+            ToolChoice::Function(name) => json!({
+                "type": "function",
+                "function": {"name": name},
+            }),
+        }
+    }
+}
+
+impl TryFrom<message::ToolChoice> for ToolChoice {
+    type Error = CompletionError;
+
+    fn try_from(value: message::ToolChoice) -> Result<Self, Self::Error> {
+        Ok(match value {
+            message::ToolChoice::Auto => ToolChoice::Auto,
+            message::ToolChoice::None => ToolChoice::None,
+            message::ToolChoice::Required => ToolChoice::Required,
+            message::ToolChoice::Specific { mut function_names } => {
+                if function_names.len() != 1 {
+                    return Err(CompletionError::ProviderError(
+                        "bigmodel only supports forcing a single function".to_string(),
+                    ));
+                }
+                ToolChoice::Function(function_names.remove(0))
+            }
+        })
+    }
 }
 
 // 函数定义
@@ -423,11 +877,59 @@ pub struct Function {
 impl CompletionModel {
     pub fn new(client: Client, model: &str) -> Self {
         Self {
+            hooks: client.hooks.clone(),
             client,
             model: model.to_string(),
+            web_search: false,
+            thinking: None,
+            thinking_output: ThinkingOutput::default(),
+            tool_choice: ToolChoice::default(),
+            parallel_tool_calls: None,
         }
     }
 
+    /// 默认的工具调用方式，请求中设置了 `tool_choice` 时以请求为准
+    pub fn with_tool_choice(mut self, tool_choice: ToolChoice) -> Self {
+        self.tool_choice = tool_choice;
+        self
+    }
+
+    /// 是否允许一次回复中并行调用多个工具，未设置时使用接口的默认行为
+    pub fn with_parallel_tool_calls(mut self, enable: bool) -> Self {
+        self.parallel_tool_calls = Some(enable);
+        self
+    }
+
+    /// 开启或关闭推理模型的思考，未设置时使用模型的默认行为
+    pub fn with_thinking(mut self, enable: bool) -> Self {
+        self.thinking = Some(enable);
+        self
+    }
+
+    /// 设置思考过程是否保留在最终回复中，默认去掉
+    pub fn with_thinking_output(mut self, output: ThinkingOutput) -> Self {
+        self.thinking_output = output;
+        self
+    }
+
+    /// 开启智谱内置的联网搜索工具，搜索结果见响应的 [`CompletionResponse::web_search`]
+    pub fn with_web_search(mut self, enable: bool) -> Self {
+        self.web_search = enable;
+        self
+    }
+
+    /// 设置仅对该模型生效的请求钩子，覆盖客户端上的设置
+    pub fn with_request_hook(mut self, hook: impl Fn(&mut Value) + Send + Sync + 'static) -> Self {
+        self.hooks.request = Some(Arc::new(hook));
+        self
+    }
+
+    /// 设置仅对该模型生效的响应钩子，覆盖客户端上的设置。流式请求不调用响应钩子
+    pub fn with_response_hook(mut self, hook: impl Fn(&Value) + Send + Sync + 'static) -> Self {
+        self.hooks.response = Some(Arc::new(hook));
+        self
+    }
+
     fn create_completion_request(
         &self,
         completion_request: CompletionRequest,
@@ -452,7 +954,7 @@ impl CompletionModel {
                 .collect::<Result<Vec<Message>, _>>()?,
         );
 
-        let request = if completion_request.tools.is_empty() {
+        let request = if completion_request.tools.is_empty() && !self.web_search {
             json!({
                 "model": self.model,
                 "messages": full_history,
@@ -460,7 +962,7 @@ impl CompletionModel {
             })
         } else {
             // tools
-            let tools = completion_request
+            let mut tools = completion_request
                 .tools
                 .into_iter()
                 .map(|item| {
@@ -469,30 +971,59 @@ impl CompletionModel {
                         description: item.description,
                         parameters: item.parameters,
                     };
-                    CustomFunctionDefinition {
+                    json!(CustomFunctionDefinition {
                         type_field: "function".to_string(),
                         function: custom_function,
-                    }
+                    })
                 })
                 .collect::<Vec<_>>();
+            if self.web_search {
+                tools.push(json!({
+                    "type": "web_search",
+                    "web_search": {
+                        "enable": true,
+                        "search_result": true,
+                    },
+                }));
+            }
 
             tracing::debug!("tools: {:?}", tools);
 
-            json!({
+            let tool_choice = match completion_request.tool_choice {
+                Some(tool_choice) => tool_choice.try_into()?,
+                None => self.tool_choice.clone(),
+            };
+            let mut request = json!({
                 "model": self.model,
                 "messages": full_history,
                 "temperature": completion_request.temperature,
                 "tools": tools,
-                "tool_choice": "auto",
-            })
+                "tool_choice": tool_choice.to_json(),
+            });
+            if let Some(parallel) = self.parallel_tool_calls {
+                request["parallel_tool_calls"] = json!(parallel);
+            }
+            request
         };
 
-        let request = if let Some(params) = completion_request.additional_params {
+        let request = match self.thinking {
+            Some(enable) => merge(
+                request,
+                json!({"thinking": {"type": if enable { "enabled" } else { "disabled" }}}),
+            ),
+            None => request,
+        };
+
+        let mut request = if let Some(params) = completion_request.additional_params {
             json_utils::merge(request, params)
         } else {
             request
         };
 
+        if let Some(hook) = &self.hooks.request {
+            hook(&mut request);
+        }
+
         Ok(request)
     }
 }
@@ -511,12 +1042,12 @@ impl completion::CompletionModel for CompletionModel {
 
         tracing::debug!(
             "request: \r\n {}",
-            serde_json::to_string_pretty(&request).unwrap()
+            redact(&serde_json::to_string_pretty(&request).unwrap())
         );
 
         let response = self
             .client
-            .post("/chat/completions")
+            .post(&self.client.completions_path)
             .json(&request)
             .send()
             .await
@@ -524,26 +1055,36 @@ impl completion::CompletionModel for CompletionModel {
 
         if response.status().is_success() {
             let data: Value = response.json().await.expect("api error");
-            tracing::debug!("response: {}", serde_json::to_string_pretty(&data).unwrap());
+            tracing::debug!(
+                "response: {}",
+                redact(&serde_json::to_string_pretty(&data).unwrap())
+            );
+            if let Some(hook) = &self.hooks.response {
+                hook(&data);
+            }
             let data: ApiResponse<CompletionResponse> =
                 serde_json::from_value(data).expect("deserialize completion response");
             match data {
-                ApiResponse::Ok(response) => {
+                ApiResponse::Ok(mut response) => {
                     tracing::info!(target: "rig",
                         "bigmodel completion token usage: {:?}",
                         response.usage
                     );
+                    response.extract_reasoning();
+                    if self.thinking_output == ThinkingOutput::Inline {
+                        response.inline_reasoning();
+                    }
                     response.try_into()
                 }
-                ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.message)),
+                ApiResponse::Err(err) => Err(CompletionError::ProviderError(redact(&err.message))),
             }
         } else {
-            Err(CompletionError::ProviderError(
-                response
+            Err(CompletionError::ProviderError(redact(
+                &response
                     .text()
                     .await
                     .map_err(|e| http_client::Error::Instance(e.into()))?,
-            ))
+            )))
         }
     }
 
@@ -559,11 +1100,7 @@ impl completion::CompletionModel for CompletionModel {
 
         let body = serde_json::to_vec(&request)?;
 
-        let url = format!(
-            "{}/{}",
-            self.client.base_url,
-            "/chat/completions".trim_start_matches('/')
-        );
+        let url = self.client.url(&self.client.completions_path);
 
         let mut builder = http_client::Builder::new().uri(url).method(Method::POST);
         for (header, value) in &self.client.default_headers {
@@ -605,3 +1142,428 @@ impl completion::CompletionModel for CompletionModel {
             .await
     }
 }
+
+// ================================================================
+// Bigmodel Embedding API
+// ================================================================
+/// `embedding-2`，固定 1024 维
+pub const BIGMODEL_EMBEDDING_2: &str = "embedding-2";
+/// `embedding-3`，默认 2048 维，可选 256/512/1024/2048
+pub const BIGMODEL_EMBEDDING_3: &str = "embedding-3";
+
+/// 单次请求最多的文本数
+const EMBEDDING_MAX_DOCUMENTS: usize = 64;
+
+#[derive(Debug, Deserialize)]
+pub struct EmbeddingResponse {
+    pub data: Vec<EmbeddingData>,
+    pub model: String,
+    pub usage: EmbeddingUsage,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmbeddingData {
+    pub embedding: Vec<f64>,
+    pub index: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EmbeddingUsage {
+    pub prompt_tokens: i64,
+    pub total_tokens: i64,
+}
+
+#[derive(Clone)]
+pub struct EmbeddingModel {
+    client: Client,
+    pub model: String,
+    ndims: usize,
+}
+
+impl EmbeddingModel {
+    pub fn new(client: Client, model: &str, ndims: usize) -> Self {
+        Self {
+            client,
+            model: model.to_string(),
+            ndims,
+        }
+    }
+
+    fn create_embedding_request(&self, documents: &[String]) -> Value {
+        let mut request = json!({
+            "model": self.model,
+            "input": documents,
+        });
+        // embedding-2 的维度固定，不接受 dimensions 参数
+        if self.ndims > 0 && self.model != BIGMODEL_EMBEDDING_2 {
+            request["dimensions"] = json!(self.ndims);
+        }
+        request
+    }
+
+    async fn embed_batch(
+        &self,
+        documents: Vec<String>,
+    ) -> Result<Vec<embeddings::Embedding>, embeddings::EmbeddingError> {
+        let request = self.create_embedding_request(&documents);
+        let response = self
+            .client
+            .post("embeddings")
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| http_client::Error::Instance(e.into()))?;
+
+        if !response.status().is_success() {
+            return Err(embeddings::EmbeddingError::ProviderError(redact(
+                &response
+                    .text()
+                    .await
+                    .map_err(|e| http_client::Error::Instance(e.into()))?,
+            )));
+        }
+
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| http_client::Error::Instance(e.into()))?;
+        match serde_json::from_slice::<ApiResponse<EmbeddingResponse>>(&body)? {
+            ApiResponse::Ok(response) => {
+                tracing::info!(target: "rig",
+                    "bigmodel embedding token usage: {:?}",
+                    response.usage
+                );
+                embeddings_from_response(response, documents)
+            }
+            ApiResponse::Err(err) => Err(embeddings::EmbeddingError::ProviderError(redact(
+                &err.message,
+            ))),
+        }
+    }
+}
+
+/// 按 index 与输入文本对应
+fn embeddings_from_response(
+    mut response: EmbeddingResponse,
+    documents: Vec<String>,
+) -> Result<Vec<embeddings::Embedding>, embeddings::EmbeddingError> {
+    if response.data.len() != documents.len() {
+        return Err(embeddings::EmbeddingError::ResponseError(
+            "Response data length does not match input length".into(),
+        ));
+    }
+    response.data.sort_by_key(|data| data.index);
+    Ok(response
+        .data
+        .into_iter()
+        .zip(documents)
+        .map(|(data, document)| embeddings::Embedding {
+            document,
+            vec: data.embedding,
+        })
+        .collect())
+}
+
+impl embeddings::EmbeddingModel for EmbeddingModel {
+    const MAX_DOCUMENTS: usize = EMBEDDING_MAX_DOCUMENTS;
+
+    fn ndims(&self) -> usize {
+        self.ndims
+    }
+
+    /// 超过单次请求上限时分批请求
+    async fn embed_texts(
+        &self,
+        documents: impl IntoIterator<Item = String> + Send,
+    ) -> Result<Vec<embeddings::Embedding>, embeddings::EmbeddingError> {
+        let documents: Vec<String> = documents.into_iter().collect();
+        let mut embeddings = Vec::with_capacity(documents.len());
+        for batch in documents.chunks(EMBEDDING_MAX_DOCUMENTS) {
+            embeddings.extend(self.embed_batch(batch.to_vec()).await?);
+        }
+        Ok(embeddings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_model_info() {
+        let long = model_info(BIGMODEL_GLM_4_LONG).unwrap();
+        assert_eq!(long.context_length, 1_000_000);
+        assert!(model_info(BIGMODEL_GLM_4_FLASH).unwrap().is_free());
+        assert_eq!(
+            model_info(BIGMODEL_GLM_4V).unwrap().modality,
+            Modality::Vision
+        );
+        assert!(model_info("glm-unknown").is_none());
+    }
+
+    #[test]
+    fn test_request_hook() {
+        let model = Client::new("test-key")
+            .with_request_hook(|request| request["do_sample"] = json!(false))
+            .completion_model(BIGMODEL_GLM_4_FLASH);
+        let request = CompletionRequest {
+            preamble: None,
+            chat_history: OneOrMany::one(message::Message::user("你好")),
+            documents: vec![],
+            tools: vec![],
+            temperature: None,
+            max_tokens: None,
+            tool_choice: None,
+            additional_params: None,
+        };
+
+        let request = model.create_completion_request(request).unwrap();
+        assert_eq!(request["do_sample"], json!(false));
+        assert_eq!(request["model"], json!(BIGMODEL_GLM_4_FLASH));
+    }
+
+    #[test]
+    fn test_client_builder() {
+        let mut headers = http_client::HeaderMap::new();
+        headers.insert("X-Title", HeaderValue::from_static("rig-extra"));
+        let client = Client::builder("test-key")
+            .base_url("https://gateway.example.com/v4")
+            .default_headers(headers)
+            .timeout(std::time::Duration::from_secs(5))
+            .custom_client(reqwest::Client::new())
+            .build()
+            .unwrap();
+
+        let request = client.post("chat/completions").build().unwrap();
+        assert_eq!(request.headers()["authorization"], "Bearer test-key");
+        assert_eq!(request.headers()["x-title"], "rig-extra");
+        assert_eq!(request.headers()["content-type"], "application/json");
+    }
+
+    #[test]
+    fn test_url_join() {
+        let client = Client::new("test-key");
+        assert_eq!(
+            client.url("/chat/completions"),
+            "https://open.bigmodel.cn/api/paas/v4/chat/completions"
+        );
+        let client = Client::from_url("test-key", "http://localhost:8080/v1");
+        assert_eq!(
+            client.url("embeddings"),
+            "http://localhost:8080/v1/embeddings"
+        );
+
+        let client = Client::builder("test-key")
+            .base_url("https://gateway.example.com/")
+            .completions_path("/openai/v1/chat")
+            .build()
+            .unwrap();
+        let request = client.post(&client.completions_path).build().unwrap();
+        assert_eq!(
+            request.url().as_str(),
+            "https://gateway.example.com/openai/v1/chat"
+        );
+    }
+
+    #[test]
+    fn test_vision_message() {
+        let text: Message = message::Message::user("你好").try_into().unwrap();
+        assert_eq!(
+            serde_json::to_value(text).unwrap(),
+            json!({"role": "user", "content": "你好"})
+        );
+
+        let vision: Message = message::Message::User {
+            content: OneOrMany::many(vec![
+                message::UserContent::image_url("https://example.com/a.png", None, None),
+                message::UserContent::text("图里有什么"),
+            ])
+            .unwrap(),
+        }
+        .try_into()
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(vision).unwrap(),
+            json!({
+                "role": "user",
+                "content": [
+                    {"type": "image_url", "image_url": {"url": "https://example.com/a.png"}},
+                    {"type": "text", "text": "图里有什么"}
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_tool_choice() {
+        let model = Client::new("test-key")
+            .completion_model(BIGMODEL_GLM_4_FLASH)
+            .with_tool_choice(ToolChoice::Required)
+            .with_parallel_tool_calls(false);
+        let request = |tool_choice| CompletionRequest {
+            preamble: None,
+            chat_history: OneOrMany::one(message::Message::user("现在几点")),
+            documents: vec![],
+            tools: vec![completion::ToolDefinition {
+                name: "now".to_string(),
+                description: "当前时间".to_string(),
+                parameters: json!({"type": "object", "properties": {}}),
+            }],
+            temperature: None,
+            max_tokens: None,
+            tool_choice,
+            additional_params: None,
+        };
+
+        let default = model.create_completion_request(request(None)).unwrap();
+        assert_eq!(default["tool_choice"], json!("required"));
+        assert_eq!(default["parallel_tool_calls"], json!(false));
+
+        let specific = model
+            .create_completion_request(request(Some(message::ToolChoice::Specific {
+                function_names: vec!["now".to_string()],
+            })))
+            .unwrap();
+        assert_eq!(
+            specific["tool_choice"],
+            json!({"type": "function", "function": {"name": "now"}})
+        );
+
+        assert!(
+            model
+                .create_completion_request(request(Some(message::ToolChoice::Specific {
+                    function_names: vec![],
+                })))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_thinking() {
+        let model = Client::new("test-key")
+            .completion_model(BIGMODEL_GLM_Z1_FLASH)
+            .with_thinking(true);
+        let request = CompletionRequest {
+            preamble: None,
+            chat_history: OneOrMany::one(message::Message::user("1+1=?")),
+            documents: vec![],
+            tools: vec![],
+            temperature: None,
+            max_tokens: None,
+            tool_choice: None,
+            additional_params: None,
+        };
+        let request = model.create_completion_request(request).unwrap();
+        assert_eq!(request["thinking"]["type"], json!("enabled"));
+
+        let response = |content: &str, reasoning: Option<&str>| -> CompletionResponse {
+            serde_json::from_value(json!({
+                "choices": [{
+                    "finish_reason": "stop",
+                    "index": 0,
+                    "message": {
+                        "role": "assistant",
+                        "content": content,
+                        "reasoning_content": reasoning
+                    }
+                }],
+                "created": 0,
+                "id": "id",
+                "model": "glm-z1-flash",
+                "request_id": "id",
+                "usage": {"completion_tokens": 1, "prompt_tokens": 1, "total_tokens": 2}
+            }))
+            .unwrap()
+        };
+        let answer = |response: &CompletionResponse| match &response.choices[0].message {
+            Message::Assistant { content, .. } => content.clone().unwrap(),
+            _ => unreachable!(),
+        };
+
+        let mut tagged = response("<think>\n1+1 等于 2\n</think>\n\n2", None);
+        tagged.extract_reasoning();
+        assert_eq!(tagged.reasoning.as_deref(), Some("1+1 等于 2"));
+        assert_eq!(answer(&tagged), "2");
+
+        let mut field = response("2", Some("1+1 等于 2"));
+        field.extract_reasoning();
+        assert_eq!(field.reasoning.as_deref(), Some("1+1 等于 2"));
+        field.inline_reasoning();
+        assert_eq!(answer(&field), "<think>\n1+1 等于 2\n</think>\n2");
+    }
+
+    #[test]
+    fn test_web_search() {
+        let model = Client::new("test-key")
+            .completion_model(BIGMODEL_GLM_4_FLASH)
+            .with_web_search(true);
+        let request = CompletionRequest {
+            preamble: None,
+            chat_history: OneOrMany::one(message::Message::user("今天的新闻")),
+            documents: vec![],
+            tools: vec![],
+            temperature: None,
+            max_tokens: None,
+            tool_choice: None,
+            additional_params: None,
+        };
+        let request = model.create_completion_request(request).unwrap();
+        assert_eq!(request["tools"][0]["type"], json!("web_search"));
+        assert_eq!(request["tools"][0]["web_search"]["enable"], json!(true));
+
+        let response: CompletionResponse = serde_json::from_value(json!({
+            "choices": [{
+                "finish_reason": "stop",
+                "index": 0,
+                "message": {"role": "assistant", "content": "今天...[ref_1]"}
+            }],
+            "created": 0,
+            "id": "id",
+            "model": "glm-4-flash",
+            "request_id": "id",
+            "usage": {"completion_tokens": 1, "prompt_tokens": 1, "total_tokens": 2},
+            "web_search": [{
+                "title": "新闻",
+                "link": "https://example.com",
+                "refer": "ref_1"
+            }]
+        }))
+        .unwrap();
+        assert_eq!(response.web_search[0].link, "https://example.com");
+        assert_eq!(response.web_search[0].refer, "ref_1");
+    }
+
+    #[test]
+    fn test_embedding_request() {
+        let client = Client::new("test-key");
+        let documents = vec!["a".to_string(), "b".to_string()];
+
+        let request = client
+            .embedding_model(BIGMODEL_EMBEDDING_2)
+            .create_embedding_request(&documents);
+        assert_eq!(request["input"], json!(["a", "b"]));
+        assert!(request.get("dimensions").is_none());
+
+        let model = client.embedding_model_with_ndims(BIGMODEL_EMBEDDING_3, 512);
+        assert_eq!(embeddings::EmbeddingModel::ndims(&model), 512);
+        assert_eq!(
+            model.create_embedding_request(&documents)["dimensions"],
+            json!(512)
+        );
+
+        let response: EmbeddingResponse = serde_json::from_value(json!({
+            "model": "embedding-3",
+            "object": "list",
+            "data": [
+                {"index": 1, "object": "embedding", "embedding": [2.0]},
+                {"index": 0, "object": "embedding", "embedding": [1.0]}
+            ],
+            "usage": {"prompt_tokens": 2, "completion_tokens": 0, "total_tokens": 2}
+        }))
+        .unwrap();
+        let embeddings = embeddings_from_response(response, documents).unwrap();
+        assert_eq!(embeddings[0].document, "a");
+        assert_eq!(embeddings[0].vec, vec![1.0]);
+        assert_eq!(embeddings[1].vec, vec![2.0]);
+    }
+}
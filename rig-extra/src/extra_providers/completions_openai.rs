@@ -9,10 +9,9 @@ use serde::{Deserialize, Serialize};
 
 /// 获取openai client
 pub fn get_completions_openai_client(base_url: &str, api_key: &str) -> Client<HttpClient> {
-    let client = providers::openai::ClientBuilder::<HttpClient>::new(api_key)
+    providers::openai::ClientBuilder::<HttpClient>::new(api_key)
         .base_url(base_url)
-        .build();
-    client
+        .build()
 }
 
 /// 获取 openai agent builder
@@ -22,11 +21,10 @@ pub fn get_completions_openai_agent_builder(
     model_name: &str,
 ) -> AgentBuilder<CompletionModel> {
     let client = get_completions_openai_client(base_url, api_key);
-    let agent_builder = client
+    client
         .completion_model(model_name)
         .completions_api()
-        .into_agent_builder();
-    agent_builder
+        .into_agent_builder()
 }
 
 /// 获取 openai extractor builder
@@ -39,6 +37,5 @@ where
     U: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync,
 {
     let client = get_completions_openai_client(base_url, api_key);
-    let extractor_builder = client.extractor_completions_api::<U>(model_name);
-    extractor_builder
+    client.extractor_completions_api::<U>(model_name)
 }
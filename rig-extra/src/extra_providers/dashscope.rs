@@ -0,0 +1,285 @@
+//! 阿里云百炼 DashScope(通义千问)客户端
+//!
+//! 使用 DashScope 的 OpenAI 兼容接口，支持工具调用和流式输出。
+//!
+//! ```rust,ignore
+//! use rig_extra::extra_providers::dashscope::{self, Client};
+//!
+//! let client = Client::new(&api_key);
+//! let agent = client.agent(dashscope::QWEN_TURBO).preamble("你是一个助手").build();
+//! let answer = agent.prompt("你好").await?;
+//! ```
+
+use crate::json_utils::merge;
+use crate::redact::redact;
+use http::header;
+use reqwest::Method;
+use reqwest::header::HeaderValue;
+use rig::client::{AsEmbeddings, AsTranscription, CompletionClient, ProviderClient, ProviderValue};
+use rig::completion::{CompletionError, CompletionRequest};
+use rig::providers::openai;
+use rig::providers::openai::send_compatible_streaming_request;
+use rig::streaming::StreamingCompletionResponse;
+use rig::{client, completion, http_client};
+use serde_json::{Value, json};
+use tracing::{Instrument, info_span};
+
+pub const DASHSCOPE_API_BASE_URL: &str = "https://dashscope.aliyuncs.com/compatible-mode/v1";
+
+pub const QWEN_MAX: &str = "qwen-max";
+pub const QWEN_PLUS: &str = "qwen-plus";
+pub const QWEN_TURBO: &str = "qwen-turbo";
+pub const QWEN_LONG: &str = "qwen-long";
+
+#[derive(Clone, Debug)]
+pub struct Client {
+    api_key: String,
+    base_url: String,
+    default_headers: http_client::HeaderMap,
+    http_client: reqwest::Client,
+}
+
+impl Client {
+    pub fn new(api_key: &str) -> Self {
+        Self::from_url(api_key, DASHSCOPE_API_BASE_URL)
+    }
+
+    /// 使用其它地域或代理网关的接口地址，如国际站
+    /// `https://dashscope-intl.aliyuncs.com/compatible-mode/v1`
+    pub fn from_url(api_key: &str, base_url: &str) -> Self {
+        Self::from_url_with_client(api_key, base_url, reqwest::Client::new())
+    }
+
+    pub fn from_url_with_client(
+        api_key: &str,
+        base_url: &str,
+        http_client: reqwest::Client,
+    ) -> Self {
+        let mut default_headers = http_client::HeaderMap::new();
+        default_headers.insert(
+            reqwest::header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        Self {
+            api_key: api_key.to_string(),
+            base_url: base_url.to_string(),
+            default_headers,
+            http_client,
+        }
+    }
+
+    /// 添加自定义请求头，同名请求头会被覆盖
+    pub fn with_headers(mut self, headers: http_client::HeaderMap) -> Self {
+        self.default_headers.extend(headers);
+        self
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!(
+            "{}/{}",
+            self.base_url.trim_end_matches('/'),
+            path.trim_start_matches('/')
+        )
+    }
+}
+
+impl ProviderClient for Client {
+    fn from_env() -> Self
+    where
+        Self: Sized,
+    {
+        let api_key = std::env::var("DASHSCOPE_API_KEY").expect("DASHSCOPE_API_KEY not set");
+        Self::new(&api_key)
+    }
+
+    fn from_val(input: ProviderValue) -> Self
+    where
+        Self: Sized,
+    {
+        let client::ProviderValue::Simple(api_key) = input else {
+            panic!("Incorrect provider value type")
+        };
+        Self::new(&api_key)
+    }
+}
+
+impl AsTranscription for Client {}
+
+impl AsEmbeddings for Client {}
+
+// 暂不支持图片和音频生成，开启对应特性时提供空实现
+#[cfg(feature = "rig-image")]
+impl rig::client::AsImageGeneration for Client {}
+
+#[cfg(feature = "rig-audio")]
+impl rig::client::AsAudioGeneration for Client {}
+
+impl CompletionClient for Client {
+    type CompletionModel = CompletionModel;
+
+    fn completion_model(&self, model: &str) -> Self::CompletionModel {
+        CompletionModel::new(self.clone(), model)
+    }
+}
+
+#[derive(Clone)]
+pub struct CompletionModel {
+    client: Client,
+    pub model: String,
+}
+
+impl CompletionModel {
+    pub fn new(client: Client, model: &str) -> Self {
+        Self {
+            client,
+            model: model.to_string(),
+        }
+    }
+
+    fn create_completion_request(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<Value, CompletionError> {
+        let max_tokens = completion_request.max_tokens;
+        let request =
+            openai::CompletionRequest::try_from((self.model.clone(), completion_request))?;
+        let request = serde_json::to_value(request)?;
+        Ok(match max_tokens {
+            Some(max_tokens) => merge(request, json!({"max_tokens": max_tokens})),
+            None => request,
+        })
+    }
+
+    /// 流式请求
+    fn request(&self, body: &Value) -> Result<http_client::Request<Vec<u8>>, CompletionError> {
+        let mut builder = http_client::Builder::new()
+            .uri(self.client.url("chat/completions"))
+            .method(Method::POST);
+        for (header, value) in &self.client.default_headers {
+            builder = builder.header(header, value);
+        }
+        let auth_header = HeaderValue::from_str(&format!("Bearer {}", &self.client.api_key))
+            .map_err(http::Error::from)
+            .map_err(http_client::Error::from)?;
+        builder
+            .header(header::AUTHORIZATION, auth_header)
+            .body(serde_json::to_vec(body)?)
+            .map_err(|e| CompletionError::HttpError(e.into()))
+    }
+}
+
+impl completion::CompletionModel for CompletionModel {
+    type Response = openai::CompletionResponse;
+    type StreamingResponse = openai::StreamingCompletionResponse;
+
+    async fn completion(
+        &self,
+        completion_request: CompletionRequest,
+    ) -> Result<completion::CompletionResponse<openai::CompletionResponse>, CompletionError> {
+        let request = self.create_completion_request(completion_request)?;
+        tracing::debug!(
+            "request: \r\n {}",
+            redact(&serde_json::to_string_pretty(&request)?)
+        );
+
+        let response = self
+            .client
+            .http_client
+            .post(self.client.url("chat/completions"))
+            .headers(self.client.default_headers.clone())
+            .bearer_auth(&self.client.api_key)
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| http_client::Error::Instance(e.into()))?;
+
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|e| http_client::Error::Instance(e.into()))?;
+        if !status.is_success() {
+            return Err(CompletionError::ProviderError(redact(&text)));
+        }
+        tracing::debug!("response: {}", redact(&text));
+
+        let response: openai::CompletionResponse = serde_json::from_str(&text)?;
+        tracing::info!(target: "rig",
+            "dashscope completion token usage: {:?}",
+            response.usage
+        );
+        response.try_into()
+    }
+
+    async fn stream(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<StreamingCompletionResponse<Self::StreamingResponse>, CompletionError> {
+        let request = merge(
+            self.create_completion_request(request)?,
+            json!({"stream": true, "stream_options": {"include_usage": true}}),
+        );
+        let req = self.request(&request)?;
+
+        let span = if tracing::Span::current().is_disabled() {
+            info_span!(
+                target: "rig::completions",
+                "chat_streaming",
+                gen_ai.operation.name = "chat_streaming",
+                gen_ai.provider.name = "dashscope",
+                gen_ai.request.model = self.model,
+                gen_ai.response.id = tracing::field::Empty,
+                gen_ai.response.model = tracing::field::Empty,
+                gen_ai.usage.output_tokens = tracing::field::Empty,
+                gen_ai.usage.input_tokens = tracing::field::Empty,
+                gen_ai.output.messages = tracing::field::Empty,
+            )
+        } else {
+            tracing::Span::current()
+        };
+
+        send_compatible_streaming_request(self.client.http_client.clone(), req)
+            .instrument(span)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rig::OneOrMany;
+    use rig::message;
+
+    #[test]
+    fn test_completion_request() {
+        let model = Client::from_url("test-key", "https://example.com/compatible-mode/v1/")
+            .completion_model(QWEN_TURBO);
+        let request = CompletionRequest {
+            preamble: Some("你是一个助手".to_string()),
+            chat_history: OneOrMany::one(message::Message::user("你好")),
+            documents: vec![],
+            tools: vec![completion::ToolDefinition {
+                name: "now".to_string(),
+                description: "当前时间".to_string(),
+                parameters: json!({"type": "object", "properties": {}}),
+            }],
+            temperature: Some(0.5),
+            max_tokens: Some(256),
+            tool_choice: None,
+            additional_params: None,
+        };
+
+        let body = model.create_completion_request(request).unwrap();
+        assert_eq!(body["model"], json!(QWEN_TURBO));
+        assert_eq!(body["messages"][0]["role"], json!("system"));
+        assert_eq!(body["tools"][0]["function"]["name"], json!("now"));
+        assert_eq!(body["max_tokens"], json!(256));
+
+        let request = model.request(&body).unwrap();
+        assert_eq!(
+            request.uri(),
+            "https://example.com/compatible-mode/v1/chat/completions"
+        );
+        assert_eq!(request.headers()["authorization"], "Bearer test-key");
+    }
+}
@@ -1,2 +1,3 @@
 pub mod bigmodel;
 pub mod completions_openai;
+pub mod dashscope;
@@ -0,0 +1,72 @@
+//! 结构化提取结果缓存
+//!
+//! 批处理流水线中经常对内容重叠的文档重复提取。缓存以 (schema 哈希, 输入哈希) 为键，
+//! 只保存解析成功的结果，有效期内相同类型、相同输入的提取直接返回缓存，不再调用模型。
+
+use rig::completion::Message;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::time::{Duration, Instant};
+
+/// (schema 哈希, 输入哈希)
+pub(crate) type ExtractionKey = (u64, u64);
+
+/// 计算缓存键
+pub(crate) fn extraction_key(schema: &Value, input: &Message) -> ExtractionKey {
+    let input = serde_json::to_string(input).unwrap_or_default();
+    (hash_str(&schema.to_string()), hash_str(&input))
+}
+
+fn hash_str(value: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// 提取结果缓存，保存的是已通过解析的 JSON 文本
+#[derive(Debug, Default)]
+pub(crate) struct ExtractionCache {
+    entries: HashMap<ExtractionKey, (Instant, String)>,
+}
+
+impl ExtractionCache {
+    /// 获取有效期内的结果，同时清理过期的结果
+    pub(crate) fn get(&mut self, key: &ExtractionKey, ttl: Duration) -> Option<String> {
+        self.entries
+            .retain(|_, (stored_at, _)| stored_at.elapsed() < ttl);
+        self.entries.get(key).map(|(_, json)| json.clone())
+    }
+
+    pub(crate) fn insert(&mut self, key: ExtractionKey, json: String) {
+        self.entries.insert(key, (Instant::now(), json));
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_extraction_key() {
+        let schema = json!({"type": "object", "properties": {"name": {"type": "string"}}});
+        let other_schema = json!({"type": "object", "properties": {"age": {"type": "integer"}}});
+        let key = extraction_key(&schema, &Message::user("张三，30 岁"));
+        assert_eq!(key, extraction_key(&schema, &Message::user("张三，30 岁")));
+        assert_ne!(
+            key,
+            extraction_key(&other_schema, &Message::user("张三，30 岁"))
+        );
+        assert_ne!(key, extraction_key(&schema, &Message::user("李四，25 岁")));
+
+        let mut cache = ExtractionCache::default();
+        cache.insert(key, r#"{"name":"张三"}"#.to_string());
+        assert!(cache.get(&key, Duration::from_secs(60)).is_some());
+        assert!(cache.get(&key, Duration::ZERO).is_none());
+    }
+}
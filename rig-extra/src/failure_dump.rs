@@ -0,0 +1,217 @@
+//! 重试最终失败时导出请求上下文
+//!
+//! 线上偶发的失败很难复现。开启后，`try_invoke_with_retry` 等重试接口在所有重试都失败时，
+//! 把本次调用的完整上下文写入目录中的一个 JSON 文件，包括请求内容、每次尝试选中的 agent、
+//! 耗时和原始错误信息，便于离线分析。
+//!
+//! ```rust,ignore
+//! let rand_agent = RandAgentBuilder::new()
+//!     .dump_failures_to("./failure-dumps")
+//!     .build();
+//!
+//! // 运行时关闭或重新开启
+//! rand_agent.set_failure_dump_enabled(false);
+//! ```
+//!
+//! 写入前所有内容都会经过 [`redact`](crate::redact::redact) 脱敏。
+
+use crate::AgentInfo;
+use crate::redact::{redact, redacted};
+use crate::session::unix_now;
+use rig::completion::{Message, PromptError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+
+/// 单次尝试的记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttemptRecord {
+    /// 尝试序号，从 1 开始
+    pub attempt: usize,
+    pub agent_id: i32,
+    pub provider: String,
+    pub model: String,
+    pub latency_ms: u64,
+    /// 失败时的错误信息(已脱敏)，成功时为空
+    pub error: Option<String>,
+}
+
+/// 一次失败调用的完整上下文
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailureBundle {
+    /// 导出时间(unix 秒)
+    pub created_at: u64,
+    /// 调用的方法，如 `try_invoke_with_retry`
+    pub method: String,
+    /// 请求内容(已脱敏)
+    pub request: serde_json::Value,
+    pub attempts: Vec<AttemptRecord>,
+    /// 最终返回给调用方的错误(已脱敏)
+    pub final_error: String,
+}
+
+/// 一次重试调用中各次尝试共享的状态
+#[derive(Debug, Default)]
+pub(crate) struct RetryContext {
+    /// 是否跳过已失败的 agent
+    pub(crate) distinct_agents: bool,
+    pub(crate) failed: HashSet<i32>,
+    pub(crate) attempts: Vec<AttemptRecord>,
+    /// 跳过已失败的 agent 后没有可选的 agent，不再继续重试
+    pub(crate) exhausted: bool,
+}
+
+impl RetryContext {
+    pub(crate) fn new(distinct_agents: bool) -> Self {
+        Self {
+            distinct_agents,
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn excludes(&self, id: i32) -> bool {
+        self.distinct_agents && self.failed.contains(&id)
+    }
+
+    /// 选不到 agent 时调用，已有失败的 agent 被跳过时标记为耗尽
+    pub(crate) fn mark_exhausted(&mut self) {
+        if self.distinct_agents && !self.failed.is_empty() {
+            self.exhausted = true;
+        }
+    }
+
+    pub(crate) fn record(
+        &mut self,
+        info: &AgentInfo,
+        result: &Result<String, PromptError>,
+        latency: Duration,
+    ) {
+        if result.is_err() {
+            self.failed.insert(info.id);
+        }
+        self.attempts.push(AttemptRecord {
+            attempt: self.attempts.len() + 1,
+            agent_id: info.id,
+            provider: info.provider.clone(),
+            model: info.model.clone(),
+            latency_ms: latency.as_millis() as u64,
+            error: result.as_ref().err().map(|err| redacted(err)),
+        });
+    }
+}
+
+/// 失败上下文导出器
+#[derive(Debug)]
+pub(crate) struct FailureDumper {
+    dir: PathBuf,
+    enabled: AtomicBool,
+    counter: AtomicU64,
+}
+
+impl FailureDumper {
+    pub(crate) fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            enabled: AtomicBool::new(true),
+            counter: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// 生成导出内容，请求先序列化再整体脱敏
+    pub(crate) fn bundle(
+        method: &str,
+        request: &Message,
+        attempts: Vec<AttemptRecord>,
+        final_error: &str,
+    ) -> FailureBundle {
+        let request = serde_json::to_string(request)
+            .map(|json| redact(&json))
+            .and_then(|json| serde_json::from_str(&json))
+            .unwrap_or(serde_json::Value::Null);
+        FailureBundle {
+            created_at: unix_now(),
+            method: method.to_string(),
+            request,
+            attempts,
+            final_error: redact(final_error),
+        }
+    }
+
+    /// 写入文件，返回文件路径
+    pub(crate) async fn dump(&self, bundle: &FailureBundle) -> std::io::Result<PathBuf> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let index = self.counter.fetch_add(1, Ordering::Relaxed);
+        let path = self.dir.join(format!(
+            "rand-agent-failure-{}-{}-{index}.json",
+            bundle.created_at,
+            std::process::id()
+        ));
+        let json = serde_json::to_vec_pretty(bundle).map_err(std::io::Error::other)?;
+        tokio::fs::write(&path, json).await?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structured::StructuredOutput;
+    use rig::completion::CompletionError;
+
+    #[tokio::test]
+    async fn test_dump_failure_bundle() {
+        let info = AgentInfo {
+            id: 2,
+            provider: "openrouter".to_string(),
+            model: "deepseek/deepseek-chat".to_string(),
+            failure_count: 1,
+            max_failures: 3,
+            avg_latency: None,
+            input_price: None,
+            output_price: None,
+            disabled: false,
+            reserved_for: None,
+            structured_output: StructuredOutput::Prompt,
+            context_length: None,
+        };
+        let err = PromptError::CompletionError(CompletionError::ProviderError(
+            r#"{"error": "invalid api_key=sk-abcdefghijklmnop"}"#.to_string(),
+        ));
+        let mut context = RetryContext::new(true);
+        context.record(&info, &Err(err), Duration::from_millis(30));
+        assert!(context.excludes(2));
+
+        let bundle = FailureDumper::bundle(
+            "try_invoke_with_retry",
+            &Message::user("你好"),
+            context.attempts,
+            "provider error",
+        );
+        assert_eq!(bundle.attempts[0].attempt, 1);
+        assert!(
+            !bundle.attempts[0]
+                .error
+                .as_ref()
+                .unwrap()
+                .contains("abcdefghijklmnop")
+        );
+
+        let dir = std::env::temp_dir().join(format!("rig-extra-dump-{}", std::process::id()));
+        let dumper = FailureDumper::new(&dir);
+        let path = dumper.dump(&bundle).await.unwrap();
+        let restored: FailureBundle =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(restored.attempts[0].agent_id, 2);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
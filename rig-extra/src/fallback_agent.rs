@@ -0,0 +1,277 @@
+//! 按优先级顺序故障转移的代理
+//!
+//! 与 [`RandAgent`](crate::rand_agent::RandAgent) 随机选择不同，`FallbackAgent` 总是按添加顺序
+//! 依次尝试，只有前一个 agent 调用失败或已失效时才切换到下一个，适用于"主提供方 + 备用提供方"的场景。
+//!
+//! ```rust,ignore
+//! use rig_extra::rand_agent::RandAgentBuilder;
+//!
+//! let agent = RandAgentBuilder::new()
+//!     .max_failures(3)
+//!     .add_agent(primary, 1, "openai".to_string(), "gpt-4o".to_string())
+//!     .add_agent(backup, 2, "bigmodel".to_string(), "glm-4-flash".to_string())
+//!     .build_fallback();
+//!
+//! let response = agent.prompt("你好").await?;
+//! ```
+
+use crate::AgentInfo;
+use crate::rand_agent::{
+    AgentSlot, AgentState, OnAgentInvalidCallback, ResponseValidator, into_slots, lock_slot,
+    no_valid_agent_error, record_result,
+};
+use rig::client::builder::BoxAgent;
+use rig::completion::{Chat, Message, Prompt, PromptError};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+/// 按优先级顺序故障转移的代理
+#[derive(Clone)]
+pub struct FallbackAgent {
+    agents: Arc<RwLock<Vec<AgentSlot>>>,
+    pub(crate) on_agent_invalid: OnAgentInvalidCallback,
+    pub(crate) response_validator: Option<ResponseValidator>,
+    pub(crate) failure_decay: Option<Duration>,
+    pub(crate) cooldown: Option<Duration>,
+}
+
+impl Prompt for FallbackAgent {
+    #[allow(refining_impl_trait)]
+    async fn prompt(&self, prompt: impl Into<Message> + Send) -> Result<String, PromptError> {
+        let prompt = prompt.into();
+        self.invoke(|agent| {
+            let prompt = prompt.clone();
+            async move { agent.prompt(prompt).await }
+        })
+        .await
+    }
+}
+
+impl Chat for FallbackAgent {
+    #[allow(refining_impl_trait)]
+    async fn chat(
+        &self,
+        prompt: impl Into<Message> + Send,
+        chat_history: Vec<Message>,
+    ) -> Result<String, PromptError> {
+        let prompt = prompt.into();
+        self.invoke(|agent| {
+            let prompt = prompt.clone();
+            let chat_history = chat_history.clone();
+            async move { agent.chat(prompt, chat_history).await }
+        })
+        .await
+    }
+}
+
+impl FallbackAgent {
+    /// 按给定顺序创建 FallbackAgent，顺序即优先级
+    pub(crate) fn new(agents: Vec<AgentState>) -> Self {
+        Self {
+            agents: Arc::new(RwLock::new(into_slots(agents))),
+            on_agent_invalid: None,
+            response_validator: None,
+            failure_decay: None,
+            cooldown: None,
+        }
+    }
+
+    /// 依次尝试可用 agent，直到成功或全部失败，全部失败时返回最后一个错误
+    async fn invoke<F, Fut>(&self, call: F) -> Result<String, PromptError>
+    where
+        F: Fn(Arc<BoxAgent<'static>>) -> Fut,
+        Fut: Future<Output = Result<String, PromptError>>,
+    {
+        let slots: Vec<AgentSlot> = self.agents.read().await.clone();
+        let mut last_error = None;
+
+        for slot in slots.iter() {
+            // 只在选择和记录结果时加锁，调用期间不持有锁
+            let (agent, id) = {
+                let mut agent_state = lock_slot(slot);
+                if let Some(window) = self.failure_decay {
+                    agent_state.decay_failures(window);
+                }
+                if !agent_state.is_available(self.cooldown) {
+                    continue;
+                }
+                agent_state.begin_trial();
+                tracing::info!(
+                    "fallback Using provider: {}, model: {},id: {}",
+                    agent_state.info.provider,
+                    agent_state.info.model,
+                    agent_state.info.id
+                );
+                (agent_state.agent.clone(), agent_state.id)
+            };
+
+            let start = Instant::now();
+            let result = call(agent).await;
+
+            let (result, invalidated) = record_result(
+                &mut lock_slot(slot),
+                result,
+                start.elapsed(),
+                self.response_validator.as_ref(),
+            );
+            // 释放锁后再触发失效回调
+            if invalidated && let Some(cb) = &self.on_agent_invalid {
+                cb(id);
+            }
+
+            match result {
+                Ok(content) => return Ok(content),
+                Err(err) => {
+                    tracing::warn!(
+                        "fallback agent 调用失败，尝试下一个: {}",
+                        crate::redact::redacted(&err)
+                    );
+                    last_error = Some(err);
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(no_valid_agent_error))
+    }
+
+    /// 获取下一次请求会尝试的代理数量，不含已停用、失效且未冷却结束的代理
+    pub async fn len(&self) -> usize {
+        let agents = self.agents.read().await;
+        agents
+            .iter()
+            .filter(|slot| {
+                let mut agent_state = lock_slot(slot);
+                if let Some(window) = self.failure_decay {
+                    agent_state.decay_failures(window);
+                }
+                agent_state.is_available(self.cooldown)
+            })
+            .count()
+    }
+
+    /// 检查是否有有效代理
+    pub async fn is_empty(&self) -> bool {
+        self.len().await == 0
+    }
+
+    /// 获取agent info，顺序即优先级
+    pub async fn get_agents_info(&self) -> Vec<AgentInfo> {
+        let agents = self.agents.read().await;
+        agents
+            .iter()
+            .map(|slot| lock_slot(slot).info.clone())
+            .collect()
+    }
+
+    /// 重置所有代理的失败计数
+    pub async fn reset_failures(&self) {
+        let agents = self.agents.read().await;
+        for slot in agents.iter() {
+            lock_slot(slot).record_success();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rand_agent::RandAgentBuilder;
+    use crate::rand_agent::tests::FakeModel;
+
+    fn fake_fallback(
+        models: &[FakeModel],
+        configure: impl FnOnce(RandAgentBuilder) -> RandAgentBuilder,
+    ) -> FallbackAgent {
+        let builder =
+            models
+                .iter()
+                .zip(1..)
+                .fold(RandAgentBuilder::new(), |builder, (model, id)| {
+                    builder.add_agent(
+                        model.agent(),
+                        id,
+                        format!("fake{id}"),
+                        "fake-model".to_string(),
+                    )
+                });
+        configure(builder).build_fallback()
+    }
+
+    #[tokio::test]
+    async fn test_fallback_order() {
+        let models = [FakeModel::failing(), FakeModel::ok(), FakeModel::ok()];
+        let agent = fake_fallback(&models, |builder| builder);
+
+        let response = agent.prompt("hi").await.unwrap();
+        assert_eq!(response, "reply 1");
+        assert_eq!(models[0].calls(), 1);
+        assert_eq!(models[1].calls(), 1);
+        assert_eq!(models[2].calls(), 0);
+
+        // 主 agent 恢复后重新优先使用
+        models[0].set_failing(false);
+        agent.prompt("hi").await.unwrap();
+        assert_eq!(models[0].calls(), 2);
+        assert_eq!(models[1].calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fallback_skips_disabled_and_invalid() {
+        let models = [FakeModel::ok(), FakeModel::failing(), FakeModel::ok()];
+        let agent = fake_fallback(&models, |builder| builder.max_failures(1));
+        lock_slot(&agent.agents.read().await[0]).info.disabled = true;
+        assert_eq!(agent.len().await, 3 - 1);
+
+        agent.prompt("hi").await.unwrap();
+        assert_eq!(models[0].calls(), 0);
+        assert_eq!(models[1].calls(), 1);
+        assert_eq!(models[2].calls(), 1);
+
+        // 第二个 agent 已失效，不再尝试
+        assert_eq!(agent.len().await, 1);
+        agent.prompt("hi").await.unwrap();
+        assert_eq!(models[1].calls(), 1);
+        assert_eq!(models[2].calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_fallback_len_counts_decayed_and_cooled_agents() {
+        let models = [FakeModel::failing(), FakeModel::ok()];
+        let agent = fake_fallback(&models, |builder| {
+            builder
+                .max_failures(1)
+                .failure_decay(Duration::from_millis(20))
+        });
+        agent.prompt("hi").await.unwrap();
+        assert_eq!(agent.len().await, 1);
+
+        // 失败计数衰减后重新计入
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(agent.len().await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_fallback_all_failed() {
+        let models = [
+            FakeModel::failing_with("first"),
+            FakeModel::failing_with("last"),
+        ];
+        let agent = fake_fallback(&models, |builder| builder);
+
+        let err = agent.prompt("hi").await.unwrap_err();
+        assert!(err.to_string().contains("last"), "{err}");
+        assert_eq!(models[0].calls(), 1);
+        assert_eq!(models[1].calls(), 1);
+
+        // 没有可用 agent 时返回无有效代理的错误
+        for slot in agent.agents.read().await.iter() {
+            lock_slot(slot).info.disabled = true;
+        }
+        assert!(agent.is_empty().await);
+        let err = agent.prompt("hi").await.unwrap_err();
+        assert_eq!(err.to_string(), no_valid_agent_error().to_string());
+        assert_eq!(models[0].calls(), 1);
+    }
+}
@@ -1,6 +1,23 @@
 //! 获取openrouter中的模型列表
+//!
+//! [`fetch_openrouter_model_list`] 使用网页前端的非公开接口，
+//! [`fetch_openrouter_models`] 使用官方的 `/api/v1/models` 接口，返回价格、支持的参数和输入输出模态。
+//!
+//! [`RandAgentBuilder::from_openrouter_free_models`] 为每个免费模型添加一个 agent，在免费模型之间轮换:
+//!
+//! ```rust,ignore
+//! let rand_agent = RandAgentBuilder::from_openrouter_free_models(
+//!     &api_key,
+//!     ModelListQuery::new().min_context_length(32_000),
+//!     "你是一个助手",
+//! )
+//! .await?
+//! .build();
+//! ```
 
-use serde::{Deserialize, Serialize};
+use crate::rand_agent::RandAgentBuilder;
+use crate::simple_rand_builder::{AgentConfig, ProviderEnum};
+use serde::{Deserialize, Deserializer, Serialize};
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Model {
@@ -40,6 +57,34 @@ pub struct Endpoint {
     pub model_variant_permaslug: String,
     #[serde(rename = "is_free")]
     pub is_free: bool,
+    #[serde(default)]
+    pub pricing: Option<Pricing>,
+}
+
+/// 模型价格，单位为美元每 token
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pricing {
+    #[serde(default)]
+    pub prompt: String,
+    #[serde(default)]
+    pub completion: String,
+}
+
+impl Model {
+    /// 每千 token 的输入、输出价格
+    pub fn price_per_1k(&self) -> Option<(f64, f64)> {
+        let pricing = self.endpoint.as_ref()?.pricing.as_ref()?;
+        let prompt: f64 = pricing.prompt.parse().ok()?;
+        let completion: f64 = pricing.completion.parse().ok()?;
+        Some((prompt * 1000.0, completion * 1000.0))
+    }
+
+    /// 是否为免费模型
+    pub fn is_free(&self) -> bool {
+        self.endpoint
+            .as_ref()
+            .is_some_and(|endpoint| endpoint.is_free)
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -47,6 +92,114 @@ pub struct ModelsResponse {
     pub data: Vec<Model>,
 }
 
+/// 模型列表的排序方式，均为从大到小
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelSort {
+    /// 上下文长度
+    ContextLength,
+    /// 创建时间，最新的在前
+    Recency,
+}
+
+/// 模型列表查询
+///
+/// ```rust,ignore
+/// // 所有上下文不少于 32k 的免费模型
+/// let models = ModelListQuery::new()
+///     .free()
+///     .min_context_length(32_000)
+///     .sort_by(ModelSort::ContextLength)
+///     .apply(&models);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ModelListQuery {
+    free: bool,
+    min_context_length: Option<i64>,
+    input_modalities: Vec<String>,
+    author: Option<String>,
+    search: Option<String>,
+    sort: Option<ModelSort>,
+}
+
+impl ModelListQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 只保留免费模型
+    pub fn free(mut self) -> Self {
+        self.free = true;
+        self
+    }
+
+    /// 最小上下文长度
+    pub fn min_context_length(mut self, min_context_length: i64) -> Self {
+        self.min_context_length = Some(min_context_length);
+        self
+    }
+
+    /// 要求支持某种输入模态，如 `image`，可多次调用
+    pub fn input_modality(mut self, modality: impl Into<String>) -> Self {
+        self.input_modalities.push(modality.into());
+        self
+    }
+
+    /// 按作者过滤，如 `deepseek`，不区分大小写
+    pub fn author(mut self, author: impl Into<String>) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    /// slug 中包含的子串，不区分大小写
+    pub fn search(mut self, keyword: impl Into<String>) -> Self {
+        self.search = Some(keyword.into().to_lowercase());
+        self
+    }
+
+    pub fn sort_by(mut self, sort: ModelSort) -> Self {
+        self.sort = Some(sort);
+        self
+    }
+
+    /// 模型是否满足所有过滤条件
+    pub fn matches(&self, model: &Model) -> bool {
+        (!self.free || model.is_free())
+            && self
+                .min_context_length
+                .is_none_or(|min| model.context_length >= min)
+            && self
+                .input_modalities
+                .iter()
+                .all(|modality| model.input_modalities.contains(modality))
+            && self
+                .author
+                .as_ref()
+                .is_none_or(|author| model.author.eq_ignore_ascii_case(author))
+            && self
+                .search
+                .as_ref()
+                .is_none_or(|keyword| model.slug.to_lowercase().contains(keyword))
+    }
+
+    /// 过滤并排序，返回满足条件的模型
+    pub fn apply(&self, models: &[Model]) -> Vec<Model> {
+        let mut models: Vec<Model> = models
+            .iter()
+            .filter(|model| self.matches(model))
+            .cloned()
+            .collect();
+        match self.sort {
+            Some(ModelSort::ContextLength) => {
+                models.sort_by_key(|model| std::cmp::Reverse(model.context_length))
+            }
+            // 时间为 RFC 3339 格式，按字符串比较即可
+            Some(ModelSort::Recency) => models.sort_by(|a, b| b.created_at.cmp(&a.created_at)),
+            None => {}
+        }
+        models
+    }
+}
+
 /// 获取 openrouter 模型列表
 pub async fn fetch_openrouter_model_list() -> Result<Vec<Model>, Box<dyn std::error::Error>> {
     let client = reqwest::Client::new();
@@ -67,3 +220,326 @@ pub async fn fetch_openrouter_model_list() -> Result<Vec<Model>, Box<dyn std::er
     let parsed: ModelsResponse = resp.json().await?;
     Ok(parsed.data)
 }
+
+/// 为满足条件的免费模型生成 agent 配置，id 从 1 开始
+///
+/// 模型名使用免费端点的变体 slug(如 `deepseek/deepseek-chat-v3.1:free`)，并填入上下文长度和零价格。
+pub fn openrouter_free_model_configs(
+    models: &[Model],
+    api_key: &str,
+    filter: &ModelListQuery,
+) -> Vec<AgentConfig> {
+    filter
+        .clone()
+        .free()
+        .apply(models)
+        .into_iter()
+        .filter_map(|model| {
+            let endpoint = model.endpoint.as_ref()?;
+            let model_name = if endpoint.model_variant_slug.is_empty() {
+                format!("{}:free", model.slug)
+            } else {
+                endpoint.model_variant_slug.clone()
+            };
+            Some((model_name, endpoint.context_length))
+        })
+        .zip(1..)
+        .map(|((model_name, context_length), id)| {
+            let mut config = AgentConfig::new(id, ProviderEnum::OpenRouter, model_name, api_key);
+            config.context_length = u32::try_from(context_length).ok().filter(|&len| len > 0);
+            config.input_price = Some(0.0);
+            config.output_price = Some(0.0);
+            config
+        })
+        .collect()
+}
+
+impl RandAgentBuilder {
+    /// 获取 openrouter 模型列表，为每个满足 `filter` 的免费模型添加一个 agent
+    ///
+    /// `filter` 中是否设置了 [`ModelListQuery::free`] 不影响结果，始终只添加免费模型。
+    /// 没有符合条件的模型时返回错误。
+    pub async fn from_openrouter_free_models(
+        api_key: &str,
+        filter: ModelListQuery,
+        global_system_prompt: impl Into<String>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let models = fetch_openrouter_model_list().await?;
+        let configs = openrouter_free_model_configs(&models, api_key, &filter);
+        if configs.is_empty() {
+            return Err("没有符合条件的 openrouter 免费模型".into());
+        }
+        tracing::info!("添加 {} 个 openrouter 免费模型", configs.len());
+        Ok(Self::new().simple_builder(configs, global_system_prompt.into()))
+    }
+}
+
+pub const OPENROUTER_MODELS_URL: &str = "https://openrouter.ai/api/v1/models";
+
+/// 官方模型接口返回的模型
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpenRouterModel {
+    /// 调用时使用的模型 id，如 `openai/gpt-4o`
+    pub id: String,
+    #[serde(default)]
+    pub canonical_slug: Option<String>,
+    #[serde(default)]
+    pub hugging_face_id: Option<String>,
+    pub name: String,
+    /// 创建时间，unix 秒
+    #[serde(default)]
+    pub created: i64,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub context_length: Option<u64>,
+    #[serde(default)]
+    pub architecture: OpenRouterArchitecture,
+    #[serde(default)]
+    pub pricing: OpenRouterPricing,
+    #[serde(default)]
+    pub top_provider: OpenRouterTopProvider,
+    /// 支持的请求参数，如 `tools`、`temperature`、`response_format`
+    #[serde(default)]
+    pub supported_parameters: Vec<String>,
+}
+
+/// 模型的输入输出模态
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpenRouterArchitecture {
+    /// 如 `text+image->text`
+    #[serde(default)]
+    pub modality: String,
+    #[serde(default)]
+    pub input_modalities: Vec<String>,
+    #[serde(default)]
+    pub output_modalities: Vec<String>,
+    #[serde(default)]
+    pub tokenizer: Option<String>,
+    #[serde(default)]
+    pub instruct_type: Option<String>,
+}
+
+/// 模型价格，单位为美元每 token(`request` 为每次请求，`image` 为每张图片)
+///
+/// 接口以字符串返回价格，这里解析为数字；未提供的价格为 `None`。
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct OpenRouterPricing {
+    #[serde(default, deserialize_with = "price")]
+    pub prompt: Option<f64>,
+    #[serde(default, deserialize_with = "price")]
+    pub completion: Option<f64>,
+    #[serde(default, deserialize_with = "price")]
+    pub request: Option<f64>,
+    #[serde(default, deserialize_with = "price")]
+    pub image: Option<f64>,
+    #[serde(default, deserialize_with = "price")]
+    pub web_search: Option<f64>,
+    #[serde(default, deserialize_with = "price")]
+    pub internal_reasoning: Option<f64>,
+    #[serde(default, deserialize_with = "price")]
+    pub input_cache_read: Option<f64>,
+    #[serde(default, deserialize_with = "price")]
+    pub input_cache_write: Option<f64>,
+}
+
+/// 主要提供方的限制
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpenRouterTopProvider {
+    #[serde(default)]
+    pub context_length: Option<u64>,
+    #[serde(default)]
+    pub max_completion_tokens: Option<u64>,
+    #[serde(default)]
+    pub is_moderated: bool,
+}
+
+fn price<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Price {
+        Number(f64),
+        Text(String),
+    }
+    match Option::<Price>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Price::Number(price)) => Ok(Some(price)),
+        Some(Price::Text(price)) if price.trim().is_empty() => Ok(None),
+        Some(Price::Text(price)) => price
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+impl OpenRouterModel {
+    /// 每千 token 的输入、输出价格，与 [`AgentConfig`](crate::simple_rand_builder::AgentConfig) 的价格单位一致
+    pub fn price_per_1k(&self) -> Option<(f64, f64)> {
+        Some((
+            self.pricing.prompt? * 1000.0,
+            self.pricing.completion? * 1000.0,
+        ))
+    }
+
+    /// 输入、输出都免费
+    pub fn is_free(&self) -> bool {
+        self.pricing.prompt == Some(0.0) && self.pricing.completion == Some(0.0)
+    }
+
+    /// 是否支持某个请求参数，如 `tools`
+    pub fn supports_parameter(&self, parameter: &str) -> bool {
+        self.supported_parameters.iter().any(|p| p == parameter)
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpenRouterModelsResponse {
+    pub data: Vec<OpenRouterModel>,
+}
+
+/// 通过官方接口获取 openrouter 模型列表
+pub async fn fetch_openrouter_models(
+    api_key: &str,
+) -> Result<Vec<OpenRouterModel>, Box<dyn std::error::Error>> {
+    fetch_models(OPENROUTER_MODELS_URL, Some(api_key))
+        .await
+        .map_err(|e| e as Box<dyn std::error::Error>)
+}
+
+/// 该接口不带密钥也可以访问
+pub(crate) async fn fetch_models(
+    url: &str,
+    api_key: Option<&str>,
+) -> Result<Vec<OpenRouterModel>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut request = reqwest::Client::new()
+        .get(url)
+        .header(reqwest::header::ACCEPT, "application/json");
+    if let Some(api_key) = api_key {
+        request = request.bearer_auth(api_key);
+    }
+    let resp = request.send().await?;
+
+    if !resp.status().is_success() {
+        return Err(format!("request failed: {}", resp.status()).into());
+    }
+
+    let parsed: OpenRouterModelsResponse = resp.json().await?;
+    Ok(parsed.data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(slug: &str, context_length: i64, created_at: &str, is_free: bool) -> Model {
+        Model {
+            slug: slug.to_string(),
+            author: slug.split('/').next().unwrap().to_string(),
+            created_at: created_at.to_string(),
+            context_length,
+            input_modalities: vec!["text".to_string()],
+            endpoint: Some(Endpoint {
+                is_free,
+                context_length,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_model_list_query() {
+        let mut vision = model("qwen/qwen2.5-vl-72b", 131072, "2025-02-01T00:00:00Z", true);
+        vision.input_modalities.push("image".to_string());
+        let models = vec![
+            model(
+                "deepseek/deepseek-chat",
+                65536,
+                "2025-03-01T00:00:00Z",
+                true,
+            ),
+            model("deepseek/deepseek-r1", 16384, "2025-01-20T00:00:00Z", true),
+            model("openai/gpt-4o", 128000, "2024-05-13T00:00:00Z", false),
+            vision,
+        ];
+
+        let free: Vec<_> = ModelListQuery::new()
+            .free()
+            .min_context_length(32_000)
+            .sort_by(ModelSort::ContextLength)
+            .apply(&models)
+            .into_iter()
+            .map(|m| m.slug)
+            .collect();
+        assert_eq!(free, vec!["qwen/qwen2.5-vl-72b", "deepseek/deepseek-chat"]);
+
+        let query = ModelListQuery::new().input_modality("image");
+        assert_eq!(query.apply(&models).len(), 1);
+
+        let deepseek = ModelListQuery::new()
+            .author("DeepSeek")
+            .search("R1")
+            .apply(&models);
+        assert_eq!(deepseek[0].slug, "deepseek/deepseek-r1");
+
+        let newest = ModelListQuery::new()
+            .sort_by(ModelSort::Recency)
+            .apply(&models);
+        assert_eq!(newest[0].slug, "deepseek/deepseek-chat");
+        assert_eq!(newest[3].slug, "openai/gpt-4o");
+
+        let configs = openrouter_free_model_configs(
+            &models,
+            "sk-or-xxx",
+            &ModelListQuery::new().author("deepseek"),
+        );
+        assert_eq!(configs.len(), 2);
+        assert_eq!(configs[0].id, 1);
+        assert_eq!(configs[1].id, 2);
+        assert_eq!(configs[0].model_name, "deepseek/deepseek-chat:free");
+        assert_eq!(configs[0].context_length, Some(65536));
+        assert_eq!(configs[0].input_price, Some(0.0));
+    }
+
+    #[test]
+    fn test_parse_openrouter_models() {
+        let body = r#"{"data": [{
+            "id": "deepseek/deepseek-chat-v3.1:free",
+            "canonical_slug": "deepseek/deepseek-chat-v3.1",
+            "name": "DeepSeek: DeepSeek V3.1 (free)",
+            "created": 1755779628,
+            "context_length": 163800,
+            "architecture": {
+                "modality": "text->text",
+                "input_modalities": ["text"],
+                "output_modalities": ["text"],
+                "tokenizer": "DeepSeek",
+                "instruct_type": null
+            },
+            "pricing": {"prompt": "0", "completion": "0", "request": "0", "image": ""},
+            "top_provider": {"context_length": 163800, "max_completion_tokens": null, "is_moderated": false},
+            "supported_parameters": ["max_tokens", "temperature", "tools"]
+        }, {
+            "id": "openai/gpt-4o",
+            "name": "OpenAI: GPT-4o",
+            "pricing": {"prompt": "0.0000025", "completion": "0.00001"}
+        }]}"#;
+        let models = serde_json::from_str::<OpenRouterModelsResponse>(body)
+            .unwrap()
+            .data;
+
+        assert!(models[0].is_free());
+        assert!(models[0].supports_parameter("tools"));
+        assert_eq!(models[0].pricing.image, None);
+        assert_eq!(models[0].architecture.input_modalities, vec!["text"]);
+        assert_eq!(models[0].top_provider.max_completion_tokens, None);
+
+        assert!(!models[1].is_free());
+        let (input, output) = models[1].price_per_1k().unwrap();
+        assert!((input - 0.0025).abs() < 1e-12 && (output - 0.01).abs() < 1e-12);
+    }
+}
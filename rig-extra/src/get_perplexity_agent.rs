@@ -0,0 +1,22 @@
+use rig::agent::{Agent, AgentBuilder};
+use rig::client::CompletionClient;
+use rig::client::completion::CompletionModelHandle;
+use rig::providers::perplexity::Client;
+use std::sync::Arc;
+
+/// Perplexity 客户端没有实现 CompletionClientDyn，手动包装成 CompletionModelHandle
+pub fn get_perplexity_agent(
+    client: Client,
+    model_name: &str,
+    agent_name: String,
+    system_prompt: String,
+) -> Agent<CompletionModelHandle<'static>> {
+    let handle = CompletionModelHandle {
+        inner: Arc::new(client.completion_model(model_name)),
+    };
+
+    AgentBuilder::new(handle)
+        .name(agent_name.as_str())
+        .preamble(&system_prompt)
+        .build()
+}
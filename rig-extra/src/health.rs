@@ -0,0 +1,134 @@
+//! 后台健康检查
+//!
+//! [`RandAgent::start_health_checks`](crate::rand_agent::RandAgent::start_health_checks)
+//! 启动后台任务，按固定间隔向每个 agent 发送很短的探测请求:
+//!
+//! - 探测失败或耗时超过慢响应阈值的 agent 被标记为无效，不再被选中
+//! - 已无效的 agent 探测成功后恢复为有效，不必等待冷却后的试探请求
+//!
+//! 最近一轮的结果可以通过 `health_report().await` 查看。被手动停用的 agent 不参与探测。
+//!
+//! ```rust,ignore
+//! let handle = rand_agent.start_health_checks(Duration::from_secs(60), "ping");
+//!
+//! let report = rand_agent.health_report().await;
+//! for agent in report.unhealthy() {
+//!     println!("{} {:?}", agent.info.model, agent.error);
+//! }
+//!
+//! // drop 句柄即停止检查
+//! drop(handle);
+//! ```
+
+use crate::AgentInfo;
+use std::time::{Duration, SystemTime};
+use tokio::task::JoinHandle;
+
+/// 健康检查设置
+#[derive(Debug, Clone)]
+pub struct HealthCheckConfig {
+    /// 两轮检查之间的间隔
+    pub interval: Duration,
+    /// 探测使用的 prompt，应尽量短
+    pub probe_prompt: String,
+    /// 单次探测的超时时间
+    pub timeout: Duration,
+    /// 超过该耗时的探测视为慢响应，为空时只按超时判断
+    pub slow_threshold: Option<Duration>,
+}
+
+impl HealthCheckConfig {
+    pub fn new(interval: Duration, probe_prompt: impl Into<String>) -> Self {
+        Self {
+            interval,
+            probe_prompt: probe_prompt.into(),
+            timeout: Duration::from_secs(30),
+            slow_threshold: None,
+        }
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn slow_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_threshold = Some(threshold);
+        self
+    }
+}
+
+/// 单个 agent 的探测结果
+#[derive(Debug, Clone)]
+pub struct AgentHealth {
+    /// 探测后的 agent 状态
+    pub info: AgentInfo,
+    pub healthy: bool,
+    /// 探测耗时，仅请求成功时有值
+    pub latency: Option<Duration>,
+    /// 失败或慢响应的原因
+    pub error: Option<String>,
+}
+
+/// 最近一轮健康检查的结果
+#[derive(Debug, Clone, Default)]
+pub struct HealthReport {
+    /// 检查完成的时间，尚未检查时为空
+    pub checked_at: Option<SystemTime>,
+    pub agents: Vec<AgentHealth>,
+}
+
+impl HealthReport {
+    pub fn unhealthy(&self) -> impl Iterator<Item = &AgentHealth> {
+        self.agents.iter().filter(|agent| !agent.healthy)
+    }
+}
+
+/// 判断一次探测是否健康
+pub(crate) fn evaluate(
+    result: Result<Duration, String>,
+    slow_threshold: Option<Duration>,
+) -> (bool, Option<Duration>, Option<String>) {
+    match result {
+        Ok(latency) if slow_threshold.is_some_and(|threshold| latency > threshold) => {
+            (false, Some(latency), Some(format!("响应过慢: {latency:?}")))
+        }
+        Ok(latency) => (true, Some(latency), None),
+        Err(err) => (false, None, Some(err)),
+    }
+}
+
+/// 后台健康检查任务的句柄，drop 时停止检查
+pub struct HealthCheckHandle {
+    pub(crate) task: JoinHandle<()>,
+}
+
+impl HealthCheckHandle {
+    /// 停止检查，等同于 drop 句柄
+    pub fn stop(self) {}
+}
+
+impl Drop for HealthCheckHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate_probe() {
+        let slow = Some(Duration::from_secs(5));
+        assert!(evaluate(Ok(Duration::from_secs(1)), slow).0);
+        assert!(evaluate(Ok(Duration::from_secs(10)), None).0);
+
+        let (healthy, latency, error) = evaluate(Ok(Duration::from_secs(10)), slow);
+        assert!(!healthy);
+        assert_eq!(latency, Some(Duration::from_secs(10)));
+        assert!(error.is_some());
+
+        assert!(!evaluate(Err("timeout".to_string()), slow).0);
+    }
+}
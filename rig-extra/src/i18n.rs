@@ -0,0 +1,145 @@
+//! 面向用户的错误信息目录
+//!
+//! 代理池和构建器产生的错误信息统一从这里获取，通过 [`set_locale`] 切换中英文，
+//! 便于应用向用户展示风格一致的错误提示。
+//!
+//! ```rust
+//! use rig_extra::i18n::{Locale, MessageKey, set_locale};
+//!
+//! set_locale(Locale::En);
+//! assert_eq!(MessageKey::NoValidAgents.text(), "No valid agents available");
+//! # set_locale(Locale::Zh);
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// 错误信息语言
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    /// 中文
+    #[default]
+    Zh,
+    /// 英文
+    En,
+}
+
+static LOCALE: AtomicU8 = AtomicU8::new(0);
+
+/// 设置全局错误信息语言，默认中文
+pub fn set_locale(locale: Locale) {
+    LOCALE.store(locale as u8, Ordering::Relaxed);
+}
+
+/// 获取当前错误信息语言
+pub fn locale() -> Locale {
+    match LOCALE.load(Ordering::Relaxed) {
+        1 => Locale::En,
+        _ => Locale::Zh,
+    }
+}
+
+/// 错误信息键
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKey {
+    /// 没有有效 agent
+    NoValidAgents,
+    /// agent 调用出错
+    AgentError,
+    /// prompt 调用出错
+    PromptError,
+    /// 响应未通过校验
+    InvalidResponse,
+    /// 请求超时
+    Timeout,
+    /// 构建 provider 客户端失败
+    ProviderBuildFailed,
+    /// provider 暂不支持 simple_builder
+    ProviderUnsupported,
+    /// 所有 agent 都达到速率限制
+    RateLimited,
+    /// 租户超出额度
+    TenantLimitExceeded,
+    /// 代理池已关闭
+    ShuttingDown,
+    /// 嵌入模型池为空
+    NoEmbeddingModels,
+    /// 嵌入模型的维度不一致
+    EmbeddingDimensionMismatch,
+    /// 没有可用的嵌入模型
+    NoValidEmbeddingModels,
+}
+
+impl MessageKey {
+    /// 按当前语言获取错误信息
+    pub fn text(self) -> &'static str {
+        self.text_in(locale())
+    }
+
+    /// 按指定语言获取错误信息
+    pub fn text_in(self, locale: Locale) -> &'static str {
+        match (self, locale) {
+            (MessageKey::NoValidAgents, Locale::Zh) => "没有有效agent",
+            (MessageKey::NoValidAgents, Locale::En) => "No valid agents available",
+            (MessageKey::AgentError, Locale::Zh) => "agent 调用出错",
+            (MessageKey::AgentError, Locale::En) => "Agent error",
+            (MessageKey::PromptError, Locale::Zh) => "prompt 调用出错",
+            (MessageKey::PromptError, Locale::En) => "Prompt error",
+            (MessageKey::InvalidResponse, Locale::Zh) => "响应内容未通过校验",
+            (MessageKey::InvalidResponse, Locale::En) => "Response failed validation",
+            (MessageKey::Timeout, Locale::Zh) => "请求超时",
+            (MessageKey::Timeout, Locale::En) => "Request timed out",
+            (MessageKey::ProviderBuildFailed, Locale::Zh) => "创建 provider 客户端失败",
+            (MessageKey::ProviderBuildFailed, Locale::En) => "Failed to build provider client",
+            (MessageKey::ProviderUnsupported, Locale::Zh) => "simple_builder 暂不支持该 provider",
+            (MessageKey::ProviderUnsupported, Locale::En) => {
+                "Provider is not supported by simple_builder yet"
+            }
+            (MessageKey::RateLimited, Locale::Zh) => "所有 agent 均已达到速率限制，需等待",
+            (MessageKey::RateLimited, Locale::En) => "All agents are rate limited, retry after",
+            (MessageKey::TenantLimitExceeded, Locale::Zh) => "租户已超出额度",
+            (MessageKey::TenantLimitExceeded, Locale::En) => "Tenant limit exceeded",
+            (MessageKey::ShuttingDown, Locale::Zh) => "代理池已关闭，不再接受新请求",
+            (MessageKey::ShuttingDown, Locale::En) => "Agent pool is shutting down",
+            (MessageKey::NoEmbeddingModels, Locale::Zh) => "嵌入模型池为空",
+            (MessageKey::NoEmbeddingModels, Locale::En) => "Embedding model pool is empty",
+            (MessageKey::EmbeddingDimensionMismatch, Locale::Zh) => "嵌入模型的维度不一致",
+            (MessageKey::EmbeddingDimensionMismatch, Locale::En) => {
+                "Embedding model dimensions do not match"
+            }
+            (MessageKey::NoValidEmbeddingModels, Locale::Zh) => "没有可用的嵌入模型",
+            (MessageKey::NoValidEmbeddingModels, Locale::En) => "No embedding models available",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_in_locale() {
+        assert_eq!(
+            MessageKey::NoValidAgents.text_in(Locale::Zh),
+            "没有有效agent"
+        );
+        assert_eq!(
+            MessageKey::NoValidAgents.text_in(Locale::En),
+            "No valid agents available"
+        );
+        assert_eq!(MessageKey::Timeout.text_in(Locale::En), "Request timed out");
+        assert!(MessageKey::ShuttingDown.text_in(Locale::En).is_ascii());
+    }
+
+    #[test]
+    fn test_set_locale() {
+        assert_eq!(locale(), Locale::Zh);
+        set_locale(Locale::En);
+        assert_eq!(locale(), Locale::En);
+        assert_eq!(MessageKey::AgentError.text(), "Agent error");
+        // 恢复默认语言，避免影响其它测试
+        set_locale(Locale::Zh);
+        assert_eq!(MessageKey::AgentError.text(), "agent 调用出错");
+    }
+}
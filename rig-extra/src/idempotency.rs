@@ -0,0 +1,164 @@
+//! 幂等键结果缓存
+//!
+//! 上游 HTTP 客户端超时重试时，同一个请求可能被提交多次。调用方为每个请求附带幂等键，
+//! 同一个键在有效期内已经成功过时直接返回保存的结果，不再调用提供方，避免重复消耗 token。
+//! 相同的键正在请求时，后到的调用等待第一个请求的结果，而不是再发起一次请求。
+//!
+//! 幂等键按租户隔离，不同租户使用相同的键互不影响。
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// 默认有效期
+pub(crate) const DEFAULT_IDEMPOTENCY_TTL: Duration = Duration::from_secs(600);
+
+/// 幂等键，由租户和调用方提供的键组成
+pub(crate) type IdempotencyKey = (Option<Arc<str>>, String);
+
+#[derive(Debug)]
+enum Entry {
+    /// 请求进行中，结束时通知等待的调用方
+    Pending(Arc<Notify>),
+    /// 成功的结果及保存时间
+    Done(Instant, String),
+}
+
+/// 查询幂等键的结果
+#[derive(Debug)]
+pub(crate) enum Lookup {
+    /// 有效期内已有成功结果
+    Done(String),
+    /// 相同的键正在请求，收到通知后重新查询
+    Pending(Arc<Notify>),
+    /// 没有结果，已登记为进行中，调用方负责发起请求
+    Started,
+}
+
+/// 成功结果缓存，只保存成功的响应
+#[derive(Debug, Default)]
+pub(crate) struct IdempotencyCache {
+    entries: HashMap<IdempotencyKey, Entry>,
+}
+
+impl IdempotencyCache {
+    /// 查询幂等键，没有结果时登记为进行中，同时清理过期的结果
+    pub(crate) fn begin(&mut self, key: &IdempotencyKey, ttl: Duration) -> Lookup {
+        self.entries.retain(|_, entry| match entry {
+            Entry::Pending(_) => true,
+            Entry::Done(stored_at, _) => stored_at.elapsed() < ttl,
+        });
+        match self.entries.get(key) {
+            Some(Entry::Done(_, content)) => Lookup::Done(content.clone()),
+            Some(Entry::Pending(notify)) => Lookup::Pending(notify.clone()),
+            None => {
+                let notify = Arc::new(Notify::new());
+                self.entries.insert(key.clone(), Entry::Pending(notify));
+                Lookup::Started
+            }
+        }
+    }
+
+    /// 是否仍在等待 `notify` 对应的请求
+    pub(crate) fn is_pending(&self, key: &IdempotencyKey, notify: &Arc<Notify>) -> bool {
+        matches!(self.entries.get(key), Some(Entry::Pending(pending)) if Arc::ptr_eq(pending, notify))
+    }
+
+    /// 结束进行中的请求: 成功时保存结果，失败时删除登记，并通知等待的调用方
+    fn finish(&mut self, key: &IdempotencyKey, content: Option<String>) {
+        let previous = match content {
+            Some(content) => self
+                .entries
+                .insert(key.clone(), Entry::Done(Instant::now(), content)),
+            None => self.entries.remove(key),
+        };
+        if let Some(Entry::Pending(notify)) = previous {
+            notify.notify_waiters();
+        }
+    }
+}
+
+/// 进行中的幂等请求，未调用 [`finish`](Self::finish) 就被丢弃(如调用方取消)时按失败处理，
+/// 等待的调用方可以重新发起请求
+pub(crate) struct PendingRequest<'a> {
+    cache: &'a Mutex<IdempotencyCache>,
+    key: Option<IdempotencyKey>,
+}
+
+impl<'a> PendingRequest<'a> {
+    pub(crate) fn new(cache: &'a Mutex<IdempotencyCache>, key: IdempotencyKey) -> Self {
+        Self {
+            cache,
+            key: Some(key),
+        }
+    }
+
+    /// 保存成功的结果，`None` 表示失败
+    pub(crate) fn finish(mut self, content: Option<String>) {
+        self.finish_with(content);
+    }
+
+    fn finish_with(&mut self, content: Option<String>) {
+        if let Some(key) = self.key.take() {
+            self.cache
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .finish(&key, content);
+        }
+    }
+}
+
+impl Drop for PendingRequest<'_> {
+    fn drop(&mut self) {
+        self.finish_with(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(tenant: Option<&str>, key: &str) -> IdempotencyKey {
+        (tenant.map(Arc::from), key.to_string())
+    }
+
+    #[test]
+    fn test_idempotency_cache_ttl() {
+        let cache = Mutex::new(IdempotencyCache::default());
+        let lookup = |key: &IdempotencyKey, ttl| cache.lock().unwrap().begin(key, ttl);
+        let req1 = key(None, "req-1");
+
+        assert!(matches!(
+            lookup(&req1, DEFAULT_IDEMPOTENCY_TTL),
+            Lookup::Started
+        ));
+        let Lookup::Pending(notify) = lookup(&req1, DEFAULT_IDEMPOTENCY_TTL) else {
+            panic!("相同的键应等待进行中的请求");
+        };
+        assert!(cache.lock().unwrap().is_pending(&req1, &notify));
+        PendingRequest::new(&cache, req1.clone()).finish(Some("你好".to_string()));
+        assert!(!cache.lock().unwrap().is_pending(&req1, &notify));
+        assert!(matches!(
+            lookup(&req1, DEFAULT_IDEMPOTENCY_TTL),
+            Lookup::Done(content) if content == "你好"
+        ));
+
+        // 不同租户的相同键互不影响
+        assert!(matches!(
+            lookup(&key(Some("acme"), "req-1"), DEFAULT_IDEMPOTENCY_TTL),
+            Lookup::Started
+        ));
+
+        assert!(matches!(lookup(&req1, Duration::ZERO), Lookup::Started));
+    }
+
+    #[test]
+    fn test_dropped_request_releases_key() {
+        let cache = Mutex::new(IdempotencyCache::default());
+        let req1 = key(None, "req-1");
+        cache.lock().unwrap().begin(&req1, DEFAULT_IDEMPOTENCY_TTL);
+        drop(PendingRequest::new(&cache, req1.clone()));
+        assert!(cache.lock().unwrap().entries.is_empty());
+    }
+}
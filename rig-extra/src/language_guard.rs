@@ -0,0 +1,190 @@
+//! 响应语言校验
+//!
+//! 免费模型经常在中英文之间随机切换。[`LanguageGuard`] 检测响应语言，与期望不一致时
+//! 按 [`LanguageAction`] 要求模型重新回答，或把响应翻译为期望的语言。
+//!
+//! ```rust,ignore
+//! use rig_extra::i18n::Locale;
+//! use rig_extra::language_guard::{LanguageAction, LanguageGuard};
+//!
+//! let agent = LanguageGuard::new(rand_agent, Locale::Zh).action(LanguageAction::Translate);
+//! let response = agent.prompt("介绍一下 Rust").await?;
+//! ```
+
+use crate::i18n::Locale;
+use rig::completion::{Chat, Message, Prompt, PromptError};
+
+/// 语言不一致时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LanguageAction {
+    /// 携带上一次的回答要求模型用期望的语言重新回答，最多重试指定次数
+    Reprompt(usize),
+    /// 将回答翻译为期望的语言
+    Translate,
+}
+
+impl Default for LanguageAction {
+    fn default() -> Self {
+        LanguageAction::Reprompt(1)
+    }
+}
+
+/// 检测文本的主要语言，无法判断时返回 None
+///
+/// 按汉字数与英文单词数比较，中文中夹杂少量英文术语仍判定为中文
+pub fn detect_language(text: &str) -> Option<Locale> {
+    let han = text.chars().filter(|c| is_han(*c)).count();
+    let latin_words = text
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .filter(|word| !word.is_empty())
+        .count();
+    match (han, latin_words) {
+        (0, 0) => None,
+        (han, words) if han >= words => Some(Locale::Zh),
+        _ => Some(Locale::En),
+    }
+}
+
+fn is_han(c: char) -> bool {
+    matches!(c, '\u{4E00}'..='\u{9FFF}' | '\u{3400}'..='\u{4DBF}' | '\u{F900}'..='\u{FAFF}')
+}
+
+/// 响应语言校验包装器
+pub struct LanguageGuard<A> {
+    agent: A,
+    language: Locale,
+    action: LanguageAction,
+}
+
+impl<A> LanguageGuard<A> {
+    pub fn new(agent: A, language: Locale) -> Self {
+        Self {
+            agent,
+            language,
+            action: LanguageAction::default(),
+        }
+    }
+
+    /// 设置语言不一致时的处理方式，默认重新回答一次
+    pub fn action(mut self, action: LanguageAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    /// 获取内部 agent
+    pub fn inner(&self) -> &A {
+        &self.agent
+    }
+
+    fn matches(&self, response: &str) -> bool {
+        detect_language(response).is_none_or(|language| language == self.language)
+    }
+
+    fn reprompt_instruction(&self) -> &'static str {
+        match self.language {
+            Locale::Zh => "请只使用中文重新回答上面的问题。",
+            Locale::En => "Please answer the question above again, in English only.",
+        }
+    }
+
+    fn translate_instruction(&self, response: &str) -> String {
+        match self.language {
+            Locale::Zh => format!("将以下内容翻译成中文，只输出译文:\n\n{response}"),
+            Locale::En => format!(
+                "Translate the following text into English. Output only the translation:\n\n{response}"
+            ),
+        }
+    }
+}
+
+impl<A> LanguageGuard<A>
+where
+    A: Prompt + Chat + Sync,
+{
+    /// 校验响应语言，不一致时按配置处理
+    async fn enforce(
+        &self,
+        prompt: Message,
+        mut history: Vec<Message>,
+        mut response: String,
+    ) -> Result<String, PromptError> {
+        if self.matches(&response) {
+            return Ok(response);
+        }
+        tracing::warn!(
+            "响应语言与期望的 {:?} 不一致，处理方式: {:?}",
+            self.language,
+            self.action
+        );
+
+        match self.action {
+            LanguageAction::Reprompt(max_retries) => {
+                history.push(prompt);
+                for _ in 0..max_retries {
+                    history.push(Message::assistant(response));
+                    response = self
+                        .agent
+                        .chat(self.reprompt_instruction(), history.clone())
+                        .await?;
+                    if self.matches(&response) {
+                        break;
+                    }
+                    history.push(Message::user(self.reprompt_instruction()));
+                }
+                Ok(response)
+            }
+            LanguageAction::Translate => {
+                self.agent
+                    .prompt(self.translate_instruction(&response))
+                    .await
+            }
+        }
+    }
+}
+
+impl<A> Prompt for LanguageGuard<A>
+where
+    A: Prompt + Chat + Sync,
+{
+    #[allow(refining_impl_trait)]
+    async fn prompt(&self, prompt: impl Into<Message> + Send) -> Result<String, PromptError> {
+        let prompt = prompt.into();
+        let response = self.agent.prompt(prompt.clone()).await?;
+        self.enforce(prompt, Vec::new(), response).await
+    }
+}
+
+impl<A> Chat for LanguageGuard<A>
+where
+    A: Prompt + Chat + Sync,
+{
+    #[allow(refining_impl_trait)]
+    async fn chat(
+        &self,
+        prompt: impl Into<Message> + Send,
+        chat_history: Vec<Message>,
+    ) -> Result<String, PromptError> {
+        let prompt = prompt.into();
+        let response = self
+            .agent
+            .chat(prompt.clone(), chat_history.clone())
+            .await?;
+        self.enforce(prompt, chat_history, response).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_language() {
+        assert_eq!(detect_language("你好，世界"), Some(Locale::Zh));
+        assert_eq!(detect_language("Hello, world"), Some(Locale::En));
+        assert_eq!(
+            detect_language("Rust 是一门注重安全和性能的系统编程语言"),
+            Some(Locale::Zh)
+        );
+        assert_eq!(detect_language("12345 !!"), None);
+    }
+}
@@ -1,12 +1,63 @@
+pub mod batch;
+#[cfg(feature = "rig-extra-tools")]
+pub mod calendar;
+#[cfg(feature = "rig-extra-chaos")]
+pub mod chaos;
+pub mod circuit_breaker;
+mod concurrency;
+#[cfg(feature = "rig-extra-config-file")]
+pub mod config_file;
+#[cfg(feature = "rig-extra-config-watch")]
+pub mod config_watch;
+pub mod consensus;
+pub mod diagnostics;
+pub mod dyn_agent;
 pub mod error;
 pub mod extra_providers;
+mod extraction_cache;
+pub mod failure_dump;
+pub mod fallback_agent;
+pub mod firewall;
 mod get_openai_agent;
 mod get_openrouter_model_list;
+mod get_perplexity_agent;
+pub mod health;
+pub mod i18n;
+mod idempotency;
 mod json_utils;
+pub mod language_guard;
+mod lifecycle;
+pub mod memory;
+pub mod model_catalog;
+pub mod params;
+pub mod pool_router;
+mod preamble;
+pub mod progress;
+pub mod prompt_templates;
 pub mod rand_agent;
+pub mod rand_embedding;
+pub mod rate_limit;
+pub mod reasoning;
+pub mod redact;
+pub mod response_cache;
+pub mod retry;
+pub mod scheduler;
+pub mod session;
+pub mod shaping;
 pub mod simple_rand_builder;
+pub mod snapshot;
+mod spans;
+pub mod stream_tee;
+pub mod structured;
+pub mod tenant;
+pub mod thread_safe_rand_agent;
+pub mod tool_events;
+pub mod tool_memory;
+pub mod tool_policy;
 #[cfg(feature = "rig-extra-tools")]
 pub mod tools;
+pub mod usage;
+pub mod usage_export;
 
 pub use get_openrouter_model_list::*;
 
@@ -26,4 +77,18 @@ pub struct AgentInfo {
     pub failure_count: u32,
     /// 最大失败次数
     pub max_failures: u32,
+    /// 成功请求的平均延迟(指数移动平均)
+    pub avg_latency: Option<std::time::Duration>,
+    /// 输入价格(每千 token)
+    pub input_price: Option<f64>,
+    /// 输出价格(每千 token)
+    pub output_price: Option<f64>,
+    /// 是否被手动停用
+    pub disabled: bool,
+    /// 预留给指定工作负载，只有该工作负载的调用可以选中
+    pub reserved_for: Option<String>,
+    /// 结构化输出支持情况
+    pub structured_output: structured::StructuredOutput,
+    /// 上下文长度(tokens)，未知时为空
+    pub context_length: Option<u32>,
 }
@@ -0,0 +1,115 @@
+//! 代理池的关闭与排空
+//!
+//! 记录正在进行的请求数和代理池启动的后台任务(健康检查、配置文件监视)，
+//! [`RandAgent::shutdown`](crate::rand_agent::RandAgent::shutdown) 关闭后不再接受新请求，
+//! 停止后台任务并等待进行中的请求完成。
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task::AbortHandle;
+
+#[derive(Debug, Default)]
+pub(crate) struct Lifecycle {
+    closed: AtomicBool,
+    in_flight: AtomicUsize,
+    idle: Notify,
+    tasks: Mutex<Vec<AbortHandle>>,
+}
+
+/// 一个进行中的请求，丢弃时计数减一
+#[derive(Debug)]
+pub(crate) struct InFlightGuard {
+    lifecycle: Arc<Lifecycle>,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if self.lifecycle.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.lifecycle.idle.notify_waiters();
+        }
+    }
+}
+
+impl Lifecycle {
+    /// 开始一个请求，已关闭时返回 None
+    ///
+    /// 先计数再检查关闭状态，关闭后等待排空时不会漏掉刚通过检查的请求
+    pub(crate) fn enter(self: &Arc<Self>) -> Option<InFlightGuard> {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        let guard = InFlightGuard {
+            lifecycle: self.clone(),
+        };
+        (!self.is_closed()).then_some(guard)
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// 登记后台任务，已关闭时立即停止
+    pub(crate) fn register(&self, task: AbortHandle) {
+        if self.is_closed() {
+            task.abort();
+            return;
+        }
+        let mut tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner());
+        tasks.retain(|task| !task.is_finished());
+        tasks.push(task);
+    }
+
+    /// 关闭并停止后台任务，在 `grace` 内等待进行中的请求完成，全部完成时返回 true
+    pub(crate) async fn shutdown(&self, grace: Duration) -> bool {
+        self.closed.store(true, Ordering::SeqCst);
+        for task in self
+            .tasks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .drain(..)
+        {
+            task.abort();
+        }
+        let drain = async {
+            loop {
+                let idle = self.idle.notified();
+                if self.in_flight() == 0 {
+                    return;
+                }
+                idle.await;
+            }
+        };
+        tokio::time::timeout(grace, drain).await.is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_shutdown_drains_in_flight() {
+        let lifecycle = Arc::new(Lifecycle::default());
+        let guard = lifecycle.enter().unwrap();
+        let task = tokio::spawn(std::future::pending::<()>());
+        lifecycle.register(task.abort_handle());
+
+        // 进行中的请求未完成时超时
+        assert!(!lifecycle.shutdown(Duration::from_millis(10)).await);
+        assert!(task.await.unwrap_err().is_cancelled());
+        assert!(lifecycle.enter().is_none());
+
+        let waiter = {
+            let lifecycle = lifecycle.clone();
+            tokio::spawn(async move { lifecycle.shutdown(Duration::from_secs(5)).await })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        drop(guard);
+        assert!(waiter.await.unwrap());
+        assert_eq!(lifecycle.in_flight(), 0);
+    }
+}
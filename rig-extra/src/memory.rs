@@ -0,0 +1,349 @@
+//! 按会话保存的对话记忆
+//!
+//! 代理池本身不保存状态，每次 `chat` 都需要调用方传入完整的历史记录。[`MemoryManager`]
+//! 按会话 id 保存历史记录，通过 [`RandAgent::chat_session`](crate::rand_agent::RandAgent::chat_session)
+//! 提问时自动带上该会话的历史，并在成功后记录本轮的提问和回答。
+//!
+//! 历史记录默认保存在内存中([`InMemoryStore`]，每个会话是一个环形缓冲区)，实现
+//! [`MemoryStore`] 可以保存到 Redis、数据库等外部存储。发送时只保留最近的
+//! `max_messages` 条消息；设置摘要 agent 后，更早的消息被压缩为一条摘要而不是直接丢弃。
+//!
+//! 设置对话记忆后，`chat` 和 `chat_session` 在选中 agent 后还会按该 agent 的上下文长度
+//! 检查历史记录: 估算的 token 数超过上下文长度的四分之三(扣除本次 prompt)时，较早的消息
+//! 同样被压缩为摘要，使长会话可以在上下文长度不同的模型之间切换。上下文长度来自
+//! `AgentConfig::context_length`、内置的智谱模型列表或
+//! [`RandAgent::apply_openrouter_context_lengths`](crate::rand_agent::RandAgent::apply_openrouter_context_lengths)，
+//! 未知上下文长度的 agent 不做检查。
+//!
+//! ```rust,ignore
+//! use rig_extra::memory::{InMemoryStore, MemoryManager};
+//!
+//! let memory = MemoryManager::new(InMemoryStore::new(200))
+//!     .max_messages(20)
+//!     .summarize_with(Arc::new(cheap_pool));
+//!
+//! let rand_agent = RandAgentBuilder::new()
+//!     .memory(memory)
+//!     .simple_builder(configs, "你是一个助手".to_string())
+//!     .build();
+//!
+//! rand_agent.chat_session("user-42", "我叫小明").await?;
+//! let response = rand_agent.chat_session("user-42", "我叫什么名字?").await?;
+//! ```
+
+use crate::dyn_agent::DynPromptAgent;
+use futures::future::BoxFuture;
+use rig::completion::Message;
+use rig::message::{AssistantContent, UserContent};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+/// 摘要消息的前缀，便于模型区分摘要和原始对话
+const SUMMARY_PREFIX: &str = "[之前的对话摘要]";
+
+/// 可插拔的历史记录存储
+pub trait MemoryStore: Send + Sync {
+    /// 读取会话的全部历史记录，按时间顺序
+    fn load<'a>(&'a self, session: &'a str) -> BoxFuture<'a, Vec<Message>>;
+
+    /// 追加消息
+    fn append<'a>(&'a self, session: &'a str, messages: Vec<Message>) -> BoxFuture<'a, ()>;
+
+    /// 清空会话
+    fn clear<'a>(&'a self, session: &'a str) -> BoxFuture<'a, ()>;
+}
+
+/// 内存中的历史记录，每个会话最多保存 `capacity` 条消息，超出后丢弃最早的消息
+pub struct InMemoryStore {
+    capacity: usize,
+    sessions: Mutex<HashMap<String, VecDeque<Message>>>,
+}
+
+impl InMemoryStore {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, VecDeque<Message>>> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl MemoryStore for InMemoryStore {
+    fn load<'a>(&'a self, session: &'a str) -> BoxFuture<'a, Vec<Message>> {
+        Box::pin(async move {
+            self.lock()
+                .get(session)
+                .map(|messages| messages.iter().cloned().collect())
+                .unwrap_or_default()
+        })
+    }
+
+    fn append<'a>(&'a self, session: &'a str, messages: Vec<Message>) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            let mut sessions = self.lock();
+            let history = sessions.entry(session.to_string()).or_default();
+            for message in messages {
+                if history.len() >= self.capacity {
+                    history.pop_front();
+                }
+                history.push_back(message);
+            }
+        })
+    }
+
+    fn clear<'a>(&'a self, session: &'a str) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            self.lock().remove(session);
+        })
+    }
+}
+
+/// 对话记忆管理
+#[derive(Clone)]
+pub struct MemoryManager {
+    store: Arc<dyn MemoryStore>,
+    max_messages: Option<usize>,
+    summarizer: Option<Arc<dyn DynPromptAgent>>,
+    /// 按被压缩消息的哈希缓存摘要
+    summaries: Arc<Mutex<HashMap<u64, String>>>,
+}
+
+impl MemoryManager {
+    pub fn new(store: impl MemoryStore + 'static) -> Self {
+        Self {
+            store: Arc::new(store),
+            max_messages: None,
+            summarizer: None,
+            summaries: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// 发送时最多携带的历史消息数，默认不限制
+    pub fn max_messages(mut self, max_messages: usize) -> Self {
+        self.max_messages = Some(max_messages);
+        self
+    }
+
+    /// 使用指定 agent 把超出 `max_messages` 的较早消息压缩为摘要，建议使用便宜、快速的模型
+    pub fn summarize_with(mut self, agent: Arc<dyn DynPromptAgent>) -> Self {
+        self.summarizer = Some(agent);
+        self
+    }
+
+    /// 会话保存的全部历史记录
+    pub async fn messages(&self, session: &str) -> Vec<Message> {
+        self.store.load(session).await
+    }
+
+    /// 发送时使用的历史记录: 截断或摘要较早的消息
+    pub async fn history(&self, session: &str) -> Vec<Message> {
+        let messages = self.store.load(session).await;
+        let Some(max_messages) = self.max_messages else {
+            return messages;
+        };
+        if messages.len() <= max_messages {
+            return messages;
+        }
+        let (older, recent) = messages.split_at(messages.len() - max_messages);
+        // 保证保留的历史从用户消息开始
+        let start = recent
+            .iter()
+            .position(|message| matches!(message, Message::User { .. }))
+            .unwrap_or(recent.len());
+        let (dropped, recent) = recent.split_at(start);
+        let older = [older, dropped].concat();
+
+        self.compress(&older, recent).await
+    }
+
+    /// 历史记录超出 `budget` 个 token(估算值)时，保留最近的、至多占一半预算的消息，
+    /// 更早的消息压缩为摘要；未设置摘要 agent 时直接丢弃
+    pub(crate) async fn fit(&self, history: Vec<Message>, budget: usize) -> Vec<Message> {
+        let sizes: Vec<usize> = history.iter().map(estimate_message_tokens).collect();
+        if sizes.iter().sum::<usize>() <= budget {
+            return history;
+        }
+        let mut start = history.len();
+        let mut kept = 0;
+        while start > 0 && kept + sizes[start - 1] <= budget / 2 {
+            start -= 1;
+            kept += sizes[start];
+        }
+        // 保证保留的历史从用户消息开始
+        while start < history.len() && !matches!(history[start], Message::User { .. }) {
+            start += 1;
+        }
+        tracing::debug!(
+            dropped = start,
+            kept = history.len() - start,
+            "历史记录超出上下文预算，压缩较早的消息"
+        );
+        let (older, recent) = history.split_at(start);
+        self.compress(older, recent).await
+    }
+
+    /// 把较早的消息替换为一条摘要消息
+    async fn compress(&self, older: &[Message], recent: &[Message]) -> Vec<Message> {
+        let mut history = Vec::with_capacity(recent.len() + 1);
+        if let Some(summary) = self.summarize(older).await {
+            history.push(Message::user(format!("{SUMMARY_PREFIX}\n{summary}")));
+        }
+        history.extend_from_slice(recent);
+        history
+    }
+
+    /// 记录一轮成功的对话
+    pub async fn record(&self, session: &str, prompt: Message, response: &str) {
+        self.store
+            .append(session, vec![prompt, Message::assistant(response)])
+            .await;
+    }
+
+    pub async fn clear(&self, session: &str) {
+        self.store.clear(session).await;
+    }
+
+    /// 把消息压缩为摘要，未设置摘要 agent 或摘要失败时返回 None
+    pub(crate) async fn summarize(&self, messages: &[Message]) -> Option<String> {
+        let agent = self.summarizer.as_ref()?;
+        let text = transcript(messages);
+        if text.is_empty() {
+            return None;
+        }
+        let key = hash_text(&text);
+        if let Some(summary) = self.cached(key) {
+            return Some(summary);
+        }
+
+        let prompt = format!(
+            "请概括以下对话，保留用户提供的事实、偏好和尚未完成的事项，只输出摘要:\n\n{text}"
+        );
+        match agent.prompt_dyn(prompt.into()).await {
+            Ok(summary) => {
+                let summary = summary.trim().to_string();
+                self.summaries
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .insert(key, summary.clone());
+                Some(summary)
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "对话摘要失败，丢弃较早的消息: {}",
+                    crate::redact::redacted(&err)
+                );
+                None
+            }
+        }
+    }
+
+    fn cached(&self, key: u64) -> Option<String> {
+        self.summaries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&key)
+            .cloned()
+    }
+}
+
+/// 把消息中的文本整理为对话记录，忽略图片、工具调用等内容
+pub(crate) fn transcript(messages: &[Message]) -> String {
+    let mut lines = Vec::new();
+    for message in messages {
+        match message {
+            Message::User { content } => {
+                for content in content.iter() {
+                    if let UserContent::Text(text) = content {
+                        lines.push(format!("用户: {}", text.text));
+                    }
+                }
+            }
+            Message::Assistant { content, .. } => {
+                for content in content.iter() {
+                    if let AssistantContent::Text(text) = content {
+                        lines.push(format!("助手: {}", text.text));
+                    }
+                }
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+/// 粗略估算 token 数: ASCII 字符按 4 个一个 token，其它字符(如中文)按 1 个一个 token
+pub(crate) fn estimate_tokens(text: &str) -> usize {
+    let ascii = text.bytes().filter(u8::is_ascii).count();
+    let other = text.chars().filter(|ch| !ch.is_ascii()).count();
+    ascii.div_ceil(4) + other
+}
+
+/// 估算一条消息的 token 数，包括工具调用等非文本内容
+pub(crate) fn estimate_message_tokens(message: &Message) -> usize {
+    let text = serde_json::to_string(message).unwrap_or_default();
+    estimate_tokens(&text)
+}
+
+fn hash_text(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_truncation() {
+        let memory = MemoryManager::new(InMemoryStore::new(5)).max_messages(3);
+        for i in 0..3 {
+            memory
+                .record("a", Message::user(format!("问题{i}")), &format!("回答{i}"))
+                .await;
+        }
+        memory.record("b", Message::user("其它会话"), "好的").await;
+
+        // 环形缓冲区只保留最近 5 条
+        assert_eq!(memory.messages("a").await.len(), 5);
+        // 截断后从用户消息开始
+        let history = memory.history("a").await;
+        assert_eq!(
+            history,
+            vec![Message::user("问题2"), Message::assistant("回答2")]
+        );
+        assert_eq!(transcript(&history), "用户: 问题2\n助手: 回答2".to_string());
+
+        memory.clear("a").await;
+        assert!(memory.history("a").await.is_empty());
+        assert_eq!(memory.messages("b").await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_fit_context_budget() {
+        assert_eq!(estimate_tokens("abcdefgh"), 2);
+        assert_eq!(estimate_tokens("你好ab"), 3);
+
+        let memory = MemoryManager::new(InMemoryStore::new(10));
+        let history: Vec<Message> = (0..4)
+            .flat_map(|i| {
+                [
+                    Message::user(format!("问题{i}")),
+                    Message::assistant(format!("回答{i}")),
+                ]
+            })
+            .collect();
+        let total: usize = history.iter().map(estimate_message_tokens).sum();
+        assert_eq!(memory.fit(history.clone(), total).await, history);
+
+        // 预算不足时只保留最近的一轮
+        let per_turn = estimate_message_tokens(&history[6]) + estimate_message_tokens(&history[7]);
+        let fitted = memory.fit(history.clone(), per_turn * 2 + 1).await;
+        assert_eq!(fitted, history[6..].to_vec());
+    }
+}
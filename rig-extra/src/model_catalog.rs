@@ -0,0 +1,637 @@
+//! 带缓存的 openrouter 模型目录
+//!
+//! [`ModelCatalog`] 缓存官方 `/api/v1/models` 接口返回的模型列表，超过有效期后再次获取。
+//! 记忆截断、路由等模块可以直接查询模型的上下文长度、价格和支持的参数，不必重复请求。
+//! 查询只读取缓存，不会阻塞；后台刷新任务或 [`ModelCatalog::ensure_fresh`] 负责更新缓存。
+//!
+//! ```rust,ignore
+//! use rig_extra::model_catalog::ModelCatalog;
+//!
+//! let catalog = ModelCatalog::new(Duration::from_secs(3600));
+//! catalog.refresh().await?;
+//! // 每个有效期刷新一次，drop 句柄即停止
+//! let handle = catalog.spawn_refresh();
+//!
+//! let context = catalog.context_length("deepseek/deepseek-chat");
+//! ```
+//!
+//! 带 `:free` 等变体后缀的 id 找不到时，按去掉后缀的 id 查询。
+//!
+//! ## 其它聚合平台的模型列表
+//!
+//! [`ModelListProvider`] 统一各平台的模型列表接口，返回与 openrouter 相同的 [`Model`]，
+//! 可以直接使用 [`ModelListQuery`](crate::ModelListQuery) 过滤:
+//!
+//! ```rust,ignore
+//! use rig_extra::model_catalog::{GroqModelList, ModelListProvider, SiliconFlowModelList};
+//!
+//! let providers: Vec<Box<dyn ModelListProvider>> = vec![
+//!     Box::new(GroqModelList::new(&groq_key)),
+//!     Box::new(SiliconFlowModelList::new(&siliconflow_key)),
+//! ];
+//! for provider in &providers {
+//!     let models = provider.fetch_models().await?;
+//!     println!("{}: {} 个模型", provider.name(), models.len());
+//! }
+//! ```
+//!
+//! 各平台返回的信息不同，缺少的字段保持默认值: Groq 没有价格，SiliconFlow 只有模型 id。
+
+use crate::get_openrouter_model_list::{
+    Endpoint, Model, OPENROUTER_MODELS_URL, OpenRouterModel, OpenRouterPricing, Pricing,
+    fetch_models, fetch_openrouter_model_list,
+};
+use crate::usage_export::utc_datetime;
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+#[derive(Default)]
+struct CatalogState {
+    models: HashMap<String, OpenRouterModel>,
+    fetched_at: Option<Instant>,
+}
+
+/// openrouter 模型目录，clone 后共享同一份缓存
+#[derive(Clone)]
+pub struct ModelCatalog {
+    url: String,
+    api_key: Option<String>,
+    ttl: Duration,
+    state: Arc<RwLock<CatalogState>>,
+}
+
+impl ModelCatalog {
+    /// 创建空目录，缓存的有效期为 `ttl`
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            url: OPENROUTER_MODELS_URL.to_string(),
+            api_key: None,
+            ttl,
+            state: Arc::new(RwLock::new(CatalogState::default())),
+        }
+    }
+
+    /// 使用已有的模型列表创建目录，视为刚刚获取
+    pub fn from_models(models: Vec<OpenRouterModel>, ttl: Duration) -> Self {
+        let catalog = Self::new(ttl);
+        catalog.store(models);
+        catalog
+    }
+
+    /// 请求时带上 API 密钥
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// 使用其它接口地址，如代理网关
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.url = url.into();
+        self
+    }
+
+    fn state(&self) -> std::sync::RwLockReadGuard<'_, CatalogState> {
+        self.state.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn store(&self, models: Vec<OpenRouterModel>) {
+        let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+        state.models = models
+            .into_iter()
+            .map(|model| (model.id.clone(), model))
+            .collect();
+        state.fetched_at = Some(Instant::now());
+    }
+
+    /// 立即重新获取模型列表，返回模型数；失败时保留原有缓存
+    pub async fn refresh(&self) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let models = fetch_models(&self.url, self.api_key.as_deref()).await?;
+        let count = models.len();
+        self.store(models);
+        Ok(count)
+    }
+
+    /// 从未获取或已超过有效期
+    pub fn is_stale(&self) -> bool {
+        self.state()
+            .fetched_at
+            .is_none_or(|fetched_at| fetched_at.elapsed() >= self.ttl)
+    }
+
+    /// 缓存过期时重新获取
+    pub async fn ensure_fresh(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if self.is_stale() {
+            self.refresh().await?;
+        }
+        Ok(())
+    }
+
+    /// 在后台任务中每个有效期刷新一次，失败时记录日志并保留原有缓存
+    pub fn spawn_refresh(&self) -> ModelCatalogHandle {
+        let catalog = self.clone();
+        let task = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(catalog.ttl);
+            loop {
+                interval.tick().await;
+                if !catalog.is_stale() {
+                    continue;
+                }
+                match catalog.refresh().await {
+                    Ok(count) => tracing::debug!("模型目录已刷新，共 {count} 个模型"),
+                    Err(err) => {
+                        tracing::warn!("刷新模型目录失败: {}", crate::redact::redacted(&err))
+                    }
+                }
+            }
+        });
+        ModelCatalogHandle { task }
+    }
+
+    /// 缓存中的模型数
+    pub fn len(&self) -> usize {
+        self.state().models.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 缓存中的所有模型，顺序不固定
+    pub fn models(&self) -> Vec<OpenRouterModel> {
+        self.state().models.values().cloned().collect()
+    }
+
+    /// 按 id 查询模型
+    pub fn get(&self, id: &str) -> Option<OpenRouterModel> {
+        self.lookup(id, |model| model.clone())
+    }
+
+    /// 模型的上下文长度
+    pub fn context_length(&self, id: &str) -> Option<u64> {
+        self.lookup(id, |model| {
+            model.context_length.or(model.top_provider.context_length)
+        })
+        .flatten()
+    }
+
+    /// 模型的最大输出 token 数
+    pub fn max_completion_tokens(&self, id: &str) -> Option<u64> {
+        self.lookup(id, |model| model.top_provider.max_completion_tokens)
+            .flatten()
+    }
+
+    /// 模型的价格
+    pub fn pricing(&self, id: &str) -> Option<OpenRouterPricing> {
+        self.lookup(id, |model| model.pricing)
+    }
+
+    /// 模型是否支持某个请求参数，未知模型返回 `false`
+    pub fn supports_parameter(&self, id: &str, parameter: &str) -> bool {
+        self.lookup(id, |model| model.supports_parameter(parameter))
+            .unwrap_or(false)
+    }
+
+    fn lookup<T>(&self, id: &str, f: impl FnOnce(&OpenRouterModel) -> T) -> Option<T> {
+        let state = self.state();
+        state
+            .models
+            .get(id)
+            .or_else(|| {
+                let (base, _variant) = id.split_once(':')?;
+                state.models.get(base)
+            })
+            .map(f)
+    }
+}
+
+/// 后台刷新任务的句柄，drop 时停止刷新
+pub struct ModelCatalogHandle {
+    task: JoinHandle<()>,
+}
+
+impl ModelCatalogHandle {
+    /// 停止刷新，等同于 drop 句柄
+    pub fn stop(self) {}
+}
+
+impl Drop for ModelCatalogHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+pub type ModelListError = Box<dyn std::error::Error + Send + Sync>;
+
+/// 获取某个平台的模型列表
+pub trait ModelListProvider: Send + Sync {
+    /// 平台名称，小写，如 `groq`
+    fn name(&self) -> &str;
+
+    /// 获取模型列表，转换为统一的 [`Model`]
+    fn fetch_models(&self) -> BoxFuture<'_, Result<Vec<Model>, ModelListError>>;
+}
+
+pub const GROQ_MODELS_URL: &str = "https://api.groq.com/openai/v1/models";
+pub const TOGETHER_MODELS_URL: &str = "https://api.together.xyz/v1/models";
+pub const SILICONFLOW_MODELS_URL: &str = "https://api.siliconflow.cn/v1/models?type=text";
+
+async fn get_body(url: &str, api_key: &str) -> Result<String, ModelListError> {
+    let resp = reqwest::Client::new()
+        .get(url)
+        .bearer_auth(api_key)
+        .header(reqwest::header::ACCEPT, "application/json")
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        return Err(format!("request failed: {}", resp.status()).into());
+    }
+    Ok(resp.text().await?)
+}
+
+/// OpenAI 兼容的 `{"data": [...]}` 列表
+#[derive(Deserialize)]
+struct ListResponse<T> {
+    data: Vec<T>,
+}
+
+/// 按统一格式填充模型的公共字段
+fn normalized_model(
+    id: String,
+    name: Option<String>,
+    author: Option<String>,
+    created: i64,
+) -> Model {
+    let (prefix, short_name) = id.split_once('/').unwrap_or(("", &id));
+    let created_at = if created > 0 {
+        let (year, month, day, hour, minute, second) = utc_datetime(created as u64);
+        format!("{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}Z")
+    } else {
+        String::new()
+    };
+    Model {
+        slug: id.clone(),
+        updated_at: created_at.clone(),
+        created_at,
+        name: name.unwrap_or_else(|| id.clone()),
+        short_name: short_name.to_string(),
+        author: author
+            .filter(|author| !author.is_empty())
+            .unwrap_or_else(|| prefix.to_string()),
+        description: String::new(),
+        context_length: 0,
+        input_modalities: vec!["text".to_string()],
+        output_modalities: vec!["text".to_string()],
+        has_text_output: true,
+        group: String::new(),
+        permaslug: id,
+        endpoint: None,
+    }
+}
+
+/// openrouter 模型列表，使用 [`fetch_openrouter_model_list`]
+#[derive(Debug, Clone, Default)]
+pub struct OpenRouterModelList;
+
+impl ModelListProvider for OpenRouterModelList {
+    fn name(&self) -> &str {
+        "openrouter"
+    }
+
+    fn fetch_models(&self) -> BoxFuture<'_, Result<Vec<Model>, ModelListError>> {
+        Box::pin(async {
+            fetch_openrouter_model_list()
+                .await
+                .map_err(|e| e.to_string().into())
+        })
+    }
+}
+
+/// Groq 模型列表
+#[derive(Debug, Clone)]
+pub struct GroqModelList {
+    api_key: String,
+    url: String,
+}
+
+impl GroqModelList {
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            url: GROQ_MODELS_URL.to_string(),
+        }
+    }
+
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.url = url.into();
+        self
+    }
+}
+
+#[derive(Deserialize)]
+struct GroqModel {
+    id: String,
+    #[serde(default)]
+    created: i64,
+    #[serde(default)]
+    owned_by: Option<String>,
+    #[serde(default = "default_true")]
+    active: bool,
+    #[serde(default)]
+    context_window: i64,
+    #[serde(default)]
+    max_completion_tokens: Option<i64>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl From<GroqModel> for Model {
+    fn from(model: GroqModel) -> Self {
+        let context_length = model.context_window;
+        let mut normalized = normalized_model(model.id, None, model.owned_by, model.created);
+        normalized.context_length = context_length;
+        normalized.endpoint = Some(Endpoint {
+            name: "Groq".to_string(),
+            context_length,
+            model_variant_slug: normalized.slug.clone(),
+            model_variant_permaslug: normalized.slug.clone(),
+            ..Default::default()
+        });
+        normalized
+    }
+}
+
+fn parse_groq_models(body: &str) -> Result<Vec<Model>, ModelListError> {
+    let response: ListResponse<GroqModel> = serde_json::from_str(body)?;
+    Ok(response
+        .data
+        .into_iter()
+        // 已下线的模型和语音识别等没有输出 token 的模型不可用于对话
+        .filter(|model| model.active && model.max_completion_tokens != Some(0))
+        .map(Model::from)
+        .collect())
+}
+
+impl ModelListProvider for GroqModelList {
+    fn name(&self) -> &str {
+        "groq"
+    }
+
+    fn fetch_models(&self) -> BoxFuture<'_, Result<Vec<Model>, ModelListError>> {
+        Box::pin(async {
+            let body = get_body(&self.url, &self.api_key).await?;
+            parse_groq_models(&body)
+        })
+    }
+}
+
+/// Together 模型列表，只保留对话模型
+#[derive(Debug, Clone)]
+pub struct TogetherModelList {
+    api_key: String,
+    url: String,
+}
+
+impl TogetherModelList {
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            url: TOGETHER_MODELS_URL.to_string(),
+        }
+    }
+
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.url = url.into();
+        self
+    }
+}
+
+#[derive(Deserialize)]
+struct TogetherModel {
+    id: String,
+    #[serde(default)]
+    created: i64,
+    #[serde(default, rename = "type")]
+    kind: String,
+    #[serde(default)]
+    display_name: Option<String>,
+    #[serde(default)]
+    organization: Option<String>,
+    #[serde(default)]
+    context_length: i64,
+    #[serde(default)]
+    pricing: Option<TogetherPricing>,
+}
+
+/// 单位为美元每百万 token
+#[derive(Deserialize)]
+struct TogetherPricing {
+    #[serde(default)]
+    input: f64,
+    #[serde(default)]
+    output: f64,
+}
+
+impl From<TogetherModel> for Model {
+    fn from(model: TogetherModel) -> Self {
+        let context_length = model.context_length;
+        let pricing = model.pricing;
+        let mut normalized = normalized_model(
+            model.id,
+            model.display_name,
+            model.organization,
+            model.created,
+        );
+        normalized.context_length = context_length;
+        normalized.endpoint = Some(Endpoint {
+            name: "Together".to_string(),
+            context_length,
+            model_variant_slug: normalized.slug.clone(),
+            model_variant_permaslug: normalized.slug.clone(),
+            is_free: pricing
+                .as_ref()
+                .is_some_and(|pricing| pricing.input == 0.0 && pricing.output == 0.0),
+            pricing: pricing.map(|pricing| Pricing {
+                prompt: (pricing.input / 1_000_000.0).to_string(),
+                completion: (pricing.output / 1_000_000.0).to_string(),
+            }),
+        });
+        normalized
+    }
+}
+
+fn parse_together_models(body: &str) -> Result<Vec<Model>, ModelListError> {
+    // 接口直接返回列表，兼容 `{"data": [...]}` 格式
+    let models: Vec<TogetherModel> = match serde_json::from_str(body) {
+        Ok(models) => models,
+        Err(_) => serde_json::from_str::<ListResponse<TogetherModel>>(body)?.data,
+    };
+    Ok(models
+        .into_iter()
+        .filter(|model| model.kind == "chat" || model.kind == "language")
+        .map(Model::from)
+        .collect())
+}
+
+impl ModelListProvider for TogetherModelList {
+    fn name(&self) -> &str {
+        "together"
+    }
+
+    fn fetch_models(&self) -> BoxFuture<'_, Result<Vec<Model>, ModelListError>> {
+        Box::pin(async {
+            let body = get_body(&self.url, &self.api_key).await?;
+            parse_together_models(&body)
+        })
+    }
+}
+
+/// SiliconFlow(硅基流动)模型列表，默认只获取文本模型
+#[derive(Debug, Clone)]
+pub struct SiliconFlowModelList {
+    api_key: String,
+    url: String,
+}
+
+impl SiliconFlowModelList {
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            url: SILICONFLOW_MODELS_URL.to_string(),
+        }
+    }
+
+    /// 如国际站 `https://api.siliconflow.com/v1/models?type=text`
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.url = url.into();
+        self
+    }
+}
+
+#[derive(Deserialize)]
+struct SiliconFlowModel {
+    id: String,
+    #[serde(default)]
+    created: i64,
+    #[serde(default)]
+    owned_by: Option<String>,
+}
+
+fn parse_siliconflow_models(body: &str) -> Result<Vec<Model>, ModelListError> {
+    let response: ListResponse<SiliconFlowModel> = serde_json::from_str(body)?;
+    Ok(response
+        .data
+        .into_iter()
+        .map(|model| normalized_model(model.id, None, model.owned_by, model.created))
+        .collect())
+}
+
+impl ModelListProvider for SiliconFlowModelList {
+    fn name(&self) -> &str {
+        "siliconflow"
+    }
+
+    fn fetch_models(&self) -> BoxFuture<'_, Result<Vec<Model>, ModelListError>> {
+        Box::pin(async {
+            let body = get_body(&self.url, &self.api_key).await?;
+            parse_siliconflow_models(&body)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_provider_model_lists() {
+        let groq = parse_groq_models(
+            r#"{"object": "list", "data": [
+                {"id": "llama-3.3-70b-versatile", "object": "model", "created": 1733447754,
+                 "owned_by": "Meta", "active": true, "context_window": 131072,
+                 "max_completion_tokens": 32768},
+                {"id": "whisper-large-v3", "object": "model", "created": 1693721698,
+                 "owned_by": "OpenAI", "active": true, "context_window": 448,
+                 "max_completion_tokens": 0}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(groq.len(), 1);
+        assert_eq!(groq[0].slug, "llama-3.3-70b-versatile");
+        assert_eq!(groq[0].author, "Meta");
+        assert_eq!(groq[0].context_length, 131072);
+        assert_eq!(groq[0].created_at, "2024-12-06T01:15:54Z");
+
+        let together = parse_together_models(
+            r#"[
+                {"id": "meta-llama/Llama-3.3-70B-Instruct-Turbo-Free", "type": "chat",
+                 "created": 1733443200, "display_name": "Meta Llama 3.3 70B Instruct Turbo Free",
+                 "organization": "Meta", "context_length": 131072,
+                 "pricing": {"hourly": 0, "input": 0, "output": 0, "base": 0, "finetune": 0}},
+                {"id": "deepseek-ai/DeepSeek-V3", "type": "chat", "organization": "DeepSeek",
+                 "context_length": 131072, "pricing": {"input": 1.25, "output": 1.25}},
+                {"id": "BAAI/bge-large-en-v1.5", "type": "embedding"}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(together.len(), 2);
+        assert!(together[0].is_free());
+        assert!(!together[1].is_free());
+        let (input, output) = together[1].price_per_1k().unwrap();
+        assert!((input - 0.00125).abs() < 1e-12 && (output - 0.00125).abs() < 1e-12);
+
+        let siliconflow = parse_siliconflow_models(
+            r#"{"object": "list", "data": [
+                {"id": "Qwen/Qwen2.5-7B-Instruct", "object": "model", "created": 0, "owned_by": ""}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(siliconflow[0].author, "Qwen");
+        assert_eq!(siliconflow[0].short_name, "Qwen2.5-7B-Instruct");
+        assert_eq!(siliconflow[0].endpoint, None);
+
+        // 统一格式后可以使用同一个查询
+        let models: Vec<Model> = groq.into_iter().chain(together).collect();
+        let free = crate::ModelListQuery::new().free().apply(&models);
+        assert_eq!(free.len(), 1);
+    }
+
+    #[test]
+    fn test_catalog_lookup() {
+        let models: Vec<OpenRouterModel> = serde_json::from_str(
+            r#"[{
+                "id": "deepseek/deepseek-chat",
+                "name": "DeepSeek V3",
+                "context_length": 163840,
+                "pricing": {"prompt": "0.0000003", "completion": "0.00000085"},
+                "top_provider": {"context_length": 163840, "max_completion_tokens": 8192},
+                "supported_parameters": ["tools", "temperature"]
+            }]"#,
+        )
+        .unwrap();
+
+        let catalog = ModelCatalog::from_models(models, Duration::from_secs(3600));
+        assert!(!catalog.is_stale());
+        assert_eq!(catalog.len(), 1);
+        assert_eq!(
+            catalog.context_length("deepseek/deepseek-chat"),
+            Some(163840)
+        );
+        assert_eq!(
+            catalog.max_completion_tokens("deepseek/deepseek-chat:free"),
+            Some(8192)
+        );
+        assert!(catalog.supports_parameter("deepseek/deepseek-chat", "tools"));
+        assert!(!catalog.supports_parameter("unknown/model", "tools"));
+        assert_eq!(catalog.context_length("unknown/model"), None);
+
+        let catalog = catalog.with_api_key("sk-xxx");
+        assert_eq!(catalog.len(), 1);
+        assert!(ModelCatalog::new(Duration::from_secs(3600)).is_stale());
+    }
+}
@@ -0,0 +1,310 @@
+//! 常用 provider 的 `additional_params` 类型化构建器
+//!
+//! 代替手写 `json!`，避免参数名拼写错误或放错层级:
+//!
+//! ```rust
+//! use rig_extra::params::{GlmParams, OllamaParams, OpenAiParams};
+//! use serde_json::json;
+//!
+//! let params: serde_json::Value = OpenAiParams::new().seed(42).logit_bias(50256, -100.0).into();
+//! assert_eq!(params, json!({"seed": 42, "logit_bias": {"50256": -100.0}}));
+//!
+//! let params: serde_json::Value = GlmParams::new().do_sample(false).into();
+//! assert_eq!(params, json!({"do_sample": false}));
+//!
+//! let params: serde_json::Value = OllamaParams::new().num_ctx(32768).into();
+//! assert_eq!(params, json!({"num_ctx": 32768}));
+//! ```
+//!
+//! 生成的参数通过 `AgentBuilder::additional_params` 传入即可。
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// OpenAI 兼容接口的常用参数，也适用于 DeepSeek、OpenRouter 等
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct OpenAiParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<i64>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    logit_bias: BTreeMap<String, f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<String>,
+}
+
+impl OpenAiParams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 随机种子，相同种子和参数尽量返回相同结果
+    pub fn seed(mut self, seed: i64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// 调整指定 token 出现的概率，取值 -100 到 100
+    pub fn logit_bias(mut self, token_id: u32, bias: f64) -> Self {
+        self.logit_bias.insert(token_id.to_string(), bias);
+        self
+    }
+
+    pub fn top_p(mut self, top_p: f64) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    pub fn frequency_penalty(mut self, penalty: f64) -> Self {
+        self.frequency_penalty = Some(penalty);
+        self
+    }
+
+    pub fn presence_penalty(mut self, penalty: f64) -> Self {
+        self.presence_penalty = Some(penalty);
+        self
+    }
+
+    /// 添加停止词
+    pub fn stop(mut self, stop: impl Into<String>) -> Self {
+        self.stop.push(stop.into());
+        self
+    }
+
+    /// 终端用户标识，用于提供方的滥用监控
+    pub fn user(mut self, user: impl Into<String>) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+}
+
+/// 智谱 GLM 的常用参数
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct GlmParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    do_sample: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user_id: Option<String>,
+}
+
+impl GlmParams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 为 false 时关闭采样，temperature 和 top_p 不再生效
+    pub fn do_sample(mut self, do_sample: bool) -> Self {
+        self.do_sample = Some(do_sample);
+        self
+    }
+
+    pub fn top_p(mut self, top_p: f64) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    /// 添加停止词，GLM 目前只支持一个
+    pub fn stop(mut self, stop: impl Into<String>) -> Self {
+        self.stop.push(stop.into());
+        self
+    }
+
+    /// 请求唯一标识，不传时由平台生成
+    pub fn request_id(mut self, request_id: impl Into<String>) -> Self {
+        self.request_id = Some(request_id.into());
+        self
+    }
+
+    /// 终端用户标识
+    pub fn user_id(mut self, user_id: impl Into<String>) -> Self {
+        self.user_id = Some(user_id.into());
+        self
+    }
+}
+
+/// Ollama 的常用参数，rig 会将其放入请求的 `options` 中(`think` 除外)
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct OllamaParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_ctx: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_predict: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    repeat_penalty: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    think: Option<bool>,
+}
+
+impl OllamaParams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 上下文窗口大小，Ollama 默认只有 2048
+    pub fn num_ctx(mut self, num_ctx: u32) -> Self {
+        self.num_ctx = Some(num_ctx);
+        self
+    }
+
+    /// 最大生成 token 数，-1 表示不限制
+    pub fn num_predict(mut self, num_predict: i32) -> Self {
+        self.num_predict = Some(num_predict);
+        self
+    }
+
+    pub fn top_k(mut self, top_k: u32) -> Self {
+        self.top_k = Some(top_k);
+        self
+    }
+
+    pub fn top_p(mut self, top_p: f64) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    pub fn repeat_penalty(mut self, penalty: f64) -> Self {
+        self.repeat_penalty = Some(penalty);
+        self
+    }
+
+    pub fn seed(mut self, seed: i64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// 是否启用思考模式(qwen3、deepseek-r1 等推理模型)
+    pub fn think(mut self, think: bool) -> Self {
+        self.think = Some(think);
+        self
+    }
+}
+
+impl From<OpenAiParams> for Value {
+    fn from(params: OpenAiParams) -> Self {
+        serde_json::to_value(params).expect("params should serialize")
+    }
+}
+
+impl From<GlmParams> for Value {
+    fn from(params: GlmParams) -> Self {
+        serde_json::to_value(params).expect("params should serialize")
+    }
+}
+
+impl From<OllamaParams> for Value {
+    fn from(params: OllamaParams) -> Self {
+        serde_json::to_value(params).expect("params should serialize")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_unset_params_omitted() {
+        assert_eq!(Value::from(OpenAiParams::new()), json!({}));
+        assert_eq!(Value::from(GlmParams::new()), json!({}));
+        assert_eq!(Value::from(OllamaParams::new()), json!({}));
+    }
+
+    #[test]
+    fn test_openai_params() {
+        let params: Value = OpenAiParams::new()
+            .seed(7)
+            .logit_bias(50256, -100.0)
+            .logit_bias(198, 5.0)
+            .top_p(0.9)
+            .frequency_penalty(0.5)
+            .presence_penalty(-0.5)
+            .stop("END")
+            .stop("\n\n")
+            .user("user-1")
+            .into();
+        assert_eq!(
+            params,
+            json!({
+                "seed": 7,
+                "logit_bias": {"50256": -100.0, "198": 5.0},
+                "top_p": 0.9,
+                "frequency_penalty": 0.5,
+                "presence_penalty": -0.5,
+                "stop": ["END", "\n\n"],
+                "user": "user-1"
+            })
+        );
+    }
+
+    #[test]
+    fn test_glm_params() {
+        let params: Value = GlmParams::new()
+            .do_sample(true)
+            .top_p(0.7)
+            .stop("<|end|>")
+            .request_id("req-1")
+            .user_id("user-1")
+            .into();
+        assert_eq!(
+            params,
+            json!({
+                "do_sample": true,
+                "top_p": 0.7,
+                "stop": ["<|end|>"],
+                "request_id": "req-1",
+                "user_id": "user-1"
+            })
+        );
+
+        let params: Value = GlmParams::new().user_id("user-1").into();
+        assert_eq!(params, json!({"user_id": "user-1"}));
+    }
+
+    #[test]
+    fn test_ollama_params() {
+        let params: Value = OllamaParams::new()
+            .num_ctx(8192)
+            .num_predict(-1)
+            .top_k(40)
+            .top_p(0.95)
+            .repeat_penalty(1.1)
+            .seed(42)
+            .think(false)
+            .into();
+        assert_eq!(
+            params,
+            json!({
+                "num_ctx": 8192,
+                "num_predict": -1,
+                "top_k": 40,
+                "top_p": 0.95,
+                "repeat_penalty": 1.1,
+                "seed": 42,
+                "think": false
+            })
+        );
+
+        let params: Value = OllamaParams::new().think(true).into();
+        assert_eq!(params, json!({"think": true}));
+    }
+}
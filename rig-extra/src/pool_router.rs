@@ -0,0 +1,206 @@
+//! 多个代理池的组合路由
+//!
+//! 大规模部署时可以按地域或用途把 agent 分成多个 [`RandAgent`] 代理池(如 "cn-pool"、
+//! "global-pool"、"local-pool")，每个代理池有各自的选择策略、重试和熔断配置。
+//! [`PoolRouter`] 在其上做一层路由: 按顺序或随机选择一个代理池，代理池内部再按自己的策略
+//! 选择 agent；整个代理池不可用或请求失败时切换到下一个代理池。
+//!
+//! ```rust,ignore
+//! let router = PoolRouter::new()
+//!     .add_pool("local-pool", local_pool)
+//!     .add_pool("cn-pool", cn_pool)
+//!     .add_pool("global-pool", global_pool);
+//!
+//! // 优先使用本地模型，不可用时依次使用国内和海外的代理池
+//! let response = router.prompt("你好").await?;
+//!
+//! // 也可以直接指定代理池
+//! let response = router.pool("global-pool").unwrap().prompt("hello").await?;
+//! ```
+
+use crate::AgentInfo;
+use crate::dyn_agent::{DynPromptAgent, DynStream};
+use crate::error::RandAgentError;
+use crate::rand_agent::{RandAgent, no_valid_agent_error};
+use futures::future::BoxFuture;
+use rand::seq::SliceRandom;
+use rig::completion::{Chat, Message, Prompt, PromptError};
+use std::future::Future;
+
+/// 代理池的选择方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PoolStrategy {
+    /// 按添加顺序，前面的代理池不可用时才使用后面的
+    #[default]
+    Ordered,
+    /// 每次请求随机打乱代理池顺序
+    Random,
+}
+
+/// 多个代理池的路由
+#[derive(Clone, Default)]
+pub struct PoolRouter {
+    pools: Vec<(String, RandAgent)>,
+    strategy: PoolStrategy,
+}
+
+impl PoolRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 添加代理池，名称重复时替换原有代理池
+    pub fn add_pool(mut self, name: impl Into<String>, pool: RandAgent) -> Self {
+        let name = name.into();
+        match self
+            .pools
+            .iter_mut()
+            .find(|(existing, _)| *existing == name)
+        {
+            Some((_, existing)) => *existing = pool,
+            None => self.pools.push((name, pool)),
+        }
+        self
+    }
+
+    /// 设置代理池的选择方式，默认按顺序
+    pub fn strategy(mut self, strategy: PoolStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// 按名称获取代理池
+    pub fn pool(&self, name: &str) -> Option<&RandAgent> {
+        self.pools
+            .iter()
+            .find(|(existing, _)| existing == name)
+            .map(|(_, pool)| pool)
+    }
+
+    /// 所有代理池的名称
+    pub fn pool_names(&self) -> Vec<&str> {
+        self.pools.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// 各代理池中 agent 的状态
+    pub async fn agents_info(&self) -> Vec<(String, Vec<AgentInfo>)> {
+        let mut infos = Vec::with_capacity(self.pools.len());
+        for (name, pool) in &self.pools {
+            infos.push((name.clone(), pool.get_agents_info().await));
+        }
+        infos
+    }
+
+    /// 本次请求尝试代理池的顺序，没有有效 agent 的代理池排在最后
+    async fn route(&self) -> Vec<&(String, RandAgent)> {
+        let mut pools: Vec<&(String, RandAgent)> = self.pools.iter().collect();
+        if self.strategy == PoolStrategy::Random {
+            pools.shuffle(&mut rand::rng());
+        }
+        let mut available = Vec::with_capacity(pools.len());
+        let mut exhausted = Vec::new();
+        for pool in pools {
+            if pool.1.is_empty().await {
+                exhausted.push(pool);
+            } else {
+                available.push(pool);
+            }
+        }
+        available.extend(exhausted);
+        available
+    }
+
+    /// 按路由顺序依次尝试代理池，直到成功
+    async fn invoke<'a, F, Fut, T>(&'a self, call: F) -> Result<T, PromptError>
+    where
+        F: Fn(&'a RandAgent) -> Fut,
+        Fut: Future<Output = Result<T, PromptError>>,
+    {
+        let mut last_error = None;
+        for (name, pool) in self.route().await {
+            match call(pool).await {
+                Ok(result) => return Ok(result),
+                Err(err) => {
+                    tracing::warn!(
+                        "代理池 {name} 调用失败，尝试下一个: {}",
+                        crate::redact::redacted(&err)
+                    );
+                    last_error = Some(err);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(no_valid_agent_error))
+    }
+}
+
+impl Prompt for PoolRouter {
+    #[allow(refining_impl_trait)]
+    async fn prompt(&self, prompt: impl Into<Message> + Send) -> Result<String, PromptError> {
+        let prompt = prompt.into();
+        self.invoke(|pool| pool.prompt(prompt.clone())).await
+    }
+}
+
+impl Chat for PoolRouter {
+    #[allow(refining_impl_trait)]
+    async fn chat(
+        &self,
+        prompt: impl Into<Message> + Send,
+        chat_history: Vec<Message>,
+    ) -> Result<String, PromptError> {
+        let prompt = prompt.into();
+        self.invoke(|pool| pool.chat(prompt.clone(), chat_history.clone()))
+            .await
+    }
+}
+
+impl DynPromptAgent for PoolRouter {
+    fn prompt_dyn(&self, prompt: Message) -> BoxFuture<'_, Result<String, PromptError>> {
+        Box::pin(async move { Prompt::prompt(self, prompt).await })
+    }
+
+    fn chat_dyn(
+        &self,
+        prompt: Message,
+        chat_history: Vec<Message>,
+    ) -> BoxFuture<'_, Result<String, PromptError>> {
+        Box::pin(async move { Chat::chat(self, prompt, chat_history).await })
+    }
+
+    /// 流式请求在第一个成功建立连接的代理池上进行
+    fn stream_dyn(&self, prompt: Message) -> BoxFuture<'_, Result<DynStream, RandAgentError>> {
+        Box::pin(async move {
+            let mut last_error = RandAgentError::NoValidAgents;
+            for (_, pool) in self.route().await {
+                match pool.stream_prompt(prompt.clone()).await {
+                    Ok(stream) => return Ok(stream),
+                    Err(err) => last_error = err,
+                }
+            }
+            Err(last_error)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rand_agent::RandAgentBuilder;
+
+    #[tokio::test]
+    async fn test_pool_router() {
+        let router = PoolRouter::new()
+            .add_pool("cn-pool", RandAgentBuilder::new().build())
+            .add_pool("global-pool", RandAgentBuilder::new().build())
+            .add_pool("cn-pool", RandAgentBuilder::new().build());
+        assert_eq!(router.pool_names(), vec!["cn-pool", "global-pool"]);
+        assert!(router.pool("local-pool").is_none());
+
+        // 所有代理池都没有 agent
+        assert!(router.prompt("你好").await.is_err());
+        assert!(matches!(
+            router.stream_dyn("你好".into()).await,
+            Err(RandAgentError::NoValidAgents)
+        ));
+    }
+}
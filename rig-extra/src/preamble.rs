@@ -0,0 +1,90 @@
+//! 代理池级别的系统提示词版本
+//!
+//! 在代理池上注册多个命名的系统提示词版本，运行时切换生效版本，无需重建代理池；
+//! 也可以通过 [`RandAgent::with_preamble_version`](crate::rand_agent::RandAgent::with_preamble_version)
+//! 让部分请求固定使用某个版本，便于线上对比新旧提示词。
+//!
+//! ```rust,ignore
+//! let rand_agent = RandAgentBuilder::new()
+//!     .preamble_version("v1", "你是一个翻译助手")
+//!     .preamble_version("v2", "你是一个专业的技术文档翻译助手，保留代码块原样")
+//!     .active_preamble("v1")
+//!     .build();
+//!
+//! // 全部请求切换到 v2
+//! rand_agent.set_active_preamble("v2");
+//!
+//! // 单次请求固定使用 v1
+//! let response = rand_agent.with_preamble_version("v1").prompt("hello").await?;
+//! ```
+//!
+//! 没有生效版本时使用各 agent 构建时的系统提示词。每次请求使用的版本记录在
+//! `rand_agent.request` span 的 `preamble_version` 字段中。
+
+use std::collections::HashMap;
+
+/// 命名的系统提示词版本集合
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PreambleVersions {
+    versions: HashMap<String, String>,
+    active: Option<String>,
+}
+
+impl PreambleVersions {
+    /// 注册或覆盖一个版本
+    pub(crate) fn register(&mut self, name: String, preamble: String) {
+        self.versions.insert(name, preamble);
+    }
+
+    /// 切换生效版本，版本不存在时返回 false 且不做修改
+    pub(crate) fn set_active(&mut self, name: &str) -> bool {
+        if !self.versions.contains_key(name) {
+            return false;
+        }
+        self.active = Some(name.to_string());
+        true
+    }
+
+    /// 取消生效版本，恢复使用各 agent 自己的系统提示词
+    pub(crate) fn clear_active(&mut self) {
+        self.active = None;
+    }
+
+    pub(crate) fn active(&self) -> Option<&str> {
+        self.active.as_deref()
+    }
+
+    /// 解析本次请求使用的版本，固定版本优先于生效版本
+    pub(crate) fn resolve(&self, pinned: Option<&str>) -> Option<(&str, &str)> {
+        let name = pinned.or(self.active.as_deref())?;
+        match self.versions.get_key_value(name) {
+            Some((name, preamble)) => Some((name.as_str(), preamble.as_str())),
+            None => {
+                tracing::warn!("系统提示词版本 {name} 不存在，使用 agent 自身的系统提示词");
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_preamble_version() {
+        let mut versions = PreambleVersions::default();
+        versions.register("v1".to_string(), "提示词一".to_string());
+        versions.register("v2".to_string(), "提示词二".to_string());
+        assert_eq!(versions.resolve(None), None);
+
+        assert!(!versions.set_active("v3"));
+        assert!(versions.set_active("v1"));
+        assert_eq!(versions.resolve(None), Some(("v1", "提示词一")));
+        assert_eq!(versions.resolve(Some("v2")), Some(("v2", "提示词二")));
+        assert_eq!(versions.resolve(Some("v3")), None);
+
+        versions.clear_active();
+        assert_eq!(versions.active(), None);
+    }
+}
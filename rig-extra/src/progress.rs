@@ -0,0 +1,164 @@
+//! 非流式请求的进度事件
+//!
+//! 本地大模型或推理模型生成较慢时，非流式请求可能长时间没有任何输出。开启后，请求进行期间
+//! 按固定间隔发送 [`ProgressEvent::InFlight`] 心跳事件，调用方可以据此显示加载状态，
+//! 并在超时之前区分"慢"和"卡住"。
+//!
+//! ```rust,ignore
+//! let rand_agent = RandAgentBuilder::new()
+//!     .progress_interval(Duration::from_secs(5))
+//!     .build();
+//!
+//! let mut events = rand_agent.subscribe_progress().unwrap();
+//! tokio::spawn(async move {
+//!     while let Ok(event) = events.recv().await {
+//!         if let ProgressEvent::InFlight { elapsed, .. } = event {
+//!             println!("仍在生成，已等待 {elapsed:?}");
+//!         }
+//!     }
+//! });
+//! ```
+
+use crate::AgentInfo;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+/// 事件通道容量，订阅方处理不及时时丢弃最早的事件
+const PROGRESS_CHANNEL_CAPACITY: usize = 64;
+
+/// 请求进度事件
+#[derive(Debug, Clone)]
+pub enum ProgressEvent {
+    /// 已选中 agent，开始请求
+    Started { request_id: u64, agent: AgentInfo },
+    /// 请求仍在进行
+    InFlight {
+        request_id: u64,
+        agent_id: i32,
+        elapsed: Duration,
+    },
+    /// 请求结束
+    Finished {
+        request_id: u64,
+        agent_id: i32,
+        elapsed: Duration,
+        success: bool,
+    },
+}
+
+/// 进度事件发送器
+#[derive(Debug)]
+pub(crate) struct ProgressReporter {
+    sender: broadcast::Sender<ProgressEvent>,
+    interval: Duration,
+    next_id: AtomicU64,
+}
+
+impl ProgressReporter {
+    pub(crate) fn new(interval: Duration) -> Self {
+        let (sender, _) = broadcast::channel(PROGRESS_CHANNEL_CAPACITY);
+        Self {
+            sender,
+            interval,
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<ProgressEvent> {
+        self.sender.subscribe()
+    }
+
+    /// 等待请求完成，期间按间隔发送心跳事件
+    pub(crate) async fn track<F, T, E>(&self, agent: &AgentInfo, call: F) -> Result<T, E>
+    where
+        F: Future<Output = Result<T, E>>,
+    {
+        let request_id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let start = Instant::now();
+        // 没有订阅方时发送失败，忽略即可
+        let _ = self.sender.send(ProgressEvent::Started {
+            request_id,
+            agent: agent.clone(),
+        });
+
+        tokio::pin!(call);
+        let mut ticker =
+            tokio::time::interval_at(tokio::time::Instant::now() + self.interval, self.interval);
+        let result = loop {
+            tokio::select! {
+                result = &mut call => break result,
+                _ = ticker.tick() => {
+                    let _ = self.sender.send(ProgressEvent::InFlight {
+                        request_id,
+                        agent_id: agent.id,
+                        elapsed: start.elapsed(),
+                    });
+                }
+            }
+        };
+
+        let _ = self.sender.send(ProgressEvent::Finished {
+            request_id,
+            agent_id: agent.id,
+            elapsed: start.elapsed(),
+            success: result.is_ok(),
+        });
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structured::StructuredOutput;
+
+    #[tokio::test]
+    async fn test_progress_keepalive() {
+        let reporter = ProgressReporter::new(Duration::from_millis(10));
+        let mut events = reporter.subscribe();
+        let agent = AgentInfo {
+            id: 7,
+            provider: "ollama".to_string(),
+            model: "qwen2.5:72b".to_string(),
+            failure_count: 0,
+            max_failures: 3,
+            avg_latency: None,
+            input_price: None,
+            output_price: None,
+            disabled: false,
+            reserved_for: None,
+            structured_output: StructuredOutput::Prompt,
+            context_length: None,
+        };
+
+        let result: Result<&str, ()> = reporter
+            .track(&agent, async {
+                tokio::time::sleep(Duration::from_millis(45)).await;
+                Ok("done")
+            })
+            .await;
+        assert_eq!(result, Ok("done"));
+
+        assert!(matches!(
+            events.recv().await,
+            Ok(ProgressEvent::Started { request_id: 1, .. })
+        ));
+        let mut in_flight = 0;
+        loop {
+            match events.recv().await.unwrap() {
+                ProgressEvent::InFlight { agent_id, .. } => {
+                    assert_eq!(agent_id, 7);
+                    in_flight += 1;
+                }
+                ProgressEvent::Finished { success, .. } => {
+                    assert!(success);
+                    break;
+                }
+                ProgressEvent::Started { .. } => unreachable!(),
+            }
+        }
+        assert!(in_flight >= 2);
+    }
+}
@@ -0,0 +1,191 @@
+//! 命名的提示词模板
+//!
+//! 在代理池上注册带 `{变量}` 占位符的模板，调用时传入变量即可，避免在应用中到处拼接
+//! `format!` 字符串。变量名只能包含字母、数字和下划线；`{{` 和 `}}` 输出字面的花括号，
+//! 其它不构成占位符的花括号(如模板中的 JSON 示例)原样保留。
+//!
+//! ```rust,ignore
+//! let rand_agent = RandAgentBuilder::new()
+//!     .prompt_template("summarize", "用不超过 {words} 个字总结下面的内容:\n{text}")
+//!     .prompt_template("translate", "把下面的内容翻译成{language}:\n{text}")
+//!     .simple_builder(configs, "你是一个助手".to_string())
+//!     .build();
+//!
+//! let summary = rand_agent
+//!     .prompt_template("summarize", [("words", "100"), ("text", article)])
+//!     .await?;
+//! ```
+//!
+//! 缺少变量或模板不存在时返回 [`TemplateError`]，不会发起请求。
+
+use std::collections::HashMap;
+
+/// 渲染模板失败
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum TemplateError {
+    #[error("提示词模板 {0} 不存在")]
+    UnknownTemplate(String),
+    #[error("提示词模板 {template} 缺少变量 {variable}")]
+    MissingVariable { template: String, variable: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Text(String),
+    Variable(String),
+}
+
+/// 解析后的提示词模板
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptTemplate {
+    segments: Vec<Segment>,
+}
+
+impl PromptTemplate {
+    pub fn new(template: &str) -> Self {
+        let mut segments = Vec::new();
+        let mut text = String::new();
+        let mut rest = template;
+        while let Some(ch) = rest.chars().next() {
+            if rest.starts_with("{{") || rest.starts_with("}}") {
+                text.push(ch);
+                rest = &rest[2..];
+                continue;
+            }
+            if ch == '{'
+                && let Some(end) = rest.find('}')
+                && is_variable_name(&rest[1..end])
+            {
+                if !text.is_empty() {
+                    segments.push(Segment::Text(std::mem::take(&mut text)));
+                }
+                segments.push(Segment::Variable(rest[1..end].to_string()));
+                rest = &rest[end + 1..];
+                continue;
+            }
+            text.push(ch);
+            rest = &rest[ch.len_utf8()..];
+        }
+        if !text.is_empty() {
+            segments.push(Segment::Text(text));
+        }
+        Self { segments }
+    }
+
+    /// 模板中的变量名，按首次出现的顺序
+    pub fn variables(&self) -> Vec<&str> {
+        let mut variables = Vec::new();
+        for segment in &self.segments {
+            if let Segment::Variable(name) = segment
+                && !variables.contains(&name.as_str())
+            {
+                variables.push(name.as_str());
+            }
+        }
+        variables
+    }
+
+    /// 替换变量，返回缺少的第一个变量名
+    pub fn render(&self, vars: &HashMap<String, String>) -> Result<String, String> {
+        let mut rendered = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Text(text) => rendered.push_str(text),
+                Segment::Variable(name) => match vars.get(name) {
+                    Some(value) => rendered.push_str(value),
+                    None => return Err(name.clone()),
+                },
+            }
+        }
+        Ok(rendered)
+    }
+}
+
+fn is_variable_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
+/// 命名的模板集合
+#[derive(Debug, Clone, Default)]
+pub struct PromptTemplates {
+    templates: HashMap<String, PromptTemplate>,
+}
+
+impl PromptTemplates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 注册或覆盖一个模板
+    pub fn register(&mut self, name: impl Into<String>, template: &str) {
+        self.templates
+            .insert(name.into(), PromptTemplate::new(template));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PromptTemplate> {
+        self.templates.get(name)
+    }
+
+    /// 渲染指定模板
+    pub fn render<K, V>(
+        &self,
+        name: &str,
+        vars: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<String, TemplateError>
+    where
+        K: Into<String>,
+        V: ToString,
+    {
+        let template = self
+            .get(name)
+            .ok_or_else(|| TemplateError::UnknownTemplate(name.to_string()))?;
+        let vars = vars
+            .into_iter()
+            .map(|(key, value)| (key.into(), value.to_string()))
+            .collect();
+        template
+            .render(&vars)
+            .map_err(|variable| TemplateError::MissingVariable {
+                template: name.to_string(),
+                variable,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_template() {
+        let mut templates = PromptTemplates::new();
+        templates.register(
+            "translate",
+            "把 {text} 翻译成{language}，输出 {{\"text\": ...}}，{不是变量} {text}",
+        );
+        assert_eq!(
+            templates.get("translate").unwrap().variables(),
+            vec!["text", "language"]
+        );
+        assert_eq!(
+            templates
+                .render("translate", [("text", "你好"), ("language", "英文")])
+                .unwrap(),
+            "把 你好 翻译成英文，输出 {\"text\": ...}，{不是变量} 你好"
+        );
+        assert_eq!(
+            templates.render("translate", [("text", "你好")]),
+            Err(TemplateError::MissingVariable {
+                template: "translate".to_string(),
+                variable: "language".to_string(),
+            })
+        );
+        assert_eq!(
+            templates.render("summarize", [("text", "")]),
+            Err(TemplateError::UnknownTemplate("summarize".to_string()))
+        );
+    }
+}
@@ -0,0 +1,414 @@
+//! 启动预热、配置校验和定期健康检查
+
+use super::{AgentSlot, RandAgent, RandAgentBuilder, lock_slot, probe_agent};
+use crate::AgentInfo;
+use crate::health::{self, HealthCheckConfig, HealthCheckHandle, HealthReport};
+use crate::i18n::MessageKey;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 预热探测失败的 agent 的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WarmUpAction {
+    /// 从代理集合中移除
+    #[default]
+    Drop,
+    /// 保留但标记为无效
+    Quarantine,
+}
+
+/// 单个 agent 的预热结果
+#[derive(Debug, Clone)]
+pub struct WarmUpResult {
+    pub info: AgentInfo,
+    /// 探测是否成功
+    pub success: bool,
+    /// 探测耗时，仅成功时有值
+    pub latency: Option<Duration>,
+    /// 失败原因
+    pub error: Option<String>,
+}
+
+/// 配置校验失败的原因，按错误信息推断
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationFailure {
+    /// api key 无效或没有权限
+    Auth,
+    /// 模型名称错误或无权使用该模型
+    Model,
+    /// 无法连接到接口地址
+    Unreachable,
+    /// 超时
+    Timeout,
+    Other,
+}
+
+impl ValidationFailure {
+    fn classify(error: &str) -> Self {
+        let error = error.to_lowercase();
+        let contains_any = |patterns: &[&str]| patterns.iter().any(|p| error.contains(p));
+        if error.starts_with(&MessageKey::Timeout.text().to_lowercase()) {
+            ValidationFailure::Timeout
+        } else if contains_any(&[
+            "401",
+            "403",
+            "unauthorized",
+            "api key",
+            "api_key",
+            "令牌",
+            "认证",
+        ]) {
+            ValidationFailure::Auth
+        } else if contains_any(&["model", "模型"])
+            && contains_any(&["not found", "not exist", "不存在", "404"])
+        {
+            ValidationFailure::Model
+        } else if error.contains("httperror")
+            || contains_any(&["connect", "dns", "error sending request"])
+        {
+            ValidationFailure::Unreachable
+        } else {
+            ValidationFailure::Other
+        }
+    }
+}
+
+/// 单个 agent 的配置校验结果
+#[derive(Debug, Clone)]
+pub struct ValidationResult {
+    pub id: i32,
+    pub provider: String,
+    pub model: String,
+    /// 探测耗时，仅成功时有值
+    pub latency: Option<Duration>,
+    /// 失败原因，成功时为空
+    pub failure: Option<ValidationFailure>,
+    /// 错误信息(已脱敏)
+    pub error: Option<String>,
+}
+
+impl ValidationResult {
+    pub fn is_ok(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+impl RandAgent {
+    /// 并发探测所有 agent，并按 `action` 处理探测失败的 agent
+    async fn warm_up(
+        &self,
+        prompt: &str,
+        timeout: Duration,
+        action: WarmUpAction,
+    ) -> Vec<WarmUpResult> {
+        // 探测期间不持有锁
+        let slots: Vec<AgentSlot> = self.agents.read().await.clone();
+        let probe_agents: Vec<_> = slots
+            .iter()
+            .map(|slot| lock_slot(slot).agent.clone())
+            .collect();
+        let results = futures::future::join_all(
+            probe_agents
+                .iter()
+                .map(|agent| probe_agent(agent, prompt, timeout)),
+        )
+        .await;
+
+        let mut report = Vec::with_capacity(results.len());
+        for (slot, result) in slots.iter().zip(results) {
+            let mut state = lock_slot(slot);
+            let (success, latency, error) = match result {
+                Ok(latency) => (true, Some(latency), None),
+                Err(err) => (false, None, Some(err)),
+            };
+            if !success {
+                tracing::warn!(
+                    "agent 预热失败 provider: {}, model: {}, id: {}, error: {:?}",
+                    state.info.provider,
+                    state.info.model,
+                    state.info.id,
+                    error
+                );
+                if action == WarmUpAction::Quarantine {
+                    state.quarantine();
+                }
+            }
+            report.push(WarmUpResult {
+                info: state.info.clone(),
+                success,
+                latency,
+                error,
+            });
+        }
+
+        if action == WarmUpAction::Drop {
+            let failed: Vec<&AgentSlot> = slots
+                .iter()
+                .zip(&report)
+                .filter(|(_, result)| !result.success)
+                .map(|(slot, _)| slot)
+                .collect();
+            let mut agents = self.agents.write().await;
+            agents.retain(|slot| !failed.iter().any(|failed| Arc::ptr_eq(slot, failed)));
+        }
+
+        report
+    }
+
+    /// 启动后台健康检查，立即进行第一轮，之后每隔 `interval` 检查一次
+    ///
+    /// 返回的句柄被 drop 时停止检查，见 [`crate::health`]
+    pub fn start_health_checks(
+        &self,
+        interval: Duration,
+        probe_prompt: impl Into<String>,
+    ) -> HealthCheckHandle {
+        self.start_health_checks_with(HealthCheckConfig::new(interval, probe_prompt))
+    }
+
+    /// 使用自定义超时和慢响应阈值启动后台健康检查
+    pub fn start_health_checks_with(&self, config: HealthCheckConfig) -> HealthCheckHandle {
+        let rand_agent = self.clone();
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(config.interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                rand_agent.check_health(&config).await;
+            }
+        });
+        self.register_background_task(task.abort_handle());
+        HealthCheckHandle { task }
+    }
+
+    /// 立即进行一轮健康检查，更新 agent 状态并返回结果
+    ///
+    /// 失败或慢响应的 agent 被标记为无效，探测成功的无效 agent 恢复为有效
+    pub async fn check_health(&self, config: &HealthCheckConfig) -> HealthReport {
+        // 探测期间不持有锁
+        let slots: Vec<AgentSlot> = self
+            .agents
+            .read()
+            .await
+            .iter()
+            .filter(|slot| !lock_slot(slot).info.disabled)
+            .cloned()
+            .collect();
+        let probe_agents: Vec<_> = slots
+            .iter()
+            .map(|slot| lock_slot(slot).agent.clone())
+            .collect();
+        let results = futures::future::join_all(
+            probe_agents
+                .iter()
+                .map(|agent| probe_agent(agent, &config.probe_prompt, config.timeout)),
+        )
+        .await;
+
+        let mut agents = Vec::with_capacity(results.len());
+        for (slot, result) in slots.iter().zip(results) {
+            let (healthy, latency, error) = health::evaluate(result, config.slow_threshold);
+            let mut state = lock_slot(slot);
+            if healthy {
+                if let Some(latency) = latency {
+                    state.record_latency(latency);
+                }
+                if !state.is_valid() {
+                    tracing::info!(
+                        "健康检查通过，恢复 agent provider: {}, model: {}, id: {}",
+                        state.info.provider,
+                        state.info.model,
+                        state.info.id
+                    );
+                    state.record_success();
+                }
+            } else {
+                tracing::warn!(
+                    "健康检查失败，标记 agent 为无效 provider: {}, model: {}, id: {}, error: {:?}",
+                    state.info.provider,
+                    state.info.model,
+                    state.info.id,
+                    error
+                );
+                state.quarantine();
+            }
+            agents.push(health::AgentHealth {
+                info: state.info.clone(),
+                healthy,
+                latency,
+                error,
+            });
+        }
+
+        let report = HealthReport {
+            checked_at: Some(std::time::SystemTime::now()),
+            agents,
+        };
+        *self.health.lock().unwrap_or_else(|e| e.into_inner()) = report.clone();
+        report
+    }
+
+    /// 最近一轮健康检查的结果
+    pub async fn health_report(&self) -> HealthReport {
+        self.health
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+impl RandAgentBuilder {
+    /// 构建 RandAgent 并预热
+    ///
+    /// 并发向每个 agent 发送一个简短的探测请求，探测失败(如 key 错误、base url 错误)的 agent
+    /// 按 [`WarmUpAction`] 移除或隔离，在启动阶段就发现配置错误，而不是等到第一个用户请求
+    pub async fn build_and_verify(self) -> (RandAgent, Vec<WarmUpResult>) {
+        let prompt = self.warm_up_prompt.clone();
+        let timeout = self.warm_up_timeout;
+        let action = self.warm_up_action;
+        let rand_agent = self.build();
+        let report = rand_agent.warm_up(&prompt, timeout, action).await;
+        (rand_agent, report)
+    }
+
+    /// 只校验不构建: 并发向每个待添加的 agent 发送预热探测请求，返回每个 agent 的结果
+    ///
+    /// 使用 [`warm_up_prompt`](Self::warm_up_prompt) 和 [`warm_up_timeout`](Self::warm_up_timeout)，
+    /// 可在代理池上线前发现 key 错误、模型名错误或接口地址不可达
+    ///
+    /// ```rust,ignore
+    /// let builder = RandAgentBuilder::new().simple_builder(configs, "你是一个助手".to_string());
+    /// let failed: Vec<_> = builder.validate().await.into_iter().filter(|r| !r.is_ok()).collect();
+    /// if !failed.is_empty() {
+    ///     anyhow::bail!("配置错误: {failed:?}");
+    /// }
+    /// let rand_agent = builder.build();
+    /// ```
+    pub async fn validate(&self) -> Vec<ValidationResult> {
+        let results =
+            futures::future::join_all(self.agents.iter().map(|entry| {
+                probe_agent(&entry.agent, &self.warm_up_prompt, self.warm_up_timeout)
+            }))
+            .await;
+        self.agents
+            .iter()
+            .zip(results)
+            .map(|(entry, result)| {
+                let (latency, error) = match result {
+                    Ok(latency) => (Some(latency), None),
+                    Err(err) => (None, Some(err)),
+                };
+                ValidationResult {
+                    id: entry.id,
+                    provider: entry.provider.clone(),
+                    model: entry.model.clone(),
+                    latency,
+                    failure: error.as_deref().map(ValidationFailure::classify),
+                    error,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rand_agent::tests::FakeModel;
+    use rig::completion::Prompt;
+
+    /// 用假模型创建 builder，agent id 从 1 开始
+    fn fake_builder(models: &[FakeModel]) -> RandAgentBuilder {
+        models
+            .iter()
+            .zip(1..)
+            .fold(RandAgentBuilder::new(), |builder, (model, id)| {
+                builder.add_agent(
+                    model.agent(),
+                    id,
+                    format!("fake{id}"),
+                    "fake-model".to_string(),
+                )
+            })
+            .warm_up_timeout(Duration::from_millis(100))
+    }
+
+    #[tokio::test]
+    async fn test_warm_up_drop() {
+        let models = [
+            FakeModel::ok(),
+            FakeModel::failing_with("401 Unauthorized"),
+            FakeModel::ok().with_delay(Duration::from_secs(5)),
+        ];
+        let (pool, report) = fake_builder(&models).build_and_verify().await;
+
+        let success: Vec<_> = report.iter().map(|r| (r.info.id, r.success)).collect();
+        assert_eq!(success, vec![(1, true), (2, false), (3, false)]);
+        assert!(report[0].latency.is_some());
+        assert!(report[1].error.as_deref().unwrap().contains("401"));
+        assert!(report[2].error.is_some());
+
+        // 探测失败的 agent 被移除，之后的请求只会使用剩下的 agent
+        assert_eq!(pool.total_len().await, 1);
+        assert_eq!(pool.get_agents_info().await[0].id, 1);
+        pool.prompt("你好").await.unwrap();
+        assert_eq!(models[0].calls(), 2);
+        assert_eq!(models[1].calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_warm_up_quarantine() {
+        let models = [FakeModel::failing(), FakeModel::ok()];
+        let (pool, report) = fake_builder(&models)
+            .max_failures(3)
+            .warm_up_action(WarmUpAction::Quarantine)
+            .build_and_verify()
+            .await;
+        assert!(!report[0].success);
+        assert!(report[1].success);
+
+        // 探测失败的 agent 保留在池中，但已失效，不会被选中
+        assert_eq!(pool.total_len().await, 2);
+        assert_eq!(pool.len().await, 1);
+        let state = pool.get_agent_by_id(1).await.unwrap();
+        assert_eq!(state.info.failure_count, 3);
+        assert!(!state.is_valid());
+        for _ in 0..3 {
+            pool.prompt("你好").await.unwrap();
+        }
+        assert_eq!(models[0].calls(), 1);
+        assert_eq!(models[1].calls(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_validate_unreachable() {
+        use rig::client::completion::CompletionClientDyn;
+
+        let agent = crate::extra_providers::bigmodel::Client::from_url("xxx", "http://127.0.0.1:1")
+            .agent("glm-4-flash")
+            .build();
+        let builder = RandAgentBuilder::new()
+            .warm_up_timeout(Duration::from_secs(5))
+            .add_agent(agent, 1, "bigmodel".to_string(), "glm-4-flash".to_string());
+        let results = builder.validate().await;
+        assert_eq!(results.len(), 1);
+        assert!(!results[0].is_ok(), "{:?}", results[0]);
+        assert_eq!(
+            results[0].failure,
+            Some(ValidationFailure::Unreachable),
+            "{:?}",
+            results[0].error
+        );
+
+        assert_eq!(
+            ValidationFailure::classify("ProviderError: 401 Unauthorized: invalid api key"),
+            ValidationFailure::Auth
+        );
+        assert_eq!(
+            ValidationFailure::classify(
+                "ProviderError: The model `gpt-9` does not exist or you do not have access"
+            ),
+            ValidationFailure::Model
+        );
+    }
+}
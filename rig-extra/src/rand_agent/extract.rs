@@ -0,0 +1,187 @@
+//! 结构化输出和结构化提取
+
+use super::{RandAgent, checkout, lock_slot};
+use crate::error::RandAgentError;
+use crate::extraction_cache::extraction_key;
+use crate::json_utils;
+use crate::redact::redacted;
+use crate::simple_rand_builder::set_preamble;
+use crate::structured::{self, StructuredOutput};
+use crate::usage::UsageRecorder;
+use rig::completion::{CompletionError, Message, PromptError};
+use rig::extractor::{ExtractionError, ExtractorBuilder};
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::Instrument;
+
+impl RandAgent {
+    /// 结构化输出，返回解析后的结果
+    ///
+    /// 按选中 agent 的 [`StructuredOutput`] 能力，使用原生 JSON Schema、JSON 模式或提示词约束
+    pub async fn prompt_structured<T>(
+        &self,
+        prompt: impl Into<Message> + Send,
+    ) -> Result<T, RandAgentError>
+    where
+        T: JsonSchema + DeserializeOwned,
+    {
+        let (name, schema) = structured::schema_of::<T>();
+        let prompt = self.screen(prompt.into())?;
+        let cache_key = extraction_key(&schema, &prompt);
+        if let Some(value) = self.cached_extraction(&cache_key) {
+            return Ok(value);
+        }
+
+        let (slot, _permit) = self.pick_slot().await?;
+        let content = self
+            .prompt_slot_with(&slot, "prompt_structured", prompt, |info, agent| {
+                let mode = info.structured_output;
+                let mut agent = (*agent).clone();
+                if let Some(params) = mode.additional_params(&name, &schema) {
+                    agent.additional_params = Some(match agent.additional_params.take() {
+                        Some(existing) => json_utils::merge(existing, params),
+                        None => params,
+                    });
+                }
+                if let Some(instructions) = mode.instructions(&schema) {
+                    let preamble = match agent.preamble.take() {
+                        Some(preamble) => format!("{preamble}\n\n{instructions}"),
+                        None => instructions,
+                    };
+                    set_preamble(&mut agent, preamble);
+                }
+                Arc::new(agent)
+            })
+            .await?;
+        let value = structured::parse_json(&content).map_err(|err| {
+            RandAgentError::from(PromptError::CompletionError(
+                CompletionError::ResponseError(format!("结构化输出解析失败: {err}")),
+            ))
+        })?;
+        self.store_extraction(cache_key, content);
+        Ok(value)
+    }
+
+    /// 从文本中提取结构化数据
+    ///
+    /// 在随机选中的 agent 上构建 rig 的 extractor(通过 `submit` 工具提交结果)，
+    /// 失败时换用其它未失败过的 agent，直到成功或没有可用 agent
+    pub async fn extract<T>(&self, text: impl Into<Message> + Send) -> Result<T, RandAgentError>
+    where
+        T: JsonSchema + DeserializeOwned + Serialize + Send + Sync + 'static,
+    {
+        let text = self.screen(text.into())?;
+        let cache_key = extraction_key(&structured::schema_of::<T>().1, &text);
+        if let Some(value) = self.cached_extraction(&cache_key) {
+            return Ok(value);
+        }
+
+        let mut failed = Vec::new();
+        let mut last_error = None;
+        loop {
+            let (slot, _permit) = match self.pick_slot_excluding(&failed).await {
+                Ok(picked) => picked,
+                Err(err) => return Err(last_error.map(RandAgentError::from).unwrap_or(err)),
+            };
+            let (agent_info, agent, span) = checkout(&slot, "extract");
+            self.hooks.request_start(&agent_info);
+
+            // Extractor 不返回用量，通过包装的模型累计每次补全请求(包括内部重试)的用量
+            let model = UsageRecorder::new((*agent.model).clone());
+            let mut builder = ExtractorBuilder::<_, T>::new(model.clone());
+            if let Some(params) = agent.additional_params.clone() {
+                builder = builder.additional_params(params);
+            }
+            if let Some(max_tokens) = agent.max_tokens {
+                builder = builder.max_tokens(max_tokens);
+            }
+            let extractor = builder.build();
+
+            let start = Instant::now();
+            let result = self
+                .call_agent(&agent_info, async {
+                    let data = extractor
+                        .extract(text.clone())
+                        .await
+                        .map_err(|err| match err {
+                            ExtractionError::CompletionError(err) => {
+                                PromptError::CompletionError(err)
+                            }
+                            err => PromptError::CompletionError(CompletionError::ResponseError(
+                                err.to_string(),
+                            )),
+                        })?;
+                    let json = serde_json::to_string(&data)
+                        .map_err(|err| PromptError::CompletionError(err.into()))?;
+                    Ok((json, model.usage()))
+                })
+                .instrument(span.clone())
+                .await;
+            match self.finish_request(&slot, &agent_info, result, start.elapsed(), &span) {
+                Ok(json) => {
+                    let value = serde_json::from_str(&json).map_err(|err| {
+                        RandAgentError::from(PromptError::CompletionError(err.into()))
+                    })?;
+                    self.store_extraction(cache_key, json);
+                    return Ok(value);
+                }
+                Err(err) => {
+                    tracing::warn!(
+                        agent_id = agent_info.id,
+                        error = %redacted(&err),
+                        "结构化提取失败，换用其它 agent"
+                    );
+                    failed.push(agent_info.id);
+                    last_error = Some(err);
+                }
+            }
+        }
+    }
+
+    /// 设置 agent 的结构化输出支持情况，覆盖自动推断的结果，返回是否找到该代理
+    pub async fn set_structured_output(
+        &self,
+        id: i32,
+        structured_output: StructuredOutput,
+    ) -> bool {
+        let agents = self.agents.read().await;
+        let mut found = false;
+        for slot in agents.iter() {
+            let mut state = lock_slot(slot);
+            if state.id == id {
+                state.info.structured_output = structured_output;
+                found = true;
+            }
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rand_agent::tests::{FakeModel, fake_pool};
+
+    #[tokio::test]
+    async fn test_extract_records_usage() {
+        #[derive(Debug, serde::Deserialize, serde::Serialize, JsonSchema)]
+        struct Person {
+            name: String,
+        }
+
+        let model = FakeModel::ok().submitting(serde_json::json!({ "name": "张三" }));
+        let pool = fake_pool(&[model], |builder| builder);
+        let person: Person = pool.extract("张三今年 20 岁").await.unwrap();
+        assert_eq!(person.name, "张三");
+
+        let usage = pool.usage_stats().await;
+        let agent = usage.agent(1).unwrap();
+        assert_eq!(agent.requests, 1);
+        assert_eq!(agent.prompt_tokens, 10);
+        assert_eq!(agent.completion_tokens, 5);
+        assert_eq!(agent.total_tokens, 15);
+    }
+}
@@ -0,0 +1,153 @@
+//! 向多个 agent 发送相同的 prompt 以及批量请求
+
+use super::{
+    AgentSlot, PROMPT_ALL_CONCURRENCY, RandAgent, blocked_error, lock_slot, unavailable_error,
+};
+use crate::AgentInfo;
+use crate::batch::{self, BatchOptions, BatchOutcome};
+use futures::StreamExt;
+use rand::seq::SliceRandom;
+use rig::completion::{Message, PromptError};
+
+impl RandAgent {
+    /// 向代理池中所有可用的 agent 发送相同的 prompt，按代理池顺序返回每个 agent 的结果
+    ///
+    /// 用于对比各模型的输出(评测、投票等)。最多同时进行 [`PROMPT_ALL_CONCURRENCY`] 个请求，
+    /// 同时受构建器设置的并发上限约束
+    pub async fn prompt_all(
+        &self,
+        prompt: impl Into<Message> + Send,
+    ) -> Vec<(AgentInfo, Result<String, PromptError>)> {
+        let slots = self.available_slots().await;
+        self.prompt_slots(slots, "prompt_all", prompt.into()).await
+    }
+
+    /// 随机选择至多 `n` 个不同的可用 agent 发送相同的 prompt，返回每个 agent 的结果
+    ///
+    /// 并发规则与 [`prompt_all`](Self::prompt_all) 相同
+    pub async fn prompt_sample(
+        &self,
+        prompt: impl Into<Message> + Send,
+        n: usize,
+    ) -> Vec<(AgentInfo, Result<String, PromptError>)> {
+        let mut slots = self.available_slots().await;
+        slots.shuffle(&mut rand::rng());
+        slots.truncate(n);
+        self.prompt_slots(slots, "prompt_sample", prompt.into())
+            .await
+    }
+
+    /// 批量提问，单项失败不影响其它项，返回每一项的状态和汇总统计
+    ///
+    /// 每一项按 [`Prompt::prompt`](rig::completion::Prompt::prompt) 的规则选择 agent，见 [`crate::batch`]
+    pub async fn prompt_batch<P>(
+        &self,
+        prompts: impl IntoIterator<Item = P>,
+        options: BatchOptions,
+    ) -> BatchOutcome
+    where
+        P: Into<Message>,
+    {
+        let items = batch::pending_items(prompts.into_iter().map(Into::into));
+        batch::run_batch(self, items, options).await
+    }
+
+    /// 只重试失败或被跳过的项，已成功的结果保持不变
+    pub async fn retry_failed(&self, outcome: BatchOutcome, options: BatchOptions) -> BatchOutcome {
+        batch::run_batch(self, outcome.items, options).await
+    }
+
+    async fn prompt_slots(
+        &self,
+        slots: Vec<AgentSlot>,
+        method: &'static str,
+        prompt: Message,
+    ) -> Vec<(AgentInfo, Result<String, PromptError>)> {
+        let prompt = match &self.firewall {
+            Some(firewall) => match firewall.check_message(prompt) {
+                Ok(prompt) => prompt,
+                Err(err) => {
+                    return slots
+                        .iter()
+                        .map(|slot| {
+                            (
+                                lock_slot(slot).info.clone(),
+                                Err(blocked_error(err.clone())),
+                            )
+                        })
+                        .collect();
+                }
+            },
+            None => prompt,
+        };
+        futures::stream::iter(slots)
+            .map(|slot| {
+                let prompt = prompt.clone();
+                async move {
+                    let result = match self.acquire_slot(&slot).await {
+                        Ok(_permit) => self.prompt_slot(&slot, method, prompt).await,
+                        Err(err) => Err(unavailable_error(err)),
+                    };
+                    let info = lock_slot(&slot).info.clone();
+                    (info, result)
+                }
+            })
+            .buffered(PROMPT_ALL_CONCURRENCY)
+            .collect()
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rand_agent::tests::{FakeModel, fake_pool};
+    use crate::rate_limit::RateLimit;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_prompt_all_bounded_concurrency() {
+        let models: Vec<FakeModel> = (0..PROMPT_ALL_CONCURRENCY + 4)
+            .map(|_| FakeModel::ok().with_delay(Duration::from_millis(30)))
+            .collect();
+        let pool = fake_pool(&models, |builder| builder);
+
+        let request = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.prompt_all("hi").await }
+        });
+        let mut max_in_flight = 0;
+        while !request.is_finished() {
+            max_in_flight = max_in_flight.max(pool.in_flight());
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        assert_eq!(max_in_flight, PROMPT_ALL_CONCURRENCY);
+
+        // 结果按代理池顺序返回，每个 agent 只调用一次
+        let results = request.await.unwrap();
+        let ids: Vec<i32> = results.iter().map(|(info, _)| info.id).collect();
+        assert_eq!(ids, (1..=models.len() as i32).collect::<Vec<_>>());
+        assert!(results.iter().all(|(_, result)| result.is_ok()));
+        assert!(models.iter().all(|model| model.calls() == 1));
+    }
+
+    #[tokio::test]
+    async fn test_fan_out_respects_rate_limit() {
+        let models = [FakeModel::ok(), FakeModel::ok()];
+        let pool = fake_pool(&models, |builder| builder);
+        let limit = RateLimit {
+            rpm: Some(1),
+            tpm: None,
+        };
+        assert!(pool.set_rate_limit(1, limit).await);
+
+        assert_eq!(pool.prompt_all("hi").await.len(), 2);
+        // agent 1 本分钟的额度已用完，不再参与广播和指定请求
+        let results = pool.prompt_all("hi").await;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0.id, 2);
+        assert!(pool.prompt_on(1, "hi").await.is_ok());
+        assert_eq!(models[0].calls(), 1);
+        assert_eq!(models[1].calls(), 3);
+    }
+}
@@ -0,0 +1,130 @@
+//! 对冲请求: 同时向多个 agent 发送相同的 prompt，取最先成功的结果
+
+use super::{AgentSlot, RandAgent, lock_slot, no_valid_agent_error, unavailable_error};
+use crate::concurrency::RequestPermit;
+use futures::StreamExt;
+use rig::completion::{Message, PromptError};
+
+/// 对冲请求中进行中的一路请求，在得到结果前被丢弃(其它请求先成功)时释放 agent 的试探状态，
+/// 不计入失败；并发名额和进行中计数随 [`RequestPermit`] 一起释放
+struct HedgedCall {
+    slot: Option<AgentSlot>,
+}
+
+impl HedgedCall {
+    fn new(slot: &AgentSlot) -> Self {
+        Self {
+            slot: Some(slot.clone()),
+        }
+    }
+
+    /// 请求已有结果，结果由 `finish_request` 记录
+    fn finish(mut self) {
+        self.slot = None;
+    }
+}
+
+impl Drop for HedgedCall {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot.take() {
+            let mut state = lock_slot(&slot);
+            state.cancel_trial();
+            tracing::debug!(agent_id = state.id, "对冲请求被取消");
+        }
+    }
+}
+
+impl RandAgent {
+    /// 对冲请求：同时向 `n` 个不同的有效 agent 发送相同的 prompt，返回最先成功的结果
+    ///
+    /// 得到结果后其余请求被取消，被取消的请求不计入失败；全部失败时返回最后一个错误。
+    /// 有效 agent 不足 `n` 个时使用全部有效 agent，设置了全局并发上限时 `n` 不超过该上限。
+    /// 适合对延迟敏感、使用廉价模型的场景
+    pub async fn prompt_hedged(
+        &self,
+        prompt: impl Into<Message> + Send,
+        n: usize,
+    ) -> Result<String, PromptError> {
+        let prompt = self.screen(prompt.into())?;
+        let cache_key = self.response_cache_key(&prompt);
+        self.with_response_cache(cache_key, self.hedge(prompt, n))
+            .await
+    }
+
+    async fn hedge(&self, prompt: Message, n: usize) -> Result<String, PromptError> {
+        let n = self
+            .concurrency
+            .global_limit()
+            .map_or(n, |limit| n.min(limit))
+            .max(1);
+
+        let mut picked: Vec<(AgentSlot, RequestPermit)> = Vec::with_capacity(n);
+        let mut excluded = Vec::with_capacity(n);
+        while picked.len() < n {
+            match self.pick_slot_excluding(&excluded).await {
+                Ok((slot, permit)) => {
+                    excluded.push(lock_slot(&slot).id);
+                    picked.push((slot, permit));
+                }
+                Err(err) if picked.is_empty() => return Err(unavailable_error(err)),
+                Err(_) => break,
+            }
+        }
+
+        let mut calls: futures::stream::FuturesUnordered<_> = picked
+            .into_iter()
+            .map(|(slot, permit)| {
+                let prompt = prompt.clone();
+                // 在 future 之外创建，未被轮询就取消时同样释放
+                let call = HedgedCall::new(&slot);
+                async move {
+                    let _permit = permit;
+                    let result = self.prompt_slot(&slot, "prompt_hedged", prompt).await;
+                    call.finish();
+                    result
+                }
+            })
+            .collect();
+
+        let mut last_error = None;
+        while let Some(result) = calls.next().await {
+            match result {
+                Ok(content) => return Ok(content),
+                Err(err) => last_error = Some(err),
+            }
+        }
+        Err(last_error.unwrap_or_else(no_valid_agent_error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rand_agent::tests::{FakeModel, fake_pool};
+    use std::time::{Duration, Instant};
+
+    #[tokio::test]
+    async fn test_hedged_loser_released() {
+        let cooldown = Duration::from_secs(60);
+        let slow = FakeModel::ok().with_delay(Duration::from_secs(5));
+        let pool = fake_pool(&[FakeModel::ok(), slow], |builder| {
+            builder.max_failures(1).cooldown(cooldown)
+        });
+        // 慢的 agent 处于半开状态，本次被选中时作为试探请求
+        let slow_slot = pool.agents.read().await[1].clone();
+        {
+            let mut state = lock_slot(&slow_slot);
+            state.quarantine();
+            state.invalid_since = Instant::now().checked_sub(cooldown * 2);
+        }
+
+        let content = pool.prompt_hedged("你好", 2).await.unwrap();
+        assert_eq!(content, "reply 1");
+        assert_eq!(pool.in_flight(), 0);
+
+        // 被取消的试探请求不计入失败，并且允许立即重新试探
+        let state = lock_slot(&slow_slot);
+        assert_eq!(state.info.failure_count, 1);
+        assert!(state.is_available(Some(cooldown)));
+    }
+}
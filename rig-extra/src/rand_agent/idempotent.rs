@@ -0,0 +1,128 @@
+//! 携带幂等键的请求，见 [`crate::idempotency`]
+
+use super::{RandAgent, unavailable_error};
+use crate::error::RandAgentError;
+use crate::idempotency::{IdempotencyCache, Lookup, PendingRequest};
+use rig::completion::{Message, PromptError};
+
+impl RandAgent {
+    /// 携带幂等键的 prompt
+    ///
+    /// 同一个键在有效期内已经成功过时直接返回保存的结果，不会再次调用提供方；
+    /// 相同的键正在请求时等待该请求的结果。失败的结果不保存，相同的键可以重新提交。
+    /// 幂等键按租户隔离，防火墙检查和关闭状态检查在查询保存的结果之前进行
+    pub async fn prompt_idempotent(
+        &self,
+        key: &str,
+        prompt: impl Into<Message> + Send,
+    ) -> Result<String, PromptError> {
+        let prompt = self.screen(prompt.into())?;
+        let cache_key = self.response_cache_key(&prompt);
+        self.run_idempotent(
+            key,
+            self.with_response_cache(cache_key, async {
+                let (slot, _permit) = self.pick_slot().await.map_err(unavailable_error)?;
+                self.prompt_slot(&slot, "prompt_idempotent", prompt).await
+            }),
+        )
+        .await
+    }
+
+    /// 携带幂等键的 chat，规则同 [`RandAgent::prompt_idempotent`]
+    pub async fn chat_idempotent(
+        &self,
+        key: &str,
+        prompt: impl Into<Message> + Send,
+        chat_history: Vec<Message>,
+    ) -> Result<String, PromptError> {
+        let prompt = self.screen(prompt.into())?;
+        self.run_idempotent(
+            key,
+            self.chat_screened("chat_idempotent", prompt, chat_history),
+        )
+        .await
+    }
+
+    /// 按租户隔离的幂等键执行请求
+    ///
+    /// 已有成功结果时直接返回；相同的键正在请求时等待其结果，该请求失败或被取消时
+    /// 由等待的调用方之一重新发起。代理池关闭后不再返回保存的结果
+    async fn run_idempotent(
+        &self,
+        key: &str,
+        request: impl std::future::Future<Output = Result<String, PromptError>>,
+    ) -> Result<String, PromptError> {
+        if self.is_shutting_down() {
+            return Err(unavailable_error(RandAgentError::ShuttingDown));
+        }
+        let key = (self.tenant.clone(), key.to_string());
+        loop {
+            let notify = match self.lock_idempotency().begin(&key, self.idempotency_ttl) {
+                Lookup::Done(content) => {
+                    tracing::info!("幂等键 {} 已有成功结果，直接返回", key.1);
+                    return Ok(content);
+                }
+                Lookup::Started => break,
+                Lookup::Pending(notify) => notify,
+            };
+            // 先登记等待再确认请求仍在进行，避免错过结束通知
+            let notified = notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if self.lock_idempotency().is_pending(&key, &notify) {
+                tracing::debug!("幂等键 {} 正在请求，等待其结果", key.1);
+                notified.await;
+            }
+        }
+
+        let pending = PendingRequest::new(&self.idempotency_cache, key);
+        let result = request.await;
+        pending.finish(result.as_ref().ok().cloned());
+        result
+    }
+
+    fn lock_idempotency(&self) -> std::sync::MutexGuard<'_, IdempotencyCache> {
+        self.idempotency_cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rand_agent::tests::{FakeModel, fake_pool};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_idempotent_dedup_and_scoping() {
+        use crate::firewall::{FirewallAction, FirewallRule, PromptFirewall};
+
+        let model = FakeModel::ok().with_delay(Duration::from_millis(30));
+        let pool = fake_pool(std::slice::from_ref(&model), |builder| {
+            builder.firewall(
+                PromptFirewall::new()
+                    .rule(FirewallRule::deny_list(["机密"]), FirewallAction::Block),
+            )
+        });
+
+        // 并发提交相同的键只请求一次
+        let (a, b) = tokio::join!(
+            pool.prompt_idempotent("req-1", "hi"),
+            pool.prompt_idempotent("req-1", "hi")
+        );
+        assert_eq!(a.unwrap(), b.unwrap());
+        assert_eq!(model.calls(), 1);
+
+        // 不同租户的相同键分别请求
+        let acme = pool.for_tenant("acme");
+        acme.prompt_idempotent("req-1", "hi").await.unwrap();
+        acme.prompt_idempotent("req-1", "hi").await.unwrap();
+        assert_eq!(model.calls(), 2);
+
+        // 防火墙和关闭检查在查询保存的结果之前
+        assert!(pool.prompt_idempotent("req-1", "机密").await.is_err());
+        pool.shutdown(Duration::from_millis(10)).await;
+        assert!(pool.prompt_idempotent("req-1", "hi").await.is_err());
+        assert_eq!(model.calls(), 2);
+    }
+}
@@ -0,0 +1,3466 @@
+//! ## 多线程使用示例
+//!
+//! ```rust,no_run
+//! use rig_extra::extra_providers::{bigmodel::Client};
+//! use rig_extra::rand_agent::RandAgentBuilder;
+//! use std::sync::Arc;
+//! use tokio::task;
+//! use rig::client::ProviderClient;
+//! use rig_extra::error::RandAgentError;
+//! #[tokio::main]
+//! async fn main() -> Result<(), RandAgentError> {
+//!     // 创建线程安全的 RandAgent
+//!
+//!     //创建多个客户端
+//!     let client1 = Client::from_env();
+//!     let client2 = Client::from_env();
+//!     use rig::client::completion::CompletionClientDyn;
+//!     use rig::completion::Prompt;
+//!
+//!
+//!     let thread_safe_agent = RandAgentBuilder::new()
+//!         .max_failures(3)
+//!         .add_agent(client1.agent("glm-4-flash").build(),1, "bigmodel".to_string(), "glm-4-flash".to_string())
+//!         .add_agent(client2.agent("glm-4-flash").build(),2, "bigmodel".to_string(), "glm-4-flash".to_string())
+//!         .build();
+//!
+//!     let agent_arc = Arc::new(thread_safe_agent);
+//!
+//!     // 创建多个并发任务
+//!     let mut handles = vec![];
+//!     for i in 0..5 {
+//!         let agent_clone = Arc::clone(&agent_arc);
+//!         let handle = task::spawn(async move {
+//!             let response = agent_clone.prompt(&format!("Hello from task {}", i)).await?;
+//!             println!("Task {} response: {}", i, response);
+//!             Ok::<(), RandAgentError>(())
+//!         });
+//!         handles.push(handle);
+//!     }
+//!
+//!     // 等待所有任务完成
+//!     for handle in handles {
+//!         handle.await.expect("task panicked")?;
+//!     }
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use crate::AgentInfo;
+use crate::circuit_breaker::{CircuitBreaker, CircuitBreakerConfig};
+use crate::concurrency::{ConcurrencyLimiter, RequestPermit};
+use crate::dyn_agent::{DynPromptAgent, DynStream};
+use crate::error::RandAgentError;
+use crate::extraction_cache::{ExtractionCache, ExtractionKey};
+use crate::failure_dump::{FailureDumper, RetryContext};
+use crate::fallback_agent::FallbackAgent;
+use crate::firewall::{FirewallViolation, PromptFirewall};
+use crate::health::HealthReport;
+use crate::i18n::MessageKey;
+use crate::idempotency::{DEFAULT_IDEMPOTENCY_TTL, IdempotencyCache};
+use crate::json_utils;
+use crate::lifecycle::Lifecycle;
+use crate::memory::{MemoryManager, estimate_message_tokens};
+use crate::preamble::PreambleVersions;
+use crate::progress::{ProgressEvent, ProgressReporter};
+use crate::prompt_templates::PromptTemplates;
+use crate::rate_limit::{RateLimit, RateWindow};
+use crate::reasoning::ReasoningEffort;
+use crate::redact::{redact_prompt_error, redacted};
+use crate::response_cache::{CacheKey, ResponseCache, context_hash};
+use crate::retry::RetryConfig;
+use crate::shaping::{RequestShaper, RequestShaping};
+use crate::simple_rand_builder::{set_preamble, shared_tool_server};
+use crate::snapshot::{AgentSnapshot, RandAgentSnapshot};
+use crate::spans;
+use crate::stream_tee::{StreamTee, TeeItem, TeeOutput, collect_with_timeout};
+use crate::structured::{self, StructuredOutput};
+use crate::tenant::{
+    TenantAuditEntry, TenantLedger, TenantLimitExceeded, TenantLimits, TenantPermit,
+};
+use crate::tool_events::{ToolEventStream, stream_with_tool_events};
+use crate::tool_memory::ToolResultSummarizer;
+use crate::tool_policy::tool_usage_policy;
+use crate::usage::{UsageStats, usage_cost};
+use backon::Retryable;
+use futures::StreamExt;
+use futures::future::BoxFuture;
+use rand::Rng;
+use rig::agent::Agent;
+use rig::client::builder::BoxAgent;
+use rig::client::completion::CompletionModelHandle;
+use rig::completion::{Chat, CompletionError, Message, Prompt, PromptError, Usage};
+use serde::de::DeserializeOwned;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::{RwLock, broadcast};
+use tracing::{Instrument, Span};
+
+mod checks;
+mod extract;
+mod fan_out;
+mod hedged;
+mod idempotent;
+mod pinned;
+
+pub use checks::{ValidationFailure, ValidationResult, WarmUpAction, WarmUpResult};
+
+/// 平均延迟指数移动平均的权重
+const LATENCY_EWMA_ALPHA: f64 = 0.3;
+
+/// 代理失效回调类型，减少类型复杂度
+pub type OnAgentInvalidCallback = Option<Arc<Box<dyn Fn(i32) + Send + Sync + 'static>>>;
+
+/// 请求开始回调，参数为选中的 agent
+pub type OnRequestStartCallback = Option<Arc<dyn Fn(&AgentInfo) + Send + Sync + 'static>>;
+
+/// 请求成功回调，参数为 agent 和请求耗时
+pub type OnSuccessCallback = Option<Arc<dyn Fn(&AgentInfo, Duration) + Send + Sync + 'static>>;
+
+/// 请求失败回调，参数为 agent 和错误(包括响应校验失败)
+pub type OnFailureCallback = Option<Arc<dyn Fn(&AgentInfo, &PromptError) + Send + Sync + 'static>>;
+
+/// 重试回调，参数为触发重试的错误和等待时间
+pub type OnRetryCallback = Option<Arc<dyn Fn(&PromptError, Duration) + Send + Sync + 'static>>;
+
+/// 请求生命周期回调，用于将事件推送到自己的监控系统
+#[derive(Clone, Default)]
+pub(crate) struct RequestHooks {
+    pub(crate) on_request_start: OnRequestStartCallback,
+    pub(crate) on_success: OnSuccessCallback,
+    pub(crate) on_failure: OnFailureCallback,
+    pub(crate) on_retry: OnRetryCallback,
+}
+
+impl RequestHooks {
+    fn request_start(&self, info: &AgentInfo) {
+        if let Some(cb) = &self.on_request_start {
+            cb(info);
+        }
+    }
+
+    fn result(&self, info: &AgentInfo, result: &Result<String, PromptError>, latency: Duration) {
+        match result {
+            Ok(_) => {
+                if let Some(cb) = &self.on_success {
+                    cb(info, latency);
+                }
+            }
+            Err(err) => {
+                if let Some(cb) = &self.on_failure {
+                    cb(info, err);
+                }
+            }
+        }
+    }
+
+    fn retry(&self, err: &PromptError, delay: Duration) {
+        if let Some(cb) = &self.on_retry {
+            cb(err, delay);
+        }
+    }
+}
+
+/// agent 选择策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SelectionStrategy {
+    /// 从有效 agent 中随机选择
+    #[default]
+    Random,
+    /// 优先选择历史平均延迟最低的 agent；还没有延迟数据的 agent(新加入的 agent)
+    /// 优先被随机选中，测得延迟后再参与比较
+    LeastLatency,
+}
+
+/// 响应校验器
+///
+/// 部分提供方会以成功状态返回空内容，校验失败的响应计为 agent 失败，从而触发故障转移
+#[derive(Clone)]
+pub enum ResponseValidator {
+    /// 内容不能为空或只包含空白字符
+    NonEmpty,
+    /// 去除首尾空白后的最小字符数
+    MinLength(usize),
+    /// 自定义校验，返回 true 表示通过
+    Custom(Arc<dyn Fn(&str) -> bool + Send + Sync + 'static>),
+}
+
+impl ResponseValidator {
+    /// 使用自定义函数创建校验器
+    pub fn custom<F>(f: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        Self::Custom(Arc::new(f))
+    }
+
+    /// 校验响应内容
+    pub fn validate(&self, content: &str) -> bool {
+        match self {
+            ResponseValidator::NonEmpty => !content.trim().is_empty(),
+            ResponseValidator::MinLength(min) => content.trim().chars().count() >= *min,
+            ResponseValidator::Custom(f) => f(content),
+        }
+    }
+}
+
+/// 单个 agent 的状态槽
+///
+/// 只在选择 agent 和记录结果时短暂加锁，不同 agent 的记账互不阻塞
+pub(crate) type AgentSlot = Arc<std::sync::Mutex<AgentState>>;
+
+/// 将 AgentState 包装为状态槽
+pub(crate) fn into_slots(agent_states: Vec<AgentState>) -> Vec<AgentSlot> {
+    agent_states
+        .into_iter()
+        .map(|state| Arc::new(std::sync::Mutex::new(state)))
+        .collect()
+}
+
+/// [`RandAgent::prompt_all`] 同时进行的最大请求数
+pub const PROMPT_ALL_CONCURRENCY: usize = 8;
+
+/// 锁定 agent 状态槽，锁中毒时继续使用内部数据
+pub(crate) fn lock_slot(slot: &AgentSlot) -> std::sync::MutexGuard<'_, AgentState> {
+    slot.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 取出 agent 及其信息，并创建本次请求的 span，锁只在函数内短暂持有
+fn checkout(slot: &AgentSlot, method: &'static str) -> (AgentInfo, Arc<BoxAgent<'static>>, Span) {
+    let agent_state = lock_slot(slot);
+    let span = spans::request_span(method, &agent_state.info);
+    (agent_state.info.clone(), agent_state.agent.clone(), span)
+}
+
+/// 没有有效 agent 时返回的错误
+pub(crate) fn no_valid_agent_error() -> PromptError {
+    PromptError::MaxDepthError {
+        max_depth: 0,
+        chat_history: Box::new(vec![]),
+        prompt: MessageKey::NoValidAgents.text().into(),
+    }
+}
+
+/// 选不到 agent 时返回给 Prompt 调用方的错误
+fn unavailable_error(err: RandAgentError) -> PromptError {
+    match err {
+        RandAgentError::RateLimited { .. } => {
+            PromptError::CompletionError(CompletionError::ProviderError(err.to_string()))
+        }
+        RandAgentError::TenantLimitExceeded(err) => {
+            PromptError::CompletionError(CompletionError::RequestError(Box::new(err)))
+        }
+        RandAgentError::ShuttingDown => {
+            PromptError::CompletionError(CompletionError::RequestError(Box::new(err)))
+        }
+        _ => no_valid_agent_error(),
+    }
+}
+
+/// 被 prompt 防火墙拦截的错误，不属于任何重试类别(除 `All` 外)
+fn blocked_error(err: FirewallViolation) -> PromptError {
+    PromptError::CompletionError(CompletionError::RequestError(Box::new(err)))
+}
+
+/// 已知模型的上下文长度，目前只内置了智谱的模型
+fn known_context_length(provider: &str, model: &str) -> Option<u32> {
+    if provider.eq_ignore_ascii_case("bigmodel") {
+        crate::extra_providers::bigmodel::model_info(model).map(|info| info.context_length)
+    } else {
+        None
+    }
+}
+
+/// 历史记录可用的 token 数: 上下文长度的四分之三减去本次 prompt，
+/// 剩余部分留给系统提示词和回答
+fn history_budget(context_length: u32, prompt: &Message) -> usize {
+    (context_length as usize * 3 / 4).saturating_sub(estimate_message_tokens(prompt))
+}
+
+/// 超时后取消请求，返回 HTTP 类别的错误，以便按重试配置换一个 agent 重试
+#[allow(clippy::result_large_err)] // 与 rig 的 Prompt 接口一致，直接返回 PromptError
+async fn with_timeout<F, T>(timeout: Option<Duration>, call: F) -> Result<T, PromptError>
+where
+    F: std::future::Future<Output = Result<T, PromptError>>,
+{
+    let Some(timeout) = timeout else {
+        return call.await;
+    };
+    tokio::time::timeout(timeout, call)
+        .await
+        .unwrap_or_else(|_| {
+            let message = format!("{}: {timeout:?}", MessageKey::Timeout.text());
+            Err(PromptError::CompletionError(CompletionError::HttpError(
+                rig::http_client::Error::Instance(message.into()),
+            )))
+        })
+}
+
+/// 向 agent 发送探测请求，成功返回耗时，失败返回错误信息
+pub(crate) async fn probe_agent(
+    agent: &BoxAgent<'static>,
+    prompt: &str,
+    timeout: Duration,
+) -> Result<Duration, String> {
+    let start = Instant::now();
+    match tokio::time::timeout(timeout, agent.prompt(prompt)).await {
+        Ok(Ok(_)) => Ok(start.elapsed()),
+        Ok(Err(err)) => Err(redacted(&err)),
+        Err(_) => Err(format!("{}: {timeout:?}", MessageKey::Timeout.text())),
+    }
+}
+
+/// 校验响应并更新 agent 的失败计数，同时返回 agent 是否因本次失败而无效
+///
+/// 调用方需持有 agent 的锁，失效回调应在释放锁后再触发
+#[allow(clippy::result_large_err)] // 与 rig 的 Prompt 接口一致，直接返回 PromptError
+pub(crate) fn record_result(
+    agent_state: &mut AgentState,
+    result: Result<String, PromptError>,
+    latency: Duration,
+    validator: Option<&ResponseValidator>,
+) -> (Result<String, PromptError>, bool) {
+    let result = result
+        .map_err(redact_prompt_error)
+        .and_then(|content| match validator {
+            Some(validator) if !validator.validate(&content) => {
+                tracing::warn!(
+                    "响应未通过校验 provider: {}, model: {}, id: {}",
+                    agent_state.info.provider,
+                    agent_state.info.model,
+                    agent_state.info.id
+                );
+                Err(PromptError::CompletionError(
+                    CompletionError::ResponseError(MessageKey::InvalidResponse.text().to_string()),
+                ))
+            }
+            _ => Ok(content),
+        });
+
+    match result {
+        Ok(content) => {
+            agent_state.record_success();
+            agent_state.record_latency(latency);
+            (Ok(content), false)
+        }
+        Err(e) => {
+            agent_state.record_failure();
+            (Err(e), !agent_state.is_valid())
+        }
+    }
+}
+
+/// 随机选择 agent 的代理池，支持多线程并发访问
+///
+/// 原 `ThreadSafeRandAgent` 已合并到 RandAgent，旧名称见 [`crate::thread_safe_rand_agent`]
+#[derive(Clone)]
+pub struct RandAgent {
+    /// 代理集合，只有增删代理时才需要写锁
+    agents: Arc<RwLock<Vec<AgentSlot>>>,
+    on_agent_invalid: OnAgentInvalidCallback,
+    response_validator: Option<ResponseValidator>,
+    failure_decay: Option<Duration>,
+    selection_strategy: SelectionStrategy,
+    retry_config: RetryConfig,
+    cooldown: Option<Duration>,
+    circuit_breaker: Option<Arc<std::sync::Mutex<CircuitBreaker>>>,
+    usage_stats: Arc<std::sync::Mutex<UsageStats>>,
+    /// 当前调用所属的工作负载
+    workload: Option<Arc<str>>,
+    /// 当前调用所属的租户
+    tenant: Option<Arc<str>>,
+    tenants: Arc<std::sync::Mutex<TenantLedger>>,
+    idempotency_ttl: Duration,
+    idempotency_cache: Arc<std::sync::Mutex<IdempotencyCache>>,
+    /// 结构化提取结果的有效期，为空时不缓存
+    extraction_cache_ttl: Option<Duration>,
+    extraction_cache: Arc<std::sync::Mutex<ExtractionCache>>,
+    /// 最近一轮健康检查的结果
+    health: Arc<std::sync::Mutex<HealthReport>>,
+    hooks: RequestHooks,
+    progress: Option<Arc<ProgressReporter>>,
+    tool_summarizer: Option<ToolResultSummarizer>,
+    memory: Option<MemoryManager>,
+    concurrency: Arc<ConcurrencyLimiter>,
+    shaper: Arc<RequestShaper>,
+    /// 进行中的请求和后台任务，用于关闭时排空
+    lifecycle: Arc<Lifecycle>,
+    preambles: Arc<std::sync::RwLock<PreambleVersions>>,
+    prompt_templates: Arc<std::sync::RwLock<PromptTemplates>>,
+    /// 单次请求的超时时间
+    prompt_timeout: Option<Duration>,
+    /// 是否在系统提示词中追加工具使用规范
+    tool_usage_policy: bool,
+    /// 响应缓存
+    response_cache: Option<Arc<dyn ResponseCache>>,
+    /// 请求前检查用户输入
+    firewall: Option<Arc<PromptFirewall>>,
+    /// 当前调用使用的推理强度
+    reasoning: Option<ReasoningEffort>,
+    /// 当前调用固定使用的系统提示词版本
+    pinned_preamble: Option<Arc<str>>,
+    /// 本次重试调用中各次尝试的记录，开启 `distinct_agents` 时不再选择已失败的 agent
+    retry_context: Option<Arc<std::sync::Mutex<RetryContext>>>,
+    failure_dumper: Option<Arc<FailureDumper>>,
+    #[cfg(feature = "rig-extra-chaos")]
+    chaos: Option<crate::chaos::ChaosConfig>,
+}
+
+// 编译期检查代理池可以在线程间共享，所有字段都是 Send + Sync，无需 unsafe impl
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<RandAgent>();
+    assert_send_sync::<FallbackAgent>();
+    assert_send_sync::<AgentState>();
+};
+
+/// 线程安全的 Agent 状态
+#[derive(Clone)]
+pub struct AgentState {
+    pub id: i32,
+    pub agent: Arc<BoxAgent<'static>>,
+    pub info: AgentInfo,
+    /// 每次失败的时间，用于失败计数衰减
+    failure_times: VecDeque<Instant>,
+    /// 变为无效的时间，用于冷却恢复
+    invalid_since: Option<Instant>,
+    /// 半开状态下试探请求的开始时间
+    trial_started: Option<Instant>,
+    /// 速率限制窗口，未设置速率限制时为空
+    rate_window: Option<RateWindow>,
+    /// 缓存的工具使用规范，外层为空表示尚未生成
+    tool_policy: Option<Option<Arc<str>>>,
+}
+
+impl Prompt for RandAgent {
+    #[allow(refining_impl_trait)]
+    async fn prompt(&self, prompt: impl Into<Message> + Send) -> Result<String, PromptError> {
+        // 第一步：选择代理，请求期间不持有任何锁
+        let prompt = self.screen(prompt.into())?;
+        let cache_key = self.response_cache_key(&prompt);
+        self.with_response_cache(cache_key, async {
+            let (slot, _permit) = self.pick_slot().await.map_err(unavailable_error)?;
+            // 第二步：发起请求，结束后再更新计数
+            self.prompt_slot(&slot, "prompt", prompt).await
+        })
+        .await
+    }
+}
+
+impl Chat for RandAgent {
+    /// 携带历史记录的多轮对话，同样在代理池中负载均衡
+    #[allow(refining_impl_trait)]
+    async fn chat(
+        &self,
+        prompt: impl Into<Message> + Send,
+        chat_history: Vec<Message>,
+    ) -> Result<String, PromptError> {
+        let prompt = self.screen(prompt.into())?;
+        self.chat_screened("chat", prompt, chat_history).await
+    }
+}
+
+impl RandAgent {
+    /// 在已通过防火墙检查的 prompt 上发起多轮对话
+    async fn chat_screened(
+        &self,
+        method: &'static str,
+        prompt: Message,
+        chat_history: Vec<Message>,
+    ) -> Result<String, PromptError> {
+        let chat_history = match &self.tool_summarizer {
+            Some(summarizer) => summarizer.compact(chat_history).await,
+            None => chat_history,
+        };
+        let (slot, _permit) = self.pick_slot().await.map_err(unavailable_error)?;
+        let (agent_info, agent, span) = checkout(&slot, method);
+        let chat_history = match (&self.memory, agent_info.context_length) {
+            (Some(memory), Some(context_length)) => {
+                let budget = history_budget(context_length, &prompt);
+                memory.fit(chat_history, budget).await
+            }
+            _ => chat_history,
+        };
+        let agent = self.prepare_agent(&slot, &agent_info, agent, &span).await;
+        self.hooks.request_start(&agent_info);
+
+        let start = Instant::now();
+        let mut chat_history = chat_history;
+        let history = &mut chat_history;
+        let result = self
+            .call_agent(&agent_info, async move {
+                agent
+                    .prompt(prompt)
+                    .with_history(history)
+                    .extended_details()
+                    .await
+                    .map(|res| (res.output, res.total_usage))
+            })
+            .instrument(span.clone())
+            .await;
+        self.finish_request(&slot, &agent_info, result, start.elapsed(), &span)
+    }
+}
+
+impl AgentState {
+    pub(crate) fn new(
+        agent: BoxAgent<'static>,
+        id: i32,
+        provider: String,
+        model: String,
+        max_failures: u32,
+    ) -> Self {
+        let structured_output = StructuredOutput::detect(&provider, &model);
+        let context_length = known_context_length(&provider, &model);
+        Self {
+            id,
+            agent: Arc::new(agent),
+            info: AgentInfo {
+                id,
+                provider,
+                model,
+                failure_count: 0,
+                max_failures,
+                avg_latency: None,
+                input_price: None,
+                output_price: None,
+                disabled: false,
+                reserved_for: None,
+                structured_output,
+                context_length,
+            },
+            failure_times: VecDeque::new(),
+            invalid_since: None,
+            trial_started: None,
+            rate_window: None,
+            tool_policy: None,
+        }
+    }
+
+    /// 设置速率限制，两项都为空时取消限制
+    pub(crate) fn set_rate_limit(&mut self, limit: RateLimit) {
+        self.rate_window = limit.non_empty().map(RateWindow::new);
+    }
+
+    /// 当前的速率限制
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        self.rate_window.as_ref().map(RateWindow::limit)
+    }
+
+    /// 达到速率限制时返回需要等待的时间
+    pub(crate) fn rate_limited(&mut self) -> Option<Duration> {
+        self.rate_window
+            .as_mut()
+            .and_then(|window| window.retry_after(Instant::now()))
+    }
+
+    /// 被选中发起请求时调用
+    pub(crate) fn begin_request(&mut self) {
+        self.begin_trial();
+        if let Some(window) = &mut self.rate_window {
+            window.record_request(Instant::now());
+        }
+    }
+
+    pub(crate) fn is_valid(&self) -> bool {
+        self.info.failure_count < self.info.max_failures
+    }
+
+    /// 冷却结束后允许一次试探请求(半开状态)
+    pub(crate) fn is_available(&self, cooldown: Option<Duration>) -> bool {
+        if self.info.disabled {
+            return false;
+        }
+        if self.is_valid() {
+            return true;
+        }
+        let Some(cooldown) = cooldown else {
+            return false;
+        };
+        let cooled = self
+            .invalid_since
+            .is_some_and(|since| since.elapsed() >= cooldown);
+        // 试探请求没有回报结果时，再过一个冷却周期允许重新试探
+        let trial_free = self
+            .trial_started
+            .is_none_or(|started| started.elapsed() >= cooldown);
+        cooled && trial_free
+    }
+
+    /// 半开状态下被选中时记录试探开始
+    pub(crate) fn begin_trial(&mut self) {
+        if !self.is_valid() {
+            tracing::info!(
+                "agent 冷却结束，发送试探请求 provider: {}, model: {}, id: {}",
+                self.info.provider,
+                self.info.model,
+                self.info.id
+            );
+            self.trial_started = Some(Instant::now());
+        }
+    }
+
+    /// 请求被取消、没有结果时调用，不计入失败，半开状态下允许立即重新试探
+    pub(crate) fn cancel_trial(&mut self) {
+        self.trial_started = None;
+    }
+
+    pub(crate) fn record_failure(&mut self) {
+        self.info.failure_count += 1;
+        self.failure_times.push_back(Instant::now());
+        if !self.is_valid() {
+            // 变为无效或试探失败，重新开始冷却
+            self.invalid_since = Some(Instant::now());
+            self.trial_started = None;
+        }
+    }
+
+    pub(crate) fn record_success(&mut self) {
+        self.info.failure_count = 0;
+        self.failure_times.clear();
+        self.invalid_since = None;
+        self.trial_started = None;
+    }
+
+    /// 直接标记为无效
+    fn quarantine(&mut self) {
+        self.info.failure_count = self.info.max_failures;
+        self.invalid_since = Some(Instant::now());
+        self.trial_started = None;
+    }
+
+    /// 从快照恢复失败计数、停用状态和平均延迟
+    ///
+    /// 快照不保存失败时间，恢复的失败按当前时间计入衰减窗口，无效 agent 从当前时间重新开始冷却
+    pub(crate) fn restore(&mut self, snapshot: &AgentSnapshot) {
+        let now = Instant::now();
+        self.info.failure_count = snapshot.failure_count;
+        self.info.disabled = snapshot.disabled;
+        if let Some(ms) = snapshot.avg_latency_ms {
+            self.info.avg_latency = Some(Duration::from_millis(ms));
+        }
+        self.failure_times = std::iter::repeat_n(now, snapshot.failure_count as usize).collect();
+        self.invalid_since = (!self.is_valid()).then_some(now);
+        self.trial_started = None;
+    }
+
+    /// 以指数移动平均更新平均延迟
+    pub(crate) fn record_latency(&mut self, latency: Duration) {
+        self.info.avg_latency = Some(match self.info.avg_latency {
+            Some(avg) => {
+                avg.mul_f64(1.0 - LATENCY_EWMA_ALPHA) + latency.mul_f64(LATENCY_EWMA_ALPHA)
+            }
+            None => latency,
+        });
+    }
+
+    /// 早于 `window` 的失败不再计数
+    pub(crate) fn decay_failures(&mut self, window: Duration) {
+        let mut expired = 0;
+        while let Some(time) = self.failure_times.front() {
+            if time.elapsed() < window {
+                break;
+            }
+            self.failure_times.pop_front();
+            expired += 1;
+        }
+        self.info.failure_count = self.info.failure_count.saturating_sub(expired);
+    }
+}
+
+impl RandAgent {
+    /// 创建新的线程安全 RandAgent
+    pub fn new(agents: Vec<(BoxAgent<'static>, i32, String, String)>) -> Self {
+        Self::with_max_failures_and_callback(agents, 3, None)
+    }
+
+    /// 使用自定义最大失败次数和回调创建线程安全 RandAgent
+    pub fn with_max_failures_and_callback(
+        agents: Vec<(BoxAgent<'static>, i32, String, String)>,
+        max_failures: u32,
+        on_agent_invalid: OnAgentInvalidCallback,
+    ) -> Self {
+        let agent_states = agents
+            .into_iter()
+            .map(|(agent, id, provider, model)| {
+                AgentState::new(agent, id, provider, model, max_failures)
+            })
+            .collect();
+        Self::from_states(agent_states, on_agent_invalid)
+    }
+
+    /// 由 AgentState 创建 RandAgent
+    pub(crate) fn from_states(
+        agent_states: Vec<AgentState>,
+        on_agent_invalid: OnAgentInvalidCallback,
+    ) -> Self {
+        Self {
+            agents: Arc::new(RwLock::new(into_slots(agent_states))),
+            on_agent_invalid,
+            response_validator: None,
+            failure_decay: None,
+            selection_strategy: SelectionStrategy::default(),
+            retry_config: RetryConfig::default(),
+            cooldown: None,
+            circuit_breaker: None,
+            usage_stats: Arc::new(std::sync::Mutex::new(UsageStats::default())),
+            workload: None,
+            tenant: None,
+            tenants: Arc::new(std::sync::Mutex::new(TenantLedger::default())),
+            idempotency_ttl: DEFAULT_IDEMPOTENCY_TTL,
+            idempotency_cache: Arc::new(std::sync::Mutex::new(IdempotencyCache::default())),
+            extraction_cache_ttl: None,
+            extraction_cache: Arc::new(std::sync::Mutex::new(ExtractionCache::default())),
+            health: Arc::new(std::sync::Mutex::new(HealthReport::default())),
+            hooks: RequestHooks::default(),
+            progress: None,
+            tool_summarizer: None,
+            memory: None,
+            concurrency: Arc::new(ConcurrencyLimiter::default()),
+            shaper: Arc::new(RequestShaper::default()),
+            lifecycle: Arc::new(Lifecycle::default()),
+            preambles: Arc::new(std::sync::RwLock::new(PreambleVersions::default())),
+            prompt_templates: Arc::new(std::sync::RwLock::new(PromptTemplates::default())),
+            tool_usage_policy: false,
+            prompt_timeout: None,
+            response_cache: None,
+            firewall: None,
+            reasoning: None,
+            pinned_preamble: None,
+            retry_context: None,
+            failure_dumper: None,
+            #[cfg(feature = "rig-extra-chaos")]
+            chaos: None,
+        }
+    }
+
+    /// 使用自定义最大失败次数创建线程安全 RandAgent
+    pub fn with_max_failures(
+        agents: Vec<(BoxAgent<'static>, i32, String, String)>,
+        max_failures: u32,
+    ) -> Self {
+        Self::with_max_failures_and_callback(agents, max_failures, None)
+    }
+
+    /// 设置 agent 失效时的回调
+    pub fn set_on_agent_invalid<F>(&mut self, callback: F)
+    where
+        F: Fn(i32) + Send + Sync + 'static,
+    {
+        self.on_agent_invalid = Some(Arc::new(Box::new(callback)));
+    }
+
+    /// 设置响应校验器
+    pub fn set_response_validator(&mut self, validator: ResponseValidator) {
+        self.response_validator = Some(validator);
+    }
+
+    /// 设置失败计数衰减时间窗口，早于该窗口的失败不再计数
+    pub fn set_failure_decay(&mut self, window: Duration) {
+        self.failure_decay = Some(window);
+    }
+
+    /// 设置 agent 选择策略
+    pub fn set_selection_strategy(&mut self, strategy: SelectionStrategy) {
+        self.selection_strategy = strategy;
+    }
+
+    /// 设置失败重试配置
+    pub fn set_retry_config(&mut self, retry_config: RetryConfig) {
+        self.retry_config = retry_config;
+    }
+
+    /// 生成重试退避配置，`retry_num` 覆盖配置中的最大重试次数
+    fn backoff(&self, retry_num: Option<usize>) -> backon::ExponentialBuilder {
+        let backoff = self.retry_config.backoff();
+        match retry_num {
+            Some(retry_num) => backoff.with_max_times(retry_num),
+            None => backoff,
+        }
+    }
+
+    /// 设置无效 agent 的冷却时间，冷却结束后允许一次试探请求，成功则恢复
+    pub fn set_cooldown(&mut self, cooldown: Duration) {
+        self.cooldown = Some(cooldown);
+    }
+
+    /// 设置提供方熔断配置
+    pub fn set_circuit_breaker(&mut self, config: CircuitBreakerConfig) {
+        self.circuit_breaker = Some(Arc::new(std::sync::Mutex::new(CircuitBreaker::new(config))));
+    }
+
+    /// 设置幂等键结果的有效期，默认 10 分钟
+    pub fn set_idempotency_ttl(&mut self, ttl: Duration) {
+        self.idempotency_ttl = ttl;
+    }
+
+    /// 缓存 [`extract`](Self::extract) 和 [`prompt_structured`](Self::prompt_structured)
+    /// 解析成功的结果，有效期内相同类型、相同输入的请求不再调用模型
+    pub fn set_extraction_cache_ttl(&mut self, ttl: Duration) {
+        self.extraction_cache_ttl = Some(ttl);
+    }
+
+    /// 清空结构化提取结果缓存
+    pub fn clear_extraction_cache(&self) {
+        self.extraction_cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+
+    fn cached_extraction<T: DeserializeOwned>(&self, key: &ExtractionKey) -> Option<T> {
+        let ttl = self.extraction_cache_ttl?;
+        let json = self
+            .extraction_cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(key, ttl)?;
+        let value = structured::parse_json(&json).ok()?;
+        tracing::debug!("结构化提取命中缓存");
+        Some(value)
+    }
+
+    fn store_extraction(&self, key: ExtractionKey, json: String) {
+        if self.extraction_cache_ttl.is_some() {
+            self.extraction_cache
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(key, json);
+        }
+    }
+
+    /// 设置故障注入配置，仅用于测试故障转移和重试配置
+    #[cfg(feature = "rig-extra-chaos")]
+    pub fn set_chaos(&mut self, config: crate::chaos::ChaosConfig) {
+        self.chaos = Some(config);
+    }
+
+    /// 设置进度事件的心跳间隔，设置后可以通过 [`RandAgent::subscribe_progress`] 订阅
+    pub fn set_progress_interval(&mut self, interval: Duration) {
+        self.progress = Some(Arc::new(ProgressReporter::new(interval)));
+    }
+
+    /// 订阅非流式请求的进度事件，未设置心跳间隔时返回 None
+    pub fn subscribe_progress(&self) -> Option<broadcast::Receiver<ProgressEvent>> {
+        self.progress.as_ref().map(|progress| progress.subscribe())
+    }
+
+    /// 调用 agent，开启故障注入时按配置注入故障，设置了心跳间隔时发送进度事件
+    async fn call_agent<F>(&self, info: &AgentInfo, call: F) -> Result<(String, Usage), PromptError>
+    where
+        F: std::future::Future<Output = Result<(String, Usage), PromptError>>,
+    {
+        self.shaper.wait(&info.provider).await;
+        #[cfg(feature = "rig-extra-chaos")]
+        let call = async {
+            match &self.chaos {
+                Some(chaos) => chaos.inject(call).await,
+                None => call.await,
+            }
+        };
+        let call = with_timeout(self.prompt_timeout, call);
+        match &self.progress {
+            Some(progress) => progress.track(info, call).await,
+            None => call.await,
+        }
+    }
+
+    /// 按选择策略从可用代理中选出一个，返回其索引
+    ///
+    /// 处于熔断状态的提供方的 agent 会被跳过
+    ///
+    /// 所有可用 agent 都达到速率限制时返回 [`RandAgentError::RateLimited`]
+    fn select_index(&self, agents: &[AgentSlot]) -> Result<usize, RandAgentError> {
+        self.select_index_excluding(agents, &[])
+    }
+
+    /// 同 [`Self::select_index`]，并跳过指定 id 的 agent
+    fn select_index_excluding(
+        &self,
+        agents: &[AgentSlot],
+        excluded: &[i32],
+    ) -> Result<usize, RandAgentError> {
+        self.select_index_for(agents, excluded, false)
+    }
+
+    /// 同 [`Self::select_index_excluding`]，`streaming` 为 true 时同时考虑流式请求的并发上限
+    fn select_index_for(
+        &self,
+        agents: &[AgentSlot],
+        excluded: &[i32],
+        streaming: bool,
+    ) -> Result<usize, RandAgentError> {
+        // 逐个短暂加锁，收集可用 agent 的索引和平均延迟
+        let mut retry_after: Option<Duration> = None;
+        let mut candidates: Vec<(usize, Option<Duration>, bool)> = agents
+            .iter()
+            .enumerate()
+            .filter_map(|(i, slot)| {
+                let mut state = lock_slot(slot);
+                self.apply_decay(&mut state);
+                let available = state.is_available(self.cooldown)
+                    && self.can_use(&state)
+                    && !self.is_provider_open(&state.info.provider)
+                    && !self.has_failed_in_request(state.id)
+                    && !excluded.contains(&state.id);
+                if !available {
+                    return None;
+                }
+                if let Some(wait) = state.rate_limited() {
+                    retry_after = Some(retry_after.map_or(wait, |min| min.min(wait)));
+                    return None;
+                }
+                let has_capacity = self.concurrency.has_capacity(state.id)
+                    && (!streaming || self.concurrency.has_stream_capacity(state.id));
+                Some((i, state.info.avg_latency, has_capacity))
+            })
+            .collect();
+
+        if candidates.is_empty() {
+            return Err(match retry_after {
+                Some(retry_after) => RandAgentError::RateLimited { retry_after },
+                None => RandAgentError::NoValidAgents,
+            });
+        }
+
+        // 优先选择未达到并发上限的 agent，都已满时在选中的 agent 上排队
+        if candidates.iter().any(|&(_, _, has_capacity)| has_capacity) {
+            candidates.retain(|&(_, _, has_capacity)| has_capacity);
+        }
+
+        let mut rng = rand::rng();
+        if self.selection_strategy == SelectionStrategy::LeastLatency {
+            // 没有延迟数据的 agent 先被随机选中，避免一直选不到而无法测得延迟
+            let cold: Vec<usize> = candidates
+                .iter()
+                .filter(|(_, latency, _)| latency.is_none())
+                .map(|&(i, _, _)| i)
+                .collect();
+            if !cold.is_empty() {
+                return Ok(cold[rng.random_range(0..cold.len())]);
+            }
+            if let Some(&(index, _, _)) = candidates.iter().min_by_key(|(_, latency, _)| *latency) {
+                return Ok(index);
+            }
+        }
+
+        let random_index = rng.random_range(0..candidates.len());
+        Ok(candidates[random_index].0)
+    }
+
+    /// 关闭代理池: 不再接受新请求，停止健康检查、配置文件监视等后台任务，
+    /// 并在 `grace` 内等待进行中的请求(包括未读完的流)完成
+    ///
+    /// 所有请求都在宽限期内完成时返回 true。关闭对共享状态的所有视图
+    /// (`for_workload`、`for_tenant` 等)同时生效，之后的请求返回
+    /// [`RandAgentError::ShuttingDown`]
+    pub async fn shutdown(&self, grace: Duration) -> bool {
+        let drained = self.lifecycle.shutdown(grace).await;
+        if drained {
+            tracing::info!("代理池已关闭");
+        } else {
+            tracing::warn!(
+                in_flight = self.lifecycle.in_flight(),
+                "代理池关闭宽限期已到，仍有请求未完成"
+            );
+        }
+        drained
+    }
+
+    /// 是否已调用 [`Self::shutdown`]
+    pub fn is_shutting_down(&self) -> bool {
+        self.lifecycle.is_closed()
+    }
+
+    /// 进行中的请求数
+    pub fn in_flight(&self) -> usize {
+        self.lifecycle.in_flight()
+    }
+
+    /// 登记后台任务，关闭代理池时一并停止
+    pub(crate) fn register_background_task(&self, task: tokio::task::AbortHandle) {
+        self.lifecycle.register(task);
+    }
+
+    /// 所有可用 agent 都达到速率限制时返回需要等待的时间
+    pub(crate) async fn rate_limit_wait(&self) -> Option<Duration> {
+        let agents = self.agents.read().await;
+        match self.select_index(&agents) {
+            Err(RandAgentError::RateLimited { retry_after }) => Some(retry_after),
+            _ => None,
+        }
+    }
+
+    fn has_failed_in_request(&self, id: i32) -> bool {
+        self.retry_context.as_ref().is_some_and(|context| {
+            context
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .excludes(id)
+        })
+    }
+
+    /// 是否按重试配置重试该错误，开启 `distinct_agents` 且所有 agent 都已失败过时不再重试
+    fn should_retry(&self, err: &PromptError) -> bool {
+        let exhausted = self
+            .retry_context
+            .as_ref()
+            .is_some_and(|context| context.lock().unwrap_or_else(|e| e.into_inner()).exhausted);
+        !exhausted && self.retry_config.should_retry(err)
+    }
+
+    /// 返回用于一次重试调用的 RandAgent
+    ///
+    /// 开启 `distinct_agents` 或失败导出时记录每次尝试，用于排除已失败的 agent 和导出上下文
+    fn for_retry(&self) -> Self {
+        let mut rand_agent = self.clone();
+        let dump_enabled = self
+            .failure_dumper
+            .as_ref()
+            .is_some_and(|dumper| dumper.is_enabled());
+        if self.retry_config.distinct_agents || dump_enabled {
+            rand_agent.retry_context = Some(Arc::new(std::sync::Mutex::new(RetryContext::new(
+                self.retry_config.distinct_agents,
+            ))));
+        }
+        rand_agent
+    }
+
+    /// 重试全部失败后导出本次调用的上下文
+    async fn dump_failure(&self, method: &str, request: &Message, err: &PromptError) {
+        let (Some(dumper), Some(context)) = (&self.failure_dumper, &self.retry_context) else {
+            return;
+        };
+        if !dumper.is_enabled() {
+            return;
+        }
+        let attempts =
+            std::mem::take(&mut context.lock().unwrap_or_else(|e| e.into_inner()).attempts);
+        let bundle = FailureDumper::bundle(method, request, attempts, &err.to_string());
+        match dumper.dump(&bundle).await {
+            Ok(path) => tracing::warn!("请求最终失败，上下文已导出到 {}", path.display()),
+            Err(err) => tracing::error!("导出失败上下文出错: {}", redacted(&err)),
+        }
+    }
+
+    /// 运行时开启或关闭失败上下文导出，需要先通过构建器设置导出目录
+    pub fn set_failure_dump_enabled(&self, enabled: bool) {
+        if let Some(dumper) = &self.failure_dumper {
+            dumper.set_enabled(enabled);
+        }
+    }
+
+    /// 预留给其它工作负载的 agent 不可使用
+    fn can_use(&self, state: &AgentState) -> bool {
+        state
+            .info
+            .reserved_for
+            .as_deref()
+            .is_none_or(|reserved| self.workload.as_deref() == Some(reserved))
+    }
+
+    /// 返回指定工作负载使用的 RandAgent，与原代理池共享状态
+    ///
+    /// 可以使用预留给该工作负载的 agent 和未预留的 agent；
+    /// 不指定工作负载的调用(如批处理任务)永远不会选中预留的 agent
+    ///
+    /// ```rust,ignore
+    /// pool.reserve_agent(1, "interactive").await;
+    /// let interactive = pool.for_workload("interactive");
+    /// let response = interactive.prompt("你好").await?;
+    /// ```
+    pub fn for_workload(&self, workload: impl Into<String>) -> Self {
+        let mut rand_agent = self.clone();
+        rand_agent.workload = Some(Arc::from(workload.into()));
+        rand_agent
+    }
+
+    /// 返回指定租户使用的 RandAgent，与原代理池共享 agent
+    ///
+    /// 通过该视图发起的请求额外记录到租户的用量和审计日志中，并受租户额度限制，
+    /// 见 [`crate::tenant`]
+    pub fn for_tenant(&self, tenant: impl Into<String>) -> Self {
+        let mut rand_agent = self.clone();
+        rand_agent.tenant = Some(Arc::from(tenant.into()));
+        rand_agent
+    }
+
+    /// 设置租户额度，覆盖之前的设置
+    pub async fn set_tenant_limits(&self, tenant: &str, limits: TenantLimits) {
+        self.lock_tenants().set_limits(tenant, limits);
+    }
+
+    /// 获取租户的 token 用量
+    pub async fn tenant_usage(&self, tenant: &str) -> UsageStats {
+        self.lock_tenants().usage(tenant)
+    }
+
+    /// 获取租户最近的请求记录
+    pub async fn tenant_audit_log(&self, tenant: &str) -> Vec<TenantAuditEntry> {
+        self.lock_tenants().audit_log(tenant)
+    }
+
+    /// 清空租户的用量和审计日志，额度设置保持不变
+    pub async fn reset_tenant_usage(&self, tenant: &str) {
+        self.lock_tenants().reset(tenant);
+    }
+
+    /// 有用量记录或额度设置的租户
+    pub async fn tenants(&self) -> Vec<String> {
+        self.lock_tenants().tenants()
+    }
+
+    fn lock_tenants(&self) -> std::sync::MutexGuard<'_, TenantLedger> {
+        self.tenants.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 检查当前租户是否超出额度，并占用租户的并发名额直到请求结束
+    fn enter_tenant(&self) -> Result<Option<TenantPermit>, TenantLimitExceeded> {
+        self.tenant
+            .as_ref()
+            .map(|tenant| TenantLedger::enter(&self.tenants, tenant))
+            .transpose()
+    }
+
+    /// 注册或覆盖一个命名的系统提示词版本
+    pub fn register_preamble(&self, name: impl Into<String>, preamble: impl Into<String>) {
+        self.preambles
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .register(name.into(), preamble.into());
+    }
+
+    /// 切换代理池的生效系统提示词版本，版本不存在时返回 false
+    pub fn set_active_preamble(&self, name: &str) -> bool {
+        self.preambles
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .set_active(name)
+    }
+
+    /// 取消生效版本，恢复使用各 agent 构建时的系统提示词
+    pub fn clear_active_preamble(&self) {
+        self.preambles
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear_active();
+    }
+
+    /// 当前生效的系统提示词版本
+    pub fn active_preamble(&self) -> Option<String> {
+        self.preambles
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .active()
+            .map(str::to_string)
+    }
+
+    /// 携带会话历史提问，成功后把本轮提问和回答记录到会话中
+    ///
+    /// 需要先通过 [`RandAgentBuilder::memory`] 设置对话记忆，见 [`crate::memory`]
+    pub async fn chat_session(
+        &self,
+        session: &str,
+        prompt: impl Into<Message> + Send,
+    ) -> Result<String, PromptError> {
+        let Some(memory) = &self.memory else {
+            return Err(PromptError::CompletionError(CompletionError::RequestError(
+                "未设置对话记忆，见 RandAgentBuilder::memory".into(),
+            )));
+        };
+        let prompt = self.screen(prompt.into())?;
+        let history = memory.history(session).await;
+        let response = self
+            .chat_screened("chat_session", prompt.clone(), history)
+            .await?;
+        memory.record(session, prompt, &response).await;
+        Ok(response)
+    }
+
+    /// 会话保存的全部历史记录，未设置对话记忆时为空
+    pub async fn session_history(&self, session: &str) -> Vec<Message> {
+        match &self.memory {
+            Some(memory) => memory.messages(session).await,
+            None => Vec::new(),
+        }
+    }
+
+    /// 清空会话的历史记录
+    pub async fn clear_session(&self, session: &str) {
+        if let Some(memory) = &self.memory {
+            memory.clear(session).await;
+        }
+    }
+
+    /// 注册或覆盖一个命名的提示词模板，见 [`crate::prompt_templates`]
+    pub fn register_prompt_template(&self, name: impl Into<String>, template: &str) {
+        self.prompt_templates
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .register(name, template);
+    }
+
+    /// 使用命名的提示词模板提问
+    ///
+    /// 模板不存在或缺少变量时返回错误，不会发起请求
+    pub async fn prompt_template<K, V>(
+        &self,
+        name: &str,
+        vars: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<String, PromptError>
+    where
+        K: Into<String>,
+        V: ToString,
+    {
+        let prompt = self
+            .prompt_templates
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .render(name, vars)
+            .map_err(|err| {
+                PromptError::CompletionError(CompletionError::RequestError(Box::new(err)))
+            })?;
+        self.prompt(prompt).await
+    }
+
+    /// 返回固定使用指定系统提示词版本的 RandAgent，与原代理池共享状态
+    pub fn with_preamble_version(&self, name: impl Into<String>) -> Self {
+        let mut rand_agent = self.clone();
+        rand_agent.pinned_preamble = Some(Arc::from(name.into()));
+        rand_agent
+    }
+
+    /// 返回使用指定推理强度的 RandAgent，与原代理池共享状态
+    ///
+    /// 不支持该参数的提供方或模型忽略此设置，见 [`crate::reasoning`]
+    pub fn with_reasoning(&self, effort: ReasoningEffort) -> Self {
+        let mut rand_agent = self.clone();
+        rand_agent.reasoning = Some(effort);
+        rand_agent
+    }
+
+    /// 按代理池的设置调整选中的 agent: 系统提示词版本、工具使用规范和推理强度
+    async fn prepare_agent(
+        &self,
+        slot: &AgentSlot,
+        info: &AgentInfo,
+        agent: Arc<BoxAgent<'static>>,
+        span: &Span,
+    ) -> Arc<BoxAgent<'static>> {
+        let agent = self.apply_preamble(agent, span);
+        let agent = self.apply_tool_policy(slot, agent).await;
+        self.apply_reasoning(info, agent)
+    }
+
+    fn apply_reasoning(
+        &self,
+        info: &AgentInfo,
+        agent: Arc<BoxAgent<'static>>,
+    ) -> Arc<BoxAgent<'static>> {
+        let Some(effort) = self.reasoning else {
+            return agent;
+        };
+        let Some(params) = effort.params(&info.provider, &info.model) else {
+            return agent;
+        };
+        let mut agent = (*agent).clone();
+        if info.provider.eq_ignore_ascii_case("anthropic") {
+            // Anthropic 要求 max_tokens 大于思考预算，额外留出回答的空间
+            let min_tokens = effort.budget_tokens() + 4096;
+            agent.max_tokens = Some(
+                agent
+                    .max_tokens
+                    .map_or(min_tokens, |max| max.max(min_tokens)),
+            );
+        }
+        agent.additional_params = Some(match agent.additional_params.take() {
+            Some(existing) => json_utils::merge(existing, params),
+            None => params,
+        });
+        Arc::new(agent)
+    }
+
+    /// 按固定版本或生效版本替换 agent 的系统提示词，并记录到请求 span
+    fn apply_preamble(&self, agent: Arc<BoxAgent<'static>>, span: &Span) -> Arc<BoxAgent<'static>> {
+        let preambles = self.preambles.read().unwrap_or_else(|e| e.into_inner());
+        let Some((name, preamble)) = preambles.resolve(self.pinned_preamble.as_deref()) else {
+            return agent;
+        };
+        span.record("preamble_version", name);
+        let mut agent = (*agent).clone();
+        set_preamble(&mut agent, preamble.to_string());
+        Arc::new(agent)
+    }
+
+    /// 开启工具使用规范时，在系统提示词末尾追加根据 agent 的工具生成的规范
+    async fn apply_tool_policy(
+        &self,
+        slot: &AgentSlot,
+        agent: Arc<BoxAgent<'static>>,
+    ) -> Arc<BoxAgent<'static>> {
+        if !self.tool_usage_policy {
+            return agent;
+        }
+        let cached = lock_slot(slot).tool_policy.clone();
+        let policy = match cached {
+            Some(policy) => policy,
+            None => {
+                let definitions = match agent.tool_server_handle.get_tool_defs(None).await {
+                    Ok(definitions) => definitions,
+                    Err(err) => {
+                        tracing::warn!(
+                            "获取工具定义失败，本次请求不追加工具使用规范: {}",
+                            redacted(&err)
+                        );
+                        return agent;
+                    }
+                };
+                let policy = tool_usage_policy(&definitions).map(Arc::from);
+                lock_slot(slot).tool_policy = Some(policy.clone());
+                policy
+            }
+        };
+        let Some(policy) = policy else {
+            return agent;
+        };
+        let mut agent = (*agent).clone();
+        let preamble = match agent.preamble.take() {
+            Some(preamble) => format!("{preamble}\n\n{policy}"),
+            None => policy.to_string(),
+        };
+        set_preamble(&mut agent, preamble);
+        Arc::new(agent)
+    }
+
+    /// 将 agent 预留给指定工作负载，返回是否找到该代理
+    pub async fn reserve_agent(&self, id: i32, workload: impl Into<String>) -> bool {
+        self.set_reserved(id, Some(workload.into())).await
+    }
+
+    /// 取消 agent 的预留，返回是否找到该代理
+    pub async fn unreserve_agent(&self, id: i32) -> bool {
+        self.set_reserved(id, None).await
+    }
+
+    async fn set_reserved(&self, id: i32, workload: Option<String>) -> bool {
+        let agents = self.agents.read().await;
+        let mut found = false;
+        for slot in agents.iter() {
+            let mut state = lock_slot(slot);
+            if state.id == id {
+                state.info.reserved_for = workload.clone();
+                found = true;
+            }
+        }
+        found
+    }
+
+    /// 提供方是否处于熔断状态
+    fn is_provider_open(&self, provider: &str) -> bool {
+        self.circuit_breaker.as_ref().is_some_and(|breaker| {
+            breaker
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .is_open(provider)
+        })
+    }
+
+    /// 按衰减窗口清理过期的失败计数
+    fn apply_decay(&self, state: &mut AgentState) {
+        if let Some(window) = self.failure_decay {
+            state.decay_failures(window);
+        }
+    }
+
+    /// 校验响应并更新 agent 的失败计数
+    ///
+    /// 用量和 agent 状态在同一次加锁中更新，快照不会看到只更新了一半的请求；
+    /// 用户回调在释放锁之后执行，回调中访问代理池不会死锁
+    #[allow(clippy::result_large_err)] // 与 rig 的 Prompt 接口一致，直接返回 PromptError
+    fn handle_result(
+        &self,
+        slot: &AgentSlot,
+        info: &AgentInfo,
+        usage: Option<&Usage>,
+        result: Result<String, PromptError>,
+        latency: Duration,
+        span: &Span,
+    ) -> Result<String, PromptError> {
+        let (result, invalidated, info) = {
+            let mut state = lock_slot(slot);
+            if let Some(usage) = usage {
+                self.add_usage(&mut state, info, usage);
+            }
+            let (result, invalidated) = record_result(
+                &mut state,
+                result,
+                latency,
+                self.response_validator.as_ref(),
+            );
+            (result, invalidated, state.info.clone())
+        };
+        if invalidated && let Some(cb) = &self.on_agent_invalid {
+            cb(info.id);
+        }
+        spans::record_result(span, latency, &result);
+        if let Some(context) = &self.retry_context {
+            context
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .record(&info, &result, latency);
+        }
+        self.hooks.result(&info, &result, latency);
+        if let Some(tenant) = &self.tenant {
+            self.lock_tenants()
+                .record_result(tenant, &info, &result, latency);
+        }
+        if let Some(breaker) = &self.circuit_breaker {
+            let mut breaker = breaker.lock().unwrap_or_else(|e| e.into_inner());
+            match &result {
+                Ok(_) => breaker.record_success(&info.provider),
+                Err(_) => breaker.record_failure(&info.provider),
+            }
+        }
+        result
+    }
+
+    /// 记录一次带用量的请求结果
+    #[allow(clippy::result_large_err)] // 与 rig 的 Prompt 接口一致，直接返回 PromptError
+    fn finish_request(
+        &self,
+        slot: &AgentSlot,
+        info: &AgentInfo,
+        result: Result<(String, Usage), PromptError>,
+        latency: Duration,
+        span: &Span,
+    ) -> Result<String, PromptError> {
+        let (result, usage) = match result {
+            Ok((content, usage)) => (Ok(content), Some(usage)),
+            Err(err) => (Err(err), None),
+        };
+        self.handle_result(slot, info, usage.as_ref(), result, latency, span)
+    }
+
+    /// 累计 token 用量和费用，调用方需持有 agent 的锁
+    fn add_usage(&self, state: &mut AgentState, info: &AgentInfo, usage: &Usage) {
+        if let Some(window) = &mut state.rate_window {
+            window.record_tokens(Instant::now(), usage.total_tokens);
+        }
+        let cost = usage_cost(usage, info.input_price, info.output_price);
+        self.usage_stats
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .record(info.id, usage, cost);
+        if let Some(tenant) = &self.tenant {
+            self.lock_tenants()
+                .record_usage(tenant, info.id, usage, cost);
+        }
+    }
+
+    /// 获取所有 agent 的累计费用
+    pub async fn total_cost(&self) -> f64 {
+        self.usage_stats
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .total
+            .cost
+    }
+
+    /// 设置 agent 的每千 token 价格，之后的请求按新价格计费
+    pub async fn set_agent_price(&self, id: i32, input_price: f64, output_price: f64) {
+        let agents = self.agents.read().await;
+        for slot in agents.iter() {
+            let mut state = lock_slot(slot);
+            if state.id == id {
+                state.info.input_price = Some(input_price);
+                state.info.output_price = Some(output_price);
+            }
+        }
+    }
+
+    /// 设置 agent 的上下文长度，用于历史记录超长时自动摘要，返回是否找到该代理
+    pub async fn set_context_length(&self, id: i32, context_length: u32) -> bool {
+        let agents = self.agents.read().await;
+        let mut found = false;
+        for slot in agents.iter() {
+            let mut state = lock_slot(slot);
+            if state.id == id {
+                state.info.context_length = Some(context_length);
+                found = true;
+            }
+        }
+        found
+    }
+
+    /// 从 OpenRouter 模型列表中为未设置上下文长度的 openrouter agent 填充上下文长度
+    pub async fn apply_openrouter_context_lengths(&self, models: &[crate::Model]) {
+        let agents = self.agents.read().await;
+        for slot in agents.iter() {
+            let mut state = lock_slot(slot);
+            if !state.info.provider.eq_ignore_ascii_case("openrouter")
+                || state.info.context_length.is_some()
+            {
+                continue;
+            }
+            if let Some(model) = models.iter().find(|model| model.slug == state.info.model) {
+                let context_length = model
+                    .endpoint
+                    .as_ref()
+                    .map_or(model.context_length, |endpoint| endpoint.context_length);
+                state.info.context_length =
+                    u32::try_from(context_length).ok().filter(|&len| len > 0);
+            }
+        }
+    }
+
+    /// 从 OpenRouter 模型列表中为未设置价格的 openrouter agent 填充价格
+    pub async fn apply_openrouter_prices(&self, models: &[crate::Model]) {
+        let agents = self.agents.read().await;
+        for slot in agents.iter() {
+            let mut state = lock_slot(slot);
+            if !state.info.provider.eq_ignore_ascii_case("openrouter")
+                || state.info.input_price.is_some()
+                || state.info.output_price.is_some()
+            {
+                continue;
+            }
+            if let Some((input_price, output_price)) = models
+                .iter()
+                .find(|model| model.slug == state.info.model)
+                .and_then(|model| model.price_per_1k())
+            {
+                state.info.input_price = Some(input_price);
+                state.info.output_price = Some(output_price);
+            }
+        }
+    }
+
+    /// 获取各 agent 及合计的 token 用量
+    pub async fn usage_stats(&self) -> UsageStats {
+        self.usage_stats
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// 一次性获取各 agent 的健康状态和 token 用量统计
+    ///
+    /// 获取期间同时持有所有 agent 的锁和用量统计的锁，得到的是同一时刻的一致视图，
+    /// 适合用于监控面板和告警；分别调用 [`Self::get_agents_info`]、[`Self::failure_stats`]
+    /// 和 [`Self::usage_stats`] 可能看到进行到一半的更新。也可用于进程重启后恢复
+    pub async fn snapshot(&self) -> RandAgentSnapshot {
+        let agents = self.agents.read().await;
+        // 加锁顺序与请求结束时一致: 先 agent 后用量统计
+        let mut states: Vec<_> = agents.iter().map(lock_slot).collect();
+        let usage = self
+            .usage_stats
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let agents = states
+            .iter_mut()
+            .map(|state| {
+                self.apply_decay(state);
+                AgentSnapshot::from(&state.info)
+            })
+            .collect();
+        RandAgentSnapshot::new(agents, usage)
+    }
+
+    /// 清空 token 用量统计
+    pub async fn reset_usage_stats(&self) {
+        *self.usage_stats.lock().unwrap_or_else(|e| e.into_inner()) = UsageStats::default();
+    }
+
+    /// 流式提问并读取完整内容，超时后返回已生成的部分内容，见 [`collect_with_timeout`]
+    pub async fn stream_prompt_collect(
+        &self,
+        prompt: impl Into<Message> + Send,
+        timeout: Duration,
+    ) -> Result<TeeOutput, RandAgentError> {
+        let stream = self.stream_prompt(prompt).await?;
+        Ok(collect_with_timeout(stream, timeout).await)
+    }
+
+    /// 流式提问
+    ///
+    /// 随机选择一个有效 agent 发起流式请求。流中出现错误时计为该 agent 失败，
+    /// 正常结束时计为成功(同样会经过响应校验)，调用方提前丢弃流时不计数
+    pub async fn stream_prompt(
+        &self,
+        prompt: impl Into<Message> + Send,
+    ) -> Result<DynStream, RandAgentError> {
+        let prompt = self.screen(prompt.into())?;
+        self.open_stream("stream_prompt", move |agent| {
+            Box::pin(async move { agent.stream_dyn(prompt).await })
+        })
+        .await
+        .map(|stream| stream.boxed())
+    }
+
+    /// 流式提问，并在流中输出工具调用的开始和结束事件
+    ///
+    /// 最多进行 `max_turns` 轮工具调用，统计规则与 [`stream_prompt`](Self::stream_prompt) 相同
+    pub async fn stream_prompt_with_tool_events(
+        &self,
+        prompt: impl Into<Message> + Send,
+        max_turns: usize,
+    ) -> Result<ToolEventStream, RandAgentError> {
+        let prompt = self.screen(prompt.into())?;
+        self.open_stream("stream_prompt_with_tool_events", move |agent| {
+            Box::pin(async move { Ok(stream_with_tool_events(agent, prompt, max_turns)) })
+        })
+        .await
+        .map(|stream| stream.boxed())
+    }
+
+    /// 选择 agent 并建立流式请求，流结束时记录结果
+    async fn open_stream<S>(
+        &self,
+        method: &'static str,
+        open: impl FnOnce(Arc<BoxAgent<'static>>) -> BoxFuture<'static, Result<S, RandAgentError>>,
+    ) -> Result<StreamTee<S>, RandAgentError>
+    where
+        S: futures::Stream + Unpin,
+        S::Item: TeeItem,
+    {
+        let (slot, permit) = self.pick_stream_slot().await?;
+        let (agent_info, agent, span) = checkout(&slot, method);
+        let agent = self.prepare_agent(&slot, &agent_info, agent, &span).await;
+        self.hooks.request_start(&agent_info);
+
+        self.shaper.wait(&agent_info.provider).await;
+        let start = Instant::now();
+        let stream = match open(agent).await {
+            Ok(stream) => stream,
+            Err(err) => {
+                let result = Err(PromptError::CompletionError(
+                    CompletionError::ResponseError(err.to_string()),
+                ));
+                let _ =
+                    self.handle_result(&slot, &agent_info, None, result, start.elapsed(), &span);
+                return Err(err);
+            }
+        };
+
+        let rand_agent = self.clone();
+        let stream = StreamTee::new(stream, move |output: TeeOutput| {
+            // 流结束或被丢弃时才释放并发名额
+            let _permit = permit;
+            let result = match output.error {
+                Some(err) => Err(PromptError::CompletionError(
+                    CompletionError::ResponseError(err),
+                )),
+                None if output.complete => Ok(output.text),
+                // 调用方提前结束，只累计用量，不计数
+                None => {
+                    if let Some(usage) = &output.usage {
+                        rand_agent.add_usage(&mut lock_slot(&slot), &agent_info, usage);
+                    }
+                    return;
+                }
+            };
+            let _ = rand_agent.handle_result(
+                &slot,
+                &agent_info,
+                output.usage.as_ref(),
+                result,
+                start.elapsed(),
+                &span,
+            );
+        });
+        Ok(stream)
+    }
+
+    /// 添加代理到集合中
+    pub async fn add_agent(
+        &self,
+        agent: BoxAgent<'static>,
+        id: i32,
+        provider: String,
+        model: String,
+    ) {
+        let mut agents = self.agents.write().await;
+        agents.push(Arc::new(std::sync::Mutex::new(AgentState::new(
+            agent, id, provider, model, 3,
+        ))));
+    }
+
+    /// 使用自定义最大失败次数添加代理
+    pub async fn add_agent_with_max_failures(
+        &self,
+        agent: BoxAgent<'static>,
+        id: i32,
+        provider: String,
+        model: String,
+        max_failures: u32,
+    ) {
+        let mut agents = self.agents.write().await;
+        agents.push(Arc::new(std::sync::Mutex::new(AgentState::new(
+            agent,
+            id,
+            provider,
+            model,
+            max_failures,
+        ))));
+    }
+
+    /// 按 id 移除代理，返回是否有代理被移除
+    ///
+    /// 正在进行的请求不受影响，结束后其结果不再计入代理池
+    pub async fn remove_agent_by_id(&self, id: i32) -> bool {
+        let mut agents = self.agents.write().await;
+        let before = agents.len();
+        agents.retain(|slot| lock_slot(slot).id != id);
+        agents.len() != before
+    }
+
+    /// 替换指定 id 的代理，用于轮换 api key 或模型，返回是否找到该代理
+    ///
+    /// 保留原有的 provider、model、最大失败次数和价格，失败计数和延迟统计重新开始
+    pub async fn replace_agent(&self, id: i32, agent: BoxAgent<'static>) -> bool {
+        let mut agents = self.agents.write().await;
+        let Some(slot) = agents.iter_mut().find(|slot| lock_slot(slot).id == id) else {
+            return false;
+        };
+        let (old, rate_window) = {
+            let old = lock_slot(slot);
+            (old.info.clone(), old.rate_window.clone())
+        };
+        let mut state = AgentState::new(agent, id, old.provider, old.model, old.max_failures);
+        state.rate_window = rate_window;
+        state.info.input_price = old.input_price;
+        state.info.output_price = old.output_price;
+        state.info.disabled = old.disabled;
+        state.info.reserved_for = old.reserved_for;
+        state.info.structured_output = old.structured_output;
+        state.info.context_length = old.context_length;
+        *slot = Arc::new(std::sync::Mutex::new(state));
+        true
+    }
+
+    /// 停用指定 id 的代理，停用后不会被选中，返回是否找到该代理
+    pub async fn disable_agent(&self, id: i32) -> bool {
+        self.set_disabled(id, true).await
+    }
+
+    /// 重新启用指定 id 的代理，返回是否找到该代理
+    pub async fn enable_agent(&self, id: i32) -> bool {
+        self.set_disabled(id, false).await
+    }
+
+    /// 设置 agent 的每分钟请求数和 token 数上限，两项都为空时取消限制，返回是否找到该代理
+    pub async fn set_rate_limit(&self, id: i32, limit: RateLimit) -> bool {
+        let agents = self.agents.read().await;
+        let mut found = false;
+        for slot in agents.iter() {
+            let mut state = lock_slot(slot);
+            if state.id == id {
+                state.set_rate_limit(limit);
+                found = true;
+            }
+        }
+        found
+    }
+
+    /// 设置发往指定提供方的请求平滑，为空时取消，见 [`crate::shaping`]
+    pub fn set_provider_shaping(&self, provider: &str, shaping: Option<RequestShaping>) {
+        self.shaper.set(provider, shaping);
+    }
+
+    async fn set_disabled(&self, id: i32, disabled: bool) -> bool {
+        let agents = self.agents.read().await;
+        let mut found = false;
+        for slot in agents.iter() {
+            let mut state = lock_slot(slot);
+            if state.id == id {
+                state.info.disabled = disabled;
+                found = true;
+            }
+        }
+        found
+    }
+
+    /// 原子替换整个代理池，返回按 id 比较的新增和移除的代理
+    ///
+    /// id 相同的代理保留手动停用状态，失败计数和延迟统计重新开始
+    #[cfg(feature = "rig-extra-config-watch")]
+    pub(crate) async fn swap_agents(
+        &self,
+        mut states: Vec<AgentState>,
+    ) -> crate::config_watch::PoolChange {
+        let mut agents = self.agents.write().await;
+        let old: Vec<AgentInfo> = agents
+            .iter()
+            .map(|slot| lock_slot(slot).info.clone())
+            .collect();
+        let mut change = crate::config_watch::PoolChange::default();
+        for state in states.iter_mut() {
+            match old.iter().find(|info| info.id == state.id) {
+                Some(info) => state.info.disabled = info.disabled,
+                None => change.added.push(state.info.clone()),
+            }
+        }
+        change.removed = old
+            .into_iter()
+            .filter(|info| states.iter().all(|state| state.id != info.id))
+            .collect();
+
+        *agents = into_slots(states);
+        change
+    }
+
+    /// 监视配置文件，修改后自动重新加载代理池
+    #[cfg(feature = "rig-extra-config-watch")]
+    pub fn watch_config(
+        &self,
+        path: impl Into<std::path::PathBuf>,
+    ) -> crate::config_watch::ConfigWatcher {
+        crate::config_watch::ConfigWatcher::new(self.clone(), path.into())
+    }
+
+    /// 获取有效代理数量
+    pub async fn len(&self) -> usize {
+        let agents = self.agents.read().await;
+        agents
+            .iter()
+            .filter(|slot| {
+                let mut state = lock_slot(slot);
+                self.apply_decay(&mut state);
+                !state.info.disabled && state.is_valid()
+            })
+            .count()
+    }
+
+    /// 选择一个可用代理并返回其状态槽
+    ///
+    /// 返回前即释放代理集合的锁，请求期间其它调用和增删代理都不会被阻塞
+    ///
+    /// 设置了并发上限时先等待名额，返回的名额在请求结束后丢弃
+    async fn pick_slot(&self) -> Result<(AgentSlot, RequestPermit), RandAgentError> {
+        self.pick_slot_excluding(&[]).await
+    }
+
+    /// 同 [`Self::pick_slot`]，并跳过指定 id 的 agent
+    async fn pick_slot_excluding(
+        &self,
+        excluded: &[i32],
+    ) -> Result<(AgentSlot, RequestPermit), RandAgentError> {
+        self.pick_slot_for(excluded, false).await
+    }
+
+    /// 为流式请求选择 agent，返回的名额同时占用该 agent 的流式请求名额
+    async fn pick_stream_slot(&self) -> Result<(AgentSlot, RequestPermit), RandAgentError> {
+        self.pick_slot_for(&[], true).await
+    }
+
+    async fn pick_slot_for(
+        &self,
+        excluded: &[i32],
+        streaming: bool,
+    ) -> Result<(AgentSlot, RequestPermit), RandAgentError> {
+        let in_flight = self.lifecycle.enter().ok_or(RandAgentError::ShuttingDown)?;
+        let tenant = self.enter_tenant()?;
+        let global = self.concurrency.acquire_global().await;
+        let slot = {
+            let agents = self.agents.read().await;
+            let agent_index = self
+                .select_index_for(&agents, excluded, streaming)
+                .inspect_err(|err| {
+                    if let (RandAgentError::NoValidAgents, Some(context)) =
+                        (err, &self.retry_context)
+                    {
+                        context
+                            .lock()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .mark_exhausted();
+                    }
+                })?;
+            agents[agent_index].clone()
+        };
+        let id = {
+            let mut state = lock_slot(&slot);
+            state.begin_request();
+            state.id
+        };
+        let mut permit = self.concurrency.acquire_agent(id, global).await;
+        if streaming {
+            permit = self.concurrency.acquire_stream(id, permit).await;
+        }
+        Ok((slot, permit.with_in_flight(in_flight).with_tenant(tenant)))
+    }
+
+    /// 在指定 agent 上发起 prompt 请求，并更新用量和失败计数
+    async fn prompt_slot(
+        &self,
+        slot: &AgentSlot,
+        method: &'static str,
+        prompt: Message,
+    ) -> Result<String, PromptError> {
+        self.prompt_slot_with(slot, method, prompt, |_, agent| agent)
+            .await
+    }
+
+    /// 同 [`Self::prompt_slot`]，发送前可按选中 agent 的信息调整 agent
+    async fn prompt_slot_with(
+        &self,
+        slot: &AgentSlot,
+        method: &'static str,
+        prompt: Message,
+        prepare: impl FnOnce(&AgentInfo, Arc<BoxAgent<'static>>) -> Arc<BoxAgent<'static>>,
+    ) -> Result<String, PromptError> {
+        let (agent_info, agent, span) = checkout(slot, method);
+        let agent = self.prepare_agent(slot, &agent_info, agent, &span).await;
+        let agent = prepare(&agent_info, agent);
+        self.hooks.request_start(&agent_info);
+
+        let start = Instant::now();
+        let result = self
+            .call_agent(&agent_info, async move {
+                agent
+                    .prompt(prompt)
+                    .extended_details()
+                    .await
+                    .map(|res| (res.output, res.total_usage))
+            })
+            .instrument(span.clone())
+            .await;
+        self.finish_request(slot, &agent_info, result, start.elapsed(), &span)
+    }
+
+    /// 响应缓存的键，未设置缓存或 prompt 包含非文本内容时为空
+    fn response_cache_key(&self, prompt: &Message) -> Option<CacheKey> {
+        self.response_cache.as_ref()?;
+        let preambles = self.preambles.read().unwrap_or_else(|e| e.into_inner());
+        let preamble = preambles
+            .resolve(self.pinned_preamble.as_deref())
+            .map(|(_, preamble)| preamble);
+        CacheKey::new(prompt, context_hash(preamble, self.reasoning))
+    }
+
+    /// 在选择 agent 之前查询响应缓存，命中时直接返回，不占用 agent 的并发名额和速率额度；
+    /// 未命中时发起请求，成功的响应写入缓存
+    async fn with_response_cache(
+        &self,
+        key: Option<CacheKey>,
+        request: impl std::future::Future<Output = Result<String, PromptError>>,
+    ) -> Result<String, PromptError> {
+        let (Some(cache), Some(key)) = (&self.response_cache, key) else {
+            return request.await;
+        };
+        if self.is_shutting_down() {
+            return Err(unavailable_error(RandAgentError::ShuttingDown));
+        }
+        if let Some(content) = cache.get(&key).await {
+            tracing::debug!("命中响应缓存");
+            return Ok(content);
+        }
+        let content = request.await?;
+        cache.put(key, content.clone()).await;
+        Ok(content)
+    }
+
+    /// 经过 prompt 防火墙检查，被拦截的请求不会选择 agent，也不计入失败
+    #[allow(clippy::result_large_err)] // 与 rig 的 Prompt 接口一致，直接返回 PromptError
+    fn screen(&self, prompt: Message) -> Result<Message, PromptError> {
+        match &self.firewall {
+            Some(firewall) => firewall.check_message(prompt).map_err(blocked_error),
+            None => Ok(prompt),
+        }
+    }
+
+    /// 当前可用且未达到速率限制的 agent
+    async fn available_slots(&self) -> Vec<AgentSlot> {
+        let agents = self.agents.read().await;
+        agents
+            .iter()
+            .filter(|slot| {
+                let mut state = lock_slot(slot);
+                self.apply_decay(&mut state);
+                state.is_available(self.cooldown)
+                    && self.can_use(&state)
+                    && state.rate_limited().is_none()
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// 在指定 agent 上开始请求并等待并发名额
+    ///
+    /// 与 [`Self::pick_slot_for`] 相同，依次检查代理池是否关闭、租户额度和速率限制，
+    /// 速率限制的检查和计数在同一次加锁中完成，并发调用不会超出限制
+    async fn acquire_slot(&self, slot: &AgentSlot) -> Result<RequestPermit, RandAgentError> {
+        let in_flight = self.lifecycle.enter().ok_or(RandAgentError::ShuttingDown)?;
+        let tenant = self.enter_tenant()?;
+        let id = {
+            let mut state = lock_slot(slot);
+            if let Some(retry_after) = state.rate_limited() {
+                return Err(RandAgentError::RateLimited { retry_after });
+            }
+            state.begin_request();
+            state.id
+        };
+        let global = self.concurrency.acquire_global().await;
+        let permit = self.concurrency.acquire_agent(id, global).await;
+        Ok(permit.with_in_flight(in_flight).with_tenant(tenant))
+    }
+
+    /// 从集合中获取一个随机有效代理的索引，代理池关闭后返回 `None`
+    /// 注意: 只做选择，不占用速率限制配额，也不发起半开试探
+    pub async fn get_random_valid_agent_index(&self) -> Option<usize> {
+        if self.is_shutting_down() {
+            return None;
+        }
+        let agents = self.agents.read().await;
+        self.select_index(&agents).ok()
+    }
+
+    /// 从集合中获取一个随机有效代理
+    /// 注意: 并不会增加失败计数
+    pub async fn get_random_valid_agent_state(&self) -> Option<AgentState> {
+        let agents = self.agents.read().await;
+        let agent_index = self.select_index(&agents).ok()?;
+        agents.get(agent_index).map(|slot| lock_slot(slot).clone())
+    }
+
+    /// 获取所有代理状态的快照(包括无效的)，用于调试
+    pub async fn agents(&self) -> Vec<AgentState> {
+        let agents = self.agents.read().await;
+        agents.iter().map(|slot| lock_slot(slot).clone()).collect()
+    }
+
+    /// 获取总代理数量（包括无效的）
+    pub async fn total_len(&self) -> usize {
+        let agents = self.agents.read().await;
+        agents.len()
+    }
+
+    /// 检查是否有有效代理
+    pub async fn is_empty(&self) -> bool {
+        self.len().await == 0
+    }
+
+    /// 获取agent info
+    pub async fn get_agents_info(&self) -> Vec<AgentInfo> {
+        let agents = self.agents.read().await;
+        let agent_infos = agents
+            .iter()
+            .map(|slot| {
+                let mut state = lock_slot(slot);
+                self.apply_decay(&mut state);
+                state.info.clone()
+            })
+            .collect::<_>();
+        tracing::info!("agents info: {:?}", agent_infos);
+        agent_infos
+    }
+
+    /// 获取失败统计
+    pub async fn failure_stats(&self) -> Vec<(usize, u32, u32)> {
+        let agents = self.agents.read().await;
+        agents
+            .iter()
+            .enumerate()
+            .map(|(i, slot)| {
+                let mut state = lock_slot(slot);
+                self.apply_decay(&mut state);
+                (i, state.info.failure_count, state.info.max_failures)
+            })
+            .collect()
+    }
+
+    /// 重置所有代理的失败计数
+    pub async fn reset_failures(&self) {
+        let agents = self.agents.read().await;
+        for slot in agents.iter() {
+            lock_slot(slot).record_success();
+        }
+    }
+
+    /// 通过名称获取 agent
+    pub async fn get_agent_by_name(
+        &self,
+        provider_name: &str,
+        model_name: &str,
+    ) -> Option<AgentState> {
+        let agents = self.agents.read().await;
+
+        for slot in agents.iter() {
+            let agent = lock_slot(slot);
+            if agent.info.provider == provider_name && agent.info.model == model_name {
+                return Some(agent.clone());
+            }
+        }
+
+        None
+    }
+
+    /// 通过id获取 agent
+    pub async fn get_agent_by_id(&self, id: i32) -> Option<AgentState> {
+        let agents = self.agents.read().await;
+
+        for slot in agents.iter() {
+            let agent = lock_slot(slot);
+            if agent.info.id == id {
+                return Some(agent.clone());
+            }
+        }
+
+        None
+    }
+
+    /// 添加失败重试
+    pub async fn try_invoke_with_retry(
+        &self,
+        info: Message,
+        retry_num: Option<usize>,
+    ) -> Result<String, RandAgentError> {
+        let config = self.backoff(retry_num);
+
+        let info = Arc::new(info);
+
+        let rand_agent = self.for_retry();
+        let attempt = AtomicUsize::new(0);
+        let result = (|| {
+            let agent = rand_agent.clone();
+            let prompt = info.clone();
+            let attempt = attempt.fetch_add(1, Ordering::Relaxed) + 1;
+            async move { agent.prompt((*prompt).clone()).await }
+                .instrument(spans::attempt_span("try_invoke_with_retry", attempt))
+        })
+        .retry(config)
+        .sleep(tokio::time::sleep)
+        .when(|err: &PromptError| rand_agent.should_retry(err))
+        .notify(|err: &PromptError, dur: Duration| {
+            tracing::warn!(error = %redacted(&err), delay_ms = dur.as_millis() as u64, "请求失败，等待后重试");
+            self.hooks.retry(err, dur);
+        })
+        .await;
+        if let Err(err) = &result {
+            rand_agent
+                .dump_failure("try_invoke_with_retry", &info, err)
+                .await;
+        }
+        Ok(result?)
+    }
+
+    #[allow(refining_impl_trait)]
+    pub async fn prompt_with_info(
+        &self,
+        prompt: impl Into<Message> + Send,
+    ) -> Result<(String, AgentInfo), PromptError> {
+        // 第一步：选择代理，请求期间不持有任何锁
+        let prompt = self.screen(prompt.into())?;
+        let (slot, _permit) = self.pick_slot().await.map_err(unavailable_error)?;
+        // 第二步：发起请求，结束后再更新计数
+        let content = self.prompt_slot(&slot, "prompt_with_info", prompt).await?;
+        let agent_info = lock_slot(&slot).info.clone();
+        Ok((content, agent_info))
+    }
+
+    /// 添加失败重试
+    pub async fn try_invoke_with_info_retry(
+        &self,
+        info: Message,
+        retry_num: Option<usize>,
+    ) -> Result<(String, AgentInfo), RandAgentError> {
+        let config = self.backoff(retry_num);
+
+        let info = Arc::new(info);
+
+        let rand_agent = self.for_retry();
+        let attempt = AtomicUsize::new(0);
+        let result = (|| {
+            let agent = rand_agent.clone();
+            let prompt = info.clone();
+            let attempt = attempt.fetch_add(1, Ordering::Relaxed) + 1;
+            async move { agent.prompt_with_info((*prompt).clone()).await }
+                .instrument(spans::attempt_span("try_invoke_with_info_retry", attempt))
+        })
+        .retry(config)
+        .sleep(tokio::time::sleep)
+        .when(|err: &PromptError| rand_agent.should_retry(err))
+        .notify(|err: &PromptError, dur: Duration| {
+            tracing::warn!(error = %redacted(&err), delay_ms = dur.as_millis() as u64, "请求失败，等待后重试");
+            self.hooks.retry(err, dur);
+        })
+        .await;
+        if let Err(err) = &result {
+            rand_agent
+                .dump_failure("try_invoke_with_info_retry", &info, err)
+                .await;
+        }
+        Ok(result?)
+    }
+}
+
+/// 构建器中待添加的代理
+pub(crate) struct AgentEntry {
+    pub(crate) agent: BoxAgent<'static>,
+    pub(crate) id: i32,
+    pub(crate) provider: String,
+    pub(crate) model: String,
+    /// 单独设置的最大失败次数，为空时使用构建器的全局设置
+    pub(crate) max_failures: Option<u32>,
+    /// 输入价格(每千 token)
+    pub(crate) input_price: Option<f64>,
+    /// 输出价格(每千 token)
+    pub(crate) output_price: Option<f64>,
+    /// 预留的工作负载
+    pub(crate) reserved_for: Option<String>,
+    /// 速率限制
+    pub(crate) rate_limit: RateLimit,
+    /// 结构化输出支持情况，为空时自动推断
+    pub(crate) structured_output: Option<StructuredOutput>,
+    /// 上下文长度，为空时按已知模型推断
+    pub(crate) context_length: Option<u32>,
+    /// 由 AgentConfig 构建，构建时挂载构建器的共享工具
+    pub(crate) shared_tools: bool,
+}
+
+impl AgentEntry {
+    pub(crate) fn new(agent: BoxAgent<'static>, id: i32, provider: String, model: String) -> Self {
+        Self {
+            agent,
+            id,
+            provider,
+            model,
+            max_failures: None,
+            input_price: None,
+            output_price: None,
+            reserved_for: None,
+            rate_limit: RateLimit::default(),
+            shared_tools: false,
+            structured_output: None,
+            context_length: None,
+        }
+    }
+}
+
+/// 线程安全 RandAgent 的构建器
+pub struct RandAgentBuilder {
+    pub(crate) agents: Vec<AgentEntry>,
+    max_failures: u32,
+    on_agent_invalid: OnAgentInvalidCallback,
+    warm_up_prompt: String,
+    warm_up_timeout: Duration,
+    warm_up_action: WarmUpAction,
+    response_validator: Option<ResponseValidator>,
+    failure_decay: Option<Duration>,
+    selection_strategy: SelectionStrategy,
+    retry_config: RetryConfig,
+    cooldown: Option<Duration>,
+    circuit_breaker: Option<CircuitBreakerConfig>,
+    idempotency_ttl: Option<Duration>,
+    extraction_cache_ttl: Option<Duration>,
+    snapshot: Option<RandAgentSnapshot>,
+    preambles: PreambleVersions,
+    prompt_templates: PromptTemplates,
+    active_preamble: Option<String>,
+    hooks: RequestHooks,
+    progress_interval: Option<Duration>,
+    tool_summarizer: Option<ToolResultSummarizer>,
+    memory: Option<MemoryManager>,
+    max_concurrent_requests: Option<usize>,
+    max_concurrent_per_agent: Option<usize>,
+    max_concurrent_streams_per_agent: Option<usize>,
+    provider_shaping: Vec<(String, RequestShaping)>,
+    tool_usage_policy: bool,
+    response_cache: Option<Arc<dyn ResponseCache>>,
+    firewall: Option<PromptFirewall>,
+    prompt_timeout: Option<Duration>,
+    failure_dump_dir: Option<std::path::PathBuf>,
+    /// simple_builder 构建的每个 agent 共用的工具
+    pub(crate) shared_tools: Vec<Arc<dyn rig::tool::ToolDyn>>,
+    #[cfg(feature = "rig-extra-chaos")]
+    chaos: Option<crate::chaos::ChaosConfig>,
+}
+
+impl RandAgentBuilder {
+    /// 创建新的 RandAgentBuilder
+    pub fn new() -> Self {
+        Self {
+            agents: Vec::new(),
+            max_failures: 3, // 默认最大失败次数
+            on_agent_invalid: None,
+            warm_up_prompt: "ping".to_string(),
+            warm_up_timeout: Duration::from_secs(30),
+            warm_up_action: WarmUpAction::default(),
+            response_validator: None,
+            failure_decay: None,
+            selection_strategy: SelectionStrategy::default(),
+            retry_config: RetryConfig::default(),
+            cooldown: None,
+            circuit_breaker: None,
+            idempotency_ttl: None,
+            extraction_cache_ttl: None,
+            snapshot: None,
+            preambles: PreambleVersions::default(),
+            prompt_templates: PromptTemplates::default(),
+            active_preamble: None,
+            hooks: RequestHooks::default(),
+            progress_interval: None,
+            tool_summarizer: None,
+            memory: None,
+            max_concurrent_requests: None,
+            max_concurrent_per_agent: None,
+            max_concurrent_streams_per_agent: None,
+            provider_shaping: Vec::new(),
+            tool_usage_policy: false,
+            response_cache: None,
+            firewall: None,
+            prompt_timeout: None,
+            failure_dump_dir: None,
+            shared_tools: Vec::new(),
+            #[cfg(feature = "rig-extra-chaos")]
+            chaos: None,
+        }
+    }
+
+    /// 设置连续失败的最大次数，超过后标记代理为无效
+    pub fn max_failures(mut self, max_failures: u32) -> Self {
+        self.max_failures = max_failures;
+        self
+    }
+
+    /// 设置 agent 失效时的回调
+    pub fn on_agent_invalid<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(i32) + Send + Sync + 'static,
+    {
+        self.on_agent_invalid = Some(Arc::new(Box::new(callback)));
+        self
+    }
+
+    /// 设置选中 agent 后、发起请求前的回调
+    pub fn on_request_start<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&AgentInfo) + Send + Sync + 'static,
+    {
+        self.hooks.on_request_start = Some(Arc::new(callback));
+        self
+    }
+
+    /// 设置请求成功的回调，参数为 agent 和请求耗时
+    pub fn on_success<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&AgentInfo, Duration) + Send + Sync + 'static,
+    {
+        self.hooks.on_success = Some(Arc::new(callback));
+        self
+    }
+
+    /// 设置请求失败的回调，响应未通过校验同样视为失败
+    pub fn on_failure<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&AgentInfo, &PromptError) + Send + Sync + 'static,
+    {
+        self.hooks.on_failure = Some(Arc::new(callback));
+        self
+    }
+
+    /// 设置 `try_invoke_with_retry` 等重试前的回调，参数为触发重试的错误和等待时间
+    pub fn on_retry<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&PromptError, Duration) + Send + Sync + 'static,
+    {
+        self.hooks.on_retry = Some(Arc::new(callback));
+        self
+    }
+
+    /// 添加代理到构建器
+    ///
+    /// # 参数
+    /// - agent: 代理实例（需要是 'static 生命周期）
+    /// - provider_name: 提供方名称（如 openai、bigmodel 等）
+    /// - model_name: 模型名称（如 gpt-3.5、glm-4-flash 等）
+    pub fn add_agent(
+        mut self,
+        agent: BoxAgent<'static>,
+        id: i32,
+        provider_name: String,
+        model_name: String,
+    ) -> Self {
+        self.agents
+            .push(AgentEntry::new(agent, id, provider_name, model_name));
+        self
+    }
+
+    /// 添加代理并单独设置最大失败次数
+    ///
+    /// 例如本地 Ollama 不稳定，可以设置比付费云服务更高的阈值
+    pub fn add_agent_with_max_failures(
+        mut self,
+        agent: BoxAgent<'static>,
+        id: i32,
+        provider_name: String,
+        model_name: String,
+        max_failures: u32,
+    ) -> Self {
+        let mut entry = AgentEntry::new(agent, id, provider_name, model_name);
+        entry.max_failures = Some(max_failures);
+        self.agents.push(entry);
+        self
+    }
+
+    /// 从 AgentBuilder 添加代理
+    ///
+    /// # 参数
+    /// - builder: AgentBuilder 实例（需要是 'static 生命周期）
+    /// - provider_name: 提供方名称（如 openai、bigmodel 等）
+    /// - model_name: 模型名称（如 gpt-3.5、glm-4-flash 等）
+    pub fn add_builder(
+        mut self,
+        builder: Agent<CompletionModelHandle<'static>>,
+        id: i32,
+        provider_name: &str,
+        model_name: &str,
+    ) -> Self {
+        self.agents.push(AgentEntry::new(
+            builder,
+            id,
+            provider_name.to_string(),
+            model_name.to_string(),
+        ));
+        self
+    }
+
+    /// 设置无效 agent 的冷却时间
+    ///
+    /// 默认无效 agent 不会自动恢复；设置后，冷却结束的 agent 会被允许一次试探请求(半开状态)，
+    /// 成功则完全恢复，失败则重新开始冷却
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = Some(cooldown);
+        self
+    }
+
+    /// 设置提供方熔断配置
+    ///
+    /// 同一提供方的 agent 在时间窗口内累计失败达到阈值时，整个提供方暂时不再被选中
+    pub fn circuit_breaker(mut self, config: CircuitBreakerConfig) -> Self {
+        self.circuit_breaker = Some(config);
+        self
+    }
+
+    /// 设置幂等键结果的有效期，默认 10 分钟
+    pub fn idempotency_ttl(mut self, ttl: Duration) -> Self {
+        self.idempotency_ttl = Some(ttl);
+        self
+    }
+
+    /// 缓存结构化提取的结果，键为 (schema 哈希, 输入哈希)，默认不缓存
+    pub fn cache_extractions(mut self, ttl: Duration) -> Self {
+        self.extraction_cache_ttl = Some(ttl);
+        self
+    }
+
+    /// 多轮对话时将较早轮次中过长的工具结果替换为摘要，见 [`ToolResultSummarizer`]
+    pub fn summarize_tool_results(mut self, summarizer: ToolResultSummarizer) -> Self {
+        self.tool_summarizer = Some(summarizer);
+        self
+    }
+
+    /// 按会话保存对话历史，通过 [`RandAgent::chat_session`] 使用，见 [`crate::memory`]
+    pub fn memory(mut self, memory: MemoryManager) -> Self {
+        self.memory = Some(memory);
+        self
+    }
+
+    /// 设置进度事件的心跳间隔
+    ///
+    /// 非流式请求进行期间按该间隔发送 [`ProgressEvent::InFlight`]，便于调用方显示加载状态
+    pub fn progress_interval(mut self, interval: Duration) -> Self {
+        self.progress_interval = Some(interval);
+        self
+    }
+
+    /// 设置代理池同时进行的最大请求数，超过后的请求排队等待
+    pub fn max_concurrent_requests(mut self, max: usize) -> Self {
+        self.max_concurrent_requests = Some(max);
+        self
+    }
+
+    /// 设置单个 agent 同时进行的最大请求数，已满的 agent 暂不被选中
+    pub fn max_concurrent_per_agent(mut self, max: usize) -> Self {
+        self.max_concurrent_per_agent = Some(max);
+        self
+    }
+
+    /// 设置单个 agent 同时打开的最大流式请求数
+    ///
+    /// 已满的 agent 暂不被流式请求选中，所有可用 agent 都已满时排队等待，
+    /// 直到有流结束或被丢弃。不影响非流式请求
+    pub fn max_concurrent_streams_per_agent(mut self, max: usize) -> Self {
+        self.max_concurrent_streams_per_agent = Some(max);
+        self
+    }
+
+    /// 平滑发往指定提供方的请求: 相邻请求保持最小间隔，空闲后允许少量突发，
+    /// 见 [`crate::shaping`]
+    pub fn shape_provider(mut self, provider: impl Into<String>, shaping: RequestShaping) -> Self {
+        self.provider_shaping.push((provider.into(), shaping));
+        self
+    }
+
+    /// 设置单次请求的超时时间
+    ///
+    /// 超时的请求被取消并计为该 agent 失败，错误属于 `http` 类别，
+    /// [`RandAgent::try_invoke_with_retry`] 会按重试配置重新选择 agent 重试
+    pub fn prompt_timeout(mut self, timeout: Duration) -> Self {
+        self.prompt_timeout = Some(timeout);
+        self
+    }
+
+    /// 根据每个 agent 挂载的工具生成工具使用规范并追加到系统提示词，默认关闭
+    ///
+    /// 见 [`crate::tool_policy`]
+    pub fn tool_usage_policy(mut self, enabled: bool) -> Self {
+        self.tool_usage_policy = enabled;
+        self
+    }
+
+    /// 缓存成功的响应，相同的 prompt 发送到相同模型时不再调用提供方
+    ///
+    /// 见 [`crate::response_cache`]
+    pub fn with_cache(mut self, cache: impl ResponseCache + 'static) -> Self {
+        self.response_cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// 在请求发送给提供方和工具之前检查用户输入，见 [`crate::firewall`]
+    pub fn firewall(mut self, firewall: PromptFirewall) -> Self {
+        self.firewall = Some(firewall);
+        self
+    }
+
+    /// 重试全部失败时把请求上下文导出到指定目录，运行时可通过
+    /// [`RandAgent::set_failure_dump_enabled`] 开关
+    pub fn dump_failures_to(mut self, dir: impl Into<std::path::PathBuf>) -> Self {
+        self.failure_dump_dir = Some(dir.into());
+        self
+    }
+
+    /// 注册命名的系统提示词版本，运行时可通过 [`RandAgent::set_active_preamble`] 切换
+    pub fn preamble_version(
+        mut self,
+        name: impl Into<String>,
+        preamble: impl Into<String>,
+    ) -> Self {
+        self.preambles.register(name.into(), preamble.into());
+        self
+    }
+
+    /// 注册命名的提示词模板，通过 [`RandAgent::prompt_template`] 使用
+    pub fn prompt_template(mut self, name: impl Into<String>, template: &str) -> Self {
+        self.prompt_templates.register(name, template);
+        self
+    }
+
+    /// 设置初始生效的系统提示词版本
+    pub fn active_preamble(mut self, name: impl Into<String>) -> Self {
+        self.active_preamble = Some(name.into());
+        self
+    }
+
+    /// 从快照恢复 agent 的健康状态和 token 用量统计，按 agent id 匹配
+    pub fn restore_from_snapshot(mut self, snapshot: RandAgentSnapshot) -> Self {
+        self.snapshot = Some(snapshot);
+        self
+    }
+
+    /// 开启故障注入，仅用于测试故障转移和重试配置
+    #[cfg(feature = "rig-extra-chaos")]
+    pub fn chaos(mut self, config: crate::chaos::ChaosConfig) -> Self {
+        self.chaos = Some(config);
+        self
+    }
+
+    /// 设置失败重试配置，一般从配置文件的 `[retry]` 段读取
+    pub fn retry_config(mut self, retry_config: RetryConfig) -> Self {
+        self.retry_config = retry_config;
+        self
+    }
+
+    /// 重试时不再选择本次调用中已失败的 agent，保证每次重试都换一个 agent，直到代理池耗尽
+    pub fn retry_on_different_agent(mut self, enabled: bool) -> Self {
+        self.retry_config.distinct_agents = enabled;
+        self
+    }
+
+    /// 设置 agent 选择策略，默认随机
+    pub fn selection_strategy(mut self, strategy: SelectionStrategy) -> Self {
+        self.selection_strategy = strategy;
+        self
+    }
+
+    /// 设置失败计数衰减时间窗口
+    ///
+    /// 早于该窗口的失败不再计数，避免提供方短暂故障恢复后 agent 仍被长期判定为无效
+    pub fn failure_decay(mut self, window: Duration) -> Self {
+        self.failure_decay = Some(window);
+        self
+    }
+
+    /// 设置响应校验器，校验失败计为 agent 失败
+    pub fn response_validator(mut self, validator: ResponseValidator) -> Self {
+        self.response_validator = Some(validator);
+        self
+    }
+
+    /// 设置预热探测使用的提示词，默认为 "ping"
+    pub fn warm_up_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.warm_up_prompt = prompt.into();
+        self
+    }
+
+    /// 设置单个 agent 预热探测的超时时间，默认 30 秒
+    pub fn warm_up_timeout(mut self, timeout: Duration) -> Self {
+        self.warm_up_timeout = timeout;
+        self
+    }
+
+    /// 设置预热失败的 agent 的处理方式，默认移除
+    pub fn warm_up_action(mut self, action: WarmUpAction) -> Self {
+        self.warm_up_action = action;
+        self
+    }
+
+    /// 将待添加的代理转换为 AgentState
+    pub(crate) fn take_agent_states(&mut self) -> Vec<AgentState> {
+        let max_failures = self.max_failures;
+        std::mem::take(&mut self.agents)
+            .into_iter()
+            .map(|mut entry| {
+                if entry.shared_tools && !self.shared_tools.is_empty() {
+                    entry.agent.tool_server_handle = shared_tool_server(&self.shared_tools);
+                }
+                let mut state = AgentState::new(
+                    entry.agent,
+                    entry.id,
+                    entry.provider,
+                    entry.model,
+                    entry.max_failures.unwrap_or(max_failures),
+                );
+                state.info.input_price = entry.input_price;
+                state.info.output_price = entry.output_price;
+                state.info.reserved_for = entry.reserved_for;
+                state.set_rate_limit(entry.rate_limit);
+                if let Some(structured_output) = entry.structured_output {
+                    state.info.structured_output = structured_output;
+                }
+                if entry.context_length.is_some() {
+                    state.info.context_length = entry.context_length;
+                }
+                if let Some(agent) = self.snapshot.as_ref().and_then(|s| s.agent(entry.id)) {
+                    state.restore(agent);
+                }
+                state
+            })
+            .collect()
+    }
+
+    /// 构建按添加顺序故障转移的 FallbackAgent
+    ///
+    /// 选择策略与预热配置对 FallbackAgent 无效
+    pub fn build_fallback(mut self) -> FallbackAgent {
+        let agent_states = self.take_agent_states();
+        let mut fallback_agent = FallbackAgent::new(agent_states);
+        fallback_agent.on_agent_invalid = self.on_agent_invalid;
+        fallback_agent.response_validator = self.response_validator;
+        fallback_agent.failure_decay = self.failure_decay;
+        fallback_agent.cooldown = self.cooldown;
+        fallback_agent
+    }
+
+    /// 构建 RandAgent
+    pub fn build(mut self) -> RandAgent {
+        let agent_states = self.take_agent_states();
+        let mut rand_agent = RandAgent::from_states(agent_states, self.on_agent_invalid);
+        if let Some(snapshot) = self.snapshot {
+            rand_agent.usage_stats = Arc::new(std::sync::Mutex::new(snapshot.usage));
+        }
+        rand_agent.response_validator = self.response_validator;
+        rand_agent.failure_decay = self.failure_decay;
+        rand_agent.selection_strategy = self.selection_strategy;
+        rand_agent.retry_config = self.retry_config;
+        rand_agent.cooldown = self.cooldown;
+        rand_agent.hooks = self.hooks;
+        rand_agent.tool_summarizer = self.tool_summarizer;
+        rand_agent.memory = self.memory;
+        rand_agent.prompt_timeout = self.prompt_timeout;
+        rand_agent.tool_usage_policy = self.tool_usage_policy;
+        rand_agent.response_cache = self.response_cache;
+        rand_agent.firewall = self.firewall.map(Arc::new);
+        rand_agent.failure_dumper = self
+            .failure_dump_dir
+            .map(|dir| Arc::new(FailureDumper::new(dir)));
+        if let Some(name) = &self.active_preamble
+            && !self.preambles.set_active(name)
+        {
+            tracing::warn!("系统提示词版本 {name} 未注册，忽略");
+        }
+        rand_agent.preambles = Arc::new(std::sync::RwLock::new(self.preambles));
+        rand_agent.prompt_templates = Arc::new(std::sync::RwLock::new(self.prompt_templates));
+        rand_agent.concurrency = Arc::new(
+            ConcurrencyLimiter::new(self.max_concurrent_requests, self.max_concurrent_per_agent)
+                .with_streams_per_agent(self.max_concurrent_streams_per_agent),
+        );
+        for (provider, shaping) in &self.provider_shaping {
+            rand_agent.set_provider_shaping(provider, Some(*shaping));
+        }
+        if let Some(config) = self.circuit_breaker {
+            rand_agent.set_circuit_breaker(config);
+        }
+        if let Some(ttl) = self.idempotency_ttl {
+            rand_agent.set_idempotency_ttl(ttl);
+        }
+        if let Some(ttl) = self.extraction_cache_ttl {
+            rand_agent.set_extraction_cache_ttl(ttl);
+        }
+        if let Some(interval) = self.progress_interval {
+            rand_agent.set_progress_interval(interval);
+        }
+        #[cfg(feature = "rig-extra-chaos")]
+        if let Some(config) = self.chaos {
+            rand_agent.set_chaos(config);
+        }
+        rand_agent
+    }
+}
+
+impl Default for RandAgentBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::health::HealthCheckConfig;
+    use rig::OneOrMany;
+    use rig::agent::AgentBuilder;
+    use rig::client::builder::FinalCompletionResponse;
+    use rig::completion::{AssistantContent, CompletionRequest, CompletionResponse};
+    use rig::streaming::StreamingCompletionResponse;
+
+    /// 测试用模型，可随时切换成功或失败，并记录调用次数
+    #[derive(Clone, Default)]
+    pub(crate) struct FakeModel {
+        /// 不为空时请求失败，返回该错误信息
+        error: Arc<std::sync::Mutex<Option<String>>>,
+        delay: Arc<std::sync::Mutex<Duration>>,
+        /// 不为空时以 `submit` 工具调用返回该参数，用于结构化提取
+        submit: Arc<std::sync::Mutex<Option<serde_json::Value>>>,
+        calls: Arc<AtomicUsize>,
+        /// 最近一次请求的消息数(历史记录加本次 prompt)
+        last_messages: Arc<AtomicUsize>,
+    }
+
+    impl FakeModel {
+        pub(crate) fn ok() -> Self {
+            Self::default()
+        }
+
+        pub(crate) fn failing() -> Self {
+            let model = Self::default();
+            model.set_failing(true);
+            model
+        }
+
+        pub(crate) fn failing_with(error: &str) -> Self {
+            let model = Self::default();
+            *model.error.lock().unwrap() = Some(error.to_string());
+            model
+        }
+
+        pub(crate) fn with_delay(self, delay: Duration) -> Self {
+            *self.delay.lock().unwrap() = delay;
+            self
+        }
+
+        pub(crate) fn submitting(self, arguments: serde_json::Value) -> Self {
+            *self.submit.lock().unwrap() = Some(arguments);
+            self
+        }
+
+        pub(crate) fn set_failing(&self, failing: bool) {
+            *self.error.lock().unwrap() = failing.then(|| "503 Service Unavailable".to_string());
+        }
+
+        pub(crate) fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+
+        pub(crate) fn last_messages(&self) -> usize {
+            self.last_messages.load(Ordering::SeqCst)
+        }
+
+        pub(crate) fn agent(&self) -> BoxAgent<'static> {
+            AgentBuilder::new(CompletionModelHandle {
+                inner: Arc::new(self.clone()),
+            })
+            .build()
+        }
+    }
+
+    impl rig::completion::CompletionModel for FakeModel {
+        type Response = ();
+        type StreamingResponse = FinalCompletionResponse;
+
+        async fn completion(
+            &self,
+            request: CompletionRequest,
+        ) -> Result<CompletionResponse<()>, CompletionError> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            self.last_messages
+                .store(request.chat_history.len(), Ordering::SeqCst);
+            let delay = *self.delay.lock().unwrap();
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+            if let Some(error) = self.error.lock().unwrap().clone() {
+                return Err(CompletionError::ProviderError(error));
+            }
+            let mut usage = Usage::new();
+            usage.input_tokens = 10;
+            usage.output_tokens = 5;
+            usage.total_tokens = 15;
+            let choice = match self.submit.lock().unwrap().clone() {
+                Some(arguments) => {
+                    AssistantContent::tool_call(format!("call {call}"), "submit", arguments)
+                }
+                None => AssistantContent::text(format!("reply {call}")),
+            };
+            Ok(CompletionResponse {
+                choice: OneOrMany::one(choice),
+                usage,
+                raw_response: (),
+            })
+        }
+
+        async fn stream(
+            &self,
+            _request: CompletionRequest,
+        ) -> Result<StreamingCompletionResponse<FinalCompletionResponse>, CompletionError> {
+            Err(CompletionError::ProviderError("stream unsupported".into()))
+        }
+    }
+
+    /// 用假模型构建代理池，agent id 从 1 开始，每个 agent 使用单独的提供方 `fake{id}`
+    pub(crate) fn fake_pool(
+        models: &[FakeModel],
+        configure: impl FnOnce(RandAgentBuilder) -> RandAgentBuilder,
+    ) -> RandAgent {
+        let builder =
+            models
+                .iter()
+                .zip(1..)
+                .fold(RandAgentBuilder::new(), |builder, (model, id)| {
+                    builder.add_agent(
+                        model.agent(),
+                        id,
+                        format!("fake{id}"),
+                        "fake-model".to_string(),
+                    )
+                });
+        configure(builder).build()
+    }
+
+    /// 重试间隔很短的重试配置
+    pub(super) fn fast_retry(max_times: usize) -> RetryConfig {
+        RetryConfig {
+            max_times,
+            min_delay_ms: 1,
+            max_delay_ms: 5,
+            ..Default::default()
+        }
+    }
+
+    async fn failure_count(pool: &RandAgent, id: i32) -> u32 {
+        pool.get_agent_by_id(id).await.unwrap().info.failure_count
+    }
+
+    #[tokio::test]
+    async fn test_latency_moving_average() {
+        let pool = fake_pool(&[FakeModel::ok()], |builder| builder);
+        let slot = pool.agents.read().await[0].clone();
+        let mut state = lock_slot(&slot);
+        state.record_latency(Duration::from_millis(100));
+        assert_eq!(state.info.avg_latency, Some(Duration::from_millis(100)));
+        state.record_latency(Duration::from_millis(200));
+        assert_eq!(state.info.avg_latency, Some(Duration::from_millis(130)));
+    }
+
+    #[tokio::test]
+    async fn test_least_latency_prefers_fastest_and_samples_cold() {
+        let models = [FakeModel::ok(), FakeModel::ok(), FakeModel::ok()];
+        let pool = fake_pool(&models, |builder| {
+            builder.selection_strategy(SelectionStrategy::LeastLatency)
+        });
+        let set_latency = |index: usize, ms: u64| {
+            let slot = pool.agents.try_read().unwrap()[index].clone();
+            lock_slot(&slot).info.avg_latency = Some(Duration::from_millis(ms));
+        };
+        set_latency(0, 50);
+        set_latency(1, 10);
+
+        // agent 3 还没有延迟数据，仍会被选中
+        pool.prompt("hi").await.unwrap();
+        assert_eq!(models[2].calls(), 1);
+
+        // 都有延迟数据后选择最快的 agent
+        set_latency(2, 80);
+        for _ in 0..5 {
+            pool.prompt("hi").await.unwrap();
+        }
+        assert_eq!(models[1].calls(), 5);
+        assert_eq!(models[0].calls(), 0);
+        assert_eq!(models[2].calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failover_to_healthy_agent() {
+        let (bad, good) = (FakeModel::failing(), FakeModel::ok());
+        let pool = fake_pool(&[bad.clone(), good.clone()], |builder| {
+            builder.max_failures(1).retry_config(fast_retry(3))
+        });
+
+        for _ in 0..5 {
+            let reply = pool.try_invoke_with_retry("hi".into(), None).await.unwrap();
+            assert!(reply.starts_with("reply"), "{reply}");
+        }
+        // 失败一次后即被标记为无效，之后不再被选中
+        assert!(bad.calls() <= 1);
+        assert_eq!(good.calls(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_cooldown_half_open_recovery() {
+        let cooldown = Duration::from_millis(50);
+        let model = FakeModel::failing();
+        let pool = fake_pool(std::slice::from_ref(&model), |builder| {
+            builder.max_failures(1).cooldown(cooldown)
+        });
+
+        assert!(pool.prompt("hi").await.is_err());
+        // 冷却期间不可用，不会调用模型
+        assert!(pool.prompt("hi").await.is_err());
+        assert_eq!(model.calls(), 1);
+
+        // 冷却结束后的试探请求失败，重新开始冷却
+        tokio::time::sleep(cooldown + Duration::from_millis(10)).await;
+        assert!(pool.prompt("hi").await.is_err());
+        assert_eq!(model.calls(), 2);
+        assert!(pool.prompt("hi").await.is_err());
+        assert_eq!(model.calls(), 2);
+
+        // 试探成功后完全恢复
+        model.set_failing(false);
+        tokio::time::sleep(cooldown + Duration::from_millis(10)).await;
+        assert!(pool.prompt("hi").await.is_ok());
+        assert_eq!(failure_count(&pool, 1).await, 0);
+        assert!(pool.prompt("hi").await.is_ok());
+        assert_eq!(model.calls(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_failure_decay() {
+        let window = Duration::from_millis(50);
+        let model = FakeModel::failing();
+        let pool = fake_pool(std::slice::from_ref(&model), |builder| {
+            builder.max_failures(2).failure_decay(window)
+        });
+
+        assert!(pool.prompt("hi").await.is_err());
+        assert_eq!(failure_count(&pool, 1).await, 1);
+
+        // 第一次失败已过期，第二次失败后仍然有效
+        tokio::time::sleep(window + Duration::from_millis(10)).await;
+        assert!(pool.prompt("hi").await.is_err());
+        assert_eq!(failure_count(&pool, 1).await, 1);
+
+        model.set_failing(false);
+        assert!(pool.prompt("hi").await.is_ok());
+        assert_eq!(model.calls(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_on_distinct_agents() {
+        let models = [
+            FakeModel::failing(),
+            FakeModel::failing(),
+            FakeModel::failing(),
+        ];
+        let retries = Arc::new(AtomicUsize::new(0));
+        let counter = retries.clone();
+        let pool = fake_pool(&models, |builder| {
+            builder
+                .max_failures(10)
+                .retry_config(fast_retry(10))
+                .retry_on_different_agent(true)
+                .on_retry(move |_, _| {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                })
+        });
+
+        assert!(
+            pool.try_invoke_with_retry("你好".into(), None)
+                .await
+                .is_err()
+        );
+        // 每个 agent 只被尝试一次，没有可换的 agent 后不再重试
+        assert!(models.iter().all(|model| model.calls() == 1));
+        assert_eq!(retries.load(Ordering::SeqCst), 3);
+
+        // 切换为成功后，新的调用重新使用全部 agent
+        models[2].set_failing(false);
+        let content = pool
+            .try_invoke_with_retry("你好".into(), None)
+            .await
+            .unwrap();
+        assert!(content.starts_with("reply"));
+        assert_eq!(models[2].calls(), 2);
+    }
+
+    /// 等待代理池中有请求正在进行
+    async fn wait_in_flight(pool: &RandAgent) {
+        while pool.in_flight() == 0 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remove_agent_while_in_flight() {
+        let slow = FakeModel::ok().with_delay(Duration::from_millis(100));
+        let other = FakeModel::ok();
+        let pool = fake_pool(std::slice::from_ref(&slow), |builder| builder);
+        let request = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.prompt("你好").await }
+        });
+        wait_in_flight(&pool).await;
+
+        pool.add_agent(
+            other.agent(),
+            2,
+            "fake2".to_string(),
+            "fake-model".to_string(),
+        )
+        .await;
+        assert!(pool.remove_agent_by_id(1).await);
+        assert!(!pool.remove_agent_by_id(1).await);
+        // 被移除的 agent 上的请求正常完成，其它 agent 的索引随之前移
+        assert_eq!(request.await.unwrap().unwrap(), "reply 1");
+        let ids: Vec<i32> = pool
+            .get_agents_info()
+            .await
+            .iter()
+            .map(|info| info.id)
+            .collect();
+        assert_eq!(ids, vec![2]);
+        assert!(pool.get_agent_by_id(1).await.is_none());
+        assert_eq!(pool.get_random_valid_agent_index().await, Some(0));
+
+        for _ in 0..3 {
+            pool.prompt("你好").await.unwrap();
+        }
+        assert_eq!(slow.calls(), 1);
+        assert_eq!(other.calls(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_replace_agent_while_in_flight() {
+        let old = FakeModel::failing().with_delay(Duration::from_millis(100));
+        let pool = fake_pool(std::slice::from_ref(&old), |builder| {
+            builder.max_failures(1)
+        });
+        let request = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.prompt("你好").await }
+        });
+        wait_in_flight(&pool).await;
+
+        let fresh = FakeModel::ok();
+        assert!(pool.replace_agent(1, fresh.agent()).await);
+        assert!(!pool.replace_agent(2, fresh.agent()).await);
+        // 旧请求的失败记录在被替换的状态上，替换后的 agent 仍然有效，id 不变
+        assert!(request.await.unwrap().is_err());
+        assert_eq!(failure_count(&pool, 1).await, 0);
+        assert_eq!(pool.len().await, 1);
+
+        assert_eq!(pool.prompt("你好").await.unwrap(), "reply 1");
+        assert_eq!(old.calls(), 1);
+        assert_eq!(fresh.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_disable_and_enable_agent() {
+        let models = [FakeModel::ok(), FakeModel::ok()];
+        let pool = fake_pool(&models, |builder| builder);
+
+        assert!(pool.disable_agent(1).await);
+        assert!(!pool.disable_agent(3).await);
+        assert_eq!(pool.len().await, 1);
+        for _ in 0..5 {
+            pool.prompt("你好").await.unwrap();
+        }
+        assert_eq!(models[0].calls(), 0);
+        assert_eq!(models[1].calls(), 5);
+
+        assert!(pool.disable_agent(2).await);
+        assert!(pool.prompt("你好").await.is_err());
+        assert!(pool.get_random_valid_agent_index().await.is_none());
+
+        // 停用期间的请求不计入失败，重新启用后立即可用
+        assert!(pool.enable_agent(1).await);
+        assert_eq!(failure_count(&pool, 1).await, 0);
+        assert_eq!(pool.prompt("你好").await.unwrap(), "reply 1");
+        assert_eq!(models[1].calls(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_reserved_agents_excluded_from_general_selection() {
+        let models = [FakeModel::ok(), FakeModel::ok()];
+        let pool = fake_pool(&models, |builder| builder);
+        assert!(pool.reserve_agent(1, "interactive").await);
+        assert!(!pool.reserve_agent(3, "interactive").await);
+
+        // 不指定工作负载或指定其它工作负载时不会选中预留的 agent
+        let batch = pool.for_workload("batch");
+        for _ in 0..5 {
+            pool.prompt("你好").await.unwrap();
+            batch.prompt("你好").await.unwrap();
+        }
+        assert_eq!(models[0].calls(), 0);
+        assert_eq!(models[1].calls(), 10);
+
+        // 对应的工作负载可以使用预留的 agent，也可以使用未预留的 agent
+        assert!(pool.disable_agent(2).await);
+        assert!(pool.prompt("你好").await.is_err());
+        let interactive = pool.for_workload("interactive");
+        assert_eq!(interactive.prompt("你好").await.unwrap(), "reply 1");
+        assert!(pool.enable_agent(2).await);
+        assert!(pool.disable_agent(1).await);
+        interactive.prompt("你好").await.unwrap();
+        assert_eq!(models[1].calls(), 11);
+
+        // 取消预留后重新参与一般请求
+        assert!(pool.enable_agent(1).await);
+        assert!(pool.unreserve_agent(1).await);
+        assert!(pool.disable_agent(2).await);
+        assert_eq!(pool.prompt("你好").await.unwrap(), "reply 2");
+    }
+
+    #[tokio::test]
+    async fn test_stream_permit_released_on_drop() {
+        let model = FakeModel::ok();
+        let pool = fake_pool(std::slice::from_ref(&model), |builder| {
+            builder
+                .max_concurrent_requests(1)
+                .max_concurrent_streams_per_agent(1)
+        });
+
+        let stream = pool.stream_prompt("你好").await.unwrap();
+        assert_eq!(pool.in_flight(), 1);
+        // 未结束的流占用并发名额，新的请求排队等待
+        let blocked = tokio::time::timeout(Duration::from_millis(50), pool.stream_prompt("你好"));
+        assert!(blocked.await.is_err());
+        let blocked = tokio::time::timeout(Duration::from_millis(50), pool.prompt("你好"));
+        assert!(blocked.await.is_err());
+
+        // 调用方提前丢弃流后释放名额，且不计入失败
+        drop(stream);
+        assert_eq!(pool.in_flight(), 0);
+        let stream = tokio::time::timeout(Duration::from_secs(1), pool.stream_prompt("你好"))
+            .await
+            .unwrap()
+            .unwrap();
+        drop(stream);
+        assert_eq!(pool.prompt("你好").await.unwrap(), "reply 1");
+        assert_eq!(failure_count(&pool, 1).await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_chat_history_failover() {
+        let models = [FakeModel::failing(), FakeModel::ok()];
+        let pool = fake_pool(&models, |builder| builder.max_failures(1));
+        let history = vec![
+            Message::user("你好"),
+            Message::assistant("你好，有什么可以帮你"),
+        ];
+
+        // 失败的 agent 最多被选中一次，之后的对话都由另一个 agent 处理
+        let mut succeeded = 0;
+        for _ in 0..4 {
+            if pool.chat("继续", history.clone()).await.is_ok() {
+                succeeded += 1;
+            }
+        }
+        assert!(succeeded >= 3);
+        assert!(models[0].calls() <= 1);
+        assert_eq!(models[1].calls(), succeeded);
+        assert_eq!(failure_count(&pool, 1).await, models[0].calls() as u32);
+        // 接手的 agent 收到完整的历史记录和本次 prompt
+        assert_eq!(models[1].last_messages(), history.len() + 1);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_drains_in_flight() {
+        let model = FakeModel::ok().with_delay(Duration::from_millis(100));
+        let pool = fake_pool(std::slice::from_ref(&model), |builder| builder);
+
+        let request = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.prompt("你好").await }
+        });
+        while pool.in_flight() == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        assert!(pool.shutdown(Duration::from_secs(5)).await);
+        assert_eq!(pool.in_flight(), 0);
+        assert_eq!(request.await.unwrap().unwrap(), "reply 1");
+
+        // 关闭后的新请求被拒绝，不会调用提供方
+        assert!(pool.prompt("你好").await.is_err());
+        assert!(pool.get_random_valid_agent_index().await.is_none());
+        assert_eq!(model.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_tenant_limits_reject_requests() {
+        let pool = fake_pool(
+            &[FakeModel::ok().with_delay(Duration::from_millis(50))],
+            |builder| builder,
+        );
+        pool.set_tenant_limits(
+            "acme",
+            TenantLimits::new().max_concurrent(1).max_requests(2),
+        )
+        .await;
+        let acme = pool.for_tenant("acme");
+
+        let first = tokio::spawn({
+            let acme = acme.clone();
+            async move { acme.prompt("你好").await }
+        });
+        while pool.in_flight() == 0 {
+            tokio::task::yield_now().await;
+        }
+        let err = acme.prompt("你好").await.unwrap_err();
+        assert!(err.to_string().contains("max_concurrent"));
+        // 其它租户不受影响
+        assert!(pool.for_tenant("globex").prompt("你好").await.is_ok());
+        assert!(first.await.unwrap().is_ok());
+
+        // 请求结束后释放并发名额，之后达到请求次数上限
+        assert!(acme.prompt("你好").await.is_ok());
+        let err = acme.prompt("你好").await.unwrap_err();
+        assert!(err.to_string().contains("max_requests"));
+        assert_eq!(pool.tenant_usage("acme").await.total.requests, 2);
+    }
+
+    #[tokio::test]
+    async fn test_preamble_version_updates_cached_system_block() {
+        let pool = fake_pool(&[FakeModel::ok()], |builder| builder);
+        pool.register_preamble("v2", "新提示词");
+        assert!(pool.set_active_preamble("v2"));
+
+        let mut agent = FakeModel::ok().agent();
+        agent.additional_params = Some(serde_json::json!({
+            "system": [{
+                "type": "text",
+                "text": "旧提示词",
+                "cache_control": {"type": "ephemeral"}
+            }]
+        }));
+        let agent = pool.apply_preamble(Arc::new(agent), &Span::none());
+        assert_eq!(agent.preamble.as_deref(), Some("新提示词"));
+        assert_eq!(
+            agent.additional_params.as_ref().unwrap()["system"][0]["text"],
+            "新提示词"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_random_valid_agent_index_has_no_side_effects() {
+        let model = FakeModel::ok();
+        let pool = fake_pool(std::slice::from_ref(&model), |builder| builder);
+        let limit = RateLimit {
+            rpm: Some(1),
+            tpm: None,
+        };
+        assert!(pool.set_rate_limit(1, limit).await);
+
+        // 多次查询不消耗 rpm 配额
+        for _ in 0..3 {
+            assert_eq!(pool.get_random_valid_agent_index().await, Some(0));
+        }
+        assert_eq!(pool.prompt("你好").await.unwrap(), "reply 1");
+        assert!(pool.get_random_valid_agent_index().await.is_none());
+
+        // 冷却结束后的查询不占用半开试探
+        let pool = fake_pool(std::slice::from_ref(&model), |builder| {
+            builder.max_failures(1).cooldown(Duration::ZERO)
+        });
+        {
+            let agents = pool.agents.read().await;
+            let mut state = lock_slot(&agents[0]);
+            state.record_failure();
+        }
+        assert_eq!(pool.get_random_valid_agent_index().await, Some(0));
+        assert_eq!(pool.get_random_valid_agent_index().await, Some(0));
+        assert!(
+            lock_slot(&pool.agents.read().await[0])
+                .trial_started
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_response_cache_before_selection() {
+        use crate::response_cache::InMemoryCache;
+
+        let model = FakeModel::ok();
+        let pool = fake_pool(std::slice::from_ref(&model), |builder| {
+            builder.with_cache(InMemoryCache::new(16, Duration::from_secs(60)))
+        });
+        let limit = RateLimit {
+            rpm: Some(1),
+            tpm: None,
+        };
+        assert!(pool.set_rate_limit(1, limit).await);
+
+        let reply = pool.prompt("你好").await.unwrap();
+        // 命中缓存时不选择 agent，不受速率限制
+        assert_eq!(pool.prompt("  你好 ").await.unwrap(), reply);
+        assert_eq!(pool.prompt_hedged("你好", 2).await.unwrap(), reply);
+        assert_eq!(model.calls(), 1);
+        assert!(pool.prompt("再见").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_errors_redacted() {
+        let model = FakeModel::failing_with("401 invalid api_key=abcdef123456");
+        let pool = fake_pool(std::slice::from_ref(&model), |builder| builder);
+        let acme = pool.for_tenant("acme");
+
+        let err = acme.prompt("hi").await.unwrap_err();
+        assert!(err.to_string().contains("api_key=***"), "{err}");
+        let audit = pool.tenant_audit_log("acme").await;
+        assert_eq!(audit[0].error.as_deref(), Some(err.to_string().as_str()));
+
+        let model = FakeModel::failing_with("Bearer abcdefghijklmnop");
+        let pool = fake_pool(std::slice::from_ref(&model), |builder| builder);
+        let config = HealthCheckConfig::new(Duration::from_secs(60), "ping");
+        let report = pool.check_health(&config).await;
+        let error = report.agents[0].error.as_deref().unwrap();
+        assert!(!error.contains("abcdefghijklmnop"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn test_hooks_run_outside_slot_lock() {
+        let model = FakeModel::failing();
+        let pool = Arc::new(std::sync::OnceLock::<RandAgent>::new());
+        let locked = Arc::new(AtomicUsize::new(0));
+        let hook = |pool: &Arc<std::sync::OnceLock<RandAgent>>, locked: &Arc<AtomicUsize>| {
+            let (pool, locked) = (pool.clone(), locked.clone());
+            move || {
+                // 回调中访问 agent 的锁，持锁调用回调时会死锁
+                let agents = pool.get().unwrap().agents.try_read().unwrap();
+                for slot in agents.iter() {
+                    if slot.try_lock().is_err() {
+                        locked.fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+            }
+        };
+        let (on_failure, on_invalid) = (hook(&pool, &locked), hook(&pool, &locked));
+        let _ = pool.set(fake_pool(std::slice::from_ref(&model), |builder| {
+            builder
+                .max_failures(1)
+                .on_failure(move |_, _| on_failure())
+                .on_agent_invalid(move |_| on_invalid())
+        }));
+
+        assert!(pool.get().unwrap().prompt("hi").await.is_err());
+        assert_eq!(model.calls(), 1);
+        assert_eq!(locked.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_prompt_timeout() {
+        use crate::retry::RetryOn;
+
+        let slow = async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            Ok("done".to_string())
+        };
+        let err = with_timeout(Some(Duration::from_millis(10)), slow)
+            .await
+            .unwrap_err();
+        assert!(RetryOn::Http.matches(&err));
+
+        let fast = async { Ok("done".to_string()) };
+        assert_eq!(with_timeout(None, fast).await.unwrap(), "done");
+    }
+
+    #[test]
+    fn test_response_validator() {
+        assert!(!ResponseValidator::NonEmpty.validate("  \n\t"));
+        assert!(ResponseValidator::NonEmpty.validate(" ok "));
+        assert!(!ResponseValidator::MinLength(3).validate(" 你好 "));
+        assert!(ResponseValidator::MinLength(2).validate(" 你好 "));
+        let custom = ResponseValidator::custom(|content| content.starts_with('{'));
+        assert!(custom.validate("{}"));
+        assert!(!custom.validate("[]"));
+    }
+
+    #[test]
+    fn test_request_hooks_dispatch() {
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        let starts = Arc::new(AtomicU32::new(0));
+        let successes = Arc::new(AtomicU32::new(0));
+        let failures = Arc::new(AtomicU32::new(0));
+        let (r, s, f) = (starts.clone(), successes.clone(), failures.clone());
+        let hooks = RequestHooks {
+            on_request_start: Some(Arc::new(move |info| {
+                assert_eq!(info.id, 1);
+                r.fetch_add(1, Ordering::SeqCst);
+            })),
+            on_success: Some(Arc::new(move |_, _| {
+                s.fetch_add(1, Ordering::SeqCst);
+            })),
+            on_failure: Some(Arc::new(move |_, _| {
+                f.fetch_add(1, Ordering::SeqCst);
+            })),
+            ..Default::default()
+        };
+        let info = AgentInfo {
+            id: 1,
+            provider: "bigmodel".to_string(),
+            model: "glm-4-flash".to_string(),
+            failure_count: 0,
+            max_failures: 3,
+            avg_latency: None,
+            input_price: None,
+            output_price: None,
+            disabled: false,
+            reserved_for: None,
+            structured_output: StructuredOutput::Prompt,
+            context_length: None,
+        };
+        hooks.result(&info, &Ok("ok".to_string()), Duration::from_millis(10));
+        hooks.result(&info, &Err(no_valid_agent_error()), Duration::ZERO);
+        assert_eq!(starts.load(Ordering::SeqCst), 0);
+        hooks.request_start(&info);
+        assert_eq!(starts.load(Ordering::SeqCst), 1);
+        assert_eq!(successes.load(Ordering::SeqCst), 1);
+        assert_eq!(failures.load(Ordering::SeqCst), 1);
+    }
+}
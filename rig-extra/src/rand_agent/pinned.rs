@@ -0,0 +1,113 @@
+//! 优先使用指定 agent 的请求，不可用或失败时回退到代理池
+
+use super::{AgentState, RandAgent, lock_slot, unavailable_error};
+use crate::error::RandAgentError;
+use crate::redact::redacted;
+use rig::completion::{Message, PromptError};
+
+impl RandAgent {
+    /// 优先使用指定 id 的 agent，该 agent 不可用或请求失败时回退到代理池中的其它 agent
+    ///
+    /// 适合让重要请求使用指定的模型，同时保留故障转移能力
+    pub async fn prompt_on(
+        &self,
+        agent_id: i32,
+        prompt: impl Into<Message> + Send,
+    ) -> Result<String, PromptError> {
+        self.prompt_pinned("prompt_on", |state| state.id == agent_id, prompt.into())
+            .await
+    }
+
+    /// 优先使用指定提供方和模型的 agent，规则同 [`prompt_on`](Self::prompt_on)
+    ///
+    /// 提供方名称不区分大小写
+    pub async fn prompt_preferring(
+        &self,
+        provider: &str,
+        model: &str,
+        prompt: impl Into<Message> + Send,
+    ) -> Result<String, PromptError> {
+        self.prompt_pinned(
+            "prompt_preferring",
+            |state| state.info.provider.eq_ignore_ascii_case(provider) && state.info.model == model,
+            prompt.into(),
+        )
+        .await
+    }
+
+    async fn prompt_pinned(
+        &self,
+        method: &'static str,
+        preferred: impl Fn(&AgentState) -> bool,
+        prompt: Message,
+    ) -> Result<String, PromptError> {
+        let prompt = self.screen(prompt)?;
+        let mut excluded = Vec::new();
+        let pinned = self.available_slots().await.into_iter().find(|slot| {
+            let state = lock_slot(slot);
+            preferred(&state) && !self.is_provider_open(&state.info.provider)
+        });
+        // 指定的 agent 在选出后达到速率限制时直接回退到代理池
+        let pinned = match pinned {
+            Some(slot) => match self.acquire_slot(&slot).await {
+                Ok(permit) => Some((slot, permit)),
+                Err(RandAgentError::RateLimited { .. }) => None,
+                Err(err) => return Err(unavailable_error(err)),
+            },
+            None => None,
+        };
+        if let Some((slot, permit)) = pinned {
+            let id = lock_slot(&slot).id;
+            match self.prompt_slot(&slot, method, prompt.clone()).await {
+                Ok(content) => return Ok(content),
+                Err(err) => {
+                    tracing::warn!(
+                        "指定的 agent {id} 请求失败，回退到代理池: {}",
+                        redacted(&err)
+                    );
+                    excluded.push(id);
+                }
+            }
+            drop(permit);
+        }
+
+        let (slot, _permit) = self
+            .pick_slot_excluding(&excluded)
+            .await
+            .map_err(unavailable_error)?;
+        self.prompt_slot(&slot, method, prompt).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rand_agent::tests::{FakeModel, fake_pool};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_pinned_prompt_checks() {
+        use crate::tenant::TenantLimits;
+
+        let model = FakeModel::ok();
+        let pool = fake_pool(std::slice::from_ref(&model), |builder| builder);
+        pool.set_tenant_limits("acme", TenantLimits::new().max_requests(1))
+            .await;
+        let acme = pool.for_tenant("acme");
+
+        assert!(acme.prompt_on(1, "hi").await.is_ok());
+        // 指定 agent 的请求同样计入租户额度
+        assert_eq!(pool.tenant_usage("acme").await.total.requests, 1);
+        let err = acme.prompt_on(1, "hi").await.unwrap_err();
+        assert!(err.to_string().contains("max_requests"), "{err}");
+
+        pool.shutdown(Duration::from_millis(10)).await;
+        assert!(pool.prompt_on(1, "hi").await.is_err());
+        assert!(
+            pool.prompt_preferring("fake1", "fake-model", "hi")
+                .await
+                .is_err()
+        );
+        assert!(pool.prompt_all("hi").await.iter().all(|(_, r)| r.is_err()));
+        assert_eq!(model.calls(), 1);
+    }
+}
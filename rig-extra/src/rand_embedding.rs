@@ -0,0 +1,358 @@
+//! 嵌入模型池
+//!
+//! 与 [`RandAgent`](crate::rand_agent::RandAgent) 相同的思路用于嵌入模型: [`RandEmbeddingBuilder`]
+//! 接收多个提供方或多个密钥的嵌入模型，构建出的 [`RandEmbeddingModel`] 实现 rig 的
+//! [`EmbeddingModel`]，每批文本随机选择一个可用模型，失败时换用其它模型重试。
+//! 连续失败达到 `max_failures` 次的模型不再被选中，成功一次即清零。
+//!
+//! ```rust,ignore
+//! use rig_extra::rand_embedding::RandEmbeddingBuilder;
+//!
+//! let embedding_model = RandEmbeddingBuilder::new()
+//!     .max_failures(3)
+//!     .add_model(openai_a.embedding_model("text-embedding-3-small"), 1, "openai", "text-embedding-3-small")
+//!     .add_model(openai_b.embedding_model("text-embedding-3-small"), 2, "openai", "text-embedding-3-small")
+//!     .build()?;
+//!
+//! let embeddings = EmbeddingsBuilder::new(embedding_model.clone())
+//!     .documents(documents)?
+//!     .build()
+//!     .await?;
+//! ```
+//!
+//! 不同模型的向量不在同一个空间中，池中的模型应当是同一个模型(或声明兼容的模型)，
+//! 构建时检查各模型的维度是否一致。
+
+use crate::i18n::MessageKey;
+use crate::redact::{redact_embedding_error, redacted};
+use rand::seq::SliceRandom;
+use rig::embeddings::{Embedding, EmbeddingError, EmbeddingModel};
+use std::sync::{Arc, Mutex};
+
+/// 构建嵌入模型池失败
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RandEmbeddingError {
+    #[error("{}", MessageKey::NoEmbeddingModels.text())]
+    NoModels,
+    #[error("{}: {id} ({found} != {expected})", MessageKey::EmbeddingDimensionMismatch.text())]
+    DimensionMismatch {
+        id: i32,
+        expected: usize,
+        found: usize,
+    },
+}
+
+/// 池中单个嵌入模型的信息和统计
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbeddingModelInfo {
+    pub id: i32,
+    /// 提供者
+    pub provider: String,
+    /// 模型名称
+    pub model: String,
+    /// 连续失败次数
+    pub failure_count: u32,
+    /// 最大失败次数
+    pub max_failures: u32,
+    /// 请求次数(每批文本一次)
+    pub requests: u64,
+    /// 失败的请求次数
+    pub failures: u64,
+    /// 成功嵌入的文本数
+    pub texts: u64,
+}
+
+impl EmbeddingModelInfo {
+    fn is_valid(&self) -> bool {
+        self.failure_count < self.max_failures
+    }
+}
+
+/// 不引入 `EmbeddingModelDyn` 的方法，避免与 `EmbeddingModel` 的同名方法冲突
+type DynEmbeddingModel = Arc<dyn rig::embeddings::EmbeddingModelDyn>;
+
+struct EmbeddingSlot {
+    model: DynEmbeddingModel,
+    info: Mutex<EmbeddingModelInfo>,
+}
+
+impl EmbeddingSlot {
+    fn info(&self) -> std::sync::MutexGuard<'_, EmbeddingModelInfo> {
+        self.info.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// 嵌入模型池构建器
+pub struct RandEmbeddingBuilder {
+    models: Vec<(DynEmbeddingModel, i32, String, String)>,
+    max_failures: u32,
+}
+
+impl Default for RandEmbeddingBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RandEmbeddingBuilder {
+    pub fn new() -> Self {
+        Self {
+            models: Vec::new(),
+            max_failures: 3,
+        }
+    }
+
+    /// 连续失败多少次后不再选中该模型，默认 3
+    pub fn max_failures(mut self, max_failures: u32) -> Self {
+        self.max_failures = max_failures;
+        self
+    }
+
+    pub fn add_model<M>(
+        mut self,
+        model: M,
+        id: i32,
+        provider: impl Into<String>,
+        model_name: impl Into<String>,
+    ) -> Self
+    where
+        M: EmbeddingModel + 'static,
+    {
+        self.models
+            .push((Arc::new(model), id, provider.into(), model_name.into()));
+        self
+    }
+
+    /// 构建嵌入模型池，模型为空或维度不一致时返回错误
+    pub fn build(self) -> Result<RandEmbeddingModel, RandEmbeddingError> {
+        let ndims = self
+            .models
+            .first()
+            .map(|(model, ..)| model.ndims())
+            .ok_or(RandEmbeddingError::NoModels)?;
+        let slots = self
+            .models
+            .into_iter()
+            .map(|(model, id, provider, model_name)| {
+                if model.ndims() != ndims {
+                    return Err(RandEmbeddingError::DimensionMismatch {
+                        id,
+                        expected: ndims,
+                        found: model.ndims(),
+                    });
+                }
+                Ok(EmbeddingSlot {
+                    model,
+                    info: Mutex::new(EmbeddingModelInfo {
+                        id,
+                        provider,
+                        model: model_name,
+                        failure_count: 0,
+                        max_failures: self.max_failures,
+                        requests: 0,
+                        failures: 0,
+                        texts: 0,
+                    }),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(RandEmbeddingModel {
+            slots: Arc::new(slots),
+            ndims,
+        })
+    }
+}
+
+/// 随机选择并在失败时切换的嵌入模型池
+#[derive(Clone)]
+pub struct RandEmbeddingModel {
+    slots: Arc<Vec<EmbeddingSlot>>,
+    ndims: usize,
+}
+
+impl RandEmbeddingModel {
+    /// 所有模型的信息和统计
+    pub fn get_models_info(&self) -> Vec<EmbeddingModelInfo> {
+        self.slots.iter().map(|slot| slot.info().clone()).collect()
+    }
+
+    /// 当前可用的模型数
+    pub fn valid_count(&self) -> usize {
+        self.slots
+            .iter()
+            .filter(|slot| slot.info().is_valid())
+            .count()
+    }
+
+    /// 清零所有模型的连续失败次数
+    pub fn reset_failures(&self) {
+        for slot in self.slots.iter() {
+            slot.info().failure_count = 0;
+        }
+    }
+
+    /// 可用模型的随机顺序，全部不可用时返回空
+    fn candidates(&self) -> Vec<&EmbeddingSlot> {
+        let mut candidates: Vec<_> = self
+            .slots
+            .iter()
+            .filter(|slot| slot.info().is_valid())
+            .collect();
+        candidates.shuffle(&mut rand::rng());
+        candidates
+    }
+
+    /// 嵌入一批文本，依次尝试可用模型直到成功
+    async fn embed_batch(&self, texts: Vec<String>) -> Result<Vec<Embedding>, EmbeddingError> {
+        let mut last_error = None;
+        for slot in self.candidates() {
+            slot.info().requests += 1;
+            match slot.model.embed_texts(texts.clone()).await {
+                Ok(embeddings) => {
+                    let mut info = slot.info();
+                    info.failure_count = 0;
+                    info.texts += texts.len() as u64;
+                    return Ok(embeddings);
+                }
+                Err(err) => {
+                    let mut info = slot.info();
+                    info.failure_count += 1;
+                    info.failures += 1;
+                    tracing::warn!(
+                        id = info.id,
+                        provider = %info.provider,
+                        model = %info.model,
+                        failure_count = info.failure_count,
+                        "嵌入请求失败: {}",
+                        redacted(&err)
+                    );
+                    last_error = Some(redact_embedding_error(err));
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            EmbeddingError::ProviderError(MessageKey::NoValidEmbeddingModels.text().to_string())
+        }))
+    }
+}
+
+impl EmbeddingModel for RandEmbeddingModel {
+    /// 实际按池中模型的最小上限分块
+    const MAX_DOCUMENTS: usize = 1024;
+
+    fn ndims(&self) -> usize {
+        self.ndims
+    }
+
+    async fn embed_texts(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        // 按池中最小的上限分块，换用其它模型重试时不必重新分块
+        let chunk_size = self
+            .slots
+            .iter()
+            .map(|slot| slot.model.max_documents())
+            .min()
+            .unwrap_or(1)
+            .max(1);
+        let texts: Vec<String> = texts.into_iter().collect();
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for chunk in texts.chunks(chunk_size) {
+            embeddings.extend(self.embed_batch(chunk.to_vec()).await?);
+        }
+        Ok(embeddings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[derive(Clone)]
+    struct FakeEmbedding {
+        ndims: usize,
+        fail: Arc<AtomicBool>,
+    }
+
+    impl EmbeddingModel for FakeEmbedding {
+        const MAX_DOCUMENTS: usize = 2;
+
+        fn ndims(&self) -> usize {
+            self.ndims
+        }
+
+        async fn embed_texts(
+            &self,
+            texts: impl IntoIterator<Item = String> + Send,
+        ) -> Result<Vec<Embedding>, EmbeddingError> {
+            if self.fail.load(Ordering::SeqCst) {
+                return Err(EmbeddingError::ProviderError("rate limited".to_string()));
+            }
+            Ok(texts
+                .into_iter()
+                .map(|document| Embedding {
+                    vec: vec![document.len() as f64; self.ndims],
+                    document,
+                })
+                .collect())
+        }
+    }
+
+    fn fake(ndims: usize, fail: bool) -> FakeEmbedding {
+        FakeEmbedding {
+            ndims,
+            fail: Arc::new(AtomicBool::new(fail)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failover_and_stats() {
+        assert_eq!(
+            RandEmbeddingBuilder::new()
+                .add_model(fake(3, false), 1, "a", "m")
+                .add_model(fake(4, false), 2, "b", "m")
+                .build()
+                .err(),
+            Some(RandEmbeddingError::DimensionMismatch {
+                id: 2,
+                expected: 3,
+                found: 4,
+            })
+        );
+
+        let broken = fake(3, true);
+        let pool = RandEmbeddingBuilder::new()
+            .max_failures(1)
+            .add_model(broken.clone(), 1, "a", "m")
+            .add_model(fake(3, false), 2, "b", "m")
+            .build()
+            .unwrap();
+
+        let texts = ["a", "bb", "ccc"].map(String::from);
+        let embeddings = pool.embed_texts(texts).await.unwrap();
+        assert_eq!(embeddings.len(), 3);
+        assert_eq!(embeddings[2].vec, vec![3.0; 3]);
+
+        // 分成两块，健康的模型嵌入了全部文本；失败的模型最多被尝试一次后停用
+        let info = pool.get_models_info();
+        assert_eq!(info[1].texts, 3);
+        assert_eq!(info[1].requests, 2);
+        assert!(info[0].failures <= 1);
+        assert_eq!(pool.valid_count(), 2 - info[0].failures as usize);
+
+        // 全部不可用时返回错误
+        let only_broken = RandEmbeddingBuilder::new()
+            .max_failures(1)
+            .add_model(broken, 1, "a", "m")
+            .build()
+            .unwrap();
+        assert!(only_broken.embed_text("a").await.is_err());
+        assert_eq!(only_broken.valid_count(), 0);
+        assert!(only_broken.embed_text("a").await.is_err());
+        assert_eq!(only_broken.get_models_info()[0].requests, 1);
+        only_broken.reset_failures();
+        assert_eq!(only_broken.valid_count(), 1);
+    }
+}
@@ -0,0 +1,127 @@
+//! 单个 agent 的速率限制
+//!
+//! 按一分钟滑动窗口统计每个 agent 的请求数和 token 数，达到 [`RateLimit`] 设置的上限后
+//! 该 agent 在窗口内不会被选中，避免发出注定返回 429 的请求。所有 agent 都达到上限时
+//! 请求返回 [`RandAgentError::RateLimited`](crate::error::RandAgentError::RateLimited)，
+//! 其中携带最早可以重试的等待时间。
+//!
+//! token 数在请求完成后才能得知，因此 `tpm` 只能限制后续请求，窗口内的实际用量可能略超上限。
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// 统计窗口长度
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// 每分钟请求数和 token 数上限，为空表示不限制
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct RateLimit {
+    /// 每分钟最大请求数
+    pub rpm: Option<u32>,
+    /// 每分钟最大 token 数
+    pub tpm: Option<u64>,
+}
+
+impl RateLimit {
+    /// 两项都为空时返回 None
+    pub(crate) fn non_empty(self) -> Option<Self> {
+        (self.rpm.is_some() || self.tpm.is_some()).then_some(self)
+    }
+}
+
+/// 滑动窗口内的请求和 token 记录
+#[derive(Debug, Clone)]
+pub(crate) struct RateWindow {
+    limit: RateLimit,
+    requests: VecDeque<Instant>,
+    tokens: VecDeque<(Instant, u64)>,
+}
+
+impl RateWindow {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            requests: VecDeque::new(),
+            tokens: VecDeque::new(),
+        }
+    }
+
+    pub(crate) fn limit(&self) -> RateLimit {
+        self.limit
+    }
+
+    pub(crate) fn record_request(&mut self, now: Instant) {
+        self.requests.push_back(now);
+    }
+
+    pub(crate) fn record_tokens(&mut self, now: Instant, tokens: u64) {
+        self.tokens.push_back((now, tokens));
+    }
+
+    /// 已达到上限时返回需要等待的时间，未达到时返回 None
+    pub(crate) fn retry_after(&mut self, now: Instant) -> Option<Duration> {
+        self.prune(now);
+        let until_expired = |since: Instant| (since + RATE_WINDOW).saturating_duration_since(now);
+
+        let request_wait = self
+            .limit
+            .rpm
+            .filter(|&rpm| self.requests.len() >= rpm as usize)
+            .and_then(|_| self.requests.front().copied().map(until_expired));
+        let token_wait = self
+            .limit
+            .tpm
+            .filter(|&tpm| self.tokens.iter().map(|(_, tokens)| tokens).sum::<u64>() >= tpm)
+            .and_then(|_| self.tokens.front().map(|&(since, _)| until_expired(since)));
+
+        request_wait.max(token_wait)
+    }
+
+    fn prune(&mut self, now: Instant) {
+        while self
+            .requests
+            .front()
+            .is_some_and(|&since| now.duration_since(since) >= RATE_WINDOW)
+        {
+            self.requests.pop_front();
+        }
+        while self
+            .tokens
+            .front()
+            .is_some_and(|&(since, _)| now.duration_since(since) >= RATE_WINDOW)
+        {
+            self.tokens.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_window() {
+        let start = Instant::now();
+        let mut window = RateWindow::new(RateLimit {
+            rpm: Some(2),
+            tpm: Some(1000),
+        });
+
+        window.record_request(start);
+        assert_eq!(window.retry_after(start), None);
+        window.record_request(start + Duration::from_secs(10));
+        assert_eq!(
+            window.retry_after(start + Duration::from_secs(20)),
+            Some(Duration::from_secs(40))
+        );
+        // 最早的请求移出窗口后恢复可用
+        assert_eq!(window.retry_after(start + Duration::from_secs(60)), None);
+
+        window.record_tokens(start + Duration::from_secs(60), 1200);
+        assert_eq!(
+            window.retry_after(start + Duration::from_secs(61)),
+            Some(Duration::from_secs(59))
+        );
+    }
+}
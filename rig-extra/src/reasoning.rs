@@ -0,0 +1,116 @@
+//! 统一的推理强度参数
+//!
+//! 各提供方控制推理(思考)强度的参数各不相同。[`ReasoningEffort`] 提供统一的
+//! low / medium / high 三档，请求时按选中 agent 的提供方和模型转换为对应参数:
+//!
+//! | 提供方 | 参数 |
+//! | --- | --- |
+//! | OpenAI、Azure(o 系列、gpt-5)，xAI(grok-3-mini) | `reasoning_effort` |
+//! | OpenRouter | `reasoning.effort` |
+//! | Anthropic(Claude 3.7 及以上) | `thinking.budget_tokens`，同时保证 `max_tokens` 大于预算 |
+//! | 智谱(GLM-4.5 及以上) | `thinking.type`，low 关闭思考，其余开启 |
+//!
+//! 不支持的提供方或模型不附加任何参数。
+//!
+//! ```rust,ignore
+//! use rig_extra::reasoning::ReasoningEffort;
+//!
+//! let response = rand_agent
+//!     .with_reasoning(ReasoningEffort::High)
+//!     .prompt("证明根号 2 是无理数")
+//!     .await?;
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+/// 推理强度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    Low,
+    Medium,
+    High,
+}
+
+impl ReasoningEffort {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReasoningEffort::Low => "low",
+            ReasoningEffort::Medium => "medium",
+            ReasoningEffort::High => "high",
+        }
+    }
+
+    /// 按思考 token 预算控制强度的提供方使用的预算
+    pub fn budget_tokens(self) -> u64 {
+        match self {
+            ReasoningEffort::Low => 1024,
+            ReasoningEffort::Medium => 4096,
+            ReasoningEffort::High => 16384,
+        }
+    }
+
+    /// 转换为指定提供方和模型的 `additional_params`，不支持时返回 None
+    pub fn params(self, provider: &str, model: &str) -> Option<Value> {
+        let model = model.to_ascii_lowercase();
+        match provider.to_ascii_lowercase().as_str() {
+            "openai" | "azure"
+                if ["o1", "o3", "o4", "gpt-5"]
+                    .iter()
+                    .any(|prefix| model.starts_with(prefix)) =>
+            {
+                Some(json!({"reasoning_effort": self.as_str()}))
+            }
+            // grok-3-mini 只支持 low 和 high
+            "xai" if model.starts_with("grok-3-mini") => {
+                let effort = match self {
+                    ReasoningEffort::Low => "low",
+                    ReasoningEffort::Medium | ReasoningEffort::High => "high",
+                };
+                Some(json!({"reasoning_effort": effort}))
+            }
+            "openrouter" => Some(json!({"reasoning": {"effort": self.as_str()}})),
+            "anthropic"
+                if ["3-7", "sonnet-4", "opus-4", "haiku-4"]
+                    .iter()
+                    .any(|version| model.contains(version)) =>
+            {
+                Some(json!({
+                    "thinking": {"type": "enabled", "budget_tokens": self.budget_tokens()}
+                }))
+            }
+            "bigmodel" if model.starts_with("glm-4.5") || model.starts_with("glm-4.6") => {
+                let thinking = match self {
+                    ReasoningEffort::Low => "disabled",
+                    ReasoningEffort::Medium | ReasoningEffort::High => "enabled",
+                };
+                Some(json!({"thinking": {"type": thinking}}))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reasoning_params() {
+        assert_eq!(
+            ReasoningEffort::Medium.params("OpenAi", "o3-mini"),
+            Some(json!({"reasoning_effort": "medium"}))
+        );
+        assert_eq!(ReasoningEffort::Medium.params("OpenAi", "gpt-4o"), None);
+        assert_eq!(
+            ReasoningEffort::High.params("Anthropic", "claude-sonnet-4-20250514"),
+            Some(json!({"thinking": {"type": "enabled", "budget_tokens": 16384}}))
+        );
+        assert_eq!(
+            ReasoningEffort::Low.params("Bigmodel", "glm-4.5-air"),
+            Some(json!({"thinking": {"type": "disabled"}}))
+        );
+        assert_eq!(ReasoningEffort::High.params("Ollama", "qwen3:8b"), None);
+    }
+}
@@ -0,0 +1,309 @@
+//! 日志和错误信息中的密钥脱敏
+//!
+//! 提供方的错误信息和调试日志有时会原样带出请求内容，其中可能包含 URL 参数或请求头里的
+//! api key。本 crate 输出的 tracing 日志和错误信息都会先经过 [`redact`] 处理: 代理池记录
+//! 每次请求结果时对错误脱敏，之后的回调、审计日志和返回给调用方的错误都使用脱敏后的错误；
+//! 日志中的错误通过同一个辅助函数输出。
+//!
+//! 内置规则会遮盖 `Bearer` 令牌、`sk-` 开头的密钥以及 `api_key=`、`"key": "..."` 一类的键值；
+//! 通过 `simple_builder` 添加的 agent 的 api key 会自动登记。也可以添加自定义规则:
+//!
+//! ```rust
+//! use rig_extra::redact::{RedactPattern, add_pattern, redact, register_secret};
+//!
+//! register_secret("my-very-secret-key");
+//! add_pattern(RedactPattern::Prefix("ghp_".to_string()));
+//!
+//! assert_eq!(
+//!     redact("token ghp_abcdefghijklmnop used with my-very-secret-key"),
+//!     "token ghp_*** used with ***"
+//! );
+//! assert_eq!(
+//!     redact("GET /v1/models?api_key=abc123456&limit=5"),
+//!     "GET /v1/models?api_key=***&limit=5"
+//! );
+//! ```
+
+use rig::completion::{CompletionError, PromptError};
+use rig::embeddings::EmbeddingError;
+use rig::http_client;
+use std::error::Error;
+use std::fmt::Display;
+use std::sync::{LazyLock, RwLock};
+
+/// 替换后的内容
+const MASK: &str = "***";
+
+/// 短于该长度的字面量密钥不登记，避免误伤普通文本(如 ollama 的占位 key)
+const MIN_SECRET_LEN: usize = 8;
+
+/// 脱敏规则
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RedactPattern {
+    /// 完整匹配的字面量，如某个具体的 api key
+    Literal(String),
+    /// 以指定前缀开头的令牌，保留前缀，如 `sk-`、`ghp_`
+    Prefix(String),
+    /// 键值对中的值，匹配 `key=value`、`key: value`、`"key": "value"` 等形式，键不区分大小写
+    ///
+    /// 键的前后必须是单词边界，`key` 不会匹配 `monkey=...` 或 `keyword: ...`
+    KeyValue(String),
+}
+
+static PATTERNS: LazyLock<RwLock<Vec<RedactPattern>>> = LazyLock::new(|| {
+    RwLock::new(vec![
+        RedactPattern::Prefix("Bearer ".to_string()),
+        RedactPattern::Prefix("sk-".to_string()),
+        RedactPattern::KeyValue("api_key".to_string()),
+        RedactPattern::KeyValue("api-key".to_string()),
+        RedactPattern::KeyValue("apikey".to_string()),
+        RedactPattern::KeyValue("key".to_string()),
+        RedactPattern::KeyValue("access_token".to_string()),
+        RedactPattern::KeyValue("secret".to_string()),
+    ])
+});
+
+/// 添加自定义脱敏规则
+pub fn add_pattern(pattern: RedactPattern) {
+    let mut patterns = PATTERNS.write().unwrap_or_else(|e| e.into_inner());
+    if !patterns.contains(&pattern) {
+        patterns.push(pattern);
+    }
+}
+
+/// 登记需要遮盖的密钥，过短的值会被忽略
+pub fn register_secret(secret: impl Into<String>) {
+    let secret = secret.into();
+    if secret.chars().count() >= MIN_SECRET_LEN {
+        add_pattern(RedactPattern::Literal(secret));
+    }
+}
+
+/// 按当前规则对文本脱敏
+pub fn redact(text: &str) -> String {
+    let patterns = PATTERNS.read().unwrap_or_else(|e| e.into_inner());
+    patterns
+        .iter()
+        .fold(text.to_string(), |text, pattern| match pattern {
+            RedactPattern::Literal(secret) => text.replace(secret.as_str(), MASK),
+            RedactPattern::Prefix(prefix) => mask_prefixed(&text, prefix),
+            RedactPattern::KeyValue(key) => mask_key_values(&text, key),
+        })
+}
+
+/// 脱敏后的错误信息，日志中输出错误统一使用
+pub(crate) fn redacted(err: &dyn Display) -> String {
+    redact(&err.to_string())
+}
+
+/// 信息中含有密钥的错误，脱敏后替换原错误
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+struct RedactedError(String);
+
+/// 对请求错误脱敏，保留错误类别，按类别重试不受影响
+pub(crate) fn redact_prompt_error(err: PromptError) -> PromptError {
+    match err {
+        PromptError::CompletionError(err) => {
+            PromptError::CompletionError(redact_completion_error(err))
+        }
+        err => err,
+    }
+}
+
+fn redact_completion_error(err: CompletionError) -> CompletionError {
+    match err {
+        CompletionError::ProviderError(message) => CompletionError::ProviderError(redact(&message)),
+        CompletionError::ResponseError(message) => CompletionError::ResponseError(redact(&message)),
+        CompletionError::HttpError(err) => CompletionError::HttpError(redact_http_error(err)),
+        CompletionError::RequestError(err) => CompletionError::RequestError(redact_boxed(err)),
+        err => err,
+    }
+}
+
+/// 对嵌入请求错误脱敏，保留错误类别
+pub(crate) fn redact_embedding_error(err: EmbeddingError) -> EmbeddingError {
+    match err {
+        EmbeddingError::ProviderError(message) => EmbeddingError::ProviderError(redact(&message)),
+        EmbeddingError::ResponseError(message) => EmbeddingError::ResponseError(redact(&message)),
+        EmbeddingError::HttpError(err) => EmbeddingError::HttpError(redact_http_error(err)),
+        err => err,
+    }
+}
+
+fn redact_http_error(err: http_client::Error) -> http_client::Error {
+    match err {
+        http_client::Error::InvalidStatusCodeWithMessage(status, message) => {
+            http_client::Error::InvalidStatusCodeWithMessage(status, redact(&message))
+        }
+        http_client::Error::Instance(err) => http_client::Error::Instance(redact_boxed(err)),
+        err => err,
+    }
+}
+
+fn redact_boxed(err: Box<dyn Error + Send + Sync>) -> Box<dyn Error + Send + Sync> {
+    let message = err.to_string();
+    let redacted = redact(&message);
+    if redacted == message {
+        err
+    } else {
+        Box::new(RedactedError(redacted))
+    }
+}
+
+fn is_token_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '~' | '+' | '/' | '=')
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// 遮盖前缀之后的令牌，令牌过短时视为普通文本
+fn mask_prefixed(text: &str, prefix: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(pos) = rest.find(prefix) {
+        let boundary = rest[..pos]
+            .chars()
+            .next_back()
+            .is_none_or(|c| !is_word_char(c));
+        let after = &rest[pos + prefix.len()..];
+        let token_len = after
+            .find(|c: char| !is_token_char(c))
+            .unwrap_or(after.len());
+        result.push_str(&rest[..pos + prefix.len()]);
+        if boundary && token_len >= MIN_SECRET_LEN && after[..token_len] != *MASK {
+            result.push_str(MASK);
+        } else {
+            result.push_str(&after[..token_len]);
+        }
+        rest = &after[token_len..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// 遮盖键值对中的值
+fn mask_key_values(text: &str, key: &str) -> String {
+    let lower = text.to_ascii_lowercase();
+    let key = key.to_ascii_lowercase();
+    let mut result = String::with_capacity(text.len());
+    let mut copied = 0;
+    let mut search_from = 0;
+
+    while let Some(found) = lower[search_from..].find(&key) {
+        let start = search_from + found;
+        let key_end = start + key.len();
+        search_from = key_end;
+
+        let boundary = text[..start]
+            .chars()
+            .next_back()
+            .is_none_or(|c| !is_word_char(c))
+            && text[key_end..]
+                .chars()
+                .next()
+                .is_none_or(|c| !is_word_char(c));
+        if !boundary {
+            continue;
+        }
+
+        // 跳过键后的引号、空白和分隔符
+        let after = &text[key_end..];
+        let Some(separator) = after.find(|c: char| !matches!(c, '"' | '\'' | ' ')) else {
+            continue;
+        };
+        if !after[separator..].starts_with(['=', ':']) {
+            continue;
+        }
+        let value_offset = separator
+            + 1
+            + after[separator + 1..]
+                .find(|c: char| !matches!(c, '"' | '\'' | ' '))
+                .unwrap_or(after.len() - separator - 1);
+        let value = &after[value_offset..];
+        let value_len = value
+            .find(|c: char| {
+                matches!(c, '&' | ',' | '"' | '\'' | '}' | ']' | ';') || c.is_whitespace()
+            })
+            .unwrap_or(value.len());
+        if value_len == 0 || &value[..value_len] == MASK {
+            continue;
+        }
+
+        let value_start = key_end + value_offset;
+        result.push_str(&text[copied..value_start]);
+        result.push_str(MASK);
+        copied = value_start + value_len;
+        search_from = copied;
+    }
+    result.push_str(&text[copied..]);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_patterns() {
+        assert_eq!(
+            redact("Authorization: Bearer abcdefghijklmnop"),
+            "Authorization: Bearer ***"
+        );
+        assert_eq!(
+            redact(r#"{"api_key": "abcdef123456", "model": "glm-4"}"#),
+            r#"{"api_key": "***", "model": "glm-4"}"#
+        );
+        assert_eq!(
+            redact("https://generativelanguage.googleapis.com/v1?key=AIzaSyA12345"),
+            "https://generativelanguage.googleapis.com/v1?key=***"
+        );
+        assert_eq!(
+            redact("invalid key sk-1234567890abcdef"),
+            "invalid key sk-***"
+        );
+        // 普通文本不受影响，键的前后必须是单词边界
+        let plain = r#"{"total_tokens": 42, "monkey": "banana", "task-list": 1}"#;
+        assert_eq!(redact(plain), plain);
+        let plain = "monkey=banana12345&keyword: turkey12345 donkey_key=1";
+        assert_eq!(redact(plain), plain);
+        assert_eq!(
+            redact("x-api-key: abcdef123456, key=abcdef"),
+            "x-api-key: ***, key=***"
+        );
+    }
+
+    #[test]
+    fn test_redact_prompt_error() {
+        let err = PromptError::CompletionError(CompletionError::ProviderError(
+            "401 invalid api_key=abcdef123456".to_string(),
+        ));
+        let err = redact_prompt_error(err);
+        assert!(matches!(
+            &err,
+            PromptError::CompletionError(CompletionError::ProviderError(message))
+                if message == "401 invalid api_key=***"
+        ));
+
+        let err = PromptError::CompletionError(CompletionError::HttpError(
+            http_client::Error::Instance("GET /v1?key=abcdef123456 timed out".into()),
+        ));
+        let err = redact_prompt_error(err);
+        // 保留错误类别
+        assert!(matches!(
+            &err,
+            PromptError::CompletionError(CompletionError::HttpError(_))
+        ));
+        assert!(!err.to_string().contains("abcdef123456"), "{err}");
+
+        let err = redact_embedding_error(EmbeddingError::ProviderError(
+            "401 invalid api_key=abcdef123456".to_string(),
+        ));
+        assert!(matches!(
+            &err,
+            EmbeddingError::ProviderError(message) if message == "401 invalid api_key=***"
+        ));
+    }
+}
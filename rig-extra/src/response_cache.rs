@@ -0,0 +1,211 @@
+//! 响应缓存
+//!
+//! 相同的 prompt 发送到代理池时直接返回缓存的响应，不再调用提供方。缓存键由
+//! 规范化后的 prompt(去掉首尾空白、合并连续空白)以及代理池生效的系统提示词版本和
+//! 推理强度的哈希组成，只缓存通过校验的成功响应。
+//!
+//! 代理池中的 agent 视为可以互相替代，缓存在选择 agent 之前查询，命中时不占用任何
+//! agent 的并发名额和速率额度。只缓存 `prompt`、幂等 prompt 和对冲请求这类纯文本的
+//! 单轮 prompt；多轮对话、流式请求、带图片等内容的 prompt，以及需要特定 agent 结果的
+//! 广播、指定 agent 和结构化输出请求不经过缓存。
+//!
+//! ```rust,ignore
+//! use rig_extra::response_cache::InMemoryCache;
+//!
+//! let rand_agent = RandAgentBuilder::new()
+//!     .with_cache(InMemoryCache::new(1000, Duration::from_secs(3600)))
+//!     .simple_builder(configs, "你是一个助手".to_string())
+//!     .build();
+//! ```
+//!
+//! 实现 [`ResponseCache`] 可以把缓存放到 Redis 等外部存储中，[`CacheKey::digest`]
+//! 提供了适合作为外部存储键的字符串。
+
+use crate::reasoning::ReasoningEffort;
+use futures::future::BoxFuture;
+use rig::completion::Message;
+use rig::message::UserContent;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 缓存键
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    /// 规范化后的 prompt
+    pub prompt: String,
+    /// 系统提示词版本和推理强度的哈希
+    pub context: u64,
+}
+
+impl CacheKey {
+    /// 由纯文本 prompt 构建缓存键，prompt 包含非文本内容时返回 None
+    pub fn new(prompt: &Message, context: u64) -> Option<Self> {
+        let Message::User { content } = prompt else {
+            return None;
+        };
+        let mut texts = Vec::new();
+        for content in content.iter() {
+            match content {
+                UserContent::Text(text) => texts.push(text.text.as_str()),
+                _ => return None,
+            }
+        }
+        Some(Self {
+            prompt: normalize(&texts.join("\n")),
+            context,
+        })
+    }
+
+    /// 适合作为外部存储键的摘要
+    pub fn digest(&self) -> String {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+/// 计算代理池生效的系统提示词和推理强度的哈希
+pub(crate) fn context_hash(preamble: Option<&str>, reasoning: Option<ReasoningEffort>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    preamble.hash(&mut hasher);
+    reasoning.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// 去掉首尾空白并把连续空白合并为一个空格
+fn normalize(prompt: &str) -> String {
+    prompt.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// 可插拔的响应缓存
+pub trait ResponseCache: Send + Sync {
+    /// 获取未过期的响应
+    fn get<'a>(&'a self, key: &'a CacheKey) -> BoxFuture<'a, Option<String>>;
+
+    /// 保存成功的响应
+    fn put<'a>(&'a self, key: CacheKey, response: String) -> BoxFuture<'a, ()>;
+}
+
+/// 内存中的 LRU 缓存
+pub struct InMemoryCache {
+    capacity: usize,
+    ttl: Duration,
+    inner: Mutex<LruEntries>,
+}
+
+#[derive(Default)]
+struct LruEntries {
+    /// 响应、保存时间和最近使用序号
+    entries: HashMap<CacheKey, (String, Instant, u64)>,
+    /// 最近使用序号到键的映射，序号最小的最久未使用
+    order: BTreeMap<u64, CacheKey>,
+    tick: u64,
+}
+
+impl LruEntries {
+    fn touch(&mut self, key: &CacheKey) {
+        self.tick += 1;
+        if let Some((_, _, used)) = self.entries.get_mut(key) {
+            self.order.remove(used);
+            *used = self.tick;
+            self.order.insert(self.tick, key.clone());
+        }
+    }
+
+    fn remove(&mut self, key: &CacheKey) {
+        if let Some((_, _, used)) = self.entries.remove(key) {
+            self.order.remove(&used);
+        }
+    }
+}
+
+impl InMemoryCache {
+    /// 最多保存 `capacity` 条响应，每条响应的有效期为 `ttl`
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            ttl,
+            inner: Mutex::new(LruEntries::default()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entries
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        *self.inner.lock().unwrap_or_else(|e| e.into_inner()) = LruEntries::default();
+    }
+}
+
+impl ResponseCache for InMemoryCache {
+    fn get<'a>(&'a self, key: &'a CacheKey) -> BoxFuture<'a, Option<String>> {
+        Box::pin(async move {
+            let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+            let (response, stored_at, _) = inner.entries.get(key)?;
+            if stored_at.elapsed() >= self.ttl {
+                inner.remove(key);
+                return None;
+            }
+            let response = response.clone();
+            inner.touch(key);
+            Some(response)
+        })
+    }
+
+    fn put<'a>(&'a self, key: CacheKey, response: String) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+            inner.remove(&key);
+            while inner.entries.len() >= self.capacity {
+                let Some((_, oldest)) = inner.order.pop_first() else {
+                    break;
+                };
+                inner.entries.remove(&oldest);
+            }
+            inner.tick += 1;
+            let tick = inner.tick;
+            inner.order.insert(tick, key.clone());
+            inner.entries.insert(key, (response, Instant::now(), tick));
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(prompt: &str) -> CacheKey {
+        CacheKey::new(&Message::user(prompt), 0).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_lru() {
+        assert_eq!(key("  你好\n 世界 "), key("你好 世界"));
+
+        let cache = InMemoryCache::new(2, Duration::from_secs(60));
+        cache.put(key("a"), "A".to_string()).await;
+        cache.put(key("b"), "B".to_string()).await;
+        // 访问 a 后 b 成为最久未使用的
+        assert_eq!(cache.get(&key("a")).await.as_deref(), Some("A"));
+        cache.put(key("c"), "C".to_string()).await;
+        assert!(cache.get(&key("b")).await.is_none());
+        assert_eq!(cache.get(&key("a")).await.as_deref(), Some("A"));
+        assert_eq!(cache.len(), 2);
+
+        let expired = InMemoryCache::new(2, Duration::ZERO);
+        expired.put(key("a"), "A".to_string()).await;
+        assert!(expired.get(&key("a")).await.is_none());
+        assert!(expired.is_empty());
+    }
+}
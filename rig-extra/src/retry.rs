@@ -0,0 +1,144 @@
+//! 可通过配置文件调整的失败重试策略
+//!
+//! 在 Settings 文件中添加 `[retry]` 配置段，无需重新编译即可调整重试参数:
+//!
+//! ```toml
+//! [retry]
+//! max_times = 3
+//! min_delay_ms = 1000
+//! max_delay_ms = 60000
+//! factor = 2.0
+//! jitter = true
+//! retry_on = ["http", "provider", "response"]
+//! distinct_agents = true
+//! ```
+//!
+//! ```rust,ignore
+//! let retry_config: RetryConfig = settings.get("retry").unwrap_or_default();
+//! let rand_agent = RandAgentBuilder::new().retry_config(retry_config).build();
+//! ```
+
+use backon::ExponentialBuilder;
+use rig::completion::{CompletionError, PromptError};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// 需要重试的错误类别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RetryOn {
+    /// 所有错误
+    All,
+    /// 网络/HTTP 错误
+    Http,
+    /// 提供方返回的错误(如限流、鉴权失败)
+    Provider,
+    /// 响应解析或校验失败
+    Response,
+    /// 工具调用错误
+    Tool,
+}
+
+impl RetryOn {
+    /// 判断错误是否属于该类别
+    pub fn matches(&self, err: &PromptError) -> bool {
+        matches!(
+            (self, err),
+            (RetryOn::All, _)
+                | (
+                    RetryOn::Http,
+                    PromptError::CompletionError(CompletionError::HttpError(_))
+                )
+                | (
+                    RetryOn::Provider,
+                    PromptError::CompletionError(CompletionError::ProviderError(_))
+                )
+                | (
+                    RetryOn::Response,
+                    PromptError::CompletionError(
+                        CompletionError::ResponseError(_) | CompletionError::JsonError(_)
+                    )
+                )
+                | (RetryOn::Tool, PromptError::ToolError(_))
+        )
+    }
+}
+
+/// 失败重试配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// 最大重试次数
+    pub max_times: usize,
+    /// 最小重试间隔(毫秒)
+    pub min_delay_ms: u64,
+    /// 最大重试间隔(毫秒)
+    pub max_delay_ms: u64,
+    /// 重试间隔增长因子
+    pub factor: f32,
+    /// 是否添加随机抖动
+    pub jitter: bool,
+    /// 需要重试的错误类别
+    pub retry_on: Vec<RetryOn>,
+    /// 同一次调用中的重试不再选择已失败的 agent，直到代理池耗尽
+    pub distinct_agents: bool,
+}
+
+impl Default for RetryConfig {
+    /// 与 `ExponentialBuilder::default()` 保持一致，重试所有错误
+    fn default() -> Self {
+        Self {
+            max_times: 3,
+            min_delay_ms: 1000,
+            max_delay_ms: 60_000,
+            factor: 2.0,
+            jitter: false,
+            retry_on: vec![RetryOn::All],
+            distinct_agents: false,
+        }
+    }
+}
+
+impl RetryConfig {
+    /// 生成 backon 指数退避配置
+    pub fn backoff(&self) -> ExponentialBuilder {
+        let backoff = ExponentialBuilder::default()
+            .with_min_delay(Duration::from_millis(self.min_delay_ms))
+            .with_max_delay(Duration::from_millis(self.max_delay_ms))
+            .with_factor(self.factor)
+            .with_max_times(self.max_times);
+        if self.jitter {
+            backoff.with_jitter()
+        } else {
+            backoff
+        }
+    }
+
+    /// 判断错误是否需要重试
+    pub fn should_retry(&self, err: &PromptError) -> bool {
+        self.retry_on.iter().any(|class| class.matches(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_config_deserialize() {
+        let config: RetryConfig =
+            serde_json::from_str(r#"{"max_times": 5, "retry_on": ["provider"]}"#).unwrap();
+        assert_eq!(config.max_times, 5);
+        assert_eq!(config.min_delay_ms, 1000);
+        assert_eq!(config.retry_on, vec![RetryOn::Provider]);
+        assert!(!config.distinct_agents);
+
+        let provider_err =
+            PromptError::CompletionError(CompletionError::ProviderError("429".to_string()));
+        let response_err =
+            PromptError::CompletionError(CompletionError::ResponseError("empty".to_string()));
+        assert!(config.should_retry(&provider_err));
+        assert!(!config.should_retry(&response_err));
+        assert!(RetryConfig::default().should_retry(&response_err));
+    }
+}
@@ -0,0 +1,251 @@
+//! 按优先级调度的代理池
+//!
+//! 交互请求和批处理任务共用一个代理池时，批处理任务可能占满并发和速率额度，使交互请求排队。
+//! [`ScheduledRandAgent`] 把请求放入优先级队列，由后台任务按优先级依次发出:
+//!
+//! - 同时进行的请求数不超过 `max_in_flight`，空出名额时总是先发出优先级最高的请求，
+//!   同一优先级按提交顺序
+//! - 所有可用 agent 都达到速率限制时暂停发出，等待限制解除，请求不会因速率限制失败
+//!
+//! ```rust,ignore
+//! use rig_extra::scheduler::{Priority, ScheduledRandAgent};
+//!
+//! let scheduler = ScheduledRandAgent::with_max_in_flight(rand_agent, 8);
+//!
+//! let batch: Vec<_> = documents
+//!     .iter()
+//!     .map(|doc| scheduler.submit(format!("总结: {doc}"), Priority::Low))
+//!     .collect();
+//!
+//! // 后提交的交互请求优先发出
+//! let answer = scheduler.submit("你好", Priority::High).await?;
+//! let summaries = futures::future::join_all(batch).await;
+//! ```
+//!
+//! 丢弃 [`JobHandle`] 即取消尚未发出的请求；丢弃调度器时停止后台任务，队列中的请求返回错误。
+//! 需要在 tokio 运行时中创建。
+
+use crate::rand_agent::RandAgent;
+use rig::completion::{CompletionError, Message, Prompt, PromptError};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::sync::{Notify, Semaphore, oneshot};
+use tokio::task::JoinHandle;
+
+/// 默认同时进行的请求数
+const DEFAULT_MAX_IN_FLIGHT: usize = 4;
+
+/// 请求优先级
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+type JobResult = Result<String, PromptError>;
+
+struct Job {
+    priority: Priority,
+    seq: u64,
+    prompt: Message,
+    reply: oneshot::Sender<JobResult>,
+}
+
+impl PartialEq for Job {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Job {}
+
+impl PartialOrd for Job {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Job {
+    /// 优先级高的先出队，同一优先级先提交的先出队
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+#[derive(Default)]
+struct JobQueue {
+    jobs: Mutex<BinaryHeap<Job>>,
+    notify: Notify,
+    seq: AtomicU64,
+}
+
+impl JobQueue {
+    fn lock(&self) -> std::sync::MutexGuard<'_, BinaryHeap<Job>> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn push(&self, priority: Priority, prompt: Message) -> oneshot::Receiver<JobResult> {
+        let (reply, receiver) = oneshot::channel();
+        let seq = self.seq.fetch_add(1, AtomicOrdering::Relaxed);
+        self.lock().push(Job {
+            priority,
+            seq,
+            prompt,
+            reply,
+        });
+        self.notify.notify_one();
+        receiver
+    }
+
+    /// 取出优先级最高且未被取消的请求
+    fn pop(&self) -> Option<Job> {
+        let mut jobs = self.lock();
+        while let Some(job) = jobs.pop() {
+            if !job.reply.is_closed() {
+                return Some(job);
+            }
+        }
+        None
+    }
+
+    /// 等待队列非空
+    async fn wait(&self) {
+        while self.lock().is_empty() {
+            self.notify.notified().await;
+        }
+    }
+}
+
+/// 已提交请求的结果，可直接 `.await`
+pub struct JobHandle {
+    receiver: oneshot::Receiver<JobResult>,
+}
+
+impl Future for JobHandle {
+    type Output = JobResult;
+
+    #[allow(clippy::result_large_err)] // 与 rig 的 Prompt 接口一致，直接返回 PromptError
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.receiver).poll(cx).map(|result| {
+            result.unwrap_or_else(|_| {
+                Err(PromptError::CompletionError(CompletionError::RequestError(
+                    "调度器已停止".into(),
+                )))
+            })
+        })
+    }
+}
+
+/// 按优先级调度请求的代理池
+pub struct ScheduledRandAgent {
+    agent: RandAgent,
+    queue: Arc<JobQueue>,
+    dispatcher: JoinHandle<()>,
+}
+
+impl ScheduledRandAgent {
+    /// 最多同时进行 4 个请求
+    pub fn new(agent: RandAgent) -> Self {
+        Self::with_max_in_flight(agent, DEFAULT_MAX_IN_FLIGHT)
+    }
+
+    /// 最多同时进行 `max_in_flight` 个请求
+    pub fn with_max_in_flight(agent: RandAgent, max_in_flight: usize) -> Self {
+        let queue = Arc::new(JobQueue::default());
+        let dispatcher = tokio::spawn(dispatch(
+            agent.clone(),
+            queue.clone(),
+            Arc::new(Semaphore::new(max_in_flight.max(1))),
+        ));
+        Self {
+            agent,
+            queue,
+            dispatcher,
+        }
+    }
+
+    /// 提交请求，返回可等待结果的句柄
+    pub fn submit(&self, prompt: impl Into<Message>, priority: Priority) -> JobHandle {
+        JobHandle {
+            receiver: self.queue.push(priority, prompt.into()),
+        }
+    }
+
+    /// 排队中尚未发出的请求数
+    pub fn queued(&self) -> usize {
+        self.queue.lock().len()
+    }
+
+    /// 底层代理池
+    pub fn agent(&self) -> &RandAgent {
+        &self.agent
+    }
+}
+
+impl Drop for ScheduledRandAgent {
+    fn drop(&mut self) {
+        self.dispatcher.abort();
+    }
+}
+
+/// 后台任务: 有空闲名额且未达到速率限制时发出优先级最高的请求
+async fn dispatch(agent: RandAgent, queue: Arc<JobQueue>, in_flight: Arc<Semaphore>) {
+    loop {
+        let Ok(permit) = in_flight.clone().acquire_owned().await else {
+            return;
+        };
+        queue.wait().await;
+        while let Some(wait) = agent.rate_limit_wait().await {
+            tracing::debug!(?wait, "所有 agent 都达到速率限制，暂停调度");
+            tokio::time::sleep(wait).await;
+        }
+        // 等待期间可能有更高优先级的请求加入，此时才出队
+        let Some(job) = queue.pop() else {
+            continue;
+        };
+        let agent = agent.clone();
+        tokio::spawn(async move {
+            let _permit = permit;
+            let result = agent.prompt(job.prompt).await;
+            let _ = job.reply.send(result);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_queue_order() {
+        let queue = JobQueue::default();
+        let _low = queue.push(Priority::Low, Message::user("low"));
+        let _first = queue.push(Priority::Normal, Message::user("normal-1"));
+        let cancelled = queue.push(Priority::High, Message::user("cancelled"));
+        let _high = queue.push(Priority::High, Message::user("high"));
+        let _second = queue.push(Priority::Normal, Message::user("normal-2"));
+        drop(cancelled);
+
+        let order: Vec<Message> = std::iter::from_fn(|| queue.pop())
+            .map(|job| job.prompt)
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                Message::user("high"),
+                Message::user("normal-1"),
+                Message::user("normal-2"),
+                Message::user("low"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,219 @@
+//! 会话记录及其可移植的导出格式
+//!
+//! [`Session`] 保存一次会话的消息(包含工具调用与结果)、每轮的 token 用量和响应的 agent，
+//! 可导出为带版本号的 JSON，用于不同部署之间迁移或离线分析，导入后可直接作为 `chat` 的历史记录。
+//!
+//! 导出格式(version 1):
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "exported_at": 1735689600,
+//!   "sessions": [{
+//!     "id": "user-42",
+//!     "created_at": 1735689000,
+//!     "turns": [
+//!       { "message": { "role": "user", "content": [...] } },
+//!       {
+//!         "message": { "role": "assistant", "content": [...] },
+//!         "agent": { "id": 1, "provider": "bigmodel", "model": "glm-4-flash" },
+//!         "usage": { "input_tokens": 12, "output_tokens": 30, "total_tokens": 42 }
+//!       }
+//!     ]
+//!   }]
+//! }
+//! ```
+//!
+//! `message` 为 rig 的 `Message` 序列化结果，工具调用和工具结果都包含在其中。
+
+use crate::AgentInfo;
+use rig::completion::{Message, Usage};
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 当前导出格式版本
+pub const SESSION_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SessionError {
+    #[error("Json Error: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("不支持的会话格式版本: {0}, 当前版本: {SESSION_FORMAT_VERSION}")]
+    UnsupportedVersion(u32),
+}
+
+/// 响应消息的 agent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentAttribution {
+    pub id: i32,
+    pub provider: String,
+    pub model: String,
+}
+
+impl From<&AgentInfo> for AgentAttribution {
+    fn from(info: &AgentInfo) -> Self {
+        Self {
+            id: info.id,
+            provider: info.provider.clone(),
+            model: info.model.clone(),
+        }
+    }
+}
+
+/// 单轮 token 用量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TurnUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub total_tokens: u64,
+}
+
+impl From<&Usage> for TurnUsage {
+    fn from(usage: &Usage) -> Self {
+        Self {
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+            total_tokens: usage.total_tokens,
+        }
+    }
+}
+
+/// 会话中的一条消息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionTurn {
+    pub message: Message,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent: Option<AgentAttribution>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TurnUsage>,
+}
+
+/// 一次会话
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    /// 创建时间(unix 秒)
+    pub created_at: u64,
+    pub turns: Vec<SessionTurn>,
+}
+
+impl Session {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            created_at: unix_now(),
+            turns: Vec::new(),
+        }
+    }
+
+    /// 添加用户消息
+    pub fn push_user(&mut self, message: impl Into<Message>) {
+        self.turns.push(SessionTurn {
+            message: message.into(),
+            agent: None,
+            usage: None,
+        });
+    }
+
+    /// 添加 agent 响应，记录响应的 agent 和用量
+    pub fn push_response(
+        &mut self,
+        message: impl Into<Message>,
+        agent: Option<&AgentInfo>,
+        usage: Option<&Usage>,
+    ) {
+        self.turns.push(SessionTurn {
+            message: message.into(),
+            agent: agent.map(AgentAttribution::from),
+            usage: usage.map(TurnUsage::from),
+        });
+    }
+
+    /// 转换为 `chat` 使用的历史记录
+    pub fn history(&self) -> Vec<Message> {
+        self.turns.iter().map(|turn| turn.message.clone()).collect()
+    }
+
+    /// 会话累计用量
+    pub fn total_usage(&self) -> TurnUsage {
+        self.turns.iter().filter_map(|turn| turn.usage).fold(
+            TurnUsage::default(),
+            |mut total, usage| {
+                total.input_tokens += usage.input_tokens;
+                total.output_tokens += usage.output_tokens;
+                total.total_tokens += usage.total_tokens;
+                total
+            },
+        )
+    }
+}
+
+/// 会话导出文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionExport {
+    pub version: u32,
+    /// 导出时间(unix 秒)
+    pub exported_at: u64,
+    pub sessions: Vec<Session>,
+}
+
+impl SessionExport {
+    pub fn new(sessions: Vec<Session>) -> Self {
+        Self {
+            version: SESSION_FORMAT_VERSION,
+            exported_at: unix_now(),
+            sessions,
+        }
+    }
+
+    /// 导出为 JSON
+    pub fn to_json(&self) -> Result<String, SessionError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// 从 JSON 导入，版本不兼容时返回错误
+    pub fn from_json(json: &str) -> Result<Self, SessionError> {
+        let export: Self = serde_json::from_str(json)?;
+        if export.version > SESSION_FORMAT_VERSION {
+            return Err(SessionError::UnsupportedVersion(export.version));
+        }
+        Ok(export)
+    }
+}
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_export_roundtrip() {
+        let mut session = Session::new("user-42");
+        session.push_user(Message::user("你好"));
+        let mut usage = Usage::new();
+        usage.input_tokens = 3;
+        usage.output_tokens = 5;
+        usage.total_tokens = 8;
+        session.push_response(Message::assistant("你好！"), None, Some(&usage));
+
+        let json = SessionExport::new(vec![session]).to_json().unwrap();
+        let imported = SessionExport::from_json(&json).unwrap();
+        let session = &imported.sessions[0];
+        assert_eq!(session.id, "user-42");
+        assert_eq!(session.history().len(), 2);
+        assert_eq!(session.total_usage().total_tokens, 8);
+        assert!(session.turns[0].usage.is_none());
+
+        let future = json.replacen("\"version\": 1", "\"version\": 99", 1);
+        assert!(matches!(
+            SessionExport::from_json(&future),
+            Err(SessionError::UnsupportedVersion(99))
+        ));
+    }
+}
@@ -0,0 +1,148 @@
+//! 按提供方平滑请求
+//!
+//! 部分提供方(如智谱免费额度、性能较弱的 Ollama)在突发流量下即使没有达到标称的速率限制
+//! 也会明显变慢或报错。为提供方设置 [`RequestShaping`] 后，发往该提供方的请求之间至少间隔
+//! `min_interval`，空闲后最多允许连续发出 `burst` 个请求。请求在发送前排队等待，不会被拒绝，
+//! 与 [`crate::rate_limit`] 的硬性上限相互独立。
+//!
+//! ```rust,ignore
+//! use rig_extra::shaping::RequestShaping;
+//!
+//! let rand_agent = RandAgentBuilder::new()
+//!     .shape_provider("bigmodel", RequestShaping::new(Duration::from_millis(500)).burst(2))
+//!     .shape_provider("ollama", RequestShaping::new(Duration::from_secs(1)))
+//!     .simple_builder(configs, "你是一个助手".to_string())
+//!     .build();
+//! ```
+//!
+//! 提供方名称不区分大小写，排队时间不计入请求超时。
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 单个提供方的请求平滑设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestShaping {
+    /// 相邻请求的最小间隔
+    pub min_interval: Duration,
+    /// 空闲后允许连续发出的请求数，至少为 1
+    pub burst: u32,
+}
+
+impl RequestShaping {
+    pub fn new(min_interval: Duration) -> Self {
+        Self {
+            min_interval,
+            burst: 1,
+        }
+    }
+
+    pub fn burst(mut self, burst: u32) -> Self {
+        self.burst = burst.max(1);
+        self
+    }
+}
+
+/// 单个提供方的排队状态
+#[derive(Debug)]
+struct ShapingState {
+    shaping: RequestShaping,
+    /// 按最小间隔排队时，下一个请求理论上的发送时间
+    next_at: Option<Instant>,
+}
+
+impl ShapingState {
+    /// 预约一次发送，返回需要等待的时间
+    fn reserve(&mut self, now: Instant) -> Duration {
+        let interval = self.shaping.min_interval;
+        let next_at = self.next_at.map_or(now, |next_at| next_at.max(now));
+        // 允许比理论时间提前 burst - 1 个间隔发送
+        let allowance = interval * self.shaping.burst.saturating_sub(1);
+        let send_at = next_at.checked_sub(allowance).map_or(now, |at| at.max(now));
+        self.next_at = Some(next_at + interval);
+        send_at - now
+    }
+}
+
+/// 所有提供方的请求平滑
+#[derive(Debug, Default)]
+pub(crate) struct RequestShaper {
+    providers: Mutex<HashMap<String, ShapingState>>,
+}
+
+impl RequestShaper {
+    pub(crate) fn set(&self, provider: &str, shaping: Option<RequestShaping>) {
+        let mut providers = self.providers.lock().unwrap_or_else(|e| e.into_inner());
+        let provider = provider.to_ascii_lowercase();
+        match shaping {
+            Some(shaping) => {
+                providers.insert(
+                    provider,
+                    ShapingState {
+                        shaping,
+                        next_at: None,
+                    },
+                );
+            }
+            None => {
+                providers.remove(&provider);
+            }
+        }
+    }
+
+    /// 等待到可以向该提供方发送请求
+    pub(crate) async fn wait(&self, provider: &str) {
+        let wait = {
+            let mut providers = self.providers.lock().unwrap_or_else(|e| e.into_inner());
+            match providers.get_mut(&provider.to_ascii_lowercase()) {
+                Some(state) => state.reserve(Instant::now()),
+                None => return,
+            }
+        };
+        if !wait.is_zero() {
+            tracing::debug!(provider, ?wait, "请求平滑排队");
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserve_burst() {
+        let interval = Duration::from_millis(100);
+        let mut state = ShapingState {
+            shaping: RequestShaping::new(interval).burst(2),
+            next_at: None,
+        };
+        let now = Instant::now();
+        assert_eq!(state.reserve(now), Duration::ZERO);
+        assert_eq!(state.reserve(now), Duration::ZERO);
+        assert_eq!(state.reserve(now), interval);
+        assert_eq!(state.reserve(now), interval * 2);
+
+        // 空闲足够久后恢复突发额度
+        let later = now + Duration::from_secs(1);
+        assert_eq!(state.reserve(later), Duration::ZERO);
+        assert_eq!(state.reserve(later), Duration::ZERO);
+        assert_eq!(state.reserve(later), interval);
+    }
+
+    #[test]
+    fn test_reserve_zero_burst() {
+        let interval = Duration::from_millis(100);
+        let mut state = ShapingState {
+            shaping: RequestShaping {
+                min_interval: interval,
+                burst: 0,
+            },
+            next_at: None,
+        };
+        let now = Instant::now();
+        assert_eq!(state.reserve(now), Duration::ZERO);
+        assert_eq!(state.reserve(now), interval);
+    }
+}
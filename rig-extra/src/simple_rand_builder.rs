@@ -1,12 +1,29 @@
-use crate::extra_providers::bigmodel;
+use crate::extra_providers::{bigmodel, dashscope};
 use crate::get_openai_agent::get_openai_agent;
-use crate::rand_agent::RandAgentBuilder;
+use crate::get_perplexity_agent::get_perplexity_agent;
+use crate::i18n::MessageKey;
+use crate::json_utils::merge;
+use crate::rand_agent::{AgentEntry, RandAgentBuilder};
+use crate::rate_limit::RateLimit;
+use crate::structured::StructuredOutput;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, USER_AGENT};
+use rig::client::builder::BoxAgent;
 use rig::client::completion::CompletionClientDyn;
+use rig::completion::ToolDefinition;
 use rig::providers::*;
+use rig::tool::server::{ToolServer, ToolServerHandle};
+use rig::tool::{Tool, ToolDyn, ToolError};
 use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
 use strum_macros::Display;
 
-#[derive(Debug, Display, Deserialize, Serialize)]
+/// Anthropic 要求必须设置 max_tokens，未配置时使用该默认值
+const ANTHROPIC_DEFAULT_MAX_TOKENS: u64 = 4096;
+
+#[derive(Debug, Clone, Display, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum ProviderEnum {
     Anthropic,
@@ -30,74 +47,452 @@ pub enum ProviderEnum {
     // embedding模型
     // Voyageai,
     Bigmodel,
+    /// 阿里云百炼(通义千问)
+    DashScope,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct AgentConfig {
     pub id: i32,
     pub provider: ProviderEnum,
     pub model_name: String,
+    #[serde(default)]
     pub api_key: String,
+    /// 同一模型的更多密钥，`simple_builder` 为每个密钥构建一个 agent，
+    /// 第一个密钥使用 `id`，其余依次使用所有配置中最大 id 之后的 id
+    #[serde(default)]
+    pub api_keys: Vec<String>,
     pub api_base_url: Option<String>,
     pub system_prompt: Option<String>,
     pub agent_name: Option<String>,
+    /// 最大失败次数，为空时使用构建器的全局设置
+    pub max_failures: Option<u32>,
+    /// 输入价格(每千 token)，用于费用统计
+    #[serde(default)]
+    pub input_price: Option<f64>,
+    /// 输出价格(每千 token)，用于费用统计
+    #[serde(default)]
+    pub output_price: Option<f64>,
+    /// 预留给指定工作负载，如 "interactive"
+    #[serde(default)]
+    pub reserved_for: Option<String>,
+    /// 每分钟最大请求数，达到后该 agent 在窗口内不会被选中
+    #[serde(default)]
+    pub rpm: Option<u32>,
+    /// 每分钟最大 token 数
+    #[serde(default)]
+    pub tpm: Option<u64>,
+    /// 结构化输出支持情况，为空时按 provider 和模型名推断
+    #[serde(default)]
+    pub structured_output: Option<StructuredOutput>,
+    /// 上下文长度(tokens)，为空时按已知模型推断，用于历史记录超长时自动摘要
+    #[serde(default)]
+    pub context_length: Option<u32>,
+    /// 自定义 User-Agent
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    /// 自定义请求头，如 OpenRouter 用于署名和排名的 `HTTP-Referer`、`X-Title`
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Anthropic 专用配置，仅在 provider 为 anthropic 时生效
+    #[serde(default)]
+    pub anthropic: Option<AnthropicOptions>,
+    /// 采样温度
+    #[serde(default)]
+    pub temperature: Option<f64>,
+    /// 最大输出 token 数，设置后覆盖 `anthropic.max_tokens`
+    #[serde(default)]
+    pub max_tokens: Option<u64>,
+    #[serde(default)]
+    pub top_p: Option<f64>,
+    /// 合并到每个请求中的其它参数，如 `{ do_sample = false }`
+    #[serde(default)]
+    pub additional_params: Option<Value>,
+}
+
+impl AgentConfig {
+    /// 只设置必填项的配置，其余配置为空
+    pub fn new(
+        id: i32,
+        provider: ProviderEnum,
+        model_name: impl Into<String>,
+        api_key: impl Into<String>,
+    ) -> Self {
+        Self {
+            id,
+            provider,
+            model_name: model_name.into(),
+            api_key: api_key.into(),
+            api_keys: Vec::new(),
+            api_base_url: None,
+            system_prompt: None,
+            agent_name: None,
+            max_failures: None,
+            input_price: None,
+            output_price: None,
+            reserved_for: None,
+            rpm: None,
+            tpm: None,
+            structured_output: None,
+            context_length: None,
+            user_agent: None,
+            headers: HashMap::new(),
+            anthropic: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            additional_params: None,
+        }
+    }
+
+    /// `api_key`(非空时)和 `api_keys` 中的所有密钥
+    pub fn keys(&self) -> Vec<&str> {
+        std::iter::once(self.api_key.as_str())
+            .filter(|key| !key.is_empty())
+            .chain(self.api_keys.iter().map(String::as_str))
+            .collect()
+    }
+
+    /// 把 `temperature`、`max_tokens`、`top_p` 和 `additional_params` 应用到 agent，
+    /// 与构建时已有的额外参数合并，同名参数以配置为准
+    fn apply_params(&self, agent: &mut BoxAgent<'static>) {
+        if let Some(temperature) = self.temperature {
+            agent.temperature = Some(temperature);
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            agent.max_tokens = Some(max_tokens);
+        }
+        let mut params = self.additional_params.clone();
+        if let Some(top_p) = self.top_p {
+            params = Some(merge(
+                params.unwrap_or_else(|| json!({})),
+                json!({"top_p": top_p}),
+            ));
+        }
+        if let Some(params) = params {
+            agent.additional_params = Some(merge(
+                agent.additional_params.take().unwrap_or_else(|| json!({})),
+                params,
+            ));
+        }
+    }
+
+    /// 由 `user_agent` 和 `headers` 生成请求头，无效的请求头会被忽略
+    pub fn header_map(&self) -> HeaderMap {
+        let mut header_map = HeaderMap::new();
+        if let Some(user_agent) = &self.user_agent {
+            match HeaderValue::from_str(user_agent) {
+                Ok(value) => {
+                    header_map.insert(USER_AGENT, value);
+                }
+                Err(_) => tracing::warn!("agent {} 的 user_agent 无效，已忽略", self.id),
+            }
+        }
+        for (name, value) in &self.headers {
+            match (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                (Ok(name), Ok(value)) => {
+                    header_map.insert(name, value);
+                }
+                _ => tracing::warn!("agent {} 的请求头 {name} 无效，已忽略", self.id),
+            }
+        }
+        header_map
+    }
+
+    /// 创建携带自定义请求头的 HTTP 客户端
+    fn http_client(&self) -> reqwest::Client {
+        reqwest::Client::builder()
+            .default_headers(self.header_map())
+            .build()
+            .unwrap_or_else(|err| {
+                tracing::error!(
+                    "{}: {}",
+                    MessageKey::ProviderBuildFailed.text(),
+                    crate::redact::redacted(&err)
+                );
+                reqwest::Client::new()
+            })
+    }
+}
+
+/// 把包含多个密钥的配置展开为每个密钥一个配置
+///
+/// 第一个密钥沿用配置的 id，其余密钥的 id 从本批配置和 `existing_ids`(构建器中已添加的 agent)
+/// 的最大 id 之后依次分配，多次调用 `simple_builder` 时不会与之前的 agent 重复
+pub(crate) fn expand_api_keys(
+    agent_configs: Vec<AgentConfig>,
+    existing_ids: &[i32],
+) -> Vec<AgentConfig> {
+    let mut next_id = agent_configs
+        .iter()
+        .map(|conf| conf.id)
+        .chain(existing_ids.iter().copied())
+        .max()
+        .unwrap_or(0);
+    let mut expanded = Vec::with_capacity(agent_configs.len());
+    for agent_conf in agent_configs {
+        let keys: Vec<String> = agent_conf.keys().into_iter().map(String::from).collect();
+        if keys.len() <= 1 {
+            expanded.push(AgentConfig {
+                api_key: keys.into_iter().next().unwrap_or_default(),
+                api_keys: Vec::new(),
+                ..agent_conf
+            });
+            continue;
+        }
+        for (index, key) in keys.into_iter().enumerate() {
+            let id = if index == 0 {
+                agent_conf.id
+            } else {
+                next_id += 1;
+                next_id
+            };
+            expanded.push(AgentConfig {
+                id,
+                api_key: key,
+                api_keys: Vec::new(),
+                ..agent_conf.clone()
+            });
+        }
+    }
+    expanded
+}
+
+/// Anthropic 专用配置
+///
+/// ```toml
+/// [[agents]]
+/// provider = "anthropic"
+/// model_name = "glm-4.5-flash"
+/// api_key = "xxxxxxxx"
+/// api_base_url = "https://open.bigmodel.cn/api/anthropic"
+/// anthropic = { max_tokens = 8192, cache_system_prompt = true }
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AnthropicOptions {
+    /// 最大输出 token 数，Anthropic 必填，默认 4096
+    pub max_tokens: Option<u64>,
+    /// 是否为系统提示词开启 prompt caching (`cache_control: ephemeral`)
+    #[serde(default)]
+    pub cache_system_prompt: bool,
+}
+
+impl AnthropicOptions {
+    /// 生成需要合并到请求中的额外参数
+    fn additional_params(&self, system_prompt: &str) -> Option<serde_json::Value> {
+        if !self.cache_system_prompt {
+            return None;
+        }
+        Some(json!({
+            "system": [{
+                "type": "text",
+                "text": system_prompt,
+                "cache_control": {"type": "ephemeral"}
+            }]
+        }))
+    }
+}
+
+/// 替换 agent 的系统提示词
+///
+/// 开启 `cache_system_prompt` 后请求使用 `additional_params` 中的 `system` 块而不是 preamble，
+/// 这里同时更新该块的文本，请求时修改的提示词(提示词版本、工具使用规范等)才会生效
+pub(crate) fn set_preamble(agent: &mut BoxAgent<'static>, preamble: String) {
+    if let Some(Value::Array(blocks)) = agent
+        .additional_params
+        .as_mut()
+        .and_then(|params| params.get_mut("system"))
+        && let Some(block) = blocks.first_mut()
+    {
+        block["text"] = Value::String(preamble.clone());
+    }
+    agent.preamble = Some(preamble);
+}
+
+/// 在多个 agent 之间共享的工具
+#[derive(Clone)]
+struct SharedTool(Arc<dyn ToolDyn>);
+
+impl Tool for SharedTool {
+    const NAME: &'static str = "shared_tool";
+    type Error = ToolError;
+    type Args = Value;
+    type Output = Value;
+
+    fn name(&self) -> String {
+        self.0.name()
+    }
+
+    fn definition(&self, prompt: String) -> impl Future<Output = ToolDefinition> + Send + Sync {
+        // ToolDyn 返回的 future 不是 Sync，放入 Mutex 中轮询
+        let definition = Mutex::new(self.0.definition(prompt));
+        std::future::poll_fn(move |cx| {
+            definition
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .as_mut()
+                .poll(cx)
+        })
+    }
+
+    async fn call(&self, args: Value) -> Result<Value, ToolError> {
+        let output = self.0.call(args.to_string()).await?;
+        Ok(serde_json::from_str(&output).unwrap_or(Value::String(output)))
+    }
+}
+
+/// 为一个 agent 启动挂载共享工具的 ToolServer
+///
+/// 每个 agent 有各自的 ToolServer，避免工具调用在同一个 ToolServer 上排队
+pub(crate) fn shared_tool_server(tools: &[Arc<dyn ToolDyn>]) -> ToolServerHandle {
+    tools
+        .iter()
+        .fold(ToolServer::new(), |server, tool| {
+            server.tool(SharedTool(tool.clone()))
+        })
+        .run()
 }
 
 impl RandAgentBuilder {
+    /// 添加由 AgentConfig 构建的代理
+    fn push_config_agent(&mut self, mut agent: BoxAgent<'static>, agent_conf: &AgentConfig) {
+        agent_conf.apply_params(&mut agent);
+        let mut entry = AgentEntry::new(
+            agent,
+            agent_conf.id,
+            agent_conf.provider.to_string(),
+            agent_conf.model_name.clone(),
+        );
+        entry.max_failures = agent_conf.max_failures;
+        entry.input_price = agent_conf.input_price;
+        entry.output_price = agent_conf.output_price;
+        entry.reserved_for = agent_conf.reserved_for.clone();
+        entry.structured_output = agent_conf.structured_output;
+        entry.context_length = agent_conf.context_length;
+        entry.shared_tools = true;
+        entry.rate_limit = RateLimit {
+            rpm: agent_conf.rpm,
+            tpm: agent_conf.tpm,
+        };
+        self.agents.push(entry);
+    }
+
+    /// 简单构建器，同时为由 AgentConfig 构建的每个 agent 挂载相同的工具
+    ///
+    /// ```rust,ignore
+    /// let tools: Vec<Box<dyn ToolDyn>> = vec![Box::new(DatetimeTool::new())];
+    /// let rand_agent = RandAgentBuilder::new()
+    ///     .simple_builder_with_tools(configs, "你是一个助手".to_string(), tools)
+    ///     .build();
+    /// ```
+    pub fn simple_builder_with_tools(
+        mut self,
+        agent_configs: Vec<AgentConfig>,
+        global_system_prompt: String,
+        tools: Vec<Box<dyn ToolDyn>>,
+    ) -> Self {
+        self.shared_tools
+            .extend(tools.into_iter().map(Arc::<dyn ToolDyn>::from));
+        self.simple_builder(agent_configs, global_system_prompt)
+    }
+
+    /// 为由 AgentConfig 构建的每个 agent 挂载注册器中的所有工具，包括 MCP 工具
+    ///
+    /// 与 [`simple_builder`](Self::simple_builder) 的调用顺序无关，工具重名时返回错误
+    ///
+    /// ```rust,ignore
+    /// let registry = ToolRegistry::new()
+    ///     .prefix(ToolSource::Mcp, "mcp_")
+    ///     .tool(ToolSource::Builtin, DatetimeTool::new())
+    ///     .dyn_tools(ToolSource::Mcp, mcp_tools);
+    /// let rand_agent = RandAgentBuilder::new()
+    ///     .simple_builder(configs, "你是一个助手".to_string())
+    ///     .tool_registry(registry)?
+    ///     .build();
+    /// ```
+    #[cfg(feature = "rig-extra-tools")]
+    pub fn tool_registry(
+        mut self,
+        registry: crate::tools::registry::ToolRegistry,
+    ) -> Result<Self, crate::tools::registry::ToolRegistryError> {
+        self.shared_tools
+            .extend(registry.build()?.into_iter().map(Arc::<dyn ToolDyn>::from));
+        Ok(self)
+    }
+
     /// 简单构建器
     pub fn simple_builder(
         mut self,
         agent_configs: Vec<AgentConfig>,
         global_system_prompt: String,
     ) -> Self {
-        for agent_conf in agent_configs {
-            let agent_name = agent_conf.agent_name.unwrap_or("rand agent".to_string());
+        let existing_ids: Vec<i32> = self.agents.iter().map(|entry| entry.id).collect();
+        for agent_conf in expand_api_keys(agent_configs, &existing_ids) {
+            let agent_name = agent_conf
+                .agent_name
+                .clone()
+                .unwrap_or("rand agent".to_string());
             let system_prompt = agent_conf
                 .system_prompt
+                .clone()
                 .unwrap_or(global_system_prompt.clone());
+            crate::redact::register_secret(&agent_conf.api_key);
+            let http_client = agent_conf.http_client();
 
             match agent_conf.provider {
                 ProviderEnum::Anthropic => {
                     let mut client_builder =
-                        anthropic::ClientBuilder::<reqwest::Client>::new(&agent_conf.api_key);
+                        anthropic::ClientBuilder::new_with_client(&agent_conf.api_key, http_client);
                     if let Some(api_base_url) = &agent_conf.api_base_url {
                         client_builder = client_builder.base_url(api_base_url);
                     }
                     match client_builder.build() {
                         Ok(client) => {
-                            let agent = client
+                            let options = agent_conf.anthropic.clone().unwrap_or_default();
+                            let mut agent_builder = client
                                 .agent(&agent_conf.model_name)
                                 .name(agent_name.as_str())
                                 .preamble(&system_prompt)
-                                .build();
-                            self.agents.push((
-                                agent,
-                                agent_conf.id,
-                                agent_conf.provider.to_string(),
-                                agent_conf.model_name,
-                            ));
+                                .max_tokens(
+                                    options.max_tokens.unwrap_or(ANTHROPIC_DEFAULT_MAX_TOKENS),
+                                );
+                            if let Some(params) = options.additional_params(&system_prompt) {
+                                agent_builder = agent_builder.additional_params(params);
+                            }
+                            let agent = agent_builder.build();
+                            self.push_config_agent(agent, &agent_conf);
                         }
                         Err(err) => {
-                            tracing::error!("添加 {} 错误: {}", agent_conf.provider, err);
+                            tracing::error!(
+                                "{} {}: {}",
+                                MessageKey::ProviderBuildFailed.text(),
+                                agent_conf.provider,
+                                crate::redact::redacted(&err)
+                            );
                         }
                     }
                 }
                 ProviderEnum::Cohere => {
-                    let client = cohere::Client::new(&agent_conf.api_key);
+                    let client = cohere::client::ClientBuilder::new_with_client(
+                        &agent_conf.api_key,
+                        http_client,
+                    )
+                    .build();
                     let agent = client
                         .agent(&agent_conf.model_name)
                         .name(agent_name.as_str())
                         .preamble(&system_prompt)
                         .build();
-                    self.agents.push((
-                        agent,
-                        agent_conf.id,
-                        agent_conf.provider.to_string(),
-                        agent_conf.model_name,
-                    ));
+                    self.push_config_agent(agent, &agent_conf);
                 }
                 ProviderEnum::Gemini => {
-                    let mut client_builder = gemini::Client::builder(&agent_conf.api_key);
+                    let mut client_builder = gemini::client::ClientBuilder::new_with_client(
+                        &agent_conf.api_key,
+                        http_client,
+                    );
                     if let Some(api_base_url) = &agent_conf.api_base_url {
                         client_builder = client_builder.base_url(api_base_url);
                     }
@@ -108,48 +503,56 @@ impl RandAgentBuilder {
                                 .name(agent_name.as_str())
                                 .preamble(&system_prompt)
                                 .build();
-                            self.agents.push((
-                                agent,
-                                agent_conf.id,
-                                agent_conf.provider.to_string(),
-                                agent_conf.model_name,
-                            ));
+                            self.push_config_agent(agent, &agent_conf);
                         }
                         Err(err) => {
-                            tracing::error!("添加 {} 错误: {}", agent_conf.provider, err);
+                            tracing::error!(
+                                "{} {}: {}",
+                                MessageKey::ProviderBuildFailed.text(),
+                                agent_conf.provider,
+                                crate::redact::redacted(&err)
+                            );
                         }
                     }
                 }
                 ProviderEnum::Huggingface => {
-                    let client = huggingface::Client::new(&agent_conf.api_key);
-                    let agent = client
-                        .agent(&agent_conf.model_name)
-                        .name(agent_name.as_str())
-                        .preamble(&system_prompt)
-                        .build();
-                    self.agents.push((
-                        agent,
-                        agent_conf.id,
-                        agent_conf.provider.to_string(),
-                        agent_conf.model_name,
-                    ));
+                    let client_builder = huggingface::ClientBuilder::new_with_client(
+                        &agent_conf.api_key,
+                        http_client,
+                    );
+                    match client_builder.build() {
+                        Ok(client) => {
+                            let agent = client
+                                .agent(&agent_conf.model_name)
+                                .name(agent_name.as_str())
+                                .preamble(&system_prompt)
+                                .build();
+                            self.push_config_agent(agent, &agent_conf);
+                        }
+                        Err(err) => {
+                            tracing::error!(
+                                "{} {}: {}",
+                                MessageKey::ProviderBuildFailed.text(),
+                                agent_conf.provider,
+                                crate::redact::redacted(&err)
+                            );
+                        }
+                    }
                 }
                 ProviderEnum::Mistral => {
-                    let client = mistral::Client::new(&agent_conf.api_key);
+                    let client =
+                        mistral::ClientBuilder::new_with_client(&agent_conf.api_key, http_client)
+                            .build();
                     let agent = client
                         .agent(&agent_conf.model_name)
                         .name(agent_name.as_str())
                         .preamble(&system_prompt)
                         .build();
-                    self.agents.push((
-                        agent,
-                        agent_conf.id,
-                        agent_conf.provider.to_string(),
-                        agent_conf.model_name,
-                    ));
+                    self.push_config_agent(agent, &agent_conf);
                 }
                 ProviderEnum::OpenAi => {
-                    let mut client_builder = openai::ClientBuilder::new(&agent_conf.api_key);
+                    let mut client_builder =
+                        openai::ClientBuilder::new_with_client(&agent_conf.api_key, http_client);
                     if let Some(api_base_url) = &agent_conf.api_base_url {
                         client_builder = client_builder.base_url(api_base_url)
                     }
@@ -158,16 +561,13 @@ impl RandAgentBuilder {
 
                     let agent =
                         get_openai_agent(client, &agent_conf.model_name, agent_name, system_prompt);
-                    self.agents.push((
-                        agent,
-                        agent_conf.id,
-                        agent_conf.provider.to_string(),
-                        agent_conf.model_name,
-                    ));
+                    self.push_config_agent(agent, &agent_conf);
                 }
                 ProviderEnum::OpenRouter => {
-                    let mut client_builder =
-                        openrouter::ClientBuilder::<reqwest::Client>::new(&agent_conf.api_key);
+                    let mut client_builder = openrouter::ClientBuilder::new_with_client(
+                        &agent_conf.api_key,
+                        http_client,
+                    );
                     if let Some(api_base_url) = &agent_conf.api_base_url {
                         client_builder = client_builder.base_url(api_base_url)
                     }
@@ -177,130 +577,113 @@ impl RandAgentBuilder {
                         .name(agent_name.as_str())
                         .preamble(&system_prompt)
                         .build();
-                    self.agents.push((
-                        agent,
-                        agent_conf.id,
-                        agent_conf.provider.to_string(),
-                        agent_conf.model_name,
-                    ));
+                    self.push_config_agent(agent, &agent_conf);
                 }
                 ProviderEnum::Together => {
-                    let client = together::Client::new(&agent_conf.api_key);
+                    let client = together::client::ClientBuilder::new_with_client(
+                        &agent_conf.api_key,
+                        http_client,
+                    )
+                    .build();
                     let agent = client
                         .agent(&agent_conf.model_name)
                         .name(agent_name.as_str())
                         .preamble(&system_prompt)
                         .build();
-                    self.agents.push((
-                        agent,
-                        agent_conf.id,
-                        agent_conf.provider.to_string(),
-                        agent_conf.model_name,
-                    ));
+                    self.push_config_agent(agent, &agent_conf);
                 }
                 ProviderEnum::XAI => {
-                    let client = xai::Client::new(&agent_conf.api_key);
+                    let client = xai::client::ClientBuilder::new_with_client(
+                        &agent_conf.api_key,
+                        http_client,
+                    )
+                    .build();
                     let agent = client
                         .agent(&agent_conf.model_name)
                         .name(agent_name.as_str())
                         .preamble(&system_prompt)
                         .build();
-                    self.agents.push((
-                        agent,
-                        agent_conf.id,
-                        agent_conf.provider.to_string(),
-                        agent_conf.model_name,
-                    ));
+                    self.push_config_agent(agent, &agent_conf);
                 }
                 ProviderEnum::Azure => {
-                    tracing::info!("Azure simple_builder暂不支持,参数有点多，可以自行添加........ ")
+                    // Azure 参数较多，可以自行添加
+                    tracing::info!(
+                        "{}: {}",
+                        MessageKey::ProviderUnsupported.text(),
+                        agent_conf.provider
+                    )
                 }
                 ProviderEnum::DeepSeek => {
-                    let client = deepseek::Client::new(&agent_conf.api_key);
+                    let client =
+                        deepseek::ClientBuilder::new_with_client(&agent_conf.api_key, http_client)
+                            .build();
                     let agent = client
                         .agent(&agent_conf.model_name)
                         .name(agent_name.as_str())
                         .preamble(&system_prompt)
                         .build();
-                    self.agents.push((
-                        agent,
-                        agent_conf.id,
-                        agent_conf.provider.to_string(),
-                        agent_conf.model_name,
-                    ));
+                    self.push_config_agent(agent, &agent_conf);
                 }
                 ProviderEnum::Galadriel => {
-                    let client = galadriel::Client::new(&agent_conf.api_key);
+                    let client =
+                        galadriel::ClientBuilder::<reqwest::Client>::new(&agent_conf.api_key)
+                            .with_client(http_client)
+                            .build();
                     let agent = client
                         .agent(&agent_conf.model_name)
                         .name(agent_name.as_str())
                         .preamble(&system_prompt)
                         .build();
-                    self.agents.push((
-                        agent,
-                        agent_conf.id,
-                        agent_conf.provider.to_string(),
-                        agent_conf.model_name,
-                    ));
+                    self.push_config_agent(agent, &agent_conf);
                 }
                 ProviderEnum::Groq => {
-                    let client = groq::Client::new(&agent_conf.api_key);
+                    let client =
+                        groq::ClientBuilder::new_with_client(&agent_conf.api_key, http_client)
+                            .build();
                     let agent = client
                         .agent(&agent_conf.model_name)
                         .name(agent_name.as_str())
                         .preamble(&system_prompt)
                         .build();
-                    self.agents.push((
-                        agent,
-                        agent_conf.id,
-                        agent_conf.provider.to_string(),
-                        agent_conf.model_name,
-                    ));
+                    self.push_config_agent(agent, &agent_conf);
                 }
                 ProviderEnum::Hyperbolic => {
-                    let client = hyperbolic::Client::new(&agent_conf.api_key);
+                    let client = hyperbolic::ClientBuilder::new_with_client(
+                        &agent_conf.api_key,
+                        http_client,
+                    )
+                    .build();
                     let agent = client
                         .agent(&agent_conf.model_name)
                         .name(agent_name.as_str())
                         .preamble(&system_prompt)
                         .build();
-                    self.agents.push((
-                        agent,
-                        agent_conf.id,
-                        agent_conf.provider.to_string(),
-                        agent_conf.model_name,
-                    ));
+                    self.push_config_agent(agent, &agent_conf);
                 }
                 ProviderEnum::Mira => {
-                    let client = mira::Client::new(&agent_conf.api_key);
+                    let client =
+                        mira::ClientBuilder::new_with_client(&agent_conf.api_key, http_client)
+                            .build();
                     let agent = client
                         .agent(&agent_conf.model_name)
                         .name(agent_name.as_str())
                         .preamble(&system_prompt)
                         .build();
-                    self.agents.push((
-                        agent,
-                        agent_conf.id,
-                        agent_conf.provider.to_string(),
-                        agent_conf.model_name,
-                    ));
+                    self.push_config_agent(agent, &agent_conf);
                 }
                 ProviderEnum::Mooshot => {
-                    let client = moonshot::Client::new(&agent_conf.api_key);
+                    let client =
+                        moonshot::ClientBuilder::new_with_client(&agent_conf.api_key, http_client)
+                            .build();
                     let agent = client
                         .agent(&agent_conf.model_name)
                         .name(agent_name.as_str())
                         .preamble(&system_prompt)
                         .build();
-                    self.agents.push((
-                        agent,
-                        agent_conf.id,
-                        agent_conf.provider.to_string(),
-                        agent_conf.model_name,
-                    ));
+                    self.push_config_agent(agent, &agent_conf);
                 }
                 ProviderEnum::Ollama => {
-                    let mut client_builder = ollama::ClientBuilder::<reqwest::Client>::new();
+                    let mut client_builder = ollama::ClientBuilder::new_with_client(http_client);
                     if let Some(api_base_url) = &agent_conf.api_base_url {
                         client_builder = client_builder.base_url(api_base_url);
                     }
@@ -311,48 +694,200 @@ impl RandAgentBuilder {
                         .name(agent_name.as_str())
                         .preamble(&system_prompt)
                         .build();
-                    self.agents.push((
-                        agent,
-                        agent_conf.id,
-                        agent_conf.provider.to_string(),
-                        agent_conf.model_name,
-                    ));
+                    self.push_config_agent(agent, &agent_conf);
                 }
                 ProviderEnum::Perplexity => {
-                    // let client = perplexity::Client::new(&agent_conf.api_key);
-                    // let agent = client
-                    //     .agent(&agent_conf.model_name)
-                    //     .name(agent_name.as_str())
-                    //     .preamble(&system_prompt)
-                    //     .build();
-                    // self.agents.push((
-                    //     agent,
-                    //     agent_conf.id,
-                    //     agent_conf.provider.to_string(),
-                    //     agent_conf.model_name,
-                    // ));
-                    tracing::info!("Perplexity 暂不支持,没有实现BoxAgent........ ")
+                    let mut client_builder = perplexity::ClientBuilder::new_with_client(
+                        &agent_conf.api_key,
+                        http_client,
+                    );
+                    if let Some(api_base_url) = &agent_conf.api_base_url {
+                        client_builder = client_builder.base_url(api_base_url);
+                    }
+                    let client = client_builder.build();
+                    let agent = get_perplexity_agent(
+                        client,
+                        &agent_conf.model_name,
+                        agent_name,
+                        system_prompt,
+                    );
+                    self.push_config_agent(agent, &agent_conf);
                 }
                 ProviderEnum::Bigmodel => {
-                    let client = if let Some(api_base_url) = agent_conf.api_base_url {
-                        bigmodel::Client::from_url(&agent_conf.api_key, &api_base_url)
+                    let client = if let Some(api_base_url) = &agent_conf.api_base_url {
+                        bigmodel::Client::from_url(&agent_conf.api_key, api_base_url)
                     } else {
                         bigmodel::Client::new(&agent_conf.api_key)
-                    };
+                    }
+                    .with_headers(agent_conf.header_map());
                     let agent = client
                         .agent(&agent_conf.model_name)
                         .name(agent_name.as_str())
                         .preamble(&system_prompt)
                         .build();
-                    self.agents.push((
-                        agent,
-                        agent_conf.id,
-                        agent_conf.provider.to_string(),
-                        agent_conf.model_name,
-                    ));
+                    self.push_config_agent(agent, &agent_conf);
+                }
+                ProviderEnum::DashScope => {
+                    let client = dashscope::Client::from_url_with_client(
+                        &agent_conf.api_key,
+                        agent_conf
+                            .api_base_url
+                            .as_deref()
+                            .unwrap_or(dashscope::DASHSCOPE_API_BASE_URL),
+                        http_client,
+                    );
+                    let agent = client
+                        .agent(&agent_conf.model_name)
+                        .name(agent_name.as_str())
+                        .preamble(&system_prompt)
+                        .build();
+                    self.push_config_agent(agent, &agent_conf);
                 }
             }
         }
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rand_agent::tests::FakeModel;
+
+    #[tokio::test]
+    async fn test_set_preamble_updates_cached_system_block() {
+        let options = AnthropicOptions {
+            max_tokens: None,
+            cache_system_prompt: true,
+        };
+        let mut agent = FakeModel::ok().agent();
+        agent.preamble = Some("旧提示词".to_string());
+        agent.additional_params = options.additional_params("旧提示词");
+
+        set_preamble(&mut agent, "新提示词".to_string());
+        assert_eq!(agent.preamble.as_deref(), Some("新提示词"));
+        let params = agent.additional_params.unwrap();
+        assert_eq!(params["system"][0]["text"], "新提示词");
+        assert_eq!(params["system"][0]["cache_control"]["type"], "ephemeral");
+
+        // 没有开启缓存时只修改 preamble
+        let mut agent = FakeModel::ok().agent();
+        set_preamble(&mut agent, "新提示词".to_string());
+        assert_eq!(agent.preamble.as_deref(), Some("新提示词"));
+        assert!(agent.additional_params.is_none());
+    }
+
+    #[test]
+    fn test_agent_config_header_map() {
+        let agent_conf: AgentConfig = serde_json::from_value(json!({
+            "id": 1,
+            "provider": "openrouter",
+            "model_name": "deepseek/deepseek-chat-v3-0324:free",
+            "api_key": "xxx",
+            "user_agent": "my-app/1.0",
+            "headers": {"HTTP-Referer": "https://example.com", "X-Title": "My App", "bad header": "x"}
+        }))
+        .unwrap();
+        let header_map = agent_conf.header_map();
+        assert_eq!(header_map.len(), 3);
+        assert_eq!(header_map[USER_AGENT], "my-app/1.0");
+        assert_eq!(header_map["x-title"], "My App");
+        assert_eq!(header_map["http-referer"], "https://example.com");
+    }
+
+    #[test]
+    fn test_expand_api_keys() {
+        let configs: Vec<AgentConfig> = serde_json::from_value(json!([
+            {"id": 1, "provider": "bigmodel", "model_name": "glm-4-flash",
+             "api_key": "k1", "api_keys": ["k2", "k3"]},
+            {"id": 5, "provider": "bigmodel", "model_name": "glm-4-flash",
+             "api_keys": ["k4"]},
+        ]))
+        .unwrap();
+        let expand = |existing_ids: &[i32]| -> Vec<(i32, String)> {
+            expand_api_keys(configs.clone(), existing_ids)
+                .into_iter()
+                .map(|conf| (conf.id, conf.api_key))
+                .collect()
+        };
+        assert_eq!(
+            expand(&[]),
+            vec![
+                (1, "k1".to_string()),
+                (6, "k2".to_string()),
+                (7, "k3".to_string()),
+                (5, "k4".to_string()),
+            ]
+        );
+        // 构建器中已有的 agent 的 id 不会被重复分配
+        assert_eq!(
+            expand(&[2, 9]),
+            vec![
+                (1, "k1".to_string()),
+                (10, "k2".to_string()),
+                (11, "k3".to_string()),
+                (5, "k4".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apply_params() {
+        let agent_conf: AgentConfig = serde_json::from_value(json!({
+            "id": 1,
+            "provider": "bigmodel",
+            "model_name": "glm-4-flash",
+            "api_key": "xxx",
+            "temperature": 0.2,
+            "max_tokens": 1024,
+            "top_p": 0.9,
+            "additional_params": {"do_sample": false}
+        }))
+        .unwrap();
+        let mut agent = bigmodel::Client::new("xxx")
+            .agent("glm-4-flash")
+            .additional_params(json!({"do_sample": true, "stop": ["\n"]}))
+            .build();
+        agent_conf.apply_params(&mut agent);
+        assert_eq!(agent.temperature, Some(0.2));
+        assert_eq!(agent.max_tokens, Some(1024));
+        assert_eq!(
+            agent.additional_params,
+            Some(json!({"do_sample": false, "stop": ["\n"], "top_p": 0.9}))
+        );
+    }
+
+    struct EchoTool;
+
+    impl Tool for EchoTool {
+        const NAME: &'static str = "echo";
+        type Error = ToolError;
+        type Args = Value;
+        type Output = Value;
+
+        async fn definition(&self, _prompt: String) -> ToolDefinition {
+            ToolDefinition {
+                name: Self::NAME.to_string(),
+                description: "echo".to_string(),
+                parameters: json!({"type": "object"}),
+            }
+        }
+
+        async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+            Ok(args)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shared_tool() {
+        let tool: Arc<dyn ToolDyn> = Arc::new(EchoTool);
+        let handle = ToolServer::new().tool(SharedTool(tool)).run();
+        let definitions = handle.get_tool_defs(None).await.unwrap();
+        assert_eq!(definitions[0].name, "echo");
+        let output = handle
+            .call_tool("echo", r#"{"city":"北京"}"#)
+            .await
+            .unwrap();
+        assert_eq!(output, r#"{"city":"北京"}"#);
+    }
+}
@@ -0,0 +1,134 @@
+//! 代理池状态快照
+//!
+//! 保存各 agent 的失败计数、是否有效、手动停用状态和 token 用量统计，进程重启后
+//! 通过 [`RandAgentBuilder::restore_from_snapshot`](crate::rand_agent::RandAgentBuilder::restore_from_snapshot)
+//! 恢复，避免重启后再次向已知故障的 agent 发送请求。
+//!
+//! ```rust,ignore
+//! // 退出前保存
+//! let snapshot = rand_agent.snapshot().await;
+//! std::fs::write("pool.json", serde_json::to_string(&snapshot)?)?;
+//!
+//! // 启动时恢复
+//! let snapshot: RandAgentSnapshot = serde_json::from_str(&std::fs::read_to_string("pool.json")?)?;
+//! let rand_agent = RandAgentBuilder::new()
+//!     .simple_builder(configs, system_prompt)
+//!     .restore_from_snapshot(snapshot)
+//!     .build();
+//! ```
+//!
+//! 按 agent id 匹配，快照中不存在的 agent 从初始状态开始，已不在代理池中的 agent 忽略。
+//! 无效 agent 的冷却时间从恢复时重新开始计算。
+//!
+//! 快照在同一时刻获取所有 agent 的状态和用量统计，也适合用于监控面板和告警。
+
+use crate::AgentInfo;
+use crate::usage::UsageStats;
+use serde::{Deserialize, Serialize};
+
+/// 当前快照格式版本
+pub const SNAPSHOT_FORMAT_VERSION: u32 = 1;
+
+/// 单个 agent 的状态
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentSnapshot {
+    pub id: i32,
+    pub provider: String,
+    pub model: String,
+    /// 失败次数
+    pub failure_count: u32,
+    /// 最大失败次数，仅供参考，恢复时以当前配置为准
+    pub max_failures: u32,
+    /// 是否有效
+    pub valid: bool,
+    /// 是否被手动停用
+    #[serde(default)]
+    pub disabled: bool,
+    /// 成功请求的平均延迟(毫秒)
+    #[serde(default)]
+    pub avg_latency_ms: Option<u64>,
+}
+
+impl From<&AgentInfo> for AgentSnapshot {
+    fn from(info: &AgentInfo) -> Self {
+        Self {
+            id: info.id,
+            provider: info.provider.clone(),
+            model: info.model.clone(),
+            failure_count: info.failure_count,
+            max_failures: info.max_failures,
+            valid: info.failure_count < info.max_failures,
+            disabled: info.disabled,
+            avg_latency_ms: info.avg_latency.map(|latency| latency.as_millis() as u64),
+        }
+    }
+}
+
+/// 代理池状态快照
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RandAgentSnapshot {
+    pub version: u32,
+    /// 快照时间(unix 秒)
+    pub taken_at: u64,
+    pub agents: Vec<AgentSnapshot>,
+    /// token 用量统计
+    #[serde(default)]
+    pub usage: UsageStats,
+}
+
+impl RandAgentSnapshot {
+    pub(crate) fn new(agents: Vec<AgentSnapshot>, usage: UsageStats) -> Self {
+        Self {
+            version: SNAPSHOT_FORMAT_VERSION,
+            taken_at: crate::session::unix_now(),
+            agents,
+            usage,
+        }
+    }
+
+    /// 获取指定 agent 的状态
+    pub fn agent(&self, id: i32) -> Option<&AgentSnapshot> {
+        self.agents.iter().find(|agent| agent.id == id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structured::StructuredOutput;
+    use rig::completion::Usage;
+    use std::time::Duration;
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let info = AgentInfo {
+            id: 1,
+            provider: "bigmodel".to_string(),
+            model: "glm-4-flash".to_string(),
+            failure_count: 3,
+            max_failures: 3,
+            avg_latency: Some(Duration::from_millis(1200)),
+            input_price: None,
+            output_price: None,
+            disabled: false,
+            reserved_for: None,
+            structured_output: StructuredOutput::Prompt,
+            context_length: None,
+        };
+        let mut usage_stats = UsageStats::default();
+        let mut usage = Usage::new();
+        usage.total_tokens = 42;
+        usage_stats.record(1, &usage, 0.0);
+
+        let snapshot = RandAgentSnapshot::new(vec![AgentSnapshot::from(&info)], usage_stats);
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let restored: RandAgentSnapshot = serde_json::from_str(&json).unwrap();
+
+        let agent = restored.agent(1).unwrap();
+        assert!(!agent.valid);
+        assert_eq!(agent.failure_count, 3);
+        assert_eq!(agent.avg_latency_ms, Some(1200));
+        assert_eq!(restored.usage.agent(1).unwrap().total_tokens, 42);
+        assert!(restored.agent(2).is_none());
+    }
+}
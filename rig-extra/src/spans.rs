@@ -0,0 +1,91 @@
+//! 代理池请求的 tracing span
+//!
+//! 每次请求都在 `rand_agent.request` span 中执行，携带 `method`、`provider`、`model`、
+//! `agent_id`，使用系统提示词版本时记录 `preamble_version`，结束时记录 `latency_ms` 和
+//! `success`；重试时每次尝试在 `rand_agent.attempt` span 中执行，携带 `attempt` 序号。
+//!
+//! 开启 `rig-extra-otel` feature 后，span 额外携带 OpenTelemetry GenAI 语义约定的属性
+//! (`gen_ai.operation.name`、`gen_ai.provider.name`、`gen_ai.request.model` 等)，
+//! 配合 `tracing-opentelemetry` 即可导出为 OTLP span。
+
+use crate::AgentInfo;
+use crate::redact::redacted;
+use rig::completion::PromptError;
+use std::time::Duration;
+use tracing::Span;
+use tracing::field::Empty;
+
+/// 单次请求的 span
+#[cfg(not(feature = "rig-extra-otel"))]
+pub(crate) fn request_span(method: &'static str, info: &AgentInfo) -> Span {
+    tracing::info_span!(
+        "rand_agent.request",
+        method,
+        provider = %info.provider,
+        model = %info.model,
+        agent_id = info.id,
+        preamble_version = Empty,
+        latency_ms = Empty,
+        success = Empty,
+    )
+}
+
+/// 单次请求的 span，携带 OpenTelemetry GenAI 属性
+#[cfg(feature = "rig-extra-otel")]
+pub(crate) fn request_span(method: &'static str, info: &AgentInfo) -> Span {
+    tracing::info_span!(
+        "rand_agent.request",
+        method,
+        provider = %info.provider,
+        model = %info.model,
+        agent_id = info.id,
+        preamble_version = Empty,
+        latency_ms = Empty,
+        success = Empty,
+        otel.name = %format!("chat {}", info.model),
+        otel.kind = "client",
+        otel.status_code = Empty,
+        gen_ai.operation.name = "chat",
+        gen_ai.provider.name = %info.provider,
+        gen_ai.request.model = %info.model,
+        error.type = Empty,
+    )
+}
+
+/// 重试中单次尝试的 span，序号从 1 开始
+pub(crate) fn attempt_span(method: &'static str, attempt: usize) -> Span {
+    tracing::info_span!("rand_agent.attempt", method, attempt)
+}
+
+/// 在 span 上记录请求结果
+pub(crate) fn record_result(span: &Span, latency: Duration, result: &Result<String, PromptError>) {
+    let latency_ms = latency.as_millis() as u64;
+    span.record("latency_ms", latency_ms);
+    span.record("success", result.is_ok());
+    match result {
+        Ok(_) => tracing::info!(parent: span, latency_ms, "请求完成"),
+        Err(err) => {
+            #[cfg(feature = "rig-extra-otel")]
+            {
+                span.record("otel.status_code", "ERROR");
+                span.record("error.type", error_type(err));
+            }
+            tracing::warn!(parent: span, latency_ms, error = %redacted(&err), "请求失败");
+        }
+    }
+}
+
+/// 错误类别，对应 OpenTelemetry 的 `error.type` 属性
+#[cfg(feature = "rig-extra-otel")]
+fn error_type(err: &PromptError) -> &'static str {
+    use rig::completion::CompletionError;
+    match err {
+        PromptError::CompletionError(CompletionError::HttpError(_)) => "http",
+        PromptError::CompletionError(CompletionError::ProviderError(_)) => "provider",
+        PromptError::CompletionError(
+            CompletionError::ResponseError(_) | CompletionError::JsonError(_),
+        ) => "response",
+        PromptError::ToolError(_) => "tool",
+        _ => "other",
+    }
+}
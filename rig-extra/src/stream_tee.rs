@@ -0,0 +1,225 @@
+//! 流式响应分流(tee)
+//!
+//! 将流式数据块原样转发给调用方，同时累积完整内容，流结束后通过回调交给
+//! 审计日志、会话历史、缓存等持久化逻辑，使流式调用也能拥有和普通调用一样的可观测性。
+//!
+//! ```rust,ignore
+//! use rig_extra::stream_tee::StreamTee;
+//!
+//! let stream = agent.stream_prompt("写一个故事").await;
+//! let mut stream = StreamTee::new(stream, |output| {
+//!     println!("完整内容: {}, 用量: {:?}", output.text, output.usage);
+//! });
+//! let res = stream_to_stdout(&mut stream).await?;
+//! ```
+//!
+//! 只需要最终文本时，[`collect_with_timeout`] 把流读到结束，超时后返回已生成的部分内容
+//! 而不是错误，`complete` 为 false:
+//!
+//! ```rust,ignore
+//! let output = rand_agent
+//!     .stream_prompt_collect("写一篇长文", Duration::from_secs(30))
+//!     .await?;
+//! if !output.complete {
+//!     println!("超时，只生成了 {} 个字符", output.text.chars().count());
+//! }
+//! ```
+
+use futures::{Stream, StreamExt};
+use rig::agent::MultiTurnStreamItem;
+use rig::completion::Usage;
+use rig::streaming::StreamedAssistantContent;
+use std::fmt::Display;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// 流结束时累积得到的内容
+#[derive(Debug, Clone, Default)]
+pub struct TeeOutput {
+    /// 累积的文本
+    pub text: String,
+    /// token 用量，收到最终响应时才有
+    pub usage: Option<Usage>,
+    /// 是否收到最终响应(流正常结束)
+    pub complete: bool,
+    /// 流中出现的错误
+    pub error: Option<String>,
+}
+
+/// 流结束回调类型
+pub type TeeCallback = Box<dyn FnOnce(TeeOutput) + Send + 'static>;
+
+/// 流式响应分流器
+///
+/// 回调只会被调用一次：流结束时，或调用方提前丢弃流时(此时 `complete` 为 false)
+pub struct StreamTee<S> {
+    inner: S,
+    output: TeeOutput,
+    on_complete: Option<TeeCallback>,
+}
+
+impl<S> StreamTee<S> {
+    pub fn new<F>(inner: S, on_complete: F) -> Self
+    where
+        F: FnOnce(TeeOutput) + Send + 'static,
+    {
+        Self {
+            inner,
+            output: TeeOutput::default(),
+            on_complete: Some(Box::new(on_complete)),
+        }
+    }
+
+    /// 获取目前为止累积的内容
+    pub fn output(&self) -> &TeeOutput {
+        &self.output
+    }
+
+    fn finish(&mut self) {
+        if let Some(cb) = self.on_complete.take() {
+            cb(std::mem::take(&mut self.output));
+        }
+    }
+}
+
+/// 可由 [`StreamTee`] 累积的流式数据项
+pub trait TeeItem {
+    /// 将数据项累积到输出中
+    fn observe(&self, output: &mut TeeOutput);
+}
+
+impl<R, E: Display> TeeItem for Result<MultiTurnStreamItem<R>, E> {
+    fn observe(&self, output: &mut TeeOutput) {
+        match self {
+            Ok(item) => observe_item(output, item),
+            Err(err) => {
+                output.error = Some(err.to_string());
+            }
+        }
+    }
+}
+
+pub(crate) fn observe_item<R>(output: &mut TeeOutput, item: &MultiTurnStreamItem<R>) {
+    match item {
+        MultiTurnStreamItem::StreamItem(StreamedAssistantContent::Text(text)) => {
+            output.text.push_str(&text.text);
+        }
+        MultiTurnStreamItem::FinalResponse(res) => {
+            if output.text.is_empty() {
+                output.text = res.response().to_string();
+            }
+            output.usage = Some(res.usage());
+            output.complete = true;
+        }
+        _ => {}
+    }
+}
+
+impl<S> Stream for StreamTee<S>
+where
+    S: Stream + Unpin,
+    S::Item: TeeItem,
+{
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        match Pin::new(&mut this.inner).poll_next(cx) {
+            Poll::Ready(Some(item)) => {
+                item.observe(&mut this.output);
+                Poll::Ready(Some(item))
+            }
+            Poll::Ready(None) => {
+                this.finish();
+                Poll::Ready(None)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// 读取流直到结束、出错或超时，返回累积的内容
+///
+/// 超时或出错时返回已收到的部分文本，`complete` 为 false，出错时 `error` 中记录错误
+pub async fn collect_with_timeout<S>(mut stream: S, timeout: Duration) -> TeeOutput
+where
+    S: Stream + Unpin,
+    S::Item: TeeItem,
+{
+    let mut output = TeeOutput::default();
+    let collect = async {
+        while let Some(item) = stream.next().await {
+            item.observe(&mut output);
+            if output.error.is_some() {
+                break;
+            }
+        }
+    };
+    if tokio::time::timeout(timeout, collect).await.is_err() {
+        tracing::debug!(?timeout, "流式响应超时，返回部分内容");
+    }
+    output
+}
+
+impl<S> Drop for StreamTee<S> {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use rig::message::Text;
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    async fn test_tee_forwards_and_collects() {
+        let items: Vec<Result<MultiTurnStreamItem<()>, String>> = vec![
+            Ok(MultiTurnStreamItem::StreamItem(
+                StreamedAssistantContent::Text(Text {
+                    text: "你好".to_string(),
+                }),
+            )),
+            Ok(MultiTurnStreamItem::StreamItem(
+                StreamedAssistantContent::Text(Text {
+                    text: "世界".to_string(),
+                }),
+            )),
+        ];
+
+        let collected = Arc::new(Mutex::new(None));
+        let collected_clone = collected.clone();
+        let mut stream = StreamTee::new(futures::stream::iter(items), move |output| {
+            *collected_clone.lock().unwrap() = Some(output);
+        });
+
+        let mut forwarded = 0;
+        while let Some(item) = stream.next().await {
+            assert!(item.is_ok());
+            forwarded += 1;
+        }
+        assert_eq!(forwarded, 2);
+
+        let output = collected.lock().unwrap().take().unwrap();
+        assert_eq!(output.text, "你好世界");
+        assert!(!output.complete);
+        assert!(output.error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_collect_partial_on_timeout() {
+        let items: Vec<Result<MultiTurnStreamItem<()>, String>> = vec![Ok(
+            MultiTurnStreamItem::StreamItem(StreamedAssistantContent::Text(Text {
+                text: "未完".to_string(),
+            })),
+        )];
+        let stream = futures::stream::iter(items).chain(futures::stream::pending());
+
+        let output = collect_with_timeout(stream, Duration::from_millis(20)).await;
+        assert_eq!(output.text, "未完");
+        assert!(!output.complete);
+    }
+}
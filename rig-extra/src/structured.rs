@@ -0,0 +1,153 @@
+//! 结构化输出的能力协商
+//!
+//! 不同 provider/模型对结构化输出的支持不同:
+//!
+//! - [`StructuredOutput::JsonSchema`]: 支持按 JSON Schema 约束输出(`response_format.json_schema`)
+//! - [`StructuredOutput::JsonMode`]: 只保证输出合法 JSON(`response_format.json_object`)，
+//!   schema 通过提示词说明
+//! - [`StructuredOutput::Prompt`]: 不支持原生结构化输出，完全依靠提示词约束并从回复中解析 JSON
+//!
+//! 每个 agent 在添加时按 provider 和模型名推断支持情况，也可以在 `AgentConfig` 中通过
+//! `structured_output` 覆盖。[`RandAgent::prompt_structured`](crate::rand_agent::RandAgent::prompt_structured)
+//! 根据选中 agent 的能力自动选择请求方式。
+
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+/// 结构化输出支持情况
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StructuredOutput {
+    /// 仅通过提示词约束
+    #[default]
+    Prompt,
+    /// 支持 JSON 模式
+    JsonMode,
+    /// 支持 JSON Schema
+    JsonSchema,
+}
+
+impl StructuredOutput {
+    /// 按 provider 和模型名推断，无法确定时返回 [`StructuredOutput::Prompt`]
+    pub fn detect(provider: &str, model: &str) -> Self {
+        let provider = provider.to_ascii_lowercase();
+        let model = model.to_ascii_lowercase();
+        match provider.as_str() {
+            "openai" | "azure" => {
+                let schema_models = ["gpt-4o", "gpt-4.1", "gpt-5", "o1", "o3", "o4"];
+                if schema_models.iter().any(|prefix| model.starts_with(prefix)) {
+                    StructuredOutput::JsonSchema
+                } else {
+                    StructuredOutput::JsonMode
+                }
+            }
+            "xai" => StructuredOutput::JsonSchema,
+            "deepseek" | "bigmodel" | "dashscope" | "mistral" | "groq" | "together" | "mooshot" => {
+                StructuredOutput::JsonMode
+            }
+            _ => StructuredOutput::Prompt,
+        }
+    }
+
+    /// 需要合并到请求中的额外参数(OpenAI 兼容格式)
+    pub(crate) fn additional_params(self, name: &str, schema: &Value) -> Option<Value> {
+        match self {
+            StructuredOutput::Prompt => None,
+            StructuredOutput::JsonMode => Some(json!({
+                "response_format": {"type": "json_object"}
+            })),
+            StructuredOutput::JsonSchema => Some(json!({
+                "response_format": {
+                    "type": "json_schema",
+                    "json_schema": {"name": name, "schema": schema, "strict": true}
+                }
+            })),
+        }
+    }
+
+    /// 需要追加到系统提示词中的说明，原生支持 schema 时不需要
+    pub(crate) fn instructions(self, schema: &Value) -> Option<String> {
+        match self {
+            StructuredOutput::JsonSchema => None,
+            StructuredOutput::JsonMode | StructuredOutput::Prompt => Some(format!(
+                "只输出一个符合以下 JSON Schema 的 JSON 对象，不要输出任何其它内容:\n{schema}"
+            )),
+        }
+    }
+}
+
+/// 生成类型的 schema 名称和 JSON Schema
+pub(crate) fn schema_of<T: JsonSchema>() -> (String, Value) {
+    let schema = serde_json::to_value(schemars::schema_for!(T)).unwrap_or(Value::Null);
+    // response_format 的 name 只允许字母、数字、下划线和连字符
+    let name = T::schema_name()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    (name, schema)
+}
+
+/// 从回复中解析 JSON，兼容 markdown 代码块和前后多余的文字
+pub(crate) fn parse_json<T: DeserializeOwned>(text: &str) -> Result<T, serde_json::Error> {
+    let text = text.trim();
+    serde_json::from_str(text).or_else(|err| {
+        let start = text.find(['{', '[']);
+        let end = text.rfind(['}', ']']);
+        match (start, end) {
+            (Some(start), Some(end)) if start < end => serde_json::from_str(&text[start..=end]),
+            _ => Err(err),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize, JsonSchema, PartialEq)]
+    struct Person {
+        name: String,
+        age: u32,
+    }
+
+    #[test]
+    fn test_structured_output() {
+        assert_eq!(
+            StructuredOutput::detect("OpenAi", "gpt-4o-mini"),
+            StructuredOutput::JsonSchema
+        );
+        assert_eq!(
+            StructuredOutput::detect("Bigmodel", "glm-4-flash"),
+            StructuredOutput::JsonMode
+        );
+        assert_eq!(
+            StructuredOutput::detect("ollama", "qwen2.5:14b"),
+            StructuredOutput::Prompt
+        );
+
+        let (name, schema) = schema_of::<Person>();
+        assert_eq!(name, "Person");
+        let params = StructuredOutput::JsonSchema
+            .additional_params(&name, &schema)
+            .unwrap();
+        assert_eq!(params["response_format"]["json_schema"]["name"], "Person");
+
+        let person: Person =
+            parse_json("好的，结果如下:\n```json\n{\"name\": \"张三\", \"age\": 30}\n```").unwrap();
+        assert_eq!(
+            person,
+            Person {
+                name: "张三".to_string(),
+                age: 30
+            }
+        );
+    }
+}
@@ -0,0 +1,300 @@
+//! 按租户隔离的用量、额度和审计日志
+//!
+//! 多个客户共用一个代理池时，[`RandAgent::for_tenant`](crate::rand_agent::RandAgent::for_tenant)
+//! 返回与原代理池共享 agent 的视图，通过该视图发起的请求单独记录用量和审计日志，
+//! 并按租户设置的额度限制请求。超出额度的请求在选择 agent 之前被拒绝，不计入 agent 失败。
+//! 并发上限按租户进行中的请求数计算，流式请求在流结束或被丢弃时才释放名额。
+//!
+//! ```rust,ignore
+//! use rig_extra::tenant::TenantLimits;
+//!
+//! rand_agent
+//!     .set_tenant_limits(
+//!         "acme",
+//!         TenantLimits::new().max_cost(10.0).max_requests(1000).max_concurrent(4),
+//!     )
+//!     .await;
+//!
+//! let acme = rand_agent.for_tenant("acme");
+//! let response = acme.prompt("你好").await?;
+//!
+//! let usage = rand_agent.tenant_usage("acme").await;
+//! println!("acme: {:?}", usage.total);
+//! for entry in rand_agent.tenant_audit_log("acme").await {
+//!     println!("{entry:?}");
+//! }
+//! ```
+
+use crate::AgentInfo;
+use crate::i18n::MessageKey;
+use crate::redact::redacted;
+use crate::usage::UsageStats;
+use rig::completion::Usage;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// 每个租户保留的审计记录数，超出后丢弃最早的记录
+const AUDIT_LOG_CAPACITY: usize = 1000;
+
+/// 租户额度，达到任一额度后拒绝该租户的请求
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TenantLimits {
+    /// 累计费用上限
+    pub max_cost: Option<f64>,
+    /// 累计 token 上限
+    pub max_tokens: Option<u64>,
+    /// 累计成功请求次数上限
+    pub max_requests: Option<u64>,
+    /// 同时进行的请求数上限
+    pub max_concurrent: Option<usize>,
+}
+
+impl TenantLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn max_cost(mut self, max_cost: f64) -> Self {
+        self.max_cost = Some(max_cost);
+        self
+    }
+
+    pub fn max_tokens(mut self, max_tokens: u64) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn max_requests(mut self, max_requests: u64) -> Self {
+        self.max_requests = Some(max_requests);
+        self
+    }
+
+    pub fn max_concurrent(mut self, max_concurrent: usize) -> Self {
+        self.max_concurrent = Some(max_concurrent);
+        self
+    }
+
+    /// 返回已达到的额度名称
+    fn exceeded(&self, usage: &UsageStats, in_flight: usize) -> Option<&'static str> {
+        let total = &usage.total;
+        if self.max_cost.is_some_and(|max| total.cost >= max) {
+            Some("max_cost")
+        } else if self.max_tokens.is_some_and(|max| total.total_tokens >= max) {
+            Some("max_tokens")
+        } else if self.max_requests.is_some_and(|max| total.requests >= max) {
+            Some("max_requests")
+        } else if self.max_concurrent.is_some_and(|max| in_flight >= max) {
+            Some("max_concurrent")
+        } else {
+            None
+        }
+    }
+}
+
+/// 租户超出额度
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{}: {tenant} ({limit})", MessageKey::TenantLimitExceeded.text())]
+pub struct TenantLimitExceeded {
+    pub tenant: String,
+    /// 达到的额度名称: `max_cost`、`max_tokens`、`max_requests` 或 `max_concurrent`
+    pub limit: &'static str,
+}
+
+/// 一次请求的审计记录
+#[derive(Debug, Clone)]
+pub struct TenantAuditEntry {
+    pub at: SystemTime,
+    pub agent_id: i32,
+    pub provider: String,
+    pub model: String,
+    pub success: bool,
+    pub latency: Duration,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct TenantState {
+    limits: TenantLimits,
+    usage: UsageStats,
+    audit_log: VecDeque<TenantAuditEntry>,
+    /// 进行中的请求数
+    in_flight: usize,
+}
+
+/// 所有租户的用量、额度和审计日志
+#[derive(Debug, Default)]
+pub(crate) struct TenantLedger {
+    tenants: HashMap<String, TenantState>,
+}
+
+impl TenantLedger {
+    pub(crate) fn set_limits(&mut self, tenant: &str, limits: TenantLimits) {
+        self.tenant(tenant).limits = limits;
+    }
+
+    pub(crate) fn check(&self, tenant: &str) -> Result<(), TenantLimitExceeded> {
+        let Some(state) = self.tenants.get(tenant) else {
+            return Ok(());
+        };
+        match state.limits.exceeded(&state.usage, state.in_flight) {
+            Some(limit) => Err(TenantLimitExceeded {
+                tenant: tenant.to_string(),
+                limit,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// 检查额度并登记一个进行中的请求，返回的 [`TenantPermit`] 丢弃时释放
+    pub(crate) fn enter(
+        ledger: &Arc<Mutex<Self>>,
+        tenant: &Arc<str>,
+    ) -> Result<TenantPermit, TenantLimitExceeded> {
+        let mut guard = ledger.lock().unwrap_or_else(|e| e.into_inner());
+        guard.check(tenant)?;
+        guard.tenant(tenant).in_flight += 1;
+        Ok(TenantPermit {
+            ledger: ledger.clone(),
+            tenant: tenant.clone(),
+        })
+    }
+
+    pub(crate) fn record_usage(&mut self, tenant: &str, id: i32, usage: &Usage, cost: f64) {
+        self.tenant(tenant).usage.record(id, usage, cost);
+    }
+
+    pub(crate) fn record_result<T, E: std::fmt::Display>(
+        &mut self,
+        tenant: &str,
+        info: &AgentInfo,
+        result: &Result<T, E>,
+        latency: Duration,
+    ) {
+        let audit_log = &mut self.tenant(tenant).audit_log;
+        if audit_log.len() >= AUDIT_LOG_CAPACITY {
+            audit_log.pop_front();
+        }
+        audit_log.push_back(TenantAuditEntry {
+            at: SystemTime::now(),
+            agent_id: info.id,
+            provider: info.provider.clone(),
+            model: info.model.clone(),
+            success: result.is_ok(),
+            latency,
+            error: result.as_ref().err().map(|err| redacted(err)),
+        });
+    }
+
+    pub(crate) fn usage(&self, tenant: &str) -> UsageStats {
+        self.tenants
+            .get(tenant)
+            .map(|state| state.usage.clone())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn audit_log(&self, tenant: &str) -> Vec<TenantAuditEntry> {
+        self.tenants
+            .get(tenant)
+            .map(|state| state.audit_log.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// 清空用量和审计日志，保留额度设置和进行中的请求数
+    pub(crate) fn reset(&mut self, tenant: &str) {
+        if let Some(state) = self.tenants.get_mut(tenant) {
+            state.usage = UsageStats::default();
+            state.audit_log.clear();
+        }
+    }
+
+    pub(crate) fn tenants(&self) -> Vec<String> {
+        self.tenants.keys().cloned().collect()
+    }
+
+    fn tenant(&mut self, tenant: &str) -> &mut TenantState {
+        self.tenants.entry(tenant.to_string()).or_default()
+    }
+}
+
+/// 租户进行中的请求，丢弃时释放租户的并发名额
+#[derive(Debug)]
+pub(crate) struct TenantPermit {
+    ledger: Arc<Mutex<TenantLedger>>,
+    tenant: Arc<str>,
+}
+
+impl Drop for TenantPermit {
+    fn drop(&mut self) {
+        let mut ledger = self.ledger.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(state) = ledger.tenants.get_mut(&*self.tenant) {
+            state.in_flight = state.in_flight.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tenant_isolation_and_limits() {
+        let mut ledger = TenantLedger::default();
+        ledger.set_limits("acme", TenantLimits::new().max_tokens(20));
+        let mut usage = Usage::new();
+        usage.total_tokens = 15;
+
+        ledger.record_usage("acme", 1, &usage, 0.0);
+        ledger.record_usage("globex", 1, &usage, 0.0);
+        assert!(ledger.check("acme").is_ok());
+        ledger.record_usage("acme", 2, &usage, 0.0);
+
+        let err = ledger.check("acme").unwrap_err();
+        assert_eq!(err.limit, "max_tokens");
+        // 其它租户不受影响
+        assert!(ledger.check("globex").is_ok());
+        assert_eq!(ledger.usage("acme").total.total_tokens, 30);
+        assert_eq!(ledger.usage("globex").total.total_tokens, 15);
+
+        let info = AgentInfo {
+            id: 1,
+            provider: "openai".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            failure_count: 0,
+            max_failures: 3,
+            avg_latency: None,
+            input_price: None,
+            output_price: None,
+            disabled: false,
+            reserved_for: None,
+            structured_output: Default::default(),
+            context_length: None,
+        };
+        let result: Result<(), String> = Err("boom".to_string());
+        ledger.record_result("acme", &info, &result, Duration::from_millis(5));
+        assert_eq!(ledger.audit_log("acme")[0].error.as_deref(), Some("boom"));
+        assert!(ledger.audit_log("globex").is_empty());
+
+        ledger.reset("acme");
+        assert!(ledger.check("acme").is_ok());
+        assert!(ledger.audit_log("acme").is_empty());
+    }
+
+    #[test]
+    fn test_tenant_concurrency_limit() {
+        let ledger = Arc::new(Mutex::new(TenantLedger::default()));
+        ledger
+            .lock()
+            .unwrap()
+            .set_limits("acme", TenantLimits::new().max_concurrent(1));
+        let acme: Arc<str> = Arc::from("acme");
+
+        let permit = TenantLedger::enter(&ledger, &acme).unwrap();
+        let err = TenantLedger::enter(&ledger, &acme).unwrap_err();
+        assert_eq!(err.limit, "max_concurrent");
+        assert!(TenantLedger::enter(&ledger, &Arc::from("globex")).is_ok());
+
+        drop(permit);
+        assert!(TenantLedger::enter(&ledger, &acme).is_ok());
+    }
+}
@@ -0,0 +1,18 @@
+//! 兼容旧版本的 `ThreadSafeRandAgent`
+//!
+//! `ThreadSafeRandAgent` 的功能(请求期间不持锁、`agents()` 调试列表)已全部合并到
+//! [`RandAgent`]，这里只保留类型别名方便迁移，后续版本将删除。
+
+use crate::rand_agent::{RandAgent, RandAgentBuilder};
+
+#[deprecated(
+    since = "0.13.2",
+    note = "已合并到 RandAgent，请直接使用 rand_agent::RandAgent"
+)]
+pub type ThreadSafeRandAgent = RandAgent;
+
+#[deprecated(
+    since = "0.13.2",
+    note = "已合并到 RandAgentBuilder，请直接使用 rand_agent::RandAgentBuilder"
+)]
+pub type ThreadSafeRandAgentBuilder = RandAgentBuilder;
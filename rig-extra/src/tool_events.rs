@@ -0,0 +1,238 @@
+//! 流式响应中的工具调用事件
+//!
+//! rig 的流式多轮对话会在内部直接执行工具，调用方只能看到最终的文本。
+//! [`stream_with_tool_events`] 自行驱动多轮对话，在执行工具前后输出
+//! [`ToolEvent`]，与文本等原始数据项按发生顺序合并在同一个流中，
+//! 界面可以据此显示"正在搜索…"之类的进度。
+//!
+//! ```rust,ignore
+//! use rig_extra::tool_events::{ToolEvent, ToolStreamItem};
+//!
+//! let mut stream = rand_agent.stream_prompt_with_tool_events("今天北京天气如何?", 3).await?;
+//! while let Some(item) = stream.next().await {
+//!     match item? {
+//!         ToolStreamItem::Tool(ToolEvent::Started { name, .. }) => println!("正在调用 {name}…"),
+//!         ToolStreamItem::Tool(ToolEvent::Finished { name, duration, .. }) => {
+//!             println!("{name} 完成，耗时 {duration:?}")
+//!         }
+//!         ToolStreamItem::Item(item) => { /* 文本等原始数据项 */ }
+//!     }
+//! }
+//! ```
+
+use crate::error::RandAgentError;
+use crate::stream_tee::{TeeItem, TeeOutput, observe_item};
+use futures::StreamExt;
+use futures::stream::BoxStream;
+use rig::OneOrMany;
+use rig::agent::MultiTurnStreamItem;
+use rig::client::builder::{BoxAgent, FinalCompletionResponse};
+use rig::completion::{CompletionError, GetTokenUsage, Message, PromptError, Usage};
+use rig::message::{AssistantContent, ToolResultContent, UserContent};
+use rig::streaming::{StreamedAssistantContent, StreamingCompletion};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// 工具调用事件
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToolEvent {
+    /// 开始调用工具
+    Started { name: String, args: String },
+    /// 工具返回结果，工具执行失败时 `result` 为错误信息
+    Finished {
+        name: String,
+        args: String,
+        result: String,
+        duration: Duration,
+    },
+}
+
+/// 带工具调用事件的流式数据项
+#[derive(Debug)]
+pub enum ToolStreamItem {
+    /// 原始的流式数据项
+    Item(MultiTurnStreamItem<FinalCompletionResponse>),
+    /// 工具调用事件
+    Tool(ToolEvent),
+}
+
+/// 带工具调用事件的流式响应
+pub type ToolEventStream = BoxStream<'static, Result<ToolStreamItem, RandAgentError>>;
+
+impl TeeItem for Result<ToolStreamItem, RandAgentError> {
+    fn observe(&self, output: &mut TeeOutput) {
+        match self {
+            Ok(ToolStreamItem::Item(item)) => observe_item(output, item),
+            Ok(ToolStreamItem::Tool(_)) => {}
+            Err(err) => output.error = Some(err.to_string()),
+        }
+    }
+}
+
+/// 流式提问，最多进行 `max_turns` 轮工具调用
+///
+/// 请求在后台任务中进行，丢弃返回的流后任务在下一次输出时结束
+pub fn stream_with_tool_events(
+    agent: Arc<BoxAgent<'static>>,
+    prompt: Message,
+    max_turns: usize,
+) -> ToolEventStream {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        if let Err(err) = run_turns(&agent, prompt, max_turns, &sender).await {
+            let _ = sender.send(Err(err));
+        }
+    });
+    futures::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|item| (item, receiver))
+    })
+    .boxed()
+}
+
+type ItemSender = mpsc::UnboundedSender<Result<ToolStreamItem, RandAgentError>>;
+
+/// 多轮对话，调用方已丢弃流时提前返回
+async fn run_turns(
+    agent: &BoxAgent<'static>,
+    prompt: Message,
+    max_turns: usize,
+    sender: &ItemSender,
+) -> Result<(), RandAgentError> {
+    let completion_error = |err: CompletionError| RandAgentError::AgentError(Box::new(err));
+    let send = |item: ToolStreamItem| sender.send(Ok(item)).is_ok();
+
+    let mut history = Vec::new();
+    let mut current = prompt;
+    let mut usage = Usage::new();
+
+    for _ in 0..=max_turns {
+        let mut stream = agent
+            .stream_completion(current.clone(), history.clone())
+            .await
+            .map_err(completion_error)?
+            .stream()
+            .await
+            .map_err(completion_error)?;
+        history.push(current.clone());
+
+        let mut text = String::new();
+        let mut tool_calls = Vec::new();
+        let mut tool_results = Vec::new();
+        while let Some(content) = stream.next().await {
+            let item = match content.map_err(completion_error)? {
+                StreamedAssistantContent::ToolCall(tool_call) => {
+                    let name = tool_call.function.name.clone();
+                    let args = tool_call.function.arguments.to_string();
+                    if !send(ToolStreamItem::Tool(ToolEvent::Started {
+                        name: name.clone(),
+                        args: args.clone(),
+                    })) {
+                        return Ok(());
+                    }
+
+                    let start = Instant::now();
+                    let result = match agent.tool_server_handle.call_tool(&name, &args).await {
+                        Ok(result) => result,
+                        Err(err) => {
+                            tracing::warn!(
+                                "工具 {name} 调用失败: {}",
+                                crate::redact::redacted(&err)
+                            );
+                            err.to_string()
+                        }
+                    };
+                    tool_results.push((
+                        tool_call.id.clone(),
+                        tool_call.call_id.clone(),
+                        result.clone(),
+                    ));
+                    tool_calls.push(AssistantContent::ToolCall(tool_call));
+                    ToolStreamItem::Tool(ToolEvent::Finished {
+                        name,
+                        args,
+                        result,
+                        duration: start.elapsed(),
+                    })
+                }
+                StreamedAssistantContent::Final(response) => {
+                    if let Some(turn_usage) = response.token_usage() {
+                        usage += turn_usage;
+                    }
+                    ToolStreamItem::Item(MultiTurnStreamItem::StreamItem(
+                        StreamedAssistantContent::Final(response),
+                    ))
+                }
+                content => {
+                    if let StreamedAssistantContent::Text(delta) = &content {
+                        text.push_str(&delta.text);
+                    }
+                    ToolStreamItem::Item(MultiTurnStreamItem::StreamItem(content))
+                }
+            };
+            if !send(item) {
+                return Ok(());
+            }
+        }
+
+        let Ok(tool_calls) = OneOrMany::many(tool_calls) else {
+            send(ToolStreamItem::Item(MultiTurnStreamItem::final_response(
+                &text, usage,
+            )));
+            return Ok(());
+        };
+        history.push(Message::Assistant {
+            id: None,
+            content: tool_calls,
+        });
+        let results = tool_results.into_iter().map(|(id, call_id, result)| {
+            let content = OneOrMany::one(ToolResultContent::text(result));
+            match call_id {
+                Some(call_id) => UserContent::tool_result_with_call_id(id, call_id, content),
+                None => UserContent::tool_result(id, content),
+            }
+        });
+        current = Message::User {
+            content: OneOrMany::many(results).expect("至少有一个工具结果"),
+        };
+    }
+
+    Err(RandAgentError::AgentError(Box::new(
+        PromptError::MaxDepthError {
+            max_depth: max_turns,
+            chat_history: Box::new(history),
+            prompt: current,
+        },
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rig::message::Text;
+
+    #[test]
+    fn test_tool_events_are_not_collected() {
+        let mut output = TeeOutput::default();
+        let items: Vec<Result<ToolStreamItem, RandAgentError>> = vec![
+            Ok(ToolStreamItem::Tool(ToolEvent::Started {
+                name: "web_search".to_string(),
+                args: "{\"query\": \"天气\"}".to_string(),
+            })),
+            Ok(ToolStreamItem::Item(MultiTurnStreamItem::StreamItem(
+                StreamedAssistantContent::Text(Text {
+                    text: "今天晴".to_string(),
+                }),
+            ))),
+            Ok(ToolStreamItem::Item(MultiTurnStreamItem::final_response(
+                "今天晴",
+                Usage::new(),
+            ))),
+        ];
+        for item in &items {
+            item.observe(&mut output);
+        }
+        assert_eq!(output.text, "今天晴");
+        assert!(output.complete);
+    }
+}
@@ -0,0 +1,212 @@
+//! 历史记录中工具结果的自动摘要
+//!
+//! 多轮对话中，早期轮次的工具结果(搜索结果、网页内容等)往往很长，却在之后的每一轮都被
+//! 原样发送给模型。[`ToolResultSummarizer`] 在发送前把较早轮次中过长的工具结果替换为
+//! 由廉价 agent 生成的简短摘要，使工具密集的对话自动保持在预算之内。
+//!
+//! ```rust,ignore
+//! let cheap_pool = RandAgentBuilder::new()
+//!     .add_agent(glm_4_flash, 1, "bigmodel".to_string(), "glm-4-flash".to_string())
+//!     .build();
+//!
+//! let rand_agent = RandAgentBuilder::new()
+//!     .summarize_tool_results(ToolResultSummarizer::new(Arc::new(cheap_pool)).max_chars(2000))
+//!     .build();
+//!
+//! // chat 时较早轮次中超过 2000 字符的工具结果会被替换为摘要
+//! let response = rand_agent.chat("继续", history).await?;
+//! ```
+//!
+//! 摘要按原文缓存，同一个工具结果只会被摘要一次；摘要失败时保留原文。
+
+use crate::dyn_agent::DynPromptAgent;
+use rig::completion::Message;
+use rig::message::{ToolResultContent, UserContent};
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+/// 摘要内容的前缀，便于模型区分摘要和原始结果
+const SUMMARY_PREFIX: &str = "[工具结果摘要]";
+
+/// 工具结果摘要器
+#[derive(Clone)]
+pub struct ToolResultSummarizer {
+    agent: Arc<dyn DynPromptAgent>,
+    max_chars: usize,
+    keep_recent: usize,
+    cache: Arc<Mutex<HashMap<u64, String>>>,
+}
+
+impl ToolResultSummarizer {
+    /// 使用指定 agent 生成摘要，建议使用便宜、快速的模型
+    pub fn new(agent: Arc<dyn DynPromptAgent>) -> Self {
+        Self {
+            agent,
+            max_chars: 2000,
+            keep_recent: 2,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// 超过该字符数的工具结果才会被摘要，默认 2000
+    pub fn max_chars(mut self, max_chars: usize) -> Self {
+        self.max_chars = max_chars;
+        self
+    }
+
+    /// 最近的若干条消息保持原样，默认 2 条(最近一次工具调用及其结果)
+    pub fn keep_recent(mut self, keep_recent: usize) -> Self {
+        self.keep_recent = keep_recent;
+        self
+    }
+
+    /// 将较早消息中过长的工具结果替换为摘要
+    pub async fn compact(&self, mut history: Vec<Message>) -> Vec<Message> {
+        let boundary = history.len().saturating_sub(self.keep_recent);
+        for message in history[..boundary].iter_mut() {
+            let Message::User { content } = message else {
+                continue;
+            };
+            for user_content in content.iter_mut() {
+                let UserContent::ToolResult(tool_result) = user_content else {
+                    continue;
+                };
+                for result_content in tool_result.content.iter_mut() {
+                    if let ToolResultContent::Text(text) = result_content
+                        && text.text.chars().count() > self.max_chars
+                        && let Some(summary) = self.summarize(&text.text).await
+                    {
+                        text.text = summary;
+                    }
+                }
+            }
+        }
+        history
+    }
+
+    async fn summarize(&self, text: &str) -> Option<String> {
+        let key = hash_text(text);
+        if let Some(summary) = self.cached(key) {
+            return Some(summary);
+        }
+
+        let prompt = format!(
+            "请用不超过 {} 个字概括以下工具调用结果，保留关键数据、名称和数字，只输出摘要:\n\n{text}",
+            self.max_chars / 4
+        );
+        match self.agent.prompt_dyn(prompt.into()).await {
+            Ok(summary) => {
+                let summary = format!("{SUMMARY_PREFIX} {}", summary.trim());
+                self.cache
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .insert(key, summary.clone());
+                Some(summary)
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "工具结果摘要失败，保留原文: {}",
+                    crate::redact::redacted(&err)
+                );
+                None
+            }
+        }
+    }
+
+    fn cached(&self, key: u64) -> Option<String> {
+        self.cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&key)
+            .cloned()
+    }
+}
+
+fn hash_text(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dyn_agent::DynStream;
+    use crate::error::RandAgentError;
+    use futures::future::BoxFuture;
+    use rig::OneOrMany;
+    use rig::completion::PromptError;
+    use rig::message::{Text, ToolResult};
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    struct CountingAgent(AtomicU32);
+
+    impl DynPromptAgent for CountingAgent {
+        fn prompt_dyn(&self, _prompt: Message) -> BoxFuture<'_, Result<String, PromptError>> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async { Ok("摘要".to_string()) })
+        }
+
+        fn chat_dyn(
+            &self,
+            prompt: Message,
+            _chat_history: Vec<Message>,
+        ) -> BoxFuture<'_, Result<String, PromptError>> {
+            self.prompt_dyn(prompt)
+        }
+
+        fn stream_dyn(&self, _prompt: Message) -> BoxFuture<'_, Result<DynStream, RandAgentError>> {
+            Box::pin(async { Err(RandAgentError::NoValidAgents) })
+        }
+    }
+
+    fn tool_result(text: &str) -> Message {
+        Message::User {
+            content: OneOrMany::one(UserContent::ToolResult(ToolResult {
+                id: "call_1".to_string(),
+                call_id: None,
+                content: OneOrMany::one(ToolResultContent::Text(Text {
+                    text: text.to_string(),
+                })),
+            })),
+        }
+    }
+
+    fn tool_text(message: &Message) -> String {
+        match message {
+            Message::User { content } => match content.first() {
+                UserContent::ToolResult(result) => match result.content.first() {
+                    ToolResultContent::Text(text) => text.text,
+                    _ => unreachable!(),
+                },
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compact_old_tool_results() {
+        let agent = Arc::new(CountingAgent(AtomicU32::new(0)));
+        let summarizer = ToolResultSummarizer::new(agent.clone())
+            .max_chars(10)
+            .keep_recent(1);
+        let long = "很长的搜索结果".repeat(5);
+        let history = vec![
+            tool_result(&long),
+            tool_result("短结果"),
+            tool_result(&long),
+        ];
+
+        let compacted = summarizer.compact(history.clone()).await;
+        assert_eq!(tool_text(&compacted[0]), "[工具结果摘要] 摘要");
+        assert_eq!(tool_text(&compacted[1]), "短结果");
+        assert_eq!(tool_text(&compacted[2]), long);
+
+        // 相同的工具结果使用缓存的摘要
+        summarizer.compact(history).await;
+        assert_eq!(agent.0.load(Ordering::SeqCst), 1);
+    }
+}
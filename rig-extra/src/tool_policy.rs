@@ -0,0 +1,95 @@
+//! 工具使用规范
+//!
+//! 较小的模型经常在不需要时调用工具、编造参数或在需要时忘记调用工具。开启
+//! `RandAgentBuilder::tool_usage_policy` 后，代理池根据每个 agent 挂载的工具定义
+//! 生成一段工具使用规范，追加到系统提示词末尾，包括工具名称、适用场景和参数说明。
+//!
+//! ```rust,ignore
+//! let rand_agent = RandAgentBuilder::new()
+//!     .simple_builder_with_tools(configs, "你是一个助手".to_string(), tools)
+//!     .tool_usage_policy(true)
+//!     .build();
+//! ```
+//!
+//! 规范在 agent 第一次被使用时生成并缓存，同时对系统提示词版本生效。
+
+use rig::completion::ToolDefinition;
+use serde_json::Value;
+
+/// 根据工具定义生成工具使用规范，没有工具时返回 None
+pub fn tool_usage_policy(definitions: &[ToolDefinition]) -> Option<String> {
+    if definitions.is_empty() {
+        return None;
+    }
+    let mut policy = String::from(
+        "## 工具使用规范\n\
+         你可以调用以下工具。只在问题需要工具提供的能力或实时信息时调用，能直接回答时不要调用；\
+         不要编造工具结果，参数必须是符合说明的 JSON。\n",
+    );
+    for definition in definitions {
+        policy.push_str(&format!(
+            "\n- `{}`: {}\n",
+            definition.name,
+            definition.description.trim()
+        ));
+        for hint in argument_hints(&definition.parameters) {
+            policy.push_str(&format!("  - {hint}\n"));
+        }
+    }
+    Some(policy)
+}
+
+/// 从 JSON Schema 中提取参数说明
+fn argument_hints(parameters: &Value) -> Vec<String> {
+    let Some(properties) = parameters["properties"].as_object() else {
+        return Vec::new();
+    };
+    let required: Vec<&str> = parameters["required"]
+        .as_array()
+        .map(|required| required.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    properties
+        .iter()
+        .map(|(name, property)| {
+            let mut hint = format!("`{name}`");
+            if let Some(kind) = property["type"].as_str() {
+                hint.push_str(&format!(" ({kind})"));
+            }
+            if required.contains(&name.as_str()) {
+                hint.push_str(" 必填");
+            }
+            if let Some(description) = property["description"].as_str() {
+                hint.push_str(&format!(": {}", description.trim()));
+            }
+            hint
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_tool_usage_policy() {
+        assert!(tool_usage_policy(&[]).is_none());
+
+        let policy = tool_usage_policy(&[ToolDefinition {
+            name: "web_search".to_string(),
+            description: "搜索网页，用于查询实时信息".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "query": {"type": "string", "description": "搜索关键词"},
+                    "num": {"type": "integer"}
+                },
+                "required": ["query"]
+            }),
+        }])
+        .unwrap();
+        assert!(policy.contains("- `web_search`: 搜索网页，用于查询实时信息"));
+        assert!(policy.contains("  - `query` (string) 必填: 搜索关键词"));
+        assert!(policy.contains("  - `num` (integer)\n"));
+    }
+}
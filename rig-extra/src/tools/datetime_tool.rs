@@ -1,14 +1,30 @@
 //! 获取时间日期
 
-use chrono::{Datelike, Local};
+use crate::i18n::Locale;
+use chrono::{DateTime, Datelike, FixedOffset, Local, Offset, Utc};
 use rig::completion::ToolDefinition;
 use rig::tool::Tool;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use tyme4rs::tyme::solar::SolarDay;
 
-#[derive(Deserialize, Serialize)]
-pub struct DatetimeTool;
+/// 时间日期工具
+///
+/// 默认使用服务器本地时区和中文输出，服务器运行在 UTC 时可通过
+/// [`DatetimeTool::with_utc_offset`] 指定用户所在时区。
+/// 英文输出只包含公历时间和星期，不包含农历、生肖、节气等中文历法信息。
+///
+/// 注意: `DatetimeTool` 不再是单元结构体，原来直接写 `DatetimeTool` 的地方
+/// 需要改为 `DatetimeTool::new()` 或 `DatetimeTool::default()`，行为与之前相同
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+pub struct DatetimeTool {
+    /// 相对 UTC 的偏移秒数，为空时使用服务器本地时区
+    #[serde(default, deserialize_with = "deserialize_utc_offset")]
+    utc_offset_seconds: Option<i32>,
+    /// 输出语言
+    #[serde(default)]
+    locale: Locale,
+}
 
 #[derive(Deserialize, Serialize, Default)]
 pub struct EmptyArgs {}
@@ -17,13 +33,107 @@ pub struct EmptyArgs {}
 #[error("DatetimeTool error")]
 pub struct DatetimeToolError;
 
+/// 时区偏移格式错误或超出范围
+#[derive(Debug, thiserror::Error)]
+#[error("无效的 UTC 偏移: {0}")]
+pub struct InvalidUtcOffset(pub String);
+
+/// 解析 `+08:00`、`-03:30`、`+0545`、`+8` 形式的偏移，`Z`/`UTC` 表示零时区
+fn parse_utc_offset(offset: &str) -> Option<FixedOffset> {
+    let trimmed = offset.trim();
+    let trimmed = trimmed.strip_prefix("UTC").unwrap_or(trimmed);
+    if trimmed.is_empty() || trimmed == "Z" {
+        return FixedOffset::east_opt(0);
+    }
+    let (sign, rest) = match trimmed.as_bytes()[0] {
+        b'+' => (1, &trimmed[1..]),
+        b'-' => (-1, &trimmed[1..]),
+        _ => return None,
+    };
+    let (hours, minutes) = match rest.split_once(':') {
+        Some((hours, minutes)) => (hours, minutes),
+        None if rest.len() == 4 => rest.split_at(2),
+        None => (rest, "0"),
+    };
+    if hours.is_empty() || hours.len() > 2 || minutes.len() > 2 {
+        return None;
+    }
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    if hours > 14 || minutes >= 60 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+fn deserialize_utc_offset<'de, D>(deserializer: D) -> Result<Option<i32>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let seconds = Option::<i32>::deserialize(deserializer)?;
+    match seconds {
+        Some(seconds) if seconds.abs() > 14 * 3600 => Err(serde::de::Error::custom(
+            InvalidUtcOffset(format!("{seconds}s")),
+        )),
+        _ => Ok(seconds),
+    }
+}
+
 impl DatetimeTool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 指定时区，格式为相对 UTC 的偏移，如东八区为 `+08:00`，尼泊尔为 `+05:45`
+    ///
+    /// 也接受 `+0800`、`+8`、`UTC+8` 和 `Z`，格式错误或超出 ±14 小时时返回错误
+    pub fn with_utc_offset(mut self, offset: &str) -> Result<Self, InvalidUtcOffset> {
+        let offset =
+            parse_utc_offset(offset).ok_or_else(|| InvalidUtcOffset(offset.to_string()))?;
+        self.utc_offset_seconds = Some(offset.local_minus_utc());
+        Ok(self)
+    }
+
+    /// 指定输出语言
+    pub fn with_locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    /// 当前使用的时区
+    fn offset(&self) -> FixedOffset {
+        self.utc_offset_seconds
+            .and_then(FixedOffset::east_opt)
+            .unwrap_or_else(|| Local::now().offset().fix())
+    }
+
     /// 获取时间信息
     pub fn get_time_info(&self) -> String {
-        let now = Local::now();
+        self.time_info_at(Utc::now())
+    }
+
+    /// 获取指定 UTC 时刻的时间信息
+    pub fn time_info_at(&self, utc: DateTime<Utc>) -> String {
+        let offset = self.offset();
+        let now = utc.with_timezone(&offset);
         let mut info = Vec::new();
-        let time_info = format!("当前时间: {}", now.format("%Y-%m-%d %H:%M:%S"));
-        info.push(time_info);
+        if self.locale == Locale::En {
+            info.push(format!(
+                "Local time: {} (UTC{})",
+                now.format("%Y-%m-%d %H:%M:%S"),
+                offset
+            ));
+            info.push(format!("UTC time: {}", utc.format("%Y-%m-%d %H:%M:%S")));
+            info.push(format!("Weekday: {}", now.format("%A")));
+            return info.join(", ");
+        }
+
+        info.push(format!(
+            "当前时间: {} (UTC{})",
+            now.format("%Y-%m-%d %H:%M:%S"),
+            offset
+        ));
+        info.push(format!("UTC时间: {}", utc.format("%Y-%m-%d %H:%M:%S")));
 
         let solar: SolarDay = SolarDay::from_ymd(
             now.year() as isize,
@@ -69,8 +179,11 @@ impl Tool for DatetimeTool {
     async fn definition(&self, _prompt: String) -> ToolDefinition {
         ToolDefinition {
             name: Self::NAME.to_string(),
-            description: "获取当前时间日期的工具,包括获取农历、公历，法定假期、生肖的信息"
-                .to_string(),
+            description: match self.locale {
+                Locale::Zh => "获取当前时间日期的工具,包括获取农历、公历，法定假期、生肖的信息",
+                Locale::En => "Get the current date, time and weekday",
+            }
+            .to_string(),
             parameters: json!({
                 "type": "object",
                 "title": "No parameters",
@@ -95,6 +208,72 @@ mod tests {
     use rig::client::CompletionClient;
     use rig::completion::Prompt;
 
+    #[test]
+    fn test_time_info_with_offset() {
+        let utc = DateTime::parse_from_rfc3339("2025-01-01T20:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let info = DatetimeTool::new()
+            .with_utc_offset("+08:00")
+            .unwrap()
+            .time_info_at(utc);
+        assert!(info.contains("当前时间: 2025-01-02 04:00:00 (UTC+08:00)"));
+        assert!(info.contains("UTC时间: 2025-01-01 20:00:00"));
+
+        let info = DatetimeTool::new()
+            .with_utc_offset("+05:45")
+            .unwrap()
+            .time_info_at(utc);
+        assert!(info.contains("当前时间: 2025-01-02 01:45:00 (UTC+05:45)"));
+
+        let info = DatetimeTool::new()
+            .with_utc_offset("Z")
+            .unwrap()
+            .with_locale(Locale::En)
+            .time_info_at(utc);
+        assert_eq!(
+            info,
+            "Local time: 2025-01-01 20:00:00 (UTC+00:00), UTC time: 2025-01-01 20:00:00, Weekday: Wednesday"
+        );
+        // 英文输出不包含中文历法信息
+        assert!(info.is_ascii());
+    }
+
+    #[test]
+    fn test_utc_offset_formats() {
+        let seconds = |offset: &str| {
+            DatetimeTool::new()
+                .with_utc_offset(offset)
+                .map(|tool| tool.utc_offset_seconds.unwrap())
+                .ok()
+        };
+        assert_eq!(seconds("+8"), Some(8 * 3600));
+        assert_eq!(seconds("UTC+8"), Some(8 * 3600));
+        assert_eq!(seconds("+0530"), Some(5 * 3600 + 30 * 60));
+        assert_eq!(seconds("-03:30"), Some(-(3 * 3600 + 30 * 60)));
+        assert_eq!(seconds("+14:00"), Some(14 * 3600));
+
+        for invalid in [
+            "8",
+            "+15",
+            "+08:60",
+            "+8:00:00",
+            "Asia/Shanghai",
+            "+",
+            "+123",
+        ] {
+            assert!(seconds(invalid).is_none(), "{invalid}");
+        }
+
+        let err = serde_json::from_value::<DatetimeTool>(json!({"utc_offset_seconds": 90000}))
+            .unwrap_err();
+        assert!(err.to_string().contains("无效的 UTC 偏移"), "{err}");
+        let tool: DatetimeTool =
+            serde_json::from_value(json!({"utc_offset_seconds": 3600})).unwrap();
+        assert_eq!(tool.utc_offset_seconds, Some(3600));
+    }
+
     #[tokio::test]
     async fn test_datetime_tool() {
         let current_dir = format!("{}\\..\\Settings", env!("CARGO_MANIFEST_DIR"));
@@ -111,7 +290,7 @@ mod tests {
         let client = bigmodel::Client::new(api_key.as_str());
         let agent = client
             .agent(BIGMODEL_GLM_4_FLASH)
-            .tool(DatetimeTool)
+            .tool(DatetimeTool::new().with_utc_offset("+08:00").unwrap())
             .name("ai agent")
             .preamble("你是一个ai助手")
             .build();
@@ -1,17 +1,74 @@
 //! 获取github趋势榜: https://github.com/trending
+//! 支持趋势仓库和趋势开发者: https://github.com/trending/developers
+//!
+//! 趋势榜变化较慢，而 agent 在一次对话中可能多次调用该工具，因此页面内容会在 TTL 内缓存，
+//! 过期后使用 ETag/If-Modified-Since 发起条件请求
 
+use reqwest::StatusCode;
+use reqwest::header::{ETAG, HeaderName, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
 use rig::completion::ToolDefinition;
 use rig::tool::Tool;
-use scraper::{Html, Selector};
+use schemars::{JsonSchema, schema_for};
+use scraper::{ElementRef, Html, Selector};
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-#[derive(Deserialize, Serialize)]
-pub struct GithubTrendingTool;
+const GITHUB_TRENDING_URL: &str = "https://github.com/trending";
+const GITHUB_TRENDING_DEVELOPERS_URL: &str = "https://github.com/trending/developers";
+/// 默认缓存时间
+const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
 
+#[derive(Clone)]
+struct CacheEntry {
+    body: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    fetched_at: Instant,
+}
+
+/// github 趋势榜工具
+///
+/// 注意: `GithubTrendingTool` 不再是单元结构体，原来直接写 `GithubTrendingTool` 的地方
+/// 需要改为 `GithubTrendingTool::new()` 或 `GithubTrendingTool::default()`
+#[derive(Clone)]
+pub struct GithubTrendingTool {
+    client: reqwest::Client,
+    cache_ttl: Duration,
+    cache: Arc<Mutex<HashMap<String, CacheEntry>>>,
+}
+
+impl Default for GithubTrendingTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 旧版本的工具参数，工具现在使用 [`GithubTrendingArgs`]，不传参数时与之前行为相同
+#[deprecated(since = "0.13.1", note = "请使用 GithubTrendingArgs")]
 #[derive(Deserialize, Serialize, Default)]
 pub struct EmptyArgs {}
 
+/// 趋势榜类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TrendingKind {
+    /// 趋势仓库
+    #[default]
+    Repositories,
+    /// 趋势开发者
+    Developers,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema)]
+/// github趋势榜参数
+pub struct GithubTrendingArgs {
+    /// 榜单类型: `repositories`: 趋势仓库(默认), `developers`: 趋势开发者
+    #[serde(default)]
+    pub kind: TrendingKind,
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum GithubTrendingToolError {
     #[error("Network request failed: {0}")]
@@ -38,12 +95,193 @@ pub struct GithubTrendingData {
     /// 代码仓库今天star数量
     pub today_stars: String,
 }
+
+#[derive(Debug, Serialize, Deserialize, schemars::JsonSchema)]
+/// github趋势开发者
+pub struct GithubTrendingDeveloper {
+    /// 开发者名称
+    pub name: String,
+    /// 开发者用户名
+    pub username: String,
+    /// 开发者主页链接
+    pub url: String,
+    /// 头像链接
+    pub avatar: String,
+    /// 热门仓库名称
+    pub repo: String,
+    /// 热门仓库描述
+    pub repo_description: String,
+    /// 热门仓库链接
+    pub repo_url: String,
+}
+
+/// github趋势榜结果
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum GithubTrendingOutput {
+    Repositories(Vec<GithubTrendingData>),
+    Developers(Vec<GithubTrendingDeveloper>),
+}
+
+fn parse_selector(selector: &str) -> Result<Selector, GithubTrendingToolError> {
+    Selector::parse(selector).map_err(|e| GithubTrendingToolError::Selector(e.to_string()))
+}
+
+/// 获取元素下第一个匹配元素的文本
+fn select_text(element: &ElementRef, selector: &Selector) -> String {
+    element
+        .select(selector)
+        .next()
+        .map(|element| element.text().collect::<Vec<_>>().join(""))
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+/// 获取元素下第一个匹配元素的属性
+fn select_attr(element: &ElementRef, selector: &Selector, attr: &str) -> Option<String> {
+    element
+        .select(selector)
+        .next()
+        .and_then(|element| element.value().attr(attr))
+        .map(|value| value.to_string())
+}
+
+/// 解析趋势开发者页面
+fn parse_developers(
+    content: &str,
+) -> Result<Vec<GithubTrendingDeveloper>, GithubTrendingToolError> {
+    let document = Html::parse_document(content);
+    let selector = parse_selector("article.Box-row")?;
+    let name_selector = parse_selector("h1.h3 a")?;
+    let username_selector = parse_selector("p.f4 a")?;
+    let avatar_selector = parse_selector("img.avatar-user")?;
+    let repo_selector = parse_selector("h1.h4 a")?;
+    let repo_desc_selector = parse_selector("div.f6.color-fg-muted.mt-1")?;
+
+    let mut results = Vec::new();
+    for element in document.select(&selector) {
+        let href = select_attr(&element, &name_selector, "href").unwrap_or_default();
+        let mut username = select_text(&element, &username_selector);
+        if username.is_empty() {
+            username = href.trim_start_matches('/').to_string();
+        }
+        let name = select_text(&element, &name_selector);
+        let repo = select_text(&element, &repo_selector)
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join("");
+
+        results.push(GithubTrendingDeveloper {
+            name: if name.is_empty() {
+                username.clone()
+            } else {
+                name
+            },
+            url: format!("https://github.com/{username}"),
+            username,
+            avatar: select_attr(&element, &avatar_selector, "src").unwrap_or_default(),
+            repo,
+            repo_description: select_text(&element, &repo_desc_selector),
+            repo_url: select_attr(&element, &repo_selector, "href")
+                .map(|href| format!("https://github.com{href}"))
+                .unwrap_or_default(),
+        });
+    }
+    Ok(results)
+}
+
 impl GithubTrendingTool {
+    /// 创建工具，默认缓存 10 分钟
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            cache_ttl: DEFAULT_CACHE_TTL,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// 设置缓存时间，为 0 时每次调用都会发起(条件)请求
+    pub fn with_cache_ttl(mut self, cache_ttl: Duration) -> Self {
+        self.cache_ttl = cache_ttl;
+        self
+    }
+
+    /// 缓存只保存完整的页面内容，锁中毒时继续使用
+    fn lock_cache(&self) -> std::sync::MutexGuard<'_, HashMap<String, CacheEntry>> {
+        self.cache.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 清空缓存
+    pub fn clear_cache(&self) {
+        self.lock_cache().clear();
+    }
+
+    /// 获取页面内容，优先使用缓存，缓存过期后发起条件请求
+    async fn fetch(&self, url: &str) -> Result<String, GithubTrendingToolError> {
+        let cached = self.lock_cache().get(url).cloned();
+        if let Some(entry) = &cached
+            && entry.fetched_at.elapsed() < self.cache_ttl
+        {
+            tracing::debug!("github trending 命中缓存: {}", url);
+            return Ok(entry.body.clone());
+        }
+
+        let mut request = self.client.get(url);
+        if let Some(entry) = &cached {
+            if let Some(etag) = &entry.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &entry.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let resp = request.send().await?;
+
+        if resp.status() == StatusCode::NOT_MODIFIED
+            && let Some(mut entry) = cached
+        {
+            tracing::debug!("github trending 未修改: {}", url);
+            entry.fetched_at = Instant::now();
+            let body = entry.body.clone();
+            self.lock_cache().insert(url.to_string(), entry);
+            return Ok(body);
+        }
+
+        let resp = resp.error_for_status()?;
+        let header_value = |name: HeaderName| {
+            resp.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string())
+        };
+        let etag = header_value(ETAG);
+        let last_modified = header_value(LAST_MODIFIED);
+        let body = resp.text().await?;
+
+        self.lock_cache().insert(
+            url.to_string(),
+            CacheEntry {
+                body: body.clone(),
+                etag,
+                last_modified,
+                fetched_at: Instant::now(),
+            },
+        );
+        Ok(body)
+    }
+
+    async fn get_github_trending_developers(
+        &self,
+    ) -> Result<Vec<GithubTrendingDeveloper>, GithubTrendingToolError> {
+        let content = self.fetch(GITHUB_TRENDING_DEVELOPERS_URL).await?;
+        parse_developers(&content)
+    }
+
     async fn get_github_trending(
         &self,
     ) -> Result<Vec<GithubTrendingData>, GithubTrendingToolError> {
-        let resp = reqwest::get("https://github.com/trending").await?;
-        let content = resp.text().await?;
+        let content = self.fetch(GITHUB_TRENDING_URL).await?;
 
         let document = Html::parse_document(&content);
         let selector = Selector::parse(".Box-row")
@@ -151,24 +389,26 @@ impl GithubTrendingTool {
 impl Tool for GithubTrendingTool {
     const NAME: &'static str = "GithubTrendingTool";
     type Error = GithubTrendingToolError;
-    type Args = EmptyArgs;
-    type Output = Vec<GithubTrendingData>;
+    type Args = GithubTrendingArgs;
+    type Output = GithubTrendingOutput;
 
     async fn definition(&self, _prompt: String) -> ToolDefinition {
         ToolDefinition {
             name: Self::NAME.to_string(),
-            description: "获取github趋势榜单".to_string(),
-            parameters: json!({
-                "type": "object",
-                "title": "No parameters",
-                "properties": {},
-                "additionalProperties": false
-            }),
+            description: "获取github趋势榜单,包括趋势仓库和趋势开发者".to_string(),
+            parameters: serde_json::to_value(schema_for!(Self::Args)).unwrap(),
         }
     }
 
-    async fn call(&self, _args: Self::Args) -> Result<Self::Output, Self::Error> {
-        let data = self.get_github_trending().await?;
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let data = match args.kind {
+            TrendingKind::Repositories => {
+                GithubTrendingOutput::Repositories(self.get_github_trending().await?)
+            }
+            TrendingKind::Developers => {
+                GithubTrendingOutput::Developers(self.get_github_trending_developers().await?)
+            }
+        };
         Ok(data)
     }
 }
@@ -197,7 +437,7 @@ mod tests {
         let client = bigmodel::Client::new(api_key.as_str());
         let agent = client
             .agent(BIGMODEL_GLM_4_FLASH)
-            .tool(GithubTrendingTool)
+            .tool(GithubTrendingTool::new())
             .name("ai agent")
             .preamble("你是一个ai助手")
             .build();
@@ -208,4 +448,120 @@ mod tests {
             .unwrap();
         println!("{}", result);
     }
+
+    /// 本地页面服务，返回 ETag 并对匹配的 If-None-Match 返回 304，记录收到的条件请求头
+    async fn serve_page(body: &'static str) -> (String, Arc<Mutex<Vec<Option<String>>>>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/trending", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..n]);
+                }
+                let if_none_match = String::from_utf8_lossy(&request)
+                    .lines()
+                    .find_map(|line| line.strip_prefix("if-none-match: ").map(str::to_string));
+                let response = if if_none_match.as_deref() == Some("\"v1\"") {
+                    "HTTP/1.1 304 Not Modified\r\nconnection: close\r\n\r\n".to_string()
+                } else {
+                    format!(
+                        "HTTP/1.1 200 OK\r\netag: \"v1\"\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                        body.len()
+                    )
+                };
+                recorded.lock().unwrap().push(if_none_match);
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (url, requests)
+    }
+
+    fn local_tool() -> GithubTrendingTool {
+        GithubTrendingTool {
+            client: reqwest::Client::builder().no_proxy().build().unwrap(),
+            ..GithubTrendingTool::new()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_cache_and_conditional_request() {
+        let (url, requests) = serve_page("trending page").await;
+        let tool = local_tool().with_cache_ttl(Duration::from_millis(50));
+
+        assert_eq!(tool.fetch(&url).await.unwrap(), "trending page");
+        // TTL 内命中缓存，不发请求
+        assert_eq!(tool.fetch(&url).await.unwrap(), "trending page");
+        assert_eq!(*requests.lock().unwrap(), vec![None]);
+
+        // 过期后带 ETag 发起条件请求，304 时沿用缓存内容并重新计时
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(tool.fetch(&url).await.unwrap(), "trending page");
+        assert_eq!(tool.fetch(&url).await.unwrap(), "trending page");
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec![None, Some("\"v1\"".to_string())]
+        );
+
+        // 清空缓存后重新完整请求
+        tool.clear_cache();
+        assert_eq!(tool.fetch(&url).await.unwrap(), "trending page");
+        assert_eq!(requests.lock().unwrap().len(), 3);
+        assert_eq!(requests.lock().unwrap()[2], None);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_without_cache_ttl() {
+        let (url, requests) = serve_page("trending page").await;
+        let tool = local_tool().with_cache_ttl(Duration::ZERO);
+
+        tool.fetch(&url).await.unwrap();
+        tool.fetch(&url).await.unwrap();
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec![None, Some("\"v1\"".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_parse_developers() {
+        let html = r#"
+            <article class="Box-row d-flex" id="pa-octocat">
+                <img class="rounded avatar-user" src="https://avatars.githubusercontent.com/u/583231" />
+                <h1 class="h3 lh-condensed"><a href="/octocat">The Octocat</a></h1>
+                <p class="f4 text-normal mb-1"><a href="/octocat">octocat</a></p>
+                <article>
+                    <h1 class="h4 lh-condensed">
+                        <a href="/octocat/Hello-World">
+                            Hello-World
+                        </a>
+                    </h1>
+                    <div class="f6 color-fg-muted mt-1">My first repository</div>
+                </article>
+            </article>
+        "#;
+        let developers = parse_developers(html).unwrap();
+        assert_eq!(developers.len(), 1);
+        let developer = &developers[0];
+        assert_eq!(developer.name, "The Octocat");
+        assert_eq!(developer.username, "octocat");
+        assert_eq!(developer.url, "https://github.com/octocat");
+        assert_eq!(
+            developer.avatar,
+            "https://avatars.githubusercontent.com/u/583231"
+        );
+        assert_eq!(developer.repo, "Hello-World");
+        assert_eq!(developer.repo_description, "My first repository");
+        assert_eq!(developer.repo_url, "https://github.com/octocat/Hello-World");
+    }
 }
@@ -3,4 +3,10 @@ pub mod datetime_tool;
 #[cfg(feature = "rig-extra-tools")]
 pub mod github_trending_tool;
 #[cfg(feature = "rig-extra-tools")]
+pub mod pagination;
+#[cfg(feature = "rig-extra-tools")]
+pub mod registry;
+#[cfg(feature = "rig-extra-tools")]
 pub mod serpapi_tool;
+#[cfg(feature = "rig-extra-tools")]
+pub mod tool_error;
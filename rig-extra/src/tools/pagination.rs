@@ -0,0 +1,262 @@
+//! 大体积工具输出分页
+//!
+//! 爬虫、SQL、GitHub API 等工具的输出可能远超上下文限制。约定如下:
+//! 工具通过 [`Paginator`] 只返回第一页和一个游标，其余页暂存在内存中，
+//! 模型需要更多内容时调用 [`NextPageTool`] 并传入游标获取下一页。
+//!
+//! ```rust,ignore
+//! use rig_extra::tools::pagination::{NextPageTool, Paginator};
+//!
+//! let paginator = Paginator::new().max_items(20).max_chars(8000);
+//! // 在自定义工具的 call 中:
+//! let page = paginator.paginate(rows);
+//!
+//! let agent = client
+//!     .agent("glm-4-flash")
+//!     .tool(MySqlTool::new(paginator.clone()))
+//!     .tool(NextPageTool::new(paginator))
+//!     .build();
+//! ```
+
+use rig::completion::ToolDefinition;
+use rig::tool::Tool;
+use schemars::{JsonSchema, schema_for};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// 一页工具输出
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Page {
+    /// 本页内容
+    pub items: Vec<Value>,
+    /// 当前页码，从 1 开始
+    pub page: usize,
+    /// 总页数
+    pub total_pages: usize,
+    /// 下一页游标，为空表示没有更多内容
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PaginationError {
+    #[error("无效或已过期的游标: {0}")]
+    InvalidCursor(String),
+}
+
+#[derive(Default)]
+struct PageStore {
+    /// 结果 id -> 所有页
+    results: HashMap<u64, Vec<Vec<Value>>>,
+    /// 按写入顺序记录结果 id，超出容量时淘汰最早的结果
+    order: VecDeque<u64>,
+}
+
+/// 工具输出分页器，克隆后共享同一份暂存数据
+#[derive(Clone)]
+pub struct Paginator {
+    max_items: usize,
+    max_chars: usize,
+    capacity: usize,
+    next_id: Arc<AtomicU64>,
+    store: Arc<Mutex<PageStore>>,
+}
+
+impl Default for Paginator {
+    fn default() -> Self {
+        Self {
+            max_items: 20,
+            max_chars: 8000,
+            capacity: 64,
+            next_id: Arc::new(AtomicU64::new(1)),
+            store: Arc::new(Mutex::new(PageStore::default())),
+        }
+    }
+}
+
+impl Paginator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 每页最多条目数，默认 20
+    pub fn max_items(mut self, max_items: usize) -> Self {
+        self.max_items = max_items.max(1);
+        self
+    }
+
+    /// 每页最多字符数(按序列化后的 JSON 计算)，默认 8000，单个条目超出时独占一页
+    pub fn max_chars(mut self, max_chars: usize) -> Self {
+        self.max_chars = max_chars.max(1);
+        self
+    }
+
+    /// 最多暂存的结果数，默认 64
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// 将条目分页，返回第一页，其余页暂存等待 [`NextPageTool`] 读取
+    pub fn paginate<T: Serialize>(&self, items: impl IntoIterator<Item = T>) -> Page {
+        let items: Vec<Value> = items
+            .into_iter()
+            .filter_map(|item| serde_json::to_value(item).ok())
+            .collect();
+        let pages = self.split(items);
+        let total_pages = pages.len();
+        let first = pages.first().cloned().unwrap_or_default();
+
+        let next_cursor = (total_pages > 1).then(|| {
+            let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+            let mut store = self.store.lock().unwrap_or_else(|e| e.into_inner());
+            store.results.insert(id, pages);
+            store.order.push_back(id);
+            while store.order.len() > self.capacity {
+                if let Some(expired) = store.order.pop_front() {
+                    store.results.remove(&expired);
+                }
+            }
+            cursor(id, 2)
+        });
+
+        Page {
+            items: first,
+            page: 1,
+            total_pages: total_pages.max(1),
+            next_cursor,
+        }
+    }
+
+    /// 将长文本按字符数切分后分页
+    pub fn paginate_text(&self, text: &str) -> Page {
+        let chars: Vec<char> = text.chars().collect();
+        let chunks: Vec<String> = chars
+            .chunks(self.max_chars)
+            .map(|chunk| chunk.iter().collect())
+            .collect();
+        self.paginate(chunks)
+    }
+
+    /// 根据游标读取一页
+    pub fn page(&self, cursor_str: &str) -> Result<Page, PaginationError> {
+        let invalid = || PaginationError::InvalidCursor(cursor_str.to_string());
+        let (id, page) = cursor_str.split_once(':').ok_or_else(invalid)?;
+        let id: u64 = id.parse().map_err(|_| invalid())?;
+        let page: usize = page.parse().map_err(|_| invalid())?;
+
+        let store = self.store.lock().unwrap_or_else(|e| e.into_inner());
+        let pages = store.results.get(&id).ok_or_else(invalid)?;
+        let items = pages
+            .get(page.checked_sub(1).ok_or_else(invalid)?)
+            .ok_or_else(invalid)?
+            .clone();
+        let total_pages = pages.len();
+
+        Ok(Page {
+            items,
+            page,
+            total_pages,
+            next_cursor: (page < total_pages).then(|| cursor(id, page + 1)),
+        })
+    }
+
+    /// 按条目数和字符数切分
+    fn split(&self, items: Vec<Value>) -> Vec<Vec<Value>> {
+        let mut pages = Vec::new();
+        let mut current = Vec::new();
+        let mut current_chars = 0;
+        for item in items {
+            let chars = item.to_string().chars().count();
+            if !current.is_empty()
+                && (current.len() >= self.max_items || current_chars + chars > self.max_chars)
+            {
+                pages.push(std::mem::take(&mut current));
+                current_chars = 0;
+            }
+            current_chars += chars;
+            current.push(item);
+        }
+        if !current.is_empty() {
+            pages.push(current);
+        }
+        pages
+    }
+}
+
+fn cursor(id: u64, page: usize) -> String {
+    format!("{id}:{page}")
+}
+
+#[derive(Deserialize, JsonSchema, Debug)]
+/// 获取下一页参数
+pub struct NextPageArgs {
+    /// 上一页返回的 next_cursor
+    pub cursor: String,
+}
+
+/// 根据游标获取工具输出的下一页
+#[derive(Clone)]
+pub struct NextPageTool {
+    paginator: Paginator,
+}
+
+impl NextPageTool {
+    pub fn new(paginator: Paginator) -> Self {
+        Self { paginator }
+    }
+}
+
+impl Tool for NextPageTool {
+    const NAME: &'static str = "next_page";
+    type Error = PaginationError;
+    type Args = NextPageArgs;
+    type Output = Page;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "当工具输出包含 next_cursor 时，使用该游标获取下一页内容".to_string(),
+            parameters: serde_json::to_value(schema_for!(Self::Args)).unwrap(),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        self.paginator.page(&args.cursor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paginate_and_next_page() {
+        let paginator = Paginator::new().max_items(2);
+        let first = paginator.paginate(1..=5);
+        assert_eq!(first.items, vec![Value::from(1), Value::from(2)]);
+        assert_eq!(first.total_pages, 3);
+
+        let second = paginator
+            .page(first.next_cursor.as_deref().unwrap())
+            .unwrap();
+        assert_eq!(second.page, 2);
+        let third = paginator
+            .page(second.next_cursor.as_deref().unwrap())
+            .unwrap();
+        assert_eq!(third.items, vec![Value::from(5)]);
+        assert!(third.next_cursor.is_none());
+
+        assert!(paginator.page("999:2").is_err());
+        assert!(paginator.page("abc").is_err());
+
+        let single = paginator.paginate(vec!["a"]);
+        assert!(single.next_cursor.is_none());
+
+        let text = Paginator::new().max_chars(3).paginate_text("你好世界！");
+        assert_eq!(text.items[0], Value::from("你好世"));
+    }
+}
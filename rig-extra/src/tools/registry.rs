@@ -0,0 +1,213 @@
+//! 工具注册与重名检测
+//!
+//! 同一个 agent 上同时挂载内置工具、MCP 工具和用户工具时，重名的工具会被静默覆盖，
+//! 模型的行为会变得难以理解。[`ToolRegistry`] 在注册阶段按来源添加前缀并检测重名，
+//! 有冲突时返回明确的错误。
+//!
+//! ```rust,ignore
+//! use rig_extra::tools::registry::{ToolRegistry, ToolSource};
+//!
+//! let tools = ToolRegistry::new()
+//!     .prefix(ToolSource::Mcp, "mcp_")
+//!     .tool(ToolSource::Builtin, DatetimeTool::new())
+//!     .dyn_tools(ToolSource::Mcp, mcp_tools)
+//!     .tool(ToolSource::User, MyTool)
+//!     .build()?;
+//!
+//! let agent = client.agent("glm-4-flash").tools(tools).build();
+//! ```
+//!
+//! 也可以通过 `RandAgentBuilder::tool_registry` 把同一个注册器的工具挂载到代理池中
+//! 由 `AgentConfig` 构建的所有 agent 上。
+
+use rig::completion::ToolDefinition;
+use rig::tool::{Tool, ToolDyn, ToolError};
+use rig::wasm_compat::WasmBoxedFuture;
+use std::collections::HashMap;
+use std::fmt;
+
+/// 工具来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ToolSource {
+    /// 本 crate 内置工具
+    Builtin,
+    /// MCP 服务提供的工具
+    Mcp,
+    /// 用户自定义工具
+    User,
+}
+
+impl fmt::Display for ToolSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ToolSource::Builtin => write!(f, "builtin"),
+            ToolSource::Mcp => write!(f, "mcp"),
+            ToolSource::User => write!(f, "user"),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ToolRegistryError {
+    #[error("工具名称冲突: `{name}` 同时来自 {first} 和 {second}，请为其中一个来源配置前缀")]
+    DuplicateName {
+        name: String,
+        first: ToolSource,
+        second: ToolSource,
+    },
+}
+
+/// 为工具名称添加前缀的包装器
+pub struct PrefixedTool {
+    name: String,
+    inner: Box<dyn ToolDyn>,
+}
+
+impl PrefixedTool {
+    pub fn new(prefix: &str, inner: Box<dyn ToolDyn>) -> Self {
+        Self {
+            name: format!("{prefix}{}", inner.name()),
+            inner,
+        }
+    }
+}
+
+impl ToolDyn for PrefixedTool {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn definition<'a>(&'a self, prompt: String) -> WasmBoxedFuture<'a, ToolDefinition> {
+        Box::pin(async move {
+            let mut definition = self.inner.definition(prompt).await;
+            definition.name = self.name.clone();
+            definition
+        })
+    }
+
+    fn call<'a>(&'a self, args: String) -> WasmBoxedFuture<'a, Result<String, ToolError>> {
+        self.inner.call(args)
+    }
+}
+
+/// 工具注册器
+#[derive(Default)]
+pub struct ToolRegistry {
+    prefixes: HashMap<ToolSource, String>,
+    tools: Vec<(ToolSource, Box<dyn ToolDyn>)>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 为某个来源的所有工具添加名称前缀
+    pub fn prefix(mut self, source: ToolSource, prefix: impl Into<String>) -> Self {
+        self.prefixes.insert(source, prefix.into());
+        self
+    }
+
+    /// 注册工具
+    pub fn tool<T: Tool + 'static>(self, source: ToolSource, tool: T) -> Self {
+        self.dyn_tool(source, Box::new(tool))
+    }
+
+    /// 注册动态工具，如 MCP 工具
+    pub fn dyn_tool(mut self, source: ToolSource, tool: Box<dyn ToolDyn>) -> Self {
+        self.tools.push((source, tool));
+        self
+    }
+
+    /// 批量注册动态工具
+    pub fn dyn_tools(
+        mut self,
+        source: ToolSource,
+        tools: impl IntoIterator<Item = Box<dyn ToolDyn>>,
+    ) -> Self {
+        self.tools
+            .extend(tools.into_iter().map(|tool| (source, tool)));
+        self
+    }
+
+    /// 应用前缀并检测重名，返回可直接传给 `AgentBuilder::tools` 的工具列表
+    pub fn build(self) -> Result<Vec<Box<dyn ToolDyn>>, ToolRegistryError> {
+        let mut seen: HashMap<String, ToolSource> = HashMap::new();
+        let mut tools = Vec::with_capacity(self.tools.len());
+
+        for (source, tool) in self.tools {
+            let tool: Box<dyn ToolDyn> = match self.prefixes.get(&source) {
+                Some(prefix) => Box::new(PrefixedTool::new(prefix, tool)),
+                None => tool,
+            };
+            let name = tool.name();
+            if let Some(first) = seen.get(&name) {
+                return Err(ToolRegistryError::DuplicateName {
+                    name,
+                    first: *first,
+                    second: source,
+                });
+            }
+            seen.insert(name, source);
+            tools.push(tool);
+        }
+
+        Ok(tools)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("echo error")]
+    struct EchoError;
+
+    struct EchoTool;
+
+    impl Tool for EchoTool {
+        const NAME: &'static str = "echo";
+        type Error = EchoError;
+        type Args = serde_json::Value;
+        type Output = serde_json::Value;
+
+        async fn definition(&self, _prompt: String) -> ToolDefinition {
+            ToolDefinition {
+                name: Self::NAME.to_string(),
+                description: "echo".to_string(),
+                parameters: json!({"type": "object"}),
+            }
+        }
+
+        async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+            Ok(args)
+        }
+    }
+
+    #[test]
+    fn test_duplicate_detection_and_prefix() {
+        let result = ToolRegistry::new()
+            .tool(ToolSource::Builtin, EchoTool)
+            .tool(ToolSource::Mcp, EchoTool)
+            .build();
+        assert!(matches!(
+            result,
+            Err(ToolRegistryError::DuplicateName {
+                first: ToolSource::Builtin,
+                second: ToolSource::Mcp,
+                ..
+            })
+        ));
+
+        let tools = ToolRegistry::new()
+            .prefix(ToolSource::Mcp, "mcp_")
+            .tool(ToolSource::Builtin, EchoTool)
+            .tool(ToolSource::Mcp, EchoTool)
+            .build()
+            .unwrap_or_else(|err| panic!("{err}"));
+        let names: Vec<String> = tools.iter().map(|tool| tool.name()).collect();
+        assert_eq!(names, vec!["echo", "mcp_echo"]);
+    }
+}
@@ -5,7 +5,8 @@ use reqwest::Client;
 use rig::completion::ToolDefinition;
 use rig::tool::Tool;
 use schemars::{JsonSchema, schema_for};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
 
 /// serpaapi 获取谷歌搜索
@@ -46,11 +47,101 @@ pub struct SerpapiArgs {
     /// 搜索关键词
     pub query: String,
 }
+/// 单条搜索结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SearchResult {
+    /// 标题
+    pub title: String,
+    /// 链接
+    pub link: String,
+    /// 摘要
+    #[serde(default)]
+    pub snippet: String,
+    /// 发布日期
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+}
+
+/// 答案框(谷歌直接给出的答案)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct AnswerBox {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub answer: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+}
+
+/// 知识图谱
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct KnowledgeGraph {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// Serpapi 搜索结果
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SerpapiOutput {
+    /// 自然搜索结果
+    pub results: Vec<SearchResult>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub answer_box: Option<AnswerBox>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub knowledge_graph: Option<KnowledgeGraph>,
+    /// 无法解析为结构化结果时保留的原始 organic_results
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw: Option<Value>,
+}
+
+impl SerpapiOutput {
+    /// 从 serpapi 返回的 json 中提取搜索结果
+    pub fn from_search_result(search_result: &Value) -> Result<Self, SerpapiError> {
+        let mut output = SerpapiOutput {
+            answer_box: search_result
+                .get("answer_box")
+                .and_then(|value| serde_json::from_value(value.clone()).ok()),
+            knowledge_graph: search_result
+                .get("knowledge_graph")
+                .and_then(|value| serde_json::from_value(value.clone()).ok()),
+            ..Default::default()
+        };
+
+        if let Some(organic_results) = search_result.get("organic_results") {
+            match serde_json::from_value::<Vec<SearchResult>>(organic_results.clone()) {
+                Ok(results) => output.results = results,
+                Err(err) => {
+                    tracing::warn!(
+                        "organic_results 解析失败，返回原始数据: {}",
+                        crate::redact::redacted(&err)
+                    );
+                    output.raw = Some(organic_results.clone());
+                }
+            }
+        }
+
+        if output.results.is_empty()
+            && output.raw.is_none()
+            && output.answer_box.is_none()
+            && output.knowledge_graph.is_none()
+        {
+            return Err(SerpapiError::CustomError("没有organic_results".to_string()));
+        }
+        Ok(output)
+    }
+}
+
 impl Tool for SerpapiTool {
     const NAME: &'static str = "Serpapi Tool";
     type Error = SerpapiError;
     type Args = SerpapiArgs;
-    type Output = String;
+    type Output = SerpapiOutput;
 
     async fn definition(&self, _prompt: String) -> ToolDefinition {
         ToolDefinition {
@@ -84,13 +175,45 @@ impl Tool for SerpapiTool {
             .query(&params)
             .send()
             .await?;
-        let search_result: serde_json::Value = response.json().await?;
+        let search_result: Value = response.json().await?;
         tracing::info!("search result: {:?}", search_result);
-        let organic_results = search_result
-            .get("organic_results")
-            .ok_or(SerpapiError::CustomError("没有organic_results".to_string()))?;
-        let result = serde_json::to_string(organic_results)?;
-        tracing::debug!("result: {}", result);
+        let result = SerpapiOutput::from_search_result(&search_result)?;
+        tracing::debug!("result: {:?}", result);
         Ok(result)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_search_result() {
+        let search_result = json!({
+            "answer_box": {"type": "organic_result", "answer": "42", "title": "答案"},
+            "knowledge_graph": {"title": "Rust", "type": "Programming language"},
+            "organic_results": [
+                {"position": 1, "title": "Rust", "link": "https://www.rust-lang.org", "snippet": "A language", "date": "2025-01-01"},
+                {"position": 2, "title": "Rust Book", "link": "https://doc.rust-lang.org/book/"}
+            ]
+        });
+        let output = SerpapiOutput::from_search_result(&search_result).unwrap();
+        assert_eq!(output.results.len(), 2);
+        assert_eq!(output.results[0].date.as_deref(), Some("2025-01-01"));
+        assert_eq!(output.results[1].snippet, "");
+        assert_eq!(output.answer_box.unwrap().answer.as_deref(), Some("42"));
+        assert_eq!(
+            output.knowledge_graph.unwrap().kind.as_deref(),
+            Some("Programming language")
+        );
+        assert!(output.raw.is_none());
+
+        let malformed = json!({"organic_results": [{"position": 1}]});
+        let output = SerpapiOutput::from_search_result(&malformed).unwrap();
+        assert!(output.results.is_empty());
+        assert!(output.raw.is_some());
+
+        assert!(SerpapiOutput::from_search_result(&json!({})).is_err());
+    }
+}
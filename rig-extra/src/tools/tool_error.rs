@@ -0,0 +1,166 @@
+//! 将工具错误作为结构化内容返回给模型
+//!
+//! 默认情况下工具调用失败会中断整个 prompt。使用 [`RecoverableTool`] 包装后，工具错误会被转换为
+//! `{"tool_error": {...}}` 结果返回给模型，模型可以据此换个参数重试或向用户说明情况。
+//!
+//! 注意: 参数反序列化失败发生在工具被调用之前，不会经过包装器。
+//!
+//! ```rust,ignore
+//! use rig_extra::tools::tool_error::RecoverableTool;
+//!
+//! let agent = client
+//!     .agent("glm-4-flash")
+//!     .tool(RecoverableTool::new(SerpapiTool::new(api_key)).hint("请缩短关键词后重试"))
+//!     .build();
+//! ```
+
+use rig::completion::ToolDefinition;
+use rig::tool::Tool;
+use serde::Serialize;
+use std::sync::Arc;
+
+/// 返回给模型的工具错误内容
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ToolErrorContent {
+    /// 工具名称
+    pub tool: String,
+    /// 错误类别
+    pub kind: String,
+    /// 错误信息
+    pub message: String,
+    /// 给模型的处理建议
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+/// 工具调用结果，成功时序列化为原始输出
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum ToolOutcome<O> {
+    Ok(O),
+    Err { tool_error: ToolErrorContent },
+}
+
+/// 错误分类函数类型
+pub type ToolErrorClassifier<E> = Arc<dyn Fn(&E) -> String + Send + Sync + 'static>;
+
+/// 工具错误恢复包装器
+pub struct RecoverableTool<T: Tool> {
+    inner: T,
+    recover: bool,
+    hint: Option<String>,
+    classifier: Option<ToolErrorClassifier<T::Error>>,
+}
+
+impl<T: Tool> RecoverableTool<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            recover: true,
+            hint: None,
+            classifier: None,
+        }
+    }
+
+    /// 是否将错误转换为结构化结果，默认开启，关闭后错误照常中断 prompt
+    pub fn recover(mut self, recover: bool) -> Self {
+        self.recover = recover;
+        self
+    }
+
+    /// 设置出错时给模型的处理建议
+    pub fn hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    /// 自定义错误分类，默认使用错误类型(枚举变体)名称
+    pub fn classify<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&T::Error) -> String + Send + Sync + 'static,
+    {
+        self.classifier = Some(Arc::new(f));
+        self
+    }
+
+    /// 将错误转换为返回给模型的内容
+    fn error_content(&self, err: &T::Error) -> ToolErrorContent {
+        let kind = match &self.classifier {
+            Some(classifier) => classifier(err),
+            None => default_kind(&format!("{err:?}")),
+        };
+        ToolErrorContent {
+            tool: self.inner.name(),
+            kind,
+            message: err.to_string(),
+            hint: self.hint.clone(),
+        }
+    }
+}
+
+/// 从 Debug 输出中取类型或枚举变体名称
+fn default_kind(debug: &str) -> String {
+    debug
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .find(|part| !part.is_empty())
+        .unwrap_or("tool_error")
+        .to_string()
+}
+
+impl<T: Tool> Tool for RecoverableTool<T> {
+    const NAME: &'static str = T::NAME;
+    type Error = T::Error;
+    type Args = T::Args;
+    type Output = ToolOutcome<T::Output>;
+
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+
+    async fn definition(&self, prompt: String) -> ToolDefinition {
+        self.inner.definition(prompt).await
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        match self.inner.call(args).await {
+            Ok(output) => Ok(ToolOutcome::Ok(output)),
+            Err(err) if self.recover => {
+                tracing::warn!(
+                    "工具 {} 调用失败，错误返回给模型: {}",
+                    self.inner.name(),
+                    crate::redact::redacted(&err)
+                );
+                Ok(ToolOutcome::Err {
+                    tool_error: self.error_content(&err),
+                })
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_outcome_serialize() {
+        assert_eq!(default_kind("RequestError(Timeout)"), "RequestError");
+        assert_eq!(default_kind(""), "tool_error");
+
+        let outcome: ToolOutcome<String> = ToolOutcome::Err {
+            tool_error: ToolErrorContent {
+                tool: "SerpapiTool".to_string(),
+                kind: "RequestError".to_string(),
+                message: "timeout".to_string(),
+                hint: None,
+            },
+        };
+        let value = serde_json::to_value(&outcome).unwrap();
+        assert_eq!(value["tool_error"]["kind"], "RequestError");
+        assert!(value["tool_error"].get("hint").is_none());
+
+        let value = serde_json::to_value(ToolOutcome::Ok("ok".to_string())).unwrap();
+        assert_eq!(value, "ok");
+    }
+}
@@ -0,0 +1,143 @@
+//! 代理池 token 用量统计
+//!
+//! 按 agent id 累计提供方返回的 token 用量，设置了价格的 agent 同时累计费用，便于按 key 计费。
+//!
+//! ```rust,ignore
+//! let stats = rand_agent.usage_stats().await;
+//! for (id, usage) in &stats.per_agent {
+//!     println!("agent {id}: {usage:?}");
+//! }
+//! println!("total: {:?}", stats.total);
+//! ```
+
+use rig::completion::{
+    CompletionError, CompletionModel, CompletionRequest, CompletionResponse, Usage,
+};
+use rig::streaming::StreamingCompletionResponse;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// token 用量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+pub struct TokenUsage {
+    /// 请求次数
+    pub requests: u64,
+    /// 输入 token 数
+    pub prompt_tokens: u64,
+    /// 输出 token 数
+    pub completion_tokens: u64,
+    /// 总 token 数
+    pub total_tokens: u64,
+    /// 累计费用，按 agent 设置的每千 token 价格计算
+    pub cost: f64,
+}
+
+impl TokenUsage {
+    fn add(&mut self, usage: &Usage, cost: f64) {
+        self.requests += 1;
+        self.prompt_tokens += usage.input_tokens;
+        self.completion_tokens += usage.output_tokens;
+        self.total_tokens += usage.total_tokens;
+        self.cost += cost;
+    }
+}
+
+/// 按每千 token 价格计算一次请求的费用，未设置的价格按 0 计算
+pub fn usage_cost(usage: &Usage, input_price: Option<f64>, output_price: Option<f64>) -> f64 {
+    (usage.input_tokens as f64 * input_price.unwrap_or_default()
+        + usage.output_tokens as f64 * output_price.unwrap_or_default())
+        / 1000.0
+}
+
+/// 代理池用量统计
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct UsageStats {
+    /// 按 agent id 统计的用量
+    pub per_agent: HashMap<i32, TokenUsage>,
+    /// 所有 agent 的用量合计
+    pub total: TokenUsage,
+}
+
+impl UsageStats {
+    /// 记录一次请求的用量和费用
+    pub(crate) fn record(&mut self, id: i32, usage: &Usage, cost: f64) {
+        self.per_agent.entry(id).or_default().add(usage, cost);
+        self.total.add(usage, cost);
+    }
+
+    /// 获取指定 agent 的用量
+    pub fn agent(&self, id: i32) -> Option<&TokenUsage> {
+        self.per_agent.get(&id)
+    }
+}
+
+/// 累计经过的所有补全请求的 token 用量
+///
+/// 用于 rig 的 `Extractor` 等不返回用量的封装: 把模型包一层再交给它们，
+/// 调用结束后通过 [`UsageRecorder::usage`] 读取包括内部重试在内的总用量
+#[derive(Clone)]
+pub(crate) struct UsageRecorder<M> {
+    inner: M,
+    usage: Arc<Mutex<Usage>>,
+}
+
+impl<M> UsageRecorder<M> {
+    pub(crate) fn new(inner: M) -> Self {
+        Self {
+            inner,
+            usage: Arc::new(Mutex::new(Usage::new())),
+        }
+    }
+
+    pub(crate) fn usage(&self) -> Usage {
+        *self.usage.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<M: CompletionModel> CompletionModel for UsageRecorder<M> {
+    type Response = M::Response;
+    type StreamingResponse = M::StreamingResponse;
+
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<CompletionResponse<Self::Response>, CompletionError> {
+        let response = self.inner.completion(request).await?;
+        *self.usage.lock().unwrap_or_else(|e| e.into_inner()) += response.usage;
+        Ok(response)
+    }
+
+    async fn stream(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<StreamingCompletionResponse<Self::StreamingResponse>, CompletionError> {
+        self.inner.stream(request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_stats_record() {
+        let mut stats = UsageStats::default();
+        let mut usage = Usage::new();
+        usage.input_tokens = 10;
+        usage.output_tokens = 5;
+        usage.total_tokens = 15;
+        let cost = usage_cost(&usage, Some(2.0), Some(4.0));
+        assert!((cost - 0.04).abs() < 1e-9);
+        stats.record(1, &usage, cost);
+        stats.record(1, &usage, cost);
+        stats.record(2, &usage, 0.0);
+
+        assert_eq!(stats.agent(1).unwrap().total_tokens, 30);
+        assert_eq!(stats.agent(1).unwrap().requests, 2);
+        assert_eq!(stats.agent(2).unwrap().prompt_tokens, 10);
+        assert_eq!(stats.total.total_tokens, 45);
+        assert!((stats.total.cost - 0.08).abs() < 1e-9);
+        assert!(stats.agent(3).is_none());
+    }
+}